@@ -2,8 +2,9 @@ use std::io::Result;
 
 fn main() -> Result<()> {
     let protos = &[
-        "src/malachite_types/proto/consensus.proto",
-        "src/malachite_types/proto/sync.proto",
+        "src/types/proto/consensus.proto",
+        "src/types/proto/sync.proto",
+        "src/types/proto/block.proto",
     ];
 
     for proto in protos {
@@ -14,7 +15,7 @@ fn main() -> Result<()> {
     config.enable_type_names();
     config.bytes(["."]);
 
-    config.compile_protos(protos, &["src/malachite_types"])?;
+    config.compile_protos(protos, &["src/types"])?;
 
     Ok(())
 }
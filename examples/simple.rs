@@ -1,8 +1,9 @@
-use frieda::proof::Proof;
-use mikan::blob::Blob;
-use mikan::rpc::RpcTransaction;
-use reqwest::Client;
-use serde_json::{json, Value};
+use mikan::client::MikanClient;
+use mikan::orchestrator::{release_binary, Orchestrator};
+use mikan::shutdown::Shutdown;
+use mikan::transactions::Transaction;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::process::Command;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -10,67 +11,72 @@ use std::time::Duration;
 use tokio::runtime::Runtime;
 
 fn main() {
-    // Start the nodes
-    println!("Starting nodes with spawn.bash...");
-    let mut child = Command::new("bash")
-        .arg("spawn.bash")
-        .arg("--nodes")
-        .arg("3")
-        .arg("--home")
-        .arg("nodes")
-        .spawn()
-        .expect("Failed to start spawn.bash");
-
-    // Wait for nodes to start
-    println!("Waiting for nodes to start...");
+    // Build the node binary supervised by the orchestrator
+    println!("Building mikan...");
+    let status = Command::new("cargo")
+        .args(["build", "-r", "--bin", "mikan"])
+        .status()
+        .expect("Failed to run cargo build");
+    assert!(status.success(), "Failed to build mikan");
 
     // Create a flag to control the transaction sending loop
     let running = Arc::new(AtomicBool::new(true));
     let running_clone = running.clone();
+    let shutdown = Shutdown::new();
+    let shutdown_clone = shutdown.clone();
 
     // Set up Ctrl+C handler
     ctrlc::set_handler(move || {
         println!("Stopping transaction sender...");
         running_clone.store(false, Ordering::SeqCst);
+        shutdown_clone.trigger();
     })
     .expect("Error setting Ctrl+C handler");
 
     // Create a runtime for async operations
     let rt = Runtime::new().expect("Failed to create Tokio runtime");
 
+    // Start the nodes
+    println!("Starting nodes...");
+    let orchestrator = Orchestrator::new(release_binary(), "nodes", 3);
+    let nodes = rt.spawn(orchestrator.run(shutdown));
+
     // Run the transaction sender in the runtime
     rt.block_on(async {
         run_workers(running).await;
     });
 
-    // Wait for the child process to finish
+    // Wait for the nodes to stop
     println!("Stopping nodes...");
-    let _ = child.kill();
-    let _ = child.wait();
+    if let Ok(Err(e)) = rt.block_on(nodes) {
+        eprintln!("Nodes failed: {e}");
+    }
 }
 
 async fn run_workers(running: Arc<AtomicBool>) {
-    let client = Client::new();
     let node_count = 3;
 
-    // Create a vector of node RPC URLs
-    let node_urls: Vec<String> = (0..node_count)
-        .map(|i| format!("http://127.0.0.1:{}", 8545 + i))
+    // Create a client for each node
+    let clients: Vec<MikanClient> = (0..node_count)
+        .map(|i| MikanClient::new(format!("http://127.0.0.1:{}", 8545 + i)).unwrap())
         .collect();
 
-    println!("Starting transaction and sampling workers...");
+    // Set MIKAN_SEED to replay the same transactions and queries
+    let seed = mikan::rng::seed_from_env();
+    println!("Starting transaction and sampling workers with MIKAN_SEED={seed}...");
 
     let mut handles = Vec::new();
 
     // Transaction workers
     let tx_workers = 6;
     for worker_id in 0..tx_workers {
-        let client_clone = client.clone();
-        let node_urls_clone = node_urls.clone();
+        let clients_clone = clients.clone();
         let running_clone = running.clone();
 
+        let rng = StdRng::seed_from_u64(seed.wrapping_add(worker_id as u64));
+
         let handle = tokio::spawn(async move {
-            transaction_worker(worker_id, client_clone, node_urls_clone, running_clone).await;
+            transaction_worker(worker_id, clients_clone, running_clone, rng).await;
         });
 
         handles.push(handle);
@@ -79,12 +85,13 @@ async fn run_workers(running: Arc<AtomicBool>) {
     // Sampling workers
     let sampling_workers = 2;
     for worker_id in 0..sampling_workers {
-        let client_clone = client.clone();
-        let node_urls_clone = node_urls.clone();
+        let clients_clone = clients.clone();
         let running_clone = running.clone();
 
+        let rng = StdRng::seed_from_u64(seed.wrapping_add((tx_workers + worker_id) as u64));
+
         let handle = tokio::spawn(async move {
-            sampling_worker(worker_id, client_clone, node_urls_clone, running_clone).await;
+            sampling_worker(worker_id, clients_clone, running_clone, rng).await;
         });
 
         handles.push(handle);
@@ -98,76 +105,74 @@ async fn run_workers(running: Arc<AtomicBool>) {
 
 async fn transaction_worker(
     worker_id: usize,
-    client: Client,
-    node_urls: Vec<String>,
+    clients: Vec<MikanClient>,
     running: Arc<AtomicBool>,
+    mut rng: StdRng,
 ) {
-    let node_count = node_urls.len();
+    let node_count = clients.len();
     println!("Transaction worker {} started", worker_id);
 
     while running.load(Ordering::SeqCst) {
         // Send a transaction to a random node
-        let node_index = rand::random::<usize>() % node_count;
-        let url = &node_urls[node_index];
+        let node_index = rng.gen_range(0..node_count);
+        let client = clients[node_index].clone();
 
         // Create a random transaction
-        let tx = RpcTransaction::random();
+        let tx = Transaction::random_with_rng(&mut rng);
 
         // Fire and forget - don't await the result
-        let client_clone = client.clone();
-        let url_clone = url.clone();
         tokio::spawn(async move {
-            let _ = send_transaction(&client_clone, &url_clone, tx).await;
+            let _ = client.submit_transaction(tx).await;
         });
     }
 }
 
 async fn sampling_worker(
     worker_id: usize,
-    client: Client,
-    node_urls: Vec<String>,
+    clients: Vec<MikanClient>,
     running: Arc<AtomicBool>,
+    mut rng: StdRng,
 ) {
-    let node_count = node_urls.len();
+    let node_count = clients.len();
     println!("Sampling worker {} started", worker_id);
 
     while running.load(Ordering::SeqCst) {
         // Select a random node to query
-        let node_index = rand::random::<usize>() % node_count;
-        let url = &node_urls[node_index];
+        let node_index = rng.gen_range(0..node_count);
+        let client = &clients[node_index];
 
         // Get current block number
-        let nb = get_block_number(&client, url).await;
+        let nb = client.block_number().await.unwrap_or_default();
 
         // Sample a blob from a block if we have blocks available
         if nb > 0 {
             // Choose a random block within the available range
-            let block_height = rand::random::<u64>() % nb + 1;
-            let blob_index = rand::random::<usize>() % 4; // Assuming the first blob in the block
+            let block_height = rng.gen_range(1..=nb);
+            let blob_index = rng.gen_range(0..4); // Assuming the first blob in the block
 
             // Randomly decide whether to provide a sampling seed
-            let sampling_seed = if rand::random::<bool>() {
-                Some(rand::random::<u64>())
+            let sampling_seed = if rng.gen::<bool>() {
+                Some(rng.gen::<u64>())
             } else {
                 None
             };
 
-            // Call the sample_blob RPC method
-            match sample_blob(&client, url, block_height, blob_index, sampling_seed).await {
-                Some(proof) => {
-                    let verified = frieda::api::verify(proof, sampling_seed);
+            // Sample the blob and verify the proof
+            match client
+                .sample_and_verify(block_height, blob_index, sampling_seed)
+                .await
+            {
+                Ok(verified) => {
                     println!(
                         "Sample Worker {}: Successfully({verified}) sampled blob {} from block {}",
                         worker_id, blob_index, block_height
                     );
-                    let blob = get_blob(&client, url, block_height, blob_index).await;
-                    if blob.is_some() {
-                        println!("Successfully got Blob: {:?}", blob.unwrap().data().len());
-                    } else {
-                        println!("Failed to get Blob");
+                    match client.get_blob(block_height, blob_index).await {
+                        Ok(blob) => println!("Successfully got Blob: {:?}", blob.data().len()),
+                        Err(_) => println!("Failed to get Blob"),
                     }
                 }
-                None => eprintln!(
+                Err(_) => eprintln!(
                     "Sample Worker {}: No blob in block {}",
                     worker_id, block_height
                 ),
@@ -178,118 +183,3 @@ async fn sampling_worker(
         tokio::time::sleep(Duration::from_millis(200)).await;
     }
 }
-
-async fn send_transaction(
-    client: &Client,
-    url: &str,
-    tx: RpcTransaction,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let response = client
-        .post(url)
-        .json(&json!({
-            "jsonrpc": "2.0",
-            "method": "mikan_sendTransaction",
-            "params": [tx],
-            "id": 1
-        }))
-        .send()
-        .await?;
-
-    let result: Value = response.json().await?;
-
-    // Extract the transaction hash from the response
-    let hash = result["result"]
-        .as_str()
-        .ok_or("Missing result in response")?;
-
-    Ok(hash.to_string())
-}
-
-async fn sample_blob(
-    client: &Client,
-    url: &str,
-    block_height: u64,
-    blob_index: usize,
-    sampling_seed: Option<u64>,
-) -> Option<Proof> {
-    let params = if let Some(seed) = sampling_seed {
-        json!([block_height, blob_index, seed])
-    } else {
-        json!([block_height, blob_index])
-    };
-
-    let response = client
-        .post(url)
-        .json(&json!({
-            "jsonrpc": "2.0",
-            "method": "mikan_sampleBlob",
-            "params": params,
-            "id": 1
-        }))
-        .send()
-        .await
-        .ok()?;
-
-    let result: Value = response.json().await.ok()?;
-
-    if result.get("error").is_some() {
-        return None;
-    }
-
-    // Parse the proof from the response
-    let proof: Proof = serde_json::from_value(result["result"].clone()).ok()?;
-
-    Some(proof)
-}
-
-async fn get_blob(
-    client: &Client,
-    url: &str,
-    block_height: u64,
-    blob_index: usize,
-) -> Option<Blob> {
-    let response = client
-        .post(url)
-        .json(&json!({
-            "jsonrpc": "2.0",
-            "method": "mikan_getBlob",
-            "params": [block_height, blob_index],
-            "id": 1
-        }))
-        .send()
-        .await
-        .ok()?;
-
-    let result: Value = response.json().await.ok()?;
-
-    if result.get("error").is_some() {
-        return None;
-    }
-
-    let blob: Blob = serde_json::from_value(result["result"].clone()).ok()?;
-
-    Some(blob)
-}
-
-async fn get_block_number(client: &Client, url: &str) -> u64 {
-    let response = client
-        .post(url)
-        .json(&json!({
-            "jsonrpc": "2.0",
-            "method": "mikan_blockNumber",
-            "params": [],
-            "id": 1
-        }))
-        .send()
-        .await;
-
-    let result: u64 = match response {
-        Ok(response) => {
-            let json_value: Value = response.json().await.unwrap();
-            json_value["result"].as_u64().unwrap_or_default()
-        }
-        Err(_) => 0,
-    };
-
-    result
-}
@@ -1,19 +1,25 @@
-use color_eyre::eyre::{self, eyre};
 use std::time::Duration;
-use tracing::{error, info};
-
-use crate::malachite_types::codec::proto::ProtobufCodec;
-use crate::malachite_types::context::TestContext;
-use crate::state::{decode_value, State};
+use tracing::{debug, error, info, warn};
+
+use crate::error::{MikanError, Result};
+use crate::peers::PeerDirection;
+use crate::shutdown::Shutdown;
+use crate::state::State;
+use crate::types::codec::proto::ProtobufCodec;
+use crate::types::context::TestContext;
 use malachitebft_app_channel::app::streaming::StreamContent;
 use malachitebft_app_channel::app::types::codec::Codec;
-use malachitebft_app_channel::app::types::core::{Round, Validity};
 use malachitebft_app_channel::app::types::sync::RawDecidedValue;
-use malachitebft_app_channel::app::types::ProposedValue;
 use malachitebft_app_channel::{AppMsg, Channels, ConsensusMsg, NetworkMsg};
 
-pub async fn run(state: &mut State, channels: &mut Channels<TestContext>) -> eyre::Result<()> {
-    while let Some(msg) = channels.consensus.recv().await {
+/// Handles consensus messages until the consensus channel closes, which is an error, or until
+/// `shutdown` is triggered, which flushes the state.
+pub async fn run(
+    state: &mut State,
+    channels: &mut Channels<TestContext>,
+    shutdown: &Shutdown,
+) -> Result<()> {
+    while let Some(Some(msg)) = shutdown.run_until(channels.consensus.recv()).await {
         match msg {
             // The first message to handle is the `ConsensusReady` message, signaling to the app
             // that Malachite is ready to start consensus
@@ -25,7 +31,10 @@ pub async fn run(state: &mut State, channels: &mut Channels<TestContext>) -> eyr
                 // We can simply respond by telling the engine to start consensus
                 // at the current height, which is initially 1
                 if reply
-                    .send((state.current_height, state.get_validator_set().clone()))
+                    .send((
+                        state.current_height,
+                        state.get_validator_set(state.current_height),
+                    ))
                     .is_err()
                 {
                     error!("Failed to send ConsensusReady reply");
@@ -46,11 +55,19 @@ pub async fn run(state: &mut State, channels: &mut Channels<TestContext>) -> eyr
                 state.current_height = height;
                 state.current_round = round;
                 state.current_proposer = Some(proposer);
+                state.round_state.started_round(
+                    height,
+                    round,
+                    proposer,
+                    &state.get_validator_set(height),
+                );
 
                 // If we have already built or seen a value for this height and round,
                 // send it back to consensus. This may happen when we are restarting after a crash.
-                if let Some(proposal) = state.store.get_undecided_proposal(height, round).await? {
+                // As the proposer of a later round, we propose again the value we built earlier.
+                if let Some(proposal) = state.undecided_proposal(height, round, proposer).await? {
                     info!(%height, %round, "Replaying already known proposed value: {}", proposal.value.id());
+                    state.round_state.proposal(&proposal);
 
                     if reply_value.send(Some(proposal)).is_err() {
                         error!("Failed to send undecided proposal");
@@ -65,37 +82,90 @@ pub async fn run(state: &mut State, channels: &mut Channels<TestContext>) -> eyr
             AppMsg::GetValue {
                 height,
                 round,
-                timeout: _,
+                timeout,
                 reply,
             } => {
-                // NOTE: We can ignore the timeout as we are building the value right away.
+                // NOTE: The timeout bounds how long block pacing can delay our answer.
                 // If we were let's say reaping as many txes from a mempool and executing them,
                 // then we would need to respect the timeout and stop at a certain point.
 
                 info!(%height, %round, "Consensus is requesting a value to propose");
 
-                // We need to create a new value to propose and send it back to consensus.
-                // Get block data
-                let block_bytes = state.make_block().await?;
-
-                let proposal = state
-                    .propose_value(height, round, block_bytes.clone())
-                    .await?;
+                // We need to create a new value to propose and send it back to consensus,
+                // unless we built one for this round before restarting.
+                let (proposal, block_bytes) = match state.built_value(height, round).await? {
+                    Some(built) => {
+                        info!(%height, %round, "Proposing the value already built for this round");
+                        built
+                    }
+                    None => {
+                        // Get block data
+                        let Some(block_bytes) = state.make_block(timeout).await? else {
+                            // Not replying makes consensus time out the propose step and move on
+                            // to the next round, with the next proposer
+                            info!(%height, %round, "No transaction to propose, waiting for the next heartbeat");
+                            drop(reply);
+                            continue;
+                        };
+
+                        let proposal = state
+                            .propose_value(height, round, block_bytes.clone())
+                            .await?;
+                        (proposal, block_bytes)
+                    }
+                };
 
                 // Send it to consensus
                 if reply.send(proposal.clone()).is_err() {
                     error!("Failed to send GetValue reply");
                 }
 
+                // The part stream borrows the state
+                #[cfg(feature = "byzantine")]
+                let byzantine = state.byzantine.clone();
+
+                #[cfg(feature = "byzantine")]
+                let equivocation = if byzantine.equivocate && byzantine.is_active(height) {
+                    let data = crate::byzantine::equivocating_block(&block_bytes)?;
+                    let value = crate::types::value::Value::new(data.clone());
+                    tracing::warn!(%height, %round, "Byzantine: equivocating with value {}", value.id());
+                    Some((
+                        malachitebft_app_channel::app::types::LocallyProposedValue::new(
+                            height, round, value,
+                        ),
+                        data,
+                    ))
+                } else {
+                    None
+                };
+
                 // Now what's left to do is to break down the value to propose into parts,
                 // and send those parts over the network to our peers, for them to re-assemble the full value.
+                // Parts are only built once the network channel has room for them.
                 for stream_message in state.stream_proposal(proposal, block_bytes) {
+                    #[cfg(feature = "byzantine")]
+                    let Some(stream_message) = byzantine.tamper(height, stream_message) else {
+                        continue;
+                    };
+
                     info!(%height, %round, "Streaming proposal part: {stream_message:?}");
 
                     channels
                         .network
                         .send(NetworkMsg::PublishProposalPart(stream_message))
-                        .await?;
+                        .await
+                        .map_err(|_| MikanError::ChannelClosed("network"))?;
+                }
+
+                #[cfg(feature = "byzantine")]
+                if let Some((value, data)) = equivocation {
+                    for stream_message in state.stream_proposal(value, data) {
+                        channels
+                            .network
+                            .send(NetworkMsg::PublishProposalPart(stream_message))
+                            .await
+                            .map_err(|_| MikanError::ChannelClosed("network"))?;
+                    }
                 }
             }
 
@@ -105,6 +175,12 @@ pub async fn run(state: &mut State, channels: &mut Channels<TestContext>) -> eyr
             // have all its constituent parts. Then we send that value back to consensus for it to
             // consider and vote for or against it (ie. vote `nil`), depending on its validity.
             AppMsg::ReceivedProposalPart { from, part, reply } => {
+                if !state.peers.is_allowed(&from) {
+                    debug!(%from, %part.sequence, "Ignoring proposal part from a rejected peer");
+                    let _ = reply.send(None);
+                    continue;
+                }
+
                 let (part_type, part_size) = match &part.content {
                     StreamContent::Data(part) => (part.get_type(), part.size_bytes()),
                     StreamContent::Fin => ("end of stream", 0),
@@ -115,7 +191,33 @@ pub async fn run(state: &mut State, channels: &mut Channels<TestContext>) -> eyr
                     "Received proposal part"
                 );
 
+                #[cfg(feature = "netsim")]
+                if let Some(link) = state.link_conditions.as_mut() {
+                    match link.on_receive(&from.to_string()) {
+                        crate::netsim::LinkAction::Drop => {
+                            tracing::debug!(%from, %part.sequence, "Netsim: dropping proposal part");
+                            let _ = reply.send(None);
+                            continue;
+                        }
+                        crate::netsim::LinkAction::Deliver { delay } => {
+                            tokio::time::sleep(delay).await;
+                        }
+                    }
+                }
+
                 let proposed_value = state.received_proposal_part(from, part).await?;
+                if let Some(proposed_value) = &proposed_value {
+                    state.round_state.proposal(proposed_value);
+                }
+
+                #[cfg(feature = "byzantine")]
+                if let Some(delay) = proposed_value
+                    .as_ref()
+                    .and_then(|value| state.byzantine.vote_delay(value.height))
+                {
+                    tracing::warn!(?delay, "Byzantine: delaying prevote");
+                    tokio::time::sleep(delay).await;
+                }
 
                 if reply.send(proposed_value).is_err() {
                     error!("Failed to send ReceivedProposalPart reply");
@@ -126,10 +228,10 @@ pub async fn run(state: &mut State, channels: &mut Channels<TestContext>) -> eyr
             // than the one we are at (e.g. because we are lagging behind a little bit),
             // the engine may ask us for the validator set at that height.
             //
-            // In our case, our validator set stays constant between heights so we can
-            // send back the validator set found in our genesis state.
-            AppMsg::GetValidatorSet { height: _, reply } => {
-                if reply.send(state.get_validator_set().clone()).is_err() {
+            // In our case, the validator set is the one of our genesis state, with no voting power
+            // for the validators jailed at that height.
+            AppMsg::GetValidatorSet { height, reply } => {
+                if reply.send(state.get_validator_set(height)).is_err() {
                     error!("Failed to send GetValidatorSet reply");
                 }
             }
@@ -151,6 +253,10 @@ pub async fn run(state: &mut State, channels: &mut Channels<TestContext>) -> eyr
                     "Consensus has decided on value"
                 );
 
+                state
+                    .round_state
+                    .decided(&certificate, &state.get_validator_set(height));
+
                 // When that happens, we store the decided value in our store
                 info!(height = %height, round = %round, value = %value_id, "Committing decided value");
                 state.commit(certificate).await?;
@@ -163,7 +269,7 @@ pub async fn run(state: &mut State, channels: &mut Channels<TestContext>) -> eyr
                 if reply
                     .send(ConsensusMsg::StartHeight(
                         state.current_height,
-                        state.get_validator_set().clone(),
+                        state.get_validator_set(state.current_height),
                     ))
                     .is_err()
                 {
@@ -177,8 +283,6 @@ pub async fn run(state: &mut State, channels: &mut Channels<TestContext>) -> eyr
             // for the heights in between the one we are currently at (included) and the one
             // that they are at. When the engine receives such a value, it will forward to the application
             // to decode it from its wire format and send back the decoded value to consensus.
-            //
-            // TODO: store the received value somewhere here
             AppMsg::ProcessSyncedValue {
                 height,
                 round,
@@ -188,20 +292,14 @@ pub async fn run(state: &mut State, channels: &mut Channels<TestContext>) -> eyr
             } => {
                 info!(%height, %round, "Processing synced value");
 
-                let value = decode_value(value_bytes);
+                // Invalid if the value does not extend our chain, the node only halts once such a
+                // value is decided with a valid certificate
+                let proposed_value = state
+                    .process_synced_value(height, round, proposer, value_bytes)
+                    .await?;
 
                 // We send to consensus to see if it has been decided on
-                if reply
-                    .send(ProposedValue {
-                        height,
-                        round,
-                        valid_round: Round::Nil,
-                        proposer,
-                        value,
-                        validity: Validity::Valid,
-                    })
-                    .is_err()
-                {
+                if reply.send(proposed_value).is_err() {
                     error!("Failed to send ProcessSyncedValue reply");
                 }
             }
@@ -238,7 +336,21 @@ pub async fn run(state: &mut State, channels: &mut Channels<TestContext>) -> eyr
                 error!("RestreamProposal not implemented");
             }
 
-            AppMsg::ExtendVote { reply, .. } => {
+            // Called for our precommits for a value, the only votes we see before a decision
+            AppMsg::ExtendVote {
+                height,
+                round,
+                value_id,
+                reply,
+            } => {
+                state.record_precommit(height, round, value_id);
+
+                #[cfg(feature = "byzantine")]
+                if let Some(delay) = state.byzantine.vote_delay(state.current_height) {
+                    tracing::warn!(?delay, "Byzantine: delaying precommit");
+                    tokio::time::sleep(delay).await;
+                }
+
                 if reply.send(None).is_err() {
                     error!("Failed to send ExtendVote reply");
                 }
@@ -251,10 +363,16 @@ pub async fn run(state: &mut State, channels: &mut Channels<TestContext>) -> eyr
             }
 
             AppMsg::PeerJoined { peer_id } => {
+                if !state.peers.is_allowed(&peer_id) {
+                    warn!(%peer_id, "Rejected peer joined, ignoring it as per [p2p] config");
+                    continue;
+                }
+
                 info!(%peer_id, "Peer joined our local view of network");
 
-                // You might want to track connected peers in your state
-                state.peers.insert(peer_id);
+                // The channel API does not tell us who dialed whom
+                state.peers.insert(peer_id, PeerDirection::Unknown);
+                state.exchange_mempool(peer_id);
             }
 
             AppMsg::PeerLeft { peer_id } => {
@@ -266,8 +384,12 @@ pub async fn run(state: &mut State, channels: &mut Channels<TestContext>) -> eyr
         }
     }
 
+    if shutdown.is_triggered() {
+        return state.shutdown().await;
+    }
+
     // If we get there, it can only be because the channel we use to receive message
     // from consensus has been closed, meaning that the consensus actor has died.
     // We can do nothing but return an error here.
-    Err(eyre!("Consensus channel closed unexpectedly"))
+    Err(MikanError::ChannelClosed("consensus"))
 }
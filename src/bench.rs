@@ -0,0 +1,232 @@
+//! `mikan bench`: measures the store, block building, block validation and FRI proof generation
+//! on blob-sized data, so that performance regressions show up between releases.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use chrono::Utc;
+use clap::{Parser, ValueEnum};
+use eyre::Result;
+use frieda::api::generate_proof;
+use rand::{CryptoRng, Rng};
+
+use crate::blob::{Blob, BLOB_SIZE};
+use crate::block::{mock_make_validator_with_rng, Block};
+use crate::compression::CompressionConfig;
+use crate::consensus_params::ConsensusParams;
+use crate::finality_params::FinalityParams;
+use crate::governance::Governance;
+use crate::liveness::Liveness;
+use crate::metrics::DbMetrics;
+use crate::namespaces::NamespaceRegistry;
+use crate::rpc::sampling_pcs_config;
+use crate::store::{StorageConfig, Store};
+use crate::transactions::cache::ValidationCache;
+use crate::transactions::pool::TransactionPool;
+use crate::transactions::{Transaction, DEFAULT_MAX_BLOBS_PER_TX};
+use crate::types::height::Height;
+use crate::types::sign_bytes::DEFAULT_CHAIN_ID;
+use crate::types::signing::PrivateKey;
+use crate::types::validator_set::{Validator, ValidatorSet};
+use crate::upgrades::ProtocolSchedule;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Scenario {
+    All,
+    /// Writes and reads of blob-sized decided block data
+    Store,
+    /// Blocks built from a full mempool
+    BuildBlock,
+    /// Full validation of blocks, without cached signatures
+    ValidateBlock,
+    /// FRIEDA sampling proofs for a blob
+    Proof,
+}
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "mikan bench",
+    about = "Measure throughput and latency of the node hot paths"
+)]
+pub struct BenchCmd {
+    #[arg(value_enum, default_value_t = Scenario::All)]
+    pub scenario: Scenario,
+
+    /// Number of iterations of each scenario
+    #[arg(long, default_value_t = 16)]
+    pub iterations: usize,
+
+    /// Directory for the store scenario, a temporary one by default
+    #[arg(long)]
+    pub dir: Option<PathBuf>,
+}
+
+impl BenchCmd {
+    pub fn run(&self) -> Result<()> {
+        let iterations = self.iterations.max(1);
+        let mut rng = crate::rng::seeded_rng();
+
+        if matches!(self.scenario, Scenario::All | Scenario::Store) {
+            let dir = self.dir.clone().unwrap_or_else(|| {
+                std::env::temp_dir().join(format!("mikan-bench-{}", std::process::id()))
+            });
+            bench_store(&dir, iterations, &mut rng)?;
+            if self.dir.is_none() {
+                std::fs::remove_dir_all(&dir)?;
+            }
+        }
+
+        let needs_blocks = matches!(
+            self.scenario,
+            Scenario::All | Scenario::BuildBlock | Scenario::ValidateBlock
+        );
+        if needs_blocks {
+            let blocks = bench_build_block(iterations, &mut rng)?;
+            if self.scenario != Scenario::BuildBlock {
+                bench_validate_block(&blocks)?;
+            }
+        }
+
+        if matches!(self.scenario, Scenario::All | Scenario::Proof) {
+            bench_proof(iterations, &mut rng);
+        }
+
+        Ok(())
+    }
+}
+
+/// Prints the mean latency and throughput of `iterations` runs that took `elapsed` in total and
+/// processed `bytes` bytes.
+fn report(name: &str, iterations: usize, elapsed: Duration, bytes: usize) {
+    let mean = elapsed / iterations as u32;
+    let throughput = bytes as f64 / (1024.0 * 1024.0) / elapsed.as_secs_f64();
+    println!(
+        "{name:<16} {iterations:>6} iterations  mean {:>10.3} ms  {:>10.2} MiB/s  {:>10.2} ops/s",
+        mean.as_secs_f64() * 1000.0,
+        throughput,
+        iterations as f64 / elapsed.as_secs_f64(),
+    );
+}
+
+fn bench_store(dir: &Path, iterations: usize, rng: &mut impl Rng) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let store = Store::open(
+        dir.join("store.db"),
+        DbMetrics::new(),
+        &CompressionConfig::default(),
+        &StorageConfig::default(),
+    )?;
+
+    let values: Vec<Bytes> = (0..iterations)
+        .map(|_| Blob::random_with_rng(rng).data().to_vec().into())
+        .collect();
+
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(async {
+        let start = Instant::now();
+        for (height, value) in (1..).zip(&values) {
+            store
+                .store_decided_block_data(Height::new(height), value.clone())
+                .await?;
+        }
+        report(
+            "store write",
+            iterations,
+            start.elapsed(),
+            iterations * BLOB_SIZE,
+        );
+
+        let start = Instant::now();
+        for height in 1..=iterations as u64 {
+            store.get_decided_block(Height::new(height)).await?;
+        }
+        report(
+            "store read",
+            iterations,
+            start.elapsed(),
+            iterations * BLOB_SIZE,
+        );
+
+        Ok::<_, eyre::Report>(())
+    })
+}
+
+fn bench_build_block(iterations: usize, rng: &mut (impl Rng + CryptoRng)) -> Result<Vec<Block>> {
+    let pool = TransactionPool::with_capacity(DEFAULT_CHAIN_ID, iterations);
+    for _ in 0..iterations {
+        pool.add_transaction(Transaction::random_with_rng(rng))?;
+    }
+
+    let proposer = mock_make_validator_with_rng(rng);
+    let timestamp = Utc::now().timestamp() as u64;
+    let genesis = Block::genesis();
+
+    let start = Instant::now();
+    let blocks: Vec<Block> = (0..iterations)
+        .map(|_| {
+            let txs = pool.get_top_transaction().into_iter().collect();
+            Block::new(1, timestamp, genesis.hash(), proposer, txs)
+        })
+        .collect();
+    report(
+        "build block",
+        iterations,
+        start.elapsed(),
+        iterations * DEFAULT_MAX_BLOBS_PER_TX * BLOB_SIZE,
+    );
+
+    Ok(blocks)
+}
+
+fn bench_validate_block(blocks: &[Block]) -> Result<()> {
+    let genesis = Block::genesis();
+
+    // Blocks on top of genesis carry no last commit, any validator set will do
+    let validator = Validator::new(PrivateKey::generate(rand::thread_rng()).public_key(), 1);
+    let finality = FinalityParams::new(DEFAULT_CHAIN_ID, ValidatorSet::new([validator]));
+
+    let start = Instant::now();
+    for block in blocks {
+        let validation_cache = ValidationCache::new(DEFAULT_CHAIN_ID);
+        let namespaces = NamespaceRegistry::new();
+        if !block.is_valid(
+            1,
+            &genesis,
+            &[],
+            &validation_cache,
+            &namespaces,
+            &Governance::default(),
+            &Liveness::default(),
+            &ConsensusParams::default(),
+            DEFAULT_MAX_BLOBS_PER_TX,
+            &ProtocolSchedule::default(),
+            &finality,
+        )? {
+            eyre::bail!("Built an invalid block");
+        }
+    }
+    report(
+        "validate block",
+        blocks.len(),
+        start.elapsed(),
+        blocks.len() * DEFAULT_MAX_BLOBS_PER_TX * BLOB_SIZE,
+    );
+
+    Ok(())
+}
+
+fn bench_proof(iterations: usize, rng: &mut impl Rng) {
+    let blob = Blob::random_with_rng(rng);
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        generate_proof(&blob.padded(), Some(rng.gen()), sampling_pcs_config());
+    }
+    report(
+        "sampling proof",
+        iterations,
+        start.elapsed(),
+        iterations * BLOB_SIZE,
+    );
+}
@@ -1,38 +1,150 @@
-use bincode::{Decode, Encode};
+use std::borrow::Cow;
+
 use bytes::Bytes;
+use frieda::api::commit;
+use malachitebft_proto::{Error as ProtoError, Protobuf};
 use rand::{thread_rng, RngCore};
-use serde::{Deserialize, Serialize};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::error::BlobError;
+use crate::types::{proto, serde_hex};
+
 pub const BLOB_SIZE: usize = 245760 * 4;
-#[derive(Debug, Encode, Decode, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Serialized as a single hex string.
+///
+/// A blob keeps the length of its data, so that small payloads are not padded with zeros on the
+/// wire, in the WAL or in the store. The data availability scheme commits to the blob padded with
+/// zeros to [`BLOB_SIZE`] bytes, see [`Blob::padded`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
 pub struct Blob {
     /// Data of the blob
-    #[bincode(with_serde)]
+    #[serde(with = "serde_hex::bytes")]
     data: Bytes,
 }
+
+impl<'de> Deserialize<'de> for Blob {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = serde_hex::bytes::deserialize(deserializer)?;
+        Self::try_new(data).map_err(D::Error::custom)
+    }
+}
+
 impl Default for Blob {
+    /// An empty blob, committed to as [`BLOB_SIZE`] zeros.
     fn default() -> Self {
-        Self {
-            data: Bytes::from_static(&[0; BLOB_SIZE]),
-        }
+        Self { data: Bytes::new() }
     }
 }
 
 impl Blob {
-    pub fn new(data: Bytes) -> Self {
+    /// Wraps `data`, which must be at most [`BLOB_SIZE`] bytes long.
+    pub fn try_new(data: Bytes) -> Result<Self, BlobError> {
         if data.len() > BLOB_SIZE {
-            panic!("Data is too large");
+            return Err(BlobError::TooLarge {
+                max: BLOB_SIZE,
+                actual: data.len(),
+            });
         }
-        Self { data }
+        Ok(Self { data })
     }
     pub fn data(&self) -> &[u8] {
         self.data.as_ref()
     }
+    /// Data padded with zeros to [`BLOB_SIZE`] bytes, as committed to and sampled.
+    pub fn padded(&self) -> Cow<'_, [u8]> {
+        if self.data.len() == BLOB_SIZE {
+            return Cow::Borrowed(self.data());
+        }
+        let mut padded = Vec::with_capacity(BLOB_SIZE);
+        padded.extend_from_slice(self.data());
+        padded.resize(BLOB_SIZE, 0);
+        Cow::Owned(padded)
+    }
+    /// FRIEDA commitment to the padded blob data, as found in the block header
+    pub fn commitment(&self) -> [u8; 32] {
+        commit(&self.padded(), 4)
+    }
     pub fn random() -> Self {
-        let mut rng = thread_rng();
+        Self::random_with_rng(&mut thread_rng())
+    }
 
+    /// A full blob of random bytes drawn from `rng`, see [`crate::rng`] for replayable fixtures.
+    pub fn random_with_rng(rng: &mut impl RngCore) -> Self {
         let mut blob = vec![0; BLOB_SIZE];
         rng.fill_bytes(&mut blob);
 
-        Self::new(Bytes::from(blob))
+        Self {
+            data: Bytes::from(blob),
+        }
+    }
+}
+
+impl Protobuf for Blob {
+    type Proto = proto::Blob;
+
+    fn from_proto(proto: Self::Proto) -> Result<Self, ProtoError> {
+        Self::try_new(proto.data).map_err(|e| ProtoError::Other(e.to_string()))
+    }
+
+    fn to_proto(&self) -> Result<Self::Proto, ProtoError> {
+        Ok(proto::Blob {
+            data: self.data.clone(),
+        })
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Blob {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut data: Vec<u8> = u.arbitrary()?;
+        data.truncate(BLOB_SIZE);
+        Ok(Self {
+            data: Bytes::from(data),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_new_rejects_oversized_data() {
+        assert!(Blob::try_new(Bytes::from(vec![0; BLOB_SIZE])).is_ok());
+        assert!(matches!(
+            Blob::try_new(Bytes::from(vec![0; BLOB_SIZE + 1])),
+            Err(BlobError::TooLarge { actual, .. }) if actual == BLOB_SIZE + 1
+        ));
+    }
+
+    #[test]
+    fn deserialize_rejects_oversized_data() {
+        let json = format!("\"{}\"", "00".repeat(BLOB_SIZE + 1));
+        let err = serde_json::from_str::<Blob>(&json).unwrap_err();
+        assert!(err.to_string().contains("Blob too large"));
+    }
+
+    #[test]
+    fn encodes_without_padding() {
+        let blob = Blob::try_new(Bytes::from_static(b"rollup batch")).unwrap();
+        assert_eq!(blob.to_proto().unwrap().data.len(), 12);
+        assert_eq!(serde_json::to_string(&blob).unwrap().len(), 2 + 2 * 12);
+        assert_eq!(blob.padded().len(), BLOB_SIZE);
+        assert_eq!(&blob.padded()[..12], b"rollup batch");
+        assert!(Blob::default().data().is_empty());
+    }
+
+    #[test]
+    fn commits_to_the_padded_data() {
+        let mut padded = b"rollup batch".to_vec();
+        padded.resize(BLOB_SIZE, 0);
+        assert_eq!(
+            Blob::try_new(Bytes::from_static(b"rollup batch"))
+                .unwrap()
+                .commitment(),
+            Blob::try_new(Bytes::from(padded)).unwrap().commitment()
+        );
     }
 }
@@ -1,24 +1,53 @@
-use bincode::config::standard;
-use bincode::{Decode, Encode};
+use std::sync::OnceLock;
+use std::time::Duration;
+
 use bytes::Bytes;
-use chrono::Utc;
-use eyre::Ok;
-use frieda::api::commit;
+use malachitebft_core_types::CommitCertificate;
+use malachitebft_proto::{Error as ProtoError, Protobuf};
 use rand::{thread_rng, RngCore};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use rs_merkle::{algorithms::Sha256, MerkleTree};
+use rs_merkle::{algorithms::Sha256, MerkleProof, MerkleTree};
+use serde::{Deserialize, Serialize};
 use tracing::{error, info};
 
-use crate::malachite_types::{address::Address, signing::PrivateKey};
-use crate::transactions::Transaction;
+use crate::consensus_params::ConsensusParams;
+use crate::finality_params::FinalityParams;
+use crate::governance::Governance;
+use crate::liveness::Liveness;
+use crate::namespaces::NamespaceRegistry;
+use crate::shares::{compute_share_root, SHARES_VERSION};
+use crate::transactions::{cache::ValidationCache, Transaction};
+use crate::types::codec::proto::{decode_certificate, decode_hash, encode_certificate};
+use crate::types::{
+    address::Address, certificate, context::TestContext, genesis::DEFAULT_GENESIS_TIME, proto,
+    serde_hex, signing::PrivateKey,
+};
+use crate::upgrades::{Feature, ProtocolSchedule};
 use crate::{blob::Blob, error::BlockError, header::Header};
 
-#[derive(Debug, Encode, Decode, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Block {
     /// Block Header.
     header: Header,
     /// list of blobs in this block.
     transactions: Vec<Transaction>,
+    /// Commitments of the blobs of the transactions, in order, the leaves of the header `da_root`
+    #[serde(with = "serde_hex::hashes")]
+    blob_commitments: Vec<[u8; 32]>,
+    /// Certificate that decided the parent block, `None` on top of genesis, see
+    /// [`FinalityParams`]
+    #[serde(default, with = "certificate::option")]
+    last_commit: Option<CommitCertificate<TestContext>>,
+    #[serde(skip)]
+    derived: Derived,
+}
+
+/// Values derived from the block content, computed at most once since validation needs them
+/// repeatedly. Must be reset with [`Block::recompute`] whenever the content changes.
+#[derive(Debug, Default)]
+struct Derived {
+    tx_tree_root: OnceLock<[u8; 32]>,
+    block_hash: OnceLock<[u8; 32]>,
 }
 
 impl Block {
@@ -30,43 +59,68 @@ impl Block {
         proposer_address: Address,
         transactions: Vec<Transaction>,
     ) -> Self {
-        let tx_commitment = if transactions.is_empty() {
-            [0; 32]
-        } else if transactions.len() == 1 {
-            transactions[0].hash()
-        } else {
-            let leaves: Vec<[u8; 32]> = transactions.iter().map(|tx| tx.hash()).collect();
-
-            let merkle_tree = MerkleTree::<Sha256>::from_leaves(&leaves);
+        let tx_commitment =
+            compute_tx_tree_root(&transactions).expect("Merkle root of non-empty leaves");
 
-            merkle_tree.root().unwrap()
-        };
-
-        let da_commitment = transactions
-            .par_iter()
-            .flat_map(|tx| tx.data())
-            .map(|data| commit(data.data(), 4))
+        // Computed when the transactions were built, or checked by `is_valid`
+        let blob_commitments = transactions
+            .iter()
+            .flat_map(|tx| tx.blob_commitments())
+            .copied()
             .collect::<Vec<[u8; 32]>>();
         let header = Header::new(
             block_number,
             timestamp,
             tx_commitment,
             proposer_address,
-            da_commitment.try_into().unwrap_or_default(),
+            compute_da_root(&blob_commitments),
+            blob_commitments.len() as u32,
             parent_hash,
         );
+        let derived = Derived {
+            tx_tree_root: OnceLock::from(tx_commitment),
+            block_hash: OnceLock::from(header.block_hash()),
+        };
         Self {
             header,
             transactions,
+            blob_commitments,
+            last_commit: None,
+            derived,
         }
     }
+
+    /// Embeds the certificate that decided the parent block. It is not part of the header, but
+    /// of the encoded block that consensus decides on.
+    pub fn with_last_commit(mut self, certificate: CommitCertificate<TestContext>) -> Self {
+        self.last_commit = Some(certificate);
+        self
+    }
+
+    /// Sets the protocol version of the header, see [`crate::upgrades`], and the share root it
+    /// commits to from [`SHARES_VERSION`] on.
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.header.version = version;
+        self.header.share_root = if version >= SHARES_VERSION {
+            compute_share_root(&self.blobs())
+        } else {
+            [0; 32]
+        };
+        self.header.block_hash = self.header.compute_block_hash();
+        self.derived.block_hash = OnceLock::from(self.header.block_hash);
+        self
+    }
+
+    pub fn last_commit(&self) -> Option<&CommitCertificate<TestContext>> {
+        self.last_commit.as_ref()
+    }
     pub fn parent_hash(&self) -> [u8; 32] {
         self.header.parent_hash()
     }
     pub fn blobs(&self) -> Vec<Blob> {
         self.transactions
             .iter()
-            .flat_map(|tx| tx.data().clone())
+            .flat_map(|tx| tx.data().iter().cloned())
             .collect::<Vec<_>>()
     }
 
@@ -74,16 +128,67 @@ impl Block {
         self.header.block_hash()
     }
 
+    /// Size of the protobuf encoding, as proposed and stored.
+    pub fn encoded_len(&self) -> usize {
+        self.to_proto()
+            .map_or(usize::MAX, |proto| prost::Message::encoded_len(&proto))
+    }
+
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Genesis block of a genesis file without `genesis_time`, see [`Genesis::block`].
+    ///
+    /// [`Genesis::block`]: crate::types::genesis::Genesis::block
     pub fn genesis() -> Self {
-        Self::new(0, 69420, [0; 32], Address::default(), vec![])
+        Self::new(0, DEFAULT_GENESIS_TIME, [0; 32], Address::default(), vec![])
     }
-    pub fn to_bytes(&self) -> eyre::Result<Bytes> {
-        let bytes = bincode::encode_to_vec(self, standard())?;
-        Ok(Bytes::from(bytes))
+    pub fn to_bytes(&self) -> Result<Bytes, ProtoError> {
+        Protobuf::to_bytes(self)
     }
 
-    pub fn is_valid(&self, height: u64, prev_block: &Block) -> eyre::Result<bool> {
+    /// Checks the block on top of `prev_block`. Transactions are checked in parallel, those
+    /// already in `validation_cache` skip the signature check.
+    ///
+    /// `recent_timestamps` are the timestamps of the last [`ConsensusParams::median_time_span`]
+    /// blocks, the timestamp must not drift more than [`ConsensusParams::max_timestamp_drift`]
+    /// ahead of their median. Without recent blocks, on top of genesis, any timestamp not behind
+    /// the parent is accepted.
+    ///
+    /// The block must fit in the byte limit `governance` sets for `height`, its transactions must
+    /// pay for their gas and fit in the block gas limit, and carry at most `max_blobs_per_tx`
+    /// blobs each, of at most [`ConsensusParams::max_blob_bytes`].
+    ///
+    /// Votes must be checked by `governance` and unjails by `liveness`.
+    ///
+    /// The header must carry the version `protocol` schedules for `height`, and only the features
+    /// active at `height` are checked.
+    ///
+    /// The last commit must decide `prev_block` under `finality`, see [`FinalityParams::verify`].
+    pub fn is_valid(
+        &self,
+        height: u64,
+        prev_block: &Block,
+        recent_timestamps: &[u64],
+        validation_cache: &ValidationCache,
+        namespaces: &NamespaceRegistry,
+        governance: &Governance,
+        liveness: &Liveness,
+        consensus: &ConsensusParams,
+        max_blobs_per_tx: usize,
+        protocol: &ProtocolSchedule,
+        finality: &FinalityParams,
+    ) -> Result<bool, BlockError> {
         info!("Validating block at height {}", height);
+        if let Err(e) = protocol.check_version(height, self.header.version) {
+            error!("{e}");
+            return Ok(false);
+        }
         let expected = prev_block.hash();
         let actual = self.parent_hash();
         if expected != actual {
@@ -104,17 +209,32 @@ impl Block {
             return Ok(false);
         }
 
-        if self.header.timestamp < Utc::now().timestamp() as u64 - 600
-            || self.header.timestamp > Utc::now().timestamp() as u64 + 600
-        {
+        // Never the local clock, which differs between validators. Without recent blocks, on top
+        // of genesis, the first block sets the clock of the chain.
+        if let Some(median) = median_timestamp(recent_timestamps) {
+            let upper_bound = median.saturating_add(consensus.max_timestamp_drift);
+            if self.header.timestamp > upper_bound {
+                error!(
+                    "Timestamp too far ahead: median of recent blocks {}, upper bound {}, current timestamp {}",
+                    median, upper_bound, self.header.timestamp
+                );
+                return Ok(false);
+            }
+        }
+        // Parameters voted by governance, the initial ones until a change is approved
+        let params = governance.params_at(height);
+        let size = self.encoded_len() as u64;
+        if size > params.max_block_bytes {
             error!(
-                "Timestamp out of range: lower bound {}, upper bound {}, current timestamp {}",
-                Utc::now().timestamp() as u64 - 600,
-                Utc::now().timestamp() as u64 + 600,
-                self.header.timestamp
+                "Block of {} bytes, at most {} are allowed",
+                size, params.max_block_bytes
             );
             return Ok(false);
         }
+        if let Err(e) = finality.verify(height, self.last_commit(), prev_block) {
+            error!("Last commit: {e}");
+            return Ok(false);
+        }
         let expected = self.tx_tree_root()?;
         let actual = self.header.tx_commitment;
         if expected != actual {
@@ -124,24 +244,122 @@ impl Block {
             );
             return Ok(false);
         }
-        let expected_commitments = if self.blobs().is_empty() {
-            vec![[0; 32]; 4]
+        // Computed once, for the DA commitment and the commitments declared by the transactions
+        let blob_commitments = self
+            .transactions
+            .par_iter()
+            .flat_map_iter(|tx| tx.data())
+            .map(Blob::commitment)
+            .collect::<Vec<[u8; 32]>>();
+
+        if !validation_cache.validate_all_with_commitments(&self.transactions, &blob_commitments) {
+            error!("Block contains an invalid transaction");
+            return Ok(false);
+        }
+
+        if protocol.is_active(Feature::Namespaces, height) {
+            if let Err(e) = namespaces.check_all(&self.transactions) {
+                error!("Block breaks a namespace policy: {e}");
+                return Ok(false);
+            }
+        }
+
+        if let Some(tx) = self
+            .transactions
+            .iter()
+            .find(|tx| tx.data().len() > max_blobs_per_tx)
+        {
+            error!(
+                "Transaction {} carries {} blobs, at most {} are allowed",
+                hex::encode(tx.hash()),
+                tx.data().len(),
+                max_blobs_per_tx
+            );
+            return Ok(false);
+        }
+        if let Some(blob) = self
+            .transactions
+            .iter()
+            .flat_map(|tx| tx.data())
+            .find(|blob| blob.data().len() > consensus.max_blob_bytes)
+        {
+            error!(
+                "Blob of {} bytes, at most {} are allowed",
+                blob.data().len(),
+                consensus.max_blob_bytes
+            );
+            return Ok(false);
+        }
+
+        if protocol.is_active(Feature::GasMetering, height) {
+            if let Err(e) = params.gas.block_gas_used(&self.transactions) {
+                error!("Block breaks the gas schedule: {e}");
+                return Ok(false);
+            }
+        }
+
+        if protocol.is_active(Feature::Governance, height) {
+            if let Err(e) = governance.check_all(&self.transactions, height) {
+                error!("Block carries an invalid vote: {e}");
+                return Ok(false);
+            }
+        } else if let Some(tx) = self
+            .transactions
+            .iter()
+            .find(|tx| tx.governance().is_some())
+        {
+            error!(
+                "Transaction {} votes for a parameter change before governance is active",
+                hex::encode(tx.hash())
+            );
+            return Ok(false);
+        }
+
+        if protocol.is_active(Feature::Jailing, height) {
+            if let Err(e) = liveness.check_all(&self.transactions, height) {
+                error!("Block carries an invalid unjail: {e}");
+                return Ok(false);
+            }
+        } else if let Some(tx) = self.transactions.iter().find(|tx| tx.unjail()) {
+            error!(
+                "Transaction {} unjails before jailing is active",
+                hex::encode(tx.hash())
+            );
+            return Ok(false);
+        }
+
+        if blob_commitments != self.blob_commitments {
+            error!("Blob commitments do not match the blobs of the block");
+            return Ok(false);
+        }
+
+        let expected = compute_da_root(&blob_commitments);
+        let actual = self.header.da_root;
+        if expected != actual || blob_commitments.len() != self.header.blob_count as usize {
+            error!(
+                "DA root mismatch: expected {:?} over {} blobs, got {:?} over {}",
+                expected,
+                blob_commitments.len(),
+                actual,
+                self.header.blob_count
+            );
+            return Ok(false);
+        }
+
+        let expected = if self.header.version >= SHARES_VERSION {
+            compute_share_root(&self.blobs())
         } else {
-            self.blobs()
-                .par_iter()
-                .map(|blob| commit(blob.data(), 4))
-                .collect::<Vec<[u8; 32]>>()
+            [0; 32]
         };
-        let actual_commitments = self.header.da_commitment;
-        if expected_commitments != actual_commitments {
+        if expected != self.header.share_root {
             error!(
-                "DA commitment mismatch: expected {:?}, got {:?}",
-                expected_commitments, actual_commitments
+                "Share root mismatch: expected {:?}, got {:?}",
+                expected, self.header.share_root
             );
             return Ok(false);
         }
 
-        let expected = self.header.compute_block_hash();
+        let expected = self.compute_block_hash();
         let actual = self.header.block_hash;
         if expected != actual {
             error!(
@@ -154,33 +372,284 @@ impl Block {
         Ok(true)
     }
 
-    /// Merklize the raw blob data
-    pub fn tx_tree_root(&self) -> eyre::Result<[u8; 32]> {
-        if self.transactions.is_empty() {
-            Ok([0; 32])
-        } else if self.transactions.len() == 1 {
-            Ok(self.transactions[0].hash())
-        } else {
-            let leaves: Vec<[u8; 32]> = self.transactions.iter().map(|tx| tx.hash()).collect();
+    /// Merkle root of the transaction hashes, computed once
+    pub fn tx_tree_root(&self) -> Result<[u8; 32], BlockError> {
+        if let Some(root) = self.derived.tx_tree_root.get() {
+            return Ok(*root);
+        }
 
-            let merkle_tree = MerkleTree::<Sha256>::from_leaves(&leaves);
+        let root = compute_tx_tree_root(&self.transactions)?;
+        Ok(*self.derived.tx_tree_root.get_or_init(|| root))
+    }
 
-            merkle_tree.root().ok_or(BlockError::MerkleTreeError.into())
+    /// Proof that the transaction at `index` is committed to by `tx_commitment`, to be checked
+    /// against [`Self::tx_tree_root`] with the hash of the transaction as leaf.
+    pub fn proof_of_transaction(&self, index: usize) -> Option<MerkleProof<Sha256>> {
+        if index >= self.transactions.len() {
+            return None;
         }
+
+        let leaves: Vec<[u8; 32]> = self.transactions.iter().map(|tx| tx.hash()).collect();
+        Some(MerkleTree::<Sha256>::from_leaves(&leaves).proof(&[index]))
+    }
+
+    /// Merkle root of [`Self::blob_commitments`], as claimed by the header.
+    pub fn da_root(&self) -> [u8; 32] {
+        self.header.da_root
+    }
+
+    /// FRIEDA commitments of the blobs of the transactions, in order.
+    pub fn blob_commitments(&self) -> &[[u8; 32]] {
+        &self.blob_commitments
+    }
+
+    /// Proof that the blob commitment at `index` in [`Self::blob_commitments`] is committed to
+    /// by [`Self::da_root`].
+    pub fn proof_of_blob(&self, index: usize) -> Option<MerkleProof<Sha256>> {
+        if index >= self.blob_commitments.len() {
+            return None;
+        }
+
+        Some(MerkleTree::<Sha256>::from_leaves(&self.blob_commitments).proof(&[index]))
+    }
+
+    /// Hash of the header content, computed once. Compare with [`Self::hash`], the hash the
+    /// header claims.
+    pub fn compute_block_hash(&self) -> [u8; 32] {
+        *self
+            .derived
+            .block_hash
+            .get_or_init(|| self.header.compute_block_hash())
+    }
+
+    /// Drops the memoized derived values and computes them again, to be called after mutating
+    /// the block.
+    pub fn recompute(&mut self) -> Result<(), BlockError> {
+        self.derived = Derived::default();
+        self.tx_tree_root()?;
+        self.compute_block_hash();
+        Ok(())
+    }
+}
+
+/// Median of `timestamps`, the lower one for an even count. Deterministic, unlike the local
+/// clock, and robust to a minority of proposers with skewed clocks.
+pub fn median_timestamp(timestamps: &[u64]) -> Option<u64> {
+    if timestamps.is_empty() {
+        return None;
+    }
+
+    let mut sorted = timestamps.to_vec();
+    sorted.sort_unstable();
+    Some(sorted[(sorted.len() - 1) / 2])
+}
+
+/// Merkle root of the blob commitments, zero without blobs.
+pub fn compute_da_root(blob_commitments: &[[u8; 32]]) -> [u8; 32] {
+    MerkleTree::<Sha256>::from_leaves(blob_commitments)
+        .root()
+        .unwrap_or_default()
+}
+
+/// Merklize the transaction hashes
+fn compute_tx_tree_root(transactions: &[Transaction]) -> Result<[u8; 32], BlockError> {
+    if transactions.is_empty() {
+        Ok([0; 32])
+    } else if transactions.len() == 1 {
+        Ok(transactions[0].hash())
+    } else {
+        let leaves: Vec<[u8; 32]> = transactions.iter().map(|tx| tx.hash()).collect();
+
+        let merkle_tree = MerkleTree::<Sha256>::from_leaves(&leaves);
+
+        merkle_tree.root().ok_or(BlockError::MerkleTreeError)
+    }
+}
+
+impl Protobuf for Block {
+    type Proto = proto::Block;
+
+    fn from_proto(proto: Self::Proto) -> Result<Self, ProtoError> {
+        Ok(Self {
+            header: proto
+                .header
+                .ok_or_else(|| ProtoError::missing_field::<Self::Proto>("header"))
+                .and_then(Header::from_proto)?,
+            transactions: proto
+                .transactions
+                .into_iter()
+                .map(Transaction::from_proto)
+                .collect::<Result<_, _>>()?,
+            blob_commitments: proto
+                .blob_commitments
+                .iter()
+                .map(|commitment| decode_hash(commitment))
+                .collect::<Result<_, _>>()?,
+            last_commit: proto.last_commit.map(decode_certificate).transpose()?,
+            derived: Derived::default(),
+        })
+    }
+
+    fn to_proto(&self) -> Result<Self::Proto, ProtoError> {
+        Ok(proto::Block {
+            header: Some(self.header.to_proto()?),
+            transactions: self
+                .transactions
+                .iter()
+                .map(Transaction::to_proto)
+                .collect::<Result<_, _>>()?,
+            blob_commitments: self
+                .blob_commitments
+                .iter()
+                .map(|commitment| commitment.to_vec().into())
+                .collect(),
+            last_commit: self
+                .last_commit
+                .as_ref()
+                .map(encode_certificate)
+                .transpose()?,
+        })
+    }
+}
+
+/// A block whose header matches its transactions, with an arbitrary last commit.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Block {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut transactions = Vec::new();
+        for _ in 0..u.int_in_range(0..=8)? {
+            transactions.push(u.arbitrary()?);
+        }
+        let block = Block::new(
+            u.arbitrary()?,
+            u.arbitrary()?,
+            u.arbitrary()?,
+            Address::new(u.arbitrary()?),
+            transactions,
+        )
+        .with_version(u.arbitrary()?);
+
+        Ok(if u.arbitrary()? {
+            block.with_last_commit(crate::fuzz::arbitrary_certificate(u)?)
+        } else {
+            block
+        })
     }
 }
 
 pub fn mock_make_validator() -> Address {
-    let mut rng = thread_rng();
+    mock_make_validator_with_rng(&mut thread_rng())
+}
+
+/// Address of a random validator key drawn from `rng`.
+pub fn mock_make_validator_with_rng(rng: &mut impl RngCore) -> Address {
     let mut bytes = [0u8; 32];
     rng.fill_bytes(&mut bytes);
     let sk = PrivateKey::from(bytes);
     Address::from_public_key(&sk.public_key())
 }
 
+/// What to propose when the mempool is empty.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyBlockPolicy {
+    /// Always propose, with an empty block if needed, so that the chain keeps moving
+    #[default]
+    Always,
+    /// Only propose an empty block once the parent is `heartbeat_interval_secs` old. Before that,
+    /// the proposer waits for a transaction as long as consensus allows, and does not answer
+    /// consensus without one, which times out the round and moves on.
+    Heartbeat,
+}
+
+/// Maximum encoded size of a proposed block when the configuration does not set one, the
+/// default `streaming.max_stream_bytes`.
+pub const DEFAULT_MAX_BLOCK_BYTES: usize = 64 * 1024 * 1024;
+
+/// Room left in a block for everything but its transactions: header, blob commitments and
+/// certificate of the parent.
+pub const BLOCK_OVERHEAD_BYTES: usize = 64 * 1024;
+
+/// `[block]` section of the node configuration.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BlockConfig {
+    pub empty_blocks: EmptyBlockPolicy,
+    /// With the `heartbeat` policy, maximum time in seconds between two blocks
+    pub heartbeat_interval_secs: u64,
+    /// Target time in milliseconds between two blocks. Proposers wait this long after the
+    /// previous decision before answering consensus, within the consensus timeout.
+    pub target_block_time_ms: u64,
+    /// Maximum encoded size in bytes of the blocks this node proposes, to be kept below
+    /// `streaming.max_stream_bytes`
+    pub max_block_bytes: usize,
+    /// Maximum encoded size in bytes of a transaction accepted by the RPC server and the mempool,
+    /// `max_block_bytes` minus [`BLOCK_OVERHEAD_BYTES`] if unset
+    pub max_tx_bytes: Option<usize>,
+}
+
+impl Default for BlockConfig {
+    fn default() -> Self {
+        Self {
+            empty_blocks: EmptyBlockPolicy::Always,
+            heartbeat_interval_secs: 30,
+            target_block_time_ms: 1000,
+            max_block_bytes: DEFAULT_MAX_BLOCK_BYTES,
+            max_tx_bytes: None,
+        }
+    }
+}
+
+impl BlockConfig {
+    /// Largest transaction that fits in a block, see [`Self::max_tx_bytes`].
+    pub fn max_tx_bytes(&self) -> usize {
+        let fits = self.max_block_bytes.saturating_sub(BLOCK_OVERHEAD_BYTES);
+        self.max_tx_bytes.map_or(fits, |max| max.min(fits))
+    }
+
+    pub fn target_block_time(&self) -> Duration {
+        Duration::from_millis(self.target_block_time_ms)
+    }
+
+    /// Whether an empty block should be proposed on top of a parent with `parent_timestamp`.
+    pub fn propose_empty(&self, parent_timestamp: u64, now: u64) -> bool {
+        match self.empty_blocks {
+            EmptyBlockPolicy::Always => true,
+            EmptyBlockPolicy::Heartbeat => {
+                now >= parent_timestamp.saturating_add(self.heartbeat_interval_secs)
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::consensus_params::DEFAULT_MAX_TIMESTAMP_DRIFT;
+    use crate::gas::GasSchedule;
+    use crate::governance::GovernanceConfig;
+    use crate::transactions::DEFAULT_MAX_BLOBS_PER_TX;
+    use crate::types::sign_bytes::DEFAULT_CHAIN_ID;
+    use crate::types::validator_set::{Validator, ValidatorSet};
+    use chrono::Utc;
+
+    /// Finality of blocks on top of genesis, which carry no last commit
+    fn finality() -> FinalityParams {
+        let validator = Validator::new(PrivateKey::generate(thread_rng()).public_key(), 1);
+        FinalityParams::new(DEFAULT_CHAIN_ID, ValidatorSet::new([validator]))
+    }
+
+    #[test]
+    fn heartbeat_policy_waits_for_interval() {
+        let config = BlockConfig {
+            empty_blocks: EmptyBlockPolicy::Heartbeat,
+            heartbeat_interval_secs: 30,
+            ..BlockConfig::default()
+        };
+        assert!(!config.propose_empty(100, 129));
+        assert!(config.propose_empty(100, 130));
+        assert!(BlockConfig::default().propose_empty(100, 100));
+    }
 
     #[test]
     fn test_block_is_valid() {
@@ -192,6 +661,307 @@ mod tests {
             mock_make_validator(),
             vec![Transaction::random()],
         );
-        assert!(block.is_valid(1, &prev_block).unwrap());
+        let validation_cache = ValidationCache::new(DEFAULT_CHAIN_ID);
+        let namespaces = NamespaceRegistry::new();
+        let finality = finality();
+        assert!(block
+            .is_valid(
+                1,
+                &prev_block,
+                &[],
+                &validation_cache,
+                &namespaces,
+                &Governance::default(),
+                &Liveness::default(),
+                &ConsensusParams::default(),
+                DEFAULT_MAX_BLOBS_PER_TX,
+                &ProtocolSchedule::default(),
+                &finality
+            )
+            .unwrap());
+        assert_eq!(validation_cache.len(), 1);
+
+        // Transactions must fit in the block gas limit
+        let validator_set = ValidatorSet::new([Validator::new(
+            PrivateKey::generate(thread_rng()).public_key(),
+            1,
+        )]);
+        let gas = Governance::new(
+            &validator_set,
+            &GovernanceConfig::default(),
+            &ConsensusParams::default(),
+            &GasSchedule {
+                block_gas_limit: block.transactions()[0].gas_limit() - 1,
+                ..Default::default()
+            },
+        );
+        assert!(!block
+            .is_valid(
+                1,
+                &prev_block,
+                &[],
+                &validation_cache,
+                &namespaces,
+                &gas,
+                &Liveness::default(),
+                &ConsensusParams::default(),
+                DEFAULT_MAX_BLOBS_PER_TX,
+                &ProtocolSchedule::default(),
+                &finality
+            )
+            .unwrap());
+        // Unless gas metering is not active yet
+        let protocol = ProtocolSchedule {
+            forks: [(Feature::GasMetering, 2)].into(),
+            ..Default::default()
+        };
+        assert!(block
+            .is_valid(
+                1,
+                &prev_block,
+                &[],
+                &validation_cache,
+                &namespaces,
+                &gas,
+                &Liveness::default(),
+                &ConsensusParams::default(),
+                DEFAULT_MAX_BLOBS_PER_TX,
+                &protocol,
+                &finality
+            )
+            .unwrap());
+
+        // The block must fit in the size voted by governance
+        let governance = Governance::new(
+            &validator_set,
+            &GovernanceConfig::default(),
+            &ConsensusParams {
+                max_block_bytes: block.encoded_len() as u64 - 1,
+                ..Default::default()
+            },
+            &GasSchedule::default(),
+        );
+        assert!(!block
+            .is_valid(
+                1,
+                &prev_block,
+                &[],
+                &validation_cache,
+                &namespaces,
+                &governance,
+                &Liveness::default(),
+                &ConsensusParams::default(),
+                DEFAULT_MAX_BLOBS_PER_TX,
+                &ProtocolSchedule::default(),
+                &finality
+            )
+            .unwrap());
+
+        // Blobs must fit in the blob size of the genesis file
+        let consensus = ConsensusParams {
+            max_blob_bytes: block.transactions()[0].data()[0].data().len() - 1,
+            ..Default::default()
+        };
+        assert!(!block
+            .is_valid(
+                1,
+                &prev_block,
+                &[],
+                &validation_cache,
+                &namespaces,
+                &Governance::default(),
+                &Liveness::default(),
+                &consensus,
+                DEFAULT_MAX_BLOBS_PER_TX,
+                &ProtocolSchedule::default(),
+                &finality
+            )
+            .unwrap());
+
+        // The header must carry the scheduled protocol version
+        let protocol = ProtocolSchedule {
+            protocol_version: 0,
+            ..Default::default()
+        };
+        assert!(!block
+            .is_valid(
+                1,
+                &prev_block,
+                &[],
+                &validation_cache,
+                &namespaces,
+                &Governance::default(),
+                &Liveness::default(),
+                &ConsensusParams::default(),
+                DEFAULT_MAX_BLOBS_PER_TX,
+                &protocol,
+                &finality
+            )
+            .unwrap());
+
+        // Transactions signed for another chain are rejected
+        let validation_cache = ValidationCache::new("other-chain");
+        assert!(!block
+            .is_valid(
+                1,
+                &prev_block,
+                &[],
+                &validation_cache,
+                &namespaces,
+                &Governance::default(),
+                &Liveness::default(),
+                &ConsensusParams::default(),
+                DEFAULT_MAX_BLOBS_PER_TX,
+                &ProtocolSchedule::default(),
+                &finality
+            )
+            .unwrap());
+    }
+
+    #[test]
+    fn timestamp_is_bounded_by_median_of_recent_blocks() {
+        assert_eq!(median_timestamp(&[]), None);
+        assert_eq!(median_timestamp(&[30, 10, 20]), Some(20));
+        assert_eq!(median_timestamp(&[10, 20, 30, 40]), Some(20));
+
+        let now = Utc::now().timestamp() as u64;
+        let prev_block = Block::new(0, now, [0; 32], Address::default(), vec![]);
+        let validation_cache = ValidationCache::new(DEFAULT_CHAIN_ID);
+        let namespaces = NamespaceRegistry::new();
+        let finality = finality();
+        let recent = [now - 2, now - 1, now];
+
+        let block = |timestamp| {
+            Block::new(
+                1,
+                timestamp,
+                prev_block.hash(),
+                mock_make_validator(),
+                vec![],
+            )
+        };
+        assert!(block(now + 5)
+            .is_valid(
+                1,
+                &prev_block,
+                &recent,
+                &validation_cache,
+                &namespaces,
+                &Governance::default(),
+                &Liveness::default(),
+                &ConsensusParams::default(),
+                DEFAULT_MAX_BLOBS_PER_TX,
+                &ProtocolSchedule::default(),
+                &finality
+            )
+            .unwrap());
+        assert!(!block(now - 1)
+            .is_valid(
+                1,
+                &prev_block,
+                &recent,
+                &validation_cache,
+                &namespaces,
+                &Governance::default(),
+                &Liveness::default(),
+                &ConsensusParams::default(),
+                DEFAULT_MAX_BLOBS_PER_TX,
+                &ProtocolSchedule::default(),
+                &finality
+            )
+            .unwrap());
+        assert!(!block(now + 2 * DEFAULT_MAX_TIMESTAMP_DRIFT)
+            .is_valid(
+                1,
+                &prev_block,
+                &recent,
+                &validation_cache,
+                &namespaces,
+                &Governance::default(),
+                &Liveness::default(),
+                &ConsensusParams::default(),
+                DEFAULT_MAX_BLOBS_PER_TX,
+                &ProtocolSchedule::default(),
+                &finality
+            )
+            .unwrap());
+    }
+
+    #[test]
+    fn proofs_verify_against_commitments() {
+        let tx = Transaction::random();
+        let block = Block::new(1, 0, [0; 32], mock_make_validator(), vec![tx.clone()]);
+
+        let proof = block.proof_of_transaction(0).unwrap();
+        let root = block.tx_tree_root().unwrap();
+        assert!(proof.verify(root, &[0], &[tx.hash()], 1));
+        assert!(block.proof_of_transaction(1).is_none());
+
+        let commitments = block.blob_commitments();
+        assert_eq!(block.header().blob_count, 4);
+        let proof = block.proof_of_blob(2).unwrap();
+        assert!(proof.verify(block.da_root(), &[2], &[commitments[2]], 4));
+        assert!(!proof.verify(block.da_root(), &[2], &[commitments[1]], 4));
+        assert!(block.proof_of_blob(4).is_none());
+    }
+
+    #[test]
+    fn test_block_protobuf_roundtrip() {
+        let block = Block::new(
+            1,
+            Utc::now().timestamp() as u64,
+            Block::genesis().hash(),
+            mock_make_validator(),
+            vec![Transaction::random()],
+        );
+
+        let bytes = block.to_bytes().unwrap();
+        let decoded = <Block as Protobuf>::from_bytes(&bytes).unwrap();
+
+        assert_eq!(decoded.hash(), block.hash());
+        assert_eq!(decoded.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_derived_values_are_memoized() {
+        let mut block = Block::new(
+            1,
+            Utc::now().timestamp() as u64,
+            Block::genesis().hash(),
+            mock_make_validator(),
+            vec![Transaction::random(), Transaction::random()],
+        );
+        assert_eq!(block.tx_tree_root().unwrap(), block.header.tx_commitment);
+        assert_eq!(block.compute_block_hash(), block.hash());
+
+        block.transactions.pop();
+        // Stale until recomputed
+        assert_eq!(block.tx_tree_root().unwrap(), block.header.tx_commitment);
+
+        block.recompute().unwrap();
+        assert_eq!(block.tx_tree_root().unwrap(), block.transactions[0].hash());
+    }
+
+    #[test]
+    fn test_block_json_roundtrip() {
+        let block = Block::new(
+            1,
+            1_700_000_000,
+            Block::genesis().hash(),
+            mock_make_validator(),
+            vec![Transaction::random()],
+        );
+
+        let json = serde_json::to_value(&block).unwrap();
+        assert_eq!(
+            json["header"]["block_hash"],
+            serde_json::json!(hex::encode(block.hash()))
+        );
+        assert!(json["header"]["da_root"].is_string());
+        assert!(json["blob_commitments"][0].is_string());
+        assert!(json["transactions"][0]["data"][0].is_string());
+
+        let decoded: Block = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.to_bytes().unwrap(), block.to_bytes().unwrap());
     }
 }
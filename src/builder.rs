@@ -0,0 +1,155 @@
+//! Optional external block builder, to experiment with specialized blob aggregators without
+//! forking the node.
+//!
+//! With a `url` in the `[builder]` section of the configuration, a proposer POSTs a
+//! [`BuildRequest`] to the builder whenever consensus asks it for a value, and proposes the
+//! transactions of the [`BuildResponse`]. The node still builds the header and attaches the
+//! parent certificate, so the builder only picks the payload. The transactions go through the
+//! checks of those taken from the pool, and the node falls back to building from its own pool
+//! when the builder fails, does not answer within `timeout_ms`, or returns a transaction that
+//! cannot be included. Asking a builder requires the `builder` feature.
+
+#[cfg(feature = "builder")]
+use std::time::Duration;
+
+use malachitebft_proto::Protobuf;
+use serde::{Deserialize, Serialize};
+
+use crate::error::BuilderError;
+use crate::transactions::Transaction;
+use crate::types::address::Address;
+
+/// `[builder]` section of the node configuration.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BuilderConfig {
+    /// Endpoint of the external builder, blocks are built locally if unset
+    pub url: Option<String>,
+    /// Time in milliseconds the builder has to answer, before falling back to a local block
+    pub timeout_ms: u64,
+}
+
+impl Default for BuilderConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            timeout_ms: 500,
+        }
+    }
+}
+
+/// Block the proposer asks the builder for.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildRequest {
+    pub chain_id: String,
+    pub height: u64,
+    #[serde(with = "hex::serde")]
+    pub parent_hash: [u8; 32],
+    pub parent_timestamp: u64,
+    /// Protocol version of the block, see [`crate::upgrades`]
+    pub version: u32,
+    pub proposer: Address,
+    /// Maximum total encoded size of the transactions
+    pub max_bytes: usize,
+    pub max_blobs_per_tx: usize,
+}
+
+/// Payload returned by the builder.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildResponse {
+    /// Hex-encoded protobuf transactions, in block order. Empty for an empty block.
+    pub transactions: Vec<String>,
+}
+
+impl BuildResponse {
+    pub fn decode(&self) -> Result<Vec<Transaction>, BuilderError> {
+        self.transactions
+            .iter()
+            .map(|tx| Ok(Transaction::from_bytes(&hex::decode(tx)?)?))
+            .collect()
+    }
+}
+
+pub struct BlockBuilder {
+    #[cfg(feature = "builder")]
+    client: reqwest::Client,
+    url: String,
+}
+
+impl BlockBuilder {
+    /// `None` without a builder `url`, which requires the `builder` feature.
+    pub fn new(config: &BuilderConfig) -> Result<Option<Self>, BuilderError> {
+        let Some(url) = &config.url else {
+            return Ok(None);
+        };
+
+        #[cfg(feature = "builder")]
+        {
+            let client = reqwest::Client::builder()
+                .timeout(Duration::from_millis(config.timeout_ms))
+                .build()?;
+
+            Ok(Some(Self {
+                client,
+                url: url.clone(),
+            }))
+        }
+        #[cfg(not(feature = "builder"))]
+        {
+            let _ = url;
+            Err(BuilderError::FeatureDisabled)
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    #[cfg(feature = "builder")]
+    pub async fn build(&self, request: &BuildRequest) -> Result<Vec<Transaction>, BuilderError> {
+        let response = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(request)?)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        serde_json::from_slice::<BuildResponse>(&response.bytes().await?)?.decode()
+    }
+
+    /// Never called, a builder cannot be created without the `builder` feature.
+    #[cfg(not(feature = "builder"))]
+    pub async fn build(&self, _request: &BuildRequest) -> Result<Vec<Transaction>, BuilderError> {
+        Err(BuilderError::FeatureDisabled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_responses() {
+        let tx = Transaction::random();
+        let response = BuildResponse {
+            transactions: vec![hex::encode(tx.to_bytes().unwrap())],
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        let decoded: BuildResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.decode().unwrap().len(), 1);
+
+        let invalid = BuildResponse {
+            transactions: vec!["zz".to_string()],
+        };
+        assert!(invalid.decode().is_err());
+    }
+
+    #[test]
+    fn builds_locally_without_url() {
+        assert!(BlockBuilder::new(&BuilderConfig::default())
+            .unwrap()
+            .is_none());
+    }
+}
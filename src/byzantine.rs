@@ -0,0 +1,136 @@
+//! Byzantine behaviors for testing, only compiled with the `byzantine` feature.
+//!
+//! A node started with a `[byzantine]` config section misbehaves from `from_height` on, so that
+//! equivocation handling and data withholding detection can be exercised end to end against
+//! honest nodes.
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use malachitebft_app_channel::app::streaming::{StreamContent, StreamMessage};
+use malachitebft_proto::Protobuf;
+use serde::{Deserialize, Serialize};
+
+use crate::block::Block;
+use crate::error::Result;
+use crate::types::height::Height;
+use crate::types::proposal_part::{ProposalData, ProposalPart};
+
+/// `[byzantine]` section of the node configuration, everything is off by default.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ByzantineConfig {
+    /// Height from which the behaviors below are enabled
+    pub from_height: u64,
+    /// Stream a second, conflicting block for each of our proposals
+    pub equivocate: bool,
+    /// Only stream the `Init` and `Fin` parts of our proposals, withholding the block data
+    pub withhold_data: bool,
+    /// Flip a byte in every data part of our proposals, after signing them
+    pub corrupt_parts: bool,
+    /// Delay in milliseconds before answering consensus with a received proposal or a vote
+    /// extension, which delays our prevotes and precommits
+    pub vote_delay_ms: u64,
+}
+
+impl ByzantineConfig {
+    pub fn is_active(&self, height: Height) -> bool {
+        height.as_u64() >= self.from_height
+    }
+
+    pub fn vote_delay(&self, height: Height) -> Option<Duration> {
+        (self.is_active(height) && self.vote_delay_ms > 0)
+            .then(|| Duration::from_millis(self.vote_delay_ms))
+    }
+
+    /// Applies data withholding and corruption to an outgoing proposal part.
+    /// Returns `None` if the part must not be sent.
+    pub fn tamper(
+        &self,
+        height: Height,
+        mut msg: StreamMessage<ProposalPart>,
+    ) -> Option<StreamMessage<ProposalPart>> {
+        if !self.is_active(height) {
+            return Some(msg);
+        }
+
+        if let StreamContent::Data(ProposalPart::Data(data)) = &mut msg.content {
+            if self.withhold_data {
+                return None;
+            }
+
+            if self.corrupt_parts {
+                *data = corrupt(data);
+            }
+        }
+
+        Some(msg)
+    }
+}
+
+/// Another valid block for the same height, with the same transactions but a different timestamp
+/// and hence a different value id.
+pub fn equivocating_block(data: &Bytes) -> Result<Bytes> {
+    let block = <Block as Protobuf>::from_bytes(data)?;
+    let header = block.header();
+
+    let conflicting = Block::new(
+        header.block_number,
+        header.timestamp + 1,
+        header.parent_hash(),
+        header.proposer_address,
+        block.transactions().to_vec(),
+    );
+
+    Ok(conflicting.to_bytes()?)
+}
+
+fn corrupt(data: &ProposalData) -> ProposalData {
+    let mut bytes = data.bytes.to_vec();
+    if let Some(byte) = bytes.first_mut() {
+        *byte ^= 0xFF;
+    }
+    ProposalData::new(Bytes::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use malachitebft_app_channel::app::streaming::StreamId;
+
+    fn data_msg(bytes: &'static [u8]) -> StreamMessage<ProposalPart> {
+        let part = ProposalPart::Data(ProposalData::new(Bytes::from_static(bytes)));
+        StreamMessage::new(StreamId::new(Bytes::new()), 1, StreamContent::Data(part))
+    }
+
+    #[test]
+    fn tampering_starts_at_configured_height() {
+        let config = ByzantineConfig {
+            from_height: 2,
+            withhold_data: true,
+            ..ByzantineConfig::default()
+        };
+
+        assert!(config.tamper(Height::new(1), data_msg(b"data")).is_some());
+        assert!(config.tamper(Height::new(2), data_msg(b"data")).is_none());
+    }
+
+    #[test]
+    fn corrupts_data_parts() {
+        let config = ByzantineConfig {
+            corrupt_parts: true,
+            ..ByzantineConfig::default()
+        };
+
+        let msg = config.tamper(Height::new(1), data_msg(b"data")).unwrap();
+        let data = msg.content.as_data().and_then(|p| p.as_data()).unwrap();
+        assert_ne!(data.bytes.as_ref(), b"data");
+    }
+
+    #[test]
+    fn equivocating_block_differs() {
+        let block = Block::genesis().to_bytes().unwrap();
+        let conflicting = equivocating_block(&block).unwrap();
+        assert_ne!(conflicting, block);
+    }
+}
@@ -0,0 +1,326 @@
+//! Typed async client for the `mikan_*` JSON-RPC API.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), mikan::client::ClientError> {
+//! use mikan::client::MikanClient;
+//! use mikan::transactions::Transaction;
+//!
+//! let client = MikanClient::new("http://127.0.0.1:8545")?;
+//! let hash = client.submit_transaction(Transaction::random()).await?;
+//! let height = client.block_number().await?;
+//! let verified = client.sample_and_verify(height, 0, None).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
+use malachitebft_test::PublicKey;
+use thiserror::Error;
+
+use crate::blob::Blob;
+use crate::block::Block;
+use crate::finality_params::FinalityProof;
+use crate::peers::PeerInfo;
+use crate::round_state::ConsensusState;
+use crate::rpc::{
+    BlobCommitmentProof, BlobSample, BlobShares, ChainInfo, DataWindow, DecidedTransaction,
+    GenesisDocument, MikanApiClient, NamespaceData, RetrievedBlob, RpcBlock, RpcTransaction,
+    TransactionReceipt, TxPoolStatus, UploadedTransaction, VoteProof,
+};
+use crate::transactions::Transaction;
+use crate::types::address::Address;
+use crate::types::codec::ssz::HeaderEncoding;
+use crate::uploads::UPLOAD_CHUNK_SIZE;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("RPC error: {0}")]
+    Rpc(#[from] jsonrpsee::core::Error),
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct MikanClient {
+    inner: HttpClient,
+}
+
+impl MikanClient {
+    /// Creates a client for the node listening at `url`, e.g. `http://127.0.0.1:8545`
+    pub fn new(url: impl AsRef<str>) -> Result<Self, ClientError> {
+        let inner = HttpClientBuilder::default().build(url)?;
+        Ok(Self { inner })
+    }
+
+    /// Submits a signed transaction and returns its hash
+    pub async fn submit_transaction(&self, tx: Transaction) -> Result<[u8; 32], ClientError> {
+        let hash = self
+            .inner
+            .send_transaction(RpcTransaction::from(tx))
+            .await?;
+
+        decode_hash(&hash)
+    }
+
+    /// Submits a signed transaction, uploading its blobs in chunks first rather than inlining them
+    /// in a single request
+    pub async fn submit_transaction_chunked(
+        &self,
+        tx: Transaction,
+    ) -> Result<[u8; 32], ClientError> {
+        let mut uploads = Vec::with_capacity(tx.data().len());
+        for blob in tx.data() {
+            uploads.push(self.upload_blob(blob).await?);
+        }
+
+        let hash = self
+            .inner
+            .send_uploaded_transaction(UploadedTransaction {
+                from: tx.from_(),
+                to: tx.to(),
+                signature: tx.signature(),
+                value: tx.value(),
+                nonce: tx.nonce(),
+                gas_price: tx.gas_price(),
+                gas_limit: tx.gas_limit(),
+                uploads,
+            })
+            .await?;
+
+        decode_hash(&hash)
+    }
+
+    /// Uploads a blob in chunks of [`UPLOAD_CHUNK_SIZE`] bytes and returns the upload id
+    pub async fn upload_blob(&self, blob: &Blob) -> Result<u64, ClientError> {
+        let upload_id = self.inner.begin_blob_upload().await?;
+
+        for (i, chunk) in blob.data().chunks(UPLOAD_CHUNK_SIZE).enumerate() {
+            self.inner
+                .upload_blob_chunk(upload_id, i * UPLOAD_CHUNK_SIZE, hex::encode(chunk))
+                .await?;
+        }
+
+        Ok(upload_id)
+    }
+
+    pub async fn block_number(&self) -> Result<u64, ClientError> {
+        Ok(self.inner.block_number().await?)
+    }
+
+    /// Chain id, genesis hash and range of retrievable heights
+    pub async fn chain_info(&self) -> Result<ChainInfo, ClientError> {
+        Ok(self.inner.chain_info().await?)
+    }
+
+    /// Genesis document of the chain, with the hash of its genesis block
+    pub async fn get_genesis(&self) -> Result<GenesisDocument, ClientError> {
+        Ok(self.inner.get_genesis().await?)
+    }
+
+    /// Heights whose blocks and certificates the node still serves
+    pub async fn get_data_window(&self) -> Result<DataWindow, ClientError> {
+        Ok(self.inner.get_data_window().await?)
+    }
+
+    /// Pending and queued transactions of the node's pool
+    pub async fn tx_pool_status(&self) -> Result<TxPoolStatus, ClientError> {
+        Ok(self.inner.tx_pool_status().await?)
+    }
+
+    /// Hashes of the pending and queued transactions of the node's pool
+    pub async fn tx_pool_hashes(&self) -> Result<Vec<[u8; 32]>, ClientError> {
+        self.inner
+            .tx_pool_hashes()
+            .await?
+            .iter()
+            .map(|hash| decode_hash(hash))
+            .collect()
+    }
+
+    /// Transaction with `hash` in the node's pool, checked against `chain_id`
+    pub async fn get_pool_transaction(
+        &self,
+        hash: [u8; 32],
+        chain_id: &str,
+    ) -> Result<Option<Transaction>, ClientError> {
+        let Some(tx) = self.inner.get_pool_transaction(hex::encode(hash)).await? else {
+            return Ok(None);
+        };
+        tx.into_transaction(chain_id)
+            .map(Some)
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))
+    }
+
+    /// Decided transaction with `hash` and its receipt, checked against `chain_id`
+    pub async fn get_transaction_by_hash(
+        &self,
+        hash: [u8; 32],
+        chain_id: &str,
+    ) -> Result<Option<(Transaction, TransactionReceipt)>, ClientError> {
+        let Some(DecidedTransaction {
+            transaction,
+            receipt,
+        }) = self
+            .inner
+            .get_transaction_by_hash(hex::encode(hash))
+            .await?
+        else {
+            return Ok(None);
+        };
+        let tx = transaction
+            .into_transaction(chain_id)
+            .map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
+        Ok(Some((tx, receipt)))
+    }
+
+    pub async fn get_block(&self, block_height: u64) -> Result<Block, ClientError> {
+        Ok(self.inner.get_block(block_height).await?)
+    }
+
+    /// Block at `block_height` as structured JSON, with the blob payloads if `include_blobs`
+    pub async fn get_block_by_height(
+        &self,
+        block_height: u64,
+        include_blobs: bool,
+    ) -> Result<RpcBlock, ClientError> {
+        Ok(self
+            .inner
+            .get_block_by_height(block_height, Some(include_blobs))
+            .await?)
+    }
+
+    /// Receipts of all the transactions of the block at `block_height`
+    pub async fn get_block_receipts(
+        &self,
+        block_height: u64,
+    ) -> Result<Vec<TransactionReceipt>, ClientError> {
+        Ok(self.inner.get_block_receipts(block_height).await?)
+    }
+
+    /// Blob of a decided block, `local` being false if the node fetched the block from a peer
+    /// after pruning it
+    pub async fn get_blob(
+        &self,
+        block_height: u64,
+        blob_index: usize,
+    ) -> Result<RetrievedBlob, ClientError> {
+        Ok(self.inner.get_blob(block_height, blob_index).await?)
+    }
+
+    /// Shares covering bytes `range` of a blob, to be checked with
+    /// [`crate::verify::verify_shares`]
+    pub async fn get_shares(
+        &self,
+        block_height: u64,
+        blob_index: usize,
+        range: std::ops::Range<usize>,
+    ) -> Result<BlobShares, ClientError> {
+        Ok(self
+            .inner
+            .get_shares(block_height, blob_index, range.start, range.end)
+            .await?)
+    }
+
+    /// Returns the encoded header, see [`HeaderEncoding`]
+    pub async fn get_header(
+        &self,
+        block_height: u64,
+        encoding: HeaderEncoding,
+    ) -> Result<Vec<u8>, ClientError> {
+        let header = self.inner.get_header(block_height, Some(encoding)).await?;
+        hex::decode(header).map_err(|e| ClientError::InvalidResponse(e.to_string()))
+    }
+
+    /// Data of `namespace` over `from_height..=to_height`, with inclusion or absence proofs
+    pub async fn get_namespace_data(
+        &self,
+        namespace: PublicKey,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<Vec<NamespaceData>, ClientError> {
+        Ok(self
+            .inner
+            .get_namespace_data(namespace, from_height, to_height)
+            .await?)
+    }
+
+    /// Proof that `validator_address` precommitted the block at `block_height`
+    pub async fn get_vote_proof(
+        &self,
+        block_height: u64,
+        validator_address: Address,
+    ) -> Result<VoteProof, ClientError> {
+        Ok(self
+            .inner
+            .get_vote_proof(block_height, validator_address)
+            .await?)
+    }
+
+    /// Header at `block_height` with its finality proof, verify it with [`FinalityProof::verify`]
+    pub async fn get_finality_proof(
+        &self,
+        block_height: u64,
+    ) -> Result<FinalityProof, ClientError> {
+        Ok(self.inner.get_finality_proof(block_height).await?)
+    }
+
+    pub async fn get_peers(&self) -> Result<Vec<PeerInfo>, ClientError> {
+        Ok(self.inner.get_peers().await?)
+    }
+
+    /// Height and round the node is in, to see where the chain stalls
+    pub async fn get_consensus_state(&self) -> Result<ConsensusState, ClientError> {
+        Ok(self.inner.get_consensus_state().await?)
+    }
+
+    /// Requests a FRIEDA sampling proof for a blob, with a seed derived from the block hash and
+    /// `sampler_nonce` unless `sampling_seed` is given
+    pub async fn sample_blob(
+        &self,
+        block_height: u64,
+        blob_index: usize,
+        sampling_seed: Option<u64>,
+        sampler_nonce: Option<u64>,
+    ) -> Result<BlobSample, ClientError> {
+        Ok(self
+            .inner
+            .sample_blob(block_height, blob_index, sampling_seed, sampler_nonce)
+            .await?)
+    }
+
+    /// Requests a sampling proof for the blob with the given commitment, wherever it was decided
+    pub async fn get_blob_proof_by_commitment(
+        &self,
+        commitment: [u8; 32],
+        sampling_seed: Option<u64>,
+        sampler_nonce: Option<u64>,
+    ) -> Result<BlobCommitmentProof, ClientError> {
+        Ok(self
+            .inner
+            .get_blob_proof_by_commitment(hex::encode(commitment), sampling_seed, sampler_nonce)
+            .await?)
+    }
+
+    /// Requests a sampling proof for a blob and verifies it locally
+    pub async fn sample_and_verify(
+        &self,
+        block_height: u64,
+        blob_index: usize,
+        sampling_seed: Option<u64>,
+    ) -> Result<bool, ClientError> {
+        let sample = self
+            .sample_blob(block_height, blob_index, sampling_seed, None)
+            .await?;
+        Ok(frieda::api::verify(
+            sample.proof,
+            Some(sample.sampling_seed),
+        ))
+    }
+}
+
+fn decode_hash(hash: &str) -> Result<[u8; 32], ClientError> {
+    let mut bytes = [0; 32];
+    hex::decode_to_slice(hash, &mut bytes)
+        .map_err(|e| ClientError::InvalidResponse(format!("Invalid hash {hash}: {e}")))?;
+    Ok(bytes)
+}
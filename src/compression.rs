@@ -0,0 +1,99 @@
+//! Optional zstd compression of block data, at rest in the store and on the wire in proposal
+//! parts. Blob payloads dominate both disk and bandwidth usage.
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::error::CompressionError;
+
+/// Decompressed block data larger than this is rejected, so that a small malicious payload can't
+/// make the node allocate unbounded memory.
+pub const MAX_DECOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
+
+/// Every zstd frame starts with this magic number, used to tell compressed entries in the store
+/// apart from raw protobuf ones (which start with a field tag).
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    #[default]
+    None,
+    Zstd,
+}
+
+impl Compression {
+    pub fn compress(&self, data: Bytes, level: i32) -> Result<Bytes, CompressionError> {
+        match self {
+            Self::None => Ok(data),
+            Self::Zstd => Ok(zstd::bulk::compress(&data, level)?.into()),
+        }
+    }
+
+    pub fn decompress(&self, data: Bytes) -> Result<Bytes, CompressionError> {
+        match self {
+            Self::None => Ok(data),
+            Self::Zstd => Ok(zstd::bulk::decompress(&data, MAX_DECOMPRESSED_SIZE)?.into()),
+        }
+    }
+
+    /// The compression `data` was written with, as far as the store is concerned.
+    pub fn detect(data: &[u8]) -> Self {
+        if data.starts_with(&ZSTD_MAGIC) {
+            Self::Zstd
+        } else {
+            Self::None
+        }
+    }
+}
+
+/// `[compression]` section of the node configuration.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressionConfig {
+    /// Compression of decided block data in the store
+    pub storage: Compression,
+    /// Compression of block data streamed in proposal parts
+    pub proposals: Compression,
+    /// zstd compression level, from 1 (fastest) to 22 (smallest)
+    pub level: i32,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            storage: Compression::None,
+            proposals: Compression::None,
+            level: zstd::DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zstd_roundtrip() {
+        let data = Bytes::from(vec![7; 100_000]);
+        let compressed = Compression::Zstd.compress(data.clone(), 3).unwrap();
+
+        assert!(compressed.len() < data.len());
+        assert_eq!(Compression::detect(&compressed), Compression::Zstd);
+        assert_eq!(Compression::Zstd.decompress(compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn raw_protobuf_is_not_detected_as_zstd() {
+        let block = crate::block::Block::genesis().to_bytes().unwrap();
+        assert_eq!(Compression::detect(&block), Compression::None);
+    }
+
+    #[test]
+    fn rejects_decompression_bombs() {
+        let data = Bytes::from(vec![0; MAX_DECOMPRESSED_SIZE + 1]);
+        let compressed = Compression::Zstd.compress(data, 3).unwrap();
+        assert!(Compression::Zstd.decompress(compressed).is_err());
+    }
+}
@@ -11,12 +11,38 @@ pub use malachitebft_app_channel::app::config::{
 
 use malachitebft_app_channel::app::node::NodeConfig;
 
+use crate::error::MikanError;
+
+pub use crate::block::{BlockConfig, EmptyBlockPolicy, DEFAULT_MAX_BLOCK_BYTES};
+pub use crate::builder::BuilderConfig;
+pub use crate::compression::{Compression, CompressionConfig};
+pub use crate::events::{EventsConfig, SinkConfig};
+pub use crate::peers::PeerAccessConfig;
+pub use crate::relayer::RelayerConfig;
+pub use crate::retrieval::RetrievalConfig;
+pub use crate::role::{NodeRole, NodeRoleConfig};
+pub use crate::sample_queue::SamplingConfig;
+pub use crate::store::StorageConfig;
+pub use crate::streaming::StreamingConfig;
+pub use crate::transactions::exchange::MempoolExchangeConfig;
+pub use crate::upgrades::{ProtocolConfig, Upgrade};
+
+#[cfg(feature = "byzantine")]
+pub use crate::byzantine::ByzantineConfig;
+
+#[cfg(feature = "netsim")]
+pub use crate::netsim::NetsimConfig;
+
 /// Malachite configuration options
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Config {
     /// A custom human-readable name for this node
     pub moniker: String,
 
+    /// Role of this node: validator, full or sampling node
+    #[serde(default)]
+    pub node: NodeRoleConfig,
+
     /// Log configuration options
     pub logging: LoggingConfig,
 
@@ -31,6 +57,64 @@ pub struct Config {
 
     /// Runtime configuration options
     pub runtime: RuntimeConfig,
+
+    /// Block data compression options
+    #[serde(default)]
+    pub compression: CompressionConfig,
+
+    /// Proposal streaming options
+    #[serde(default)]
+    pub streaming: StreamingConfig,
+
+    /// Block production options
+    #[serde(default)]
+    pub block: BlockConfig,
+
+    /// External block builder
+    #[serde(default)]
+    pub builder: BuilderConfig,
+
+    /// Chain event sinks, for indexers
+    #[serde(default)]
+    pub events: EventsConfig,
+
+    /// Relaying of data roots to Starknet
+    #[serde(default)]
+    pub relayer: RelayerConfig,
+
+    /// Store size limits
+    #[serde(default)]
+    pub storage: StorageConfig,
+
+    /// Protocol upgrades scheduled on top of those of the genesis file
+    #[serde(default)]
+    pub protocol: ProtocolConfig,
+
+    /// Fetching the pools of joining peers
+    #[serde(default)]
+    pub mempool_exchange: MempoolExchangeConfig,
+
+    /// Peer allowlist and denylist
+    #[serde(default)]
+    pub p2p: PeerAccessConfig,
+
+    /// Limits on the sampling proofs served by the RPC server
+    #[serde(default)]
+    pub sampling: SamplingConfig,
+
+    /// Fetching pruned blocks from peers
+    #[serde(default)]
+    pub retrieval: RetrievalConfig,
+
+    /// Test-only misbehaviors
+    #[cfg(feature = "byzantine")]
+    #[serde(default)]
+    pub byzantine: ByzantineConfig,
+
+    /// Test-only adverse network conditions
+    #[cfg(feature = "netsim")]
+    #[serde(default)]
+    pub netsim: NetsimConfig,
 }
 
 impl NodeConfig for Config {
@@ -49,15 +133,15 @@ impl NodeConfig for Config {
 
 /// load_config parses the environment variables and loads the provided config file path
 /// to create a Config struct.
-pub fn load_config(path: impl AsRef<Path>, prefix: Option<&str>) -> eyre::Result<Config> {
-    ::config::Config::builder()
+pub fn load_config(path: impl AsRef<Path>, prefix: Option<&str>) -> Result<Config, MikanError> {
+    let config = ::config::Config::builder()
         .add_source(::config::File::from(path.as_ref()))
         .add_source(
             ::config::Environment::with_prefix(prefix.unwrap_or("MALACHITE")).separator("__"),
         )
         .build()?
-        .try_deserialize()
-        .map_err(Into::into)
+        .try_deserialize()?;
+    Ok(config)
 }
 
 #[cfg(test)]
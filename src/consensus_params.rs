@@ -0,0 +1,102 @@
+//! Limits of block validation, part of the genesis file so that every node of a network checks
+//! blocks against the same values.
+//!
+//! The block byte limit and the retention of block data are the initial values of the parameters
+//! validators can change by vote, see [`crate::governance`]. The others only change with a new
+//! genesis file.
+
+use serde::{Deserialize, Serialize};
+
+use crate::blob::BLOB_SIZE;
+use crate::block::DEFAULT_MAX_BLOCK_BYTES;
+use crate::error::ConsensusParamsError;
+use crate::streaming::DEFAULT_CHUNK_SIZE;
+
+/// Number of previous blocks whose median timestamp bounds the timestamp of a new block, when
+/// the genesis file does not set it.
+pub const DEFAULT_MEDIAN_TIME_SPAN: usize = 11;
+
+/// Maximum drift in seconds of a block timestamp ahead of the median of recent blocks, when the
+/// genesis file does not set it.
+pub const DEFAULT_MAX_TIMESTAMP_DRIFT: u64 = 600;
+
+/// Decided heights whose block data is retained when the genesis file does not set it.
+pub const DEFAULT_RETAIN_HEIGHTS: u64 = 25;
+
+/// `consensus` section of the genesis file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConsensusParams {
+    /// Maximum encoded size of a block, until changed by governance
+    pub max_block_bytes: u64,
+    /// Maximum length of the data of a blob, up to [`BLOB_SIZE`], the size blobs are padded to
+    /// when committed to
+    pub max_blob_bytes: usize,
+    /// Size in bytes of the chunks a proposal is streamed in, larger parts are rejected
+    pub chunk_size: usize,
+    /// Number of previous blocks whose median timestamp bounds the timestamp of a new block
+    pub median_time_span: usize,
+    /// Maximum drift in seconds of a block timestamp ahead of the median of recent blocks
+    pub max_timestamp_drift: u64,
+    /// Decided heights whose block data is retained, until changed by governance
+    pub retain_heights: u64,
+}
+
+impl Default for ConsensusParams {
+    fn default() -> Self {
+        Self {
+            max_block_bytes: DEFAULT_MAX_BLOCK_BYTES as u64,
+            max_blob_bytes: BLOB_SIZE,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            median_time_span: DEFAULT_MEDIAN_TIME_SPAN,
+            max_timestamp_drift: DEFAULT_MAX_TIMESTAMP_DRIFT,
+            retain_heights: DEFAULT_RETAIN_HEIGHTS,
+        }
+    }
+}
+
+impl ConsensusParams {
+    /// Checks that the parameters can be enforced, when loading the genesis file.
+    pub fn check(&self) -> Result<(), ConsensusParamsError> {
+        if self.max_blob_bytes > BLOB_SIZE {
+            return Err(ConsensusParamsError::MaxBlobBytesUnsupported {
+                max_blob_bytes: self.max_blob_bytes,
+                supported: BLOB_SIZE,
+            });
+        }
+        let positive = [
+            ("max_block_bytes", self.max_block_bytes),
+            ("chunk_size", self.chunk_size as u64),
+            ("median_time_span", self.median_time_span as u64),
+        ];
+        match positive.into_iter().find(|(_, value)| *value == 0) {
+            Some((name, _)) => Err(ConsensusParamsError::Zero(name)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_unenforceable_params() {
+        assert!(ConsensusParams::default().check().is_ok());
+
+        let params: ConsensusParams = serde_json::from_str(r#"{"chunk_size":0}"#).unwrap();
+        assert_eq!(
+            params.check(),
+            Err(ConsensusParamsError::Zero("chunk_size"))
+        );
+
+        let params = ConsensusParams {
+            max_blob_bytes: BLOB_SIZE + 1,
+            ..Default::default()
+        };
+        assert!(matches!(
+            params.check(),
+            Err(ConsensusParamsError::MaxBlobBytesUnsupported { .. })
+        ));
+    }
+}
@@ -1,5 +1,63 @@
+use std::path::PathBuf;
+use std::process::ExitStatus;
+
+use jsonrpsee::types::error::INTERNAL_ERROR_CODE;
+use jsonrpsee::types::{ErrorObject, ErrorObjectOwned};
+use serde_json::json;
 use thiserror::Error;
 
+use crate::client::ClientError;
+use crate::forks::Divergence;
+use crate::store::StoreError;
+use crate::types::address::Address;
+use crate::types::codec::ssz::SszError;
+use crate::types::height::Height;
+use crate::upgrades::Feature;
+
+/// Errors of the library APIs, so embedders can match on failures. The binary reports them with
+/// eyre.
+#[derive(Debug, Error)]
+pub enum MikanError {
+    #[error(transparent)]
+    Block(#[from] BlockError),
+    #[error(transparent)]
+    Store(#[from] StoreError),
+    #[error(transparent)]
+    State(#[from] StateError),
+    #[error(transparent)]
+    Compression(#[from] CompressionError),
+    #[error("Failed to encode/decode Protobuf: {0}")]
+    Protobuf(#[from] malachitebft_proto::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to join on task: {0}")]
+    TaskJoin(#[from] tokio::task::JoinError),
+    #[error("Invalid configuration: {0}")]
+    Config(#[from] ::config::ConfigError),
+    #[error("The {0} channel closed unexpectedly")]
+    ChannelClosed(&'static str),
+    #[error(transparent)]
+    Event(#[from] EventError),
+    #[error(transparent)]
+    Relayer(#[from] RelayerError),
+    #[error(transparent)]
+    Builder(#[from] BuilderError),
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    #[error(transparent)]
+    Sampling(#[from] SamplingError),
+    #[error(transparent)]
+    Wal(#[from] WalError),
+    #[error(transparent)]
+    Devnet(#[from] DevnetError),
+    #[error(transparent)]
+    Testnet(#[from] TestnetError),
+}
+
+pub type Result<T, E = MikanError> = std::result::Result<T, E>;
+
 #[derive(Debug, Error)]
 pub enum BlockError {
     #[error("Null Parent Hash")]
@@ -19,30 +77,468 @@ pub enum BlockError {
 }
 
 #[derive(Debug, Error)]
-pub enum StoreError {
-    #[error("Database error: {0}")]
-    Database(#[from] redb::DatabaseError),
+pub enum CompressionError {
+    #[error("zstd error: {0}")]
+    Zstd(#[from] std::io::Error),
+}
 
-    #[error("Storage error: {0}")]
-    Storage(#[from] redb::StorageError),
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BlobError {
+    #[error("Blob too large: expected at most {max} bytes, got {actual}")]
+    TooLarge { max: usize, actual: usize },
+}
 
-    #[error("Table error: {0}")]
-    Table(#[from] redb::TableError),
+#[derive(Debug, Error)]
+pub enum TransactionError {
+    #[error("Invalid transaction signature")]
+    InvalidSignature,
+    #[error("Transaction pool is full ({0} transactions)")]
+    PoolFull(usize),
+    #[error("Transaction queue is full ({0} transactions)")]
+    QueueFull(usize),
+    #[error("Nonce {actual} already used, the next nonce is {expected}")]
+    NonceTooLow { expected: u64, actual: u64 },
+    #[error("Too many blobs: expected at most {max}, got {actual}")]
+    TooManyBlobs { max: usize, actual: usize },
+    #[error("Transaction too large: expected at most {max} bytes, got {actual}")]
+    TooLarge { max: usize, actual: usize },
+    #[error("Blob too large: expected at most {max} bytes, got {actual}")]
+    BlobTooLarge { max: usize, actual: usize },
+    #[error("{0}")]
+    Namespace(#[from] NamespaceError),
+    #[error("{0}")]
+    Gas(#[from] GasError),
+    #[error("{0}")]
+    Governance(#[from] GovernanceError),
+    #[error("{0}")]
+    Liveness(#[from] LivenessError),
+}
 
-    #[error("Commit error: {0}")]
-    Commit(#[from] redb::CommitError),
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ConsensusParamsError {
+    #[error("Blobs of {max_blob_bytes} bytes are not supported, at most {supported}")]
+    MaxBlobBytesUnsupported {
+        max_blob_bytes: usize,
+        supported: usize,
+    },
+    #[error("Consensus parameter {0} must be positive")]
+    Zero(&'static str),
+}
 
-    #[error("Transaction error: {0}")]
-    Transaction(#[from] redb::TransactionError),
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum UpgradeError {
+    #[error("Upgrade to version {version} at height {height} does not follow the previous ones")]
+    OutOfOrder { height: u64, version: u32 },
+    #[error("Protocol version {version} of height {height} is not supported, this node supports up to version {supported}")]
+    Unsupported {
+        height: u64,
+        version: u32,
+        supported: u32,
+    },
+    #[error("Unexpected protocol version at height {height}: expected {expected}, got {actual}")]
+    UnexpectedVersion {
+        height: u64,
+        expected: u32,
+        actual: u32,
+    },
+    #[error("Activation of {feature:?} at height {height} conflicts with its activation at height {scheduled}")]
+    ConflictingActivation {
+        feature: Feature,
+        height: u64,
+        scheduled: u64,
+    },
+}
 
-    #[error("Failed to encode/decode Protobuf: {0}")]
-    Protobuf(#[from] malachitebft_proto::Error),
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum NamespaceError {
+    #[error("Namespace {namespace} is registered by another sender")]
+    AlreadyRegistered { namespace: String },
+    #[error("{poster} is not allowed to post to namespace {namespace}")]
+    PosterNotAllowed { namespace: String, poster: String },
+    #[error("Gas price {actual} below the minimum {required} of namespace {namespace}")]
+    GasPriceTooLow {
+        namespace: String,
+        required: u64,
+        actual: u64,
+    },
+}
 
-    #[error("Failed to join on task: {0}")]
-    TaskJoin(#[from] tokio::task::JoinError),
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum GovernanceError {
+    #[error("{sender} is not a validator and cannot vote for parameter changes")]
+    NotAValidator { sender: String },
+    #[error("Parameter change at height {activation} does not apply after height {height}")]
+    ActivationNotInFuture { activation: u64, height: u64 },
+}
 
-    #[error("{0}")]
-    UnknownError(String),
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum LivenessError {
+    #[error("{sender} is not a validator and cannot unjail")]
+    NotAValidator { sender: String },
+    #[error("Validator {validator} is not jailed")]
+    NotJailed { validator: String },
+    #[error("Validator jailed until height {release}, cannot unjail at height {height}")]
+    StillJailed { release: u64, height: u64 },
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EvidenceError {
+    #[error("{validator} is not a validator at the height of the evidence")]
+    UnknownValidator { validator: String },
+    #[error("Messages of the evidence do not conflict")]
+    NotConflicting,
+    #[error("Invalid signature of {validator} in the evidence")]
+    InvalidSignature { validator: String },
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum GasError {
+    #[error("Gas limit {limit} below the intrinsic gas {required} of the transaction")]
+    IntrinsicGasTooLow { required: u64, limit: u64 },
+    #[error("Gas limit {limit} above the block gas limit {block_gas_limit}")]
+    AboveBlockGasLimit { limit: u64, block_gas_limit: u64 },
+    #[error("Out of gas: {requested} requested with {used} of {limit} used")]
+    OutOfGas {
+        limit: u64,
+        used: u64,
+        requested: u64,
+    },
+    #[error("Block uses {used} gas, above the block gas limit {block_gas_limit}")]
+    BlockGasExceeded { used: u64, block_gas_limit: u64 },
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FinalityError {
+    #[error("Missing commit of the parent block at height {0}")]
+    MissingLastCommit(u64),
+    #[error("Unexpected last commit at height {0}, the genesis block is not committed")]
+    UnexpectedLastCommit(u64),
+    #[error("Last commit is for height {actual}, expected {expected}")]
+    HeightMismatch { expected: u64, actual: u64 },
+    #[error("Last commit does not commit to the parent block")]
+    ValueMismatch,
+    #[error("Block does not match the header")]
+    HeaderMismatch,
+    #[error("Validator set does not chain up to the trusted checkpoint")]
+    ValidatorSetMismatch,
+    #[error("Invalid last commit: {0}")]
+    Certificate(#[from] CommitCertificateError),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum UploadError {
+    #[error("Unknown upload {0}")]
+    UnknownUpload(u64),
+    #[error("Unexpected chunk offset: expected {expected}, got {actual}")]
+    UnexpectedOffset { expected: usize, actual: usize },
+    #[error("Upload exceeds the maximum blob size of {0} bytes")]
+    TooLarge(usize),
+    #[error("Too many uploads in progress (at most {0})")]
+    TooManyUploads(usize),
+}
+
+/// Reasons for rejecting an incoming proposal part stream.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum StreamError {
+    #[error("Peer is temporarily banned for exceeding stream limits")]
+    PeerBanned,
+    #[error("Too many concurrent streams from peer (at most {0})")]
+    TooManyStreams(usize),
+    #[error("Too many parts in stream (at most {0})")]
+    TooManyParts(usize),
+    #[error("Stream exceeds the maximum size of {0} bytes")]
+    TooLarge(usize),
+    #[error("Part exceeds the chunk size of {0} bytes")]
+    ChunkTooLarge(usize),
+    #[error("Unexpected part at sequence {0}")]
+    UnexpectedPart(u64),
+}
+
+/// Errors returned by the `mikan_*` RPC methods.
+///
+/// Each variant has its own JSON-RPC error code, in the implementation-defined server error range,
+/// and a structured `data` payload so that clients can branch on the failure.
+#[derive(Debug, Error)]
+pub enum MikanRpcError {
+    #[error("Block {block_height} not found")]
+    BlockNotFound { block_height: u64 },
+    #[error("Blob index {blob_index} out of range, block {block_height} has {blob_count} blobs")]
+    BlobIndexOutOfRange {
+        block_height: u64,
+        blob_index: usize,
+        blob_count: usize,
+    },
+    #[error("Couldn't decode {what}: {reason}")]
+    Decode { what: &'static str, reason: String },
+    #[error("Mempool is full ({capacity} transactions)")]
+    MempoolFull { capacity: usize },
+    #[error("Invalid transaction signature")]
+    InvalidSignature { from: String },
+    #[error("Blob upload failed: {0}")]
+    Upload(#[from] UploadError),
+    #[error("Rejected by namespace policy: {0}")]
+    Namespace(#[from] NamespaceError),
+    #[error("Invalid height range {from_height}..={to_height}, at most {max_range} heights")]
+    InvalidRange {
+        from_height: u64,
+        to_height: u64,
+        max_range: u64,
+    },
+    #[error(
+        "Validator {validator_address} has no precommit in the commit of block {block_height}"
+    )]
+    VoteNotFound {
+        block_height: u64,
+        validator_address: String,
+    },
+    #[error("No decided blob with commitment {commitment}")]
+    BlobNotFound { commitment: String },
+    #[error("Rejected by gas metering: {0}")]
+    Gas(#[from] GasError),
+    #[error("Nonce {nonce} already used, the next nonce is {expected}")]
+    NonceTooLow {
+        from: String,
+        expected: u64,
+        nonce: u64,
+    },
+    #[error("Transaction carries {blob_count} blobs, at most {max_blobs} are allowed")]
+    TooManyBlobs { max_blobs: usize, blob_count: usize },
+    #[error("Transaction of {size} bytes, at most {max_bytes} are allowed")]
+    TransactionTooLarge { max_bytes: usize, size: usize },
+    #[error("Blob of {size} bytes, at most {max_bytes} are allowed")]
+    BlobTooLarge { max_bytes: usize, size: usize },
+    #[error("Rejected by governance: {0}")]
+    Governance(#[from] GovernanceError),
+    #[error("Unjail rejected: {0}")]
+    Liveness(#[from] LivenessError),
+    #[error("Invalid evidence: {0}")]
+    Evidence(#[from] EvidenceError),
+    #[error("Block {block_height} of version {version} does not commit to shares")]
+    SharesNotCommitted { block_height: u64, version: u32 },
+    #[error("Byte range {from_byte}..{to_byte} is not within the blob of {blob_len} bytes")]
+    ByteRangeOutOfRange {
+        from_byte: usize,
+        to_byte: usize,
+        blob_len: usize,
+    },
+    /// The 429 of sampling requests, see [`crate::sample_queue`]
+    #[error(
+        "Too many sampling requests ({max_concurrent} running, {max_queued} queued), retry later"
+    )]
+    SamplingBusy {
+        max_concurrent: usize,
+        max_queued: usize,
+    },
+    /// The 429 of a connection with too many sampling requests, see [`crate::sample_queue`]
+    #[error("Too many sampling requests on this connection ({max_per_connection}), retry later")]
+    SamplingConnectionBusy { max_per_connection: usize },
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+impl MikanRpcError {
+    pub const BLOCK_NOT_FOUND: i32 = -32001;
+    pub const BLOB_INDEX_OUT_OF_RANGE: i32 = -32002;
+    pub const DECODE_FAILURE: i32 = -32003;
+    pub const MEMPOOL_FULL: i32 = -32004;
+    pub const INVALID_SIGNATURE: i32 = -32005;
+    pub const UPLOAD_FAILURE: i32 = -32006;
+    pub const INVALID_RANGE: i32 = -32007;
+    pub const NAMESPACE_REJECTED: i32 = -32008;
+    pub const VOTE_NOT_FOUND: i32 = -32009;
+    pub const BLOB_NOT_FOUND: i32 = -32010;
+    pub const GAS_REJECTED: i32 = -32011;
+    pub const NONCE_TOO_LOW: i32 = -32012;
+    pub const TOO_MANY_BLOBS: i32 = -32013;
+    pub const TRANSACTION_TOO_LARGE: i32 = -32014;
+    pub const GOVERNANCE_REJECTED: i32 = -32015;
+    pub const BLOB_TOO_LARGE: i32 = -32016;
+    pub const UNJAIL_REJECTED: i32 = -32017;
+    pub const INVALID_EVIDENCE: i32 = -32018;
+    pub const SAMPLING_BUSY: i32 = -32019;
+    pub const SHARES_NOT_COMMITTED: i32 = -32020;
+    pub const BYTE_RANGE_OUT_OF_RANGE: i32 = -32021;
+    pub const SAMPLING_CONNECTION_BUSY: i32 = -32022;
+
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::BlockNotFound { .. } => Self::BLOCK_NOT_FOUND,
+            Self::BlobIndexOutOfRange { .. } => Self::BLOB_INDEX_OUT_OF_RANGE,
+            Self::Decode { .. } => Self::DECODE_FAILURE,
+            Self::MempoolFull { .. } => Self::MEMPOOL_FULL,
+            Self::InvalidSignature { .. } => Self::INVALID_SIGNATURE,
+            Self::Upload(_) => Self::UPLOAD_FAILURE,
+            Self::InvalidRange { .. } => Self::INVALID_RANGE,
+            Self::Namespace(_) => Self::NAMESPACE_REJECTED,
+            Self::VoteNotFound { .. } => Self::VOTE_NOT_FOUND,
+            Self::BlobNotFound { .. } => Self::BLOB_NOT_FOUND,
+            Self::Gas(_) => Self::GAS_REJECTED,
+            Self::NonceTooLow { .. } => Self::NONCE_TOO_LOW,
+            Self::TooManyBlobs { .. } => Self::TOO_MANY_BLOBS,
+            Self::TransactionTooLarge { .. } => Self::TRANSACTION_TOO_LARGE,
+            Self::Governance(_) => Self::GOVERNANCE_REJECTED,
+            Self::BlobTooLarge { .. } => Self::BLOB_TOO_LARGE,
+            Self::Liveness(_) => Self::UNJAIL_REJECTED,
+            Self::Evidence(_) => Self::INVALID_EVIDENCE,
+            Self::SamplingBusy { .. } => Self::SAMPLING_BUSY,
+            Self::SamplingConnectionBusy { .. } => Self::SAMPLING_CONNECTION_BUSY,
+            Self::SharesNotCommitted { .. } => Self::SHARES_NOT_COMMITTED,
+            Self::ByteRangeOutOfRange { .. } => Self::BYTE_RANGE_OUT_OF_RANGE,
+            Self::Internal(_) => INTERNAL_ERROR_CODE,
+        }
+    }
+
+    fn data(&self) -> Option<serde_json::Value> {
+        match self {
+            Self::BlockNotFound { block_height } => Some(json!({ "block_height": block_height })),
+            Self::BlobIndexOutOfRange {
+                block_height,
+                blob_index,
+                blob_count,
+            } => Some(json!({
+                "block_height": block_height,
+                "blob_index": blob_index,
+                "blob_count": blob_count,
+            })),
+            Self::Decode { what, reason } => Some(json!({ "what": what, "reason": reason })),
+            Self::MempoolFull { capacity } => Some(json!({ "capacity": capacity })),
+            Self::InvalidSignature { from } => Some(json!({ "from": from })),
+            Self::Upload(error) => Some(match error {
+                UploadError::UnknownUpload(upload_id) => json!({ "upload_id": upload_id }),
+                UploadError::UnexpectedOffset { expected, actual } => {
+                    json!({ "expected_offset": expected, "offset": actual })
+                }
+                UploadError::TooLarge(max_size) => json!({ "max_size": max_size }),
+                UploadError::TooManyUploads(max_uploads) => json!({ "max_uploads": max_uploads }),
+            }),
+            Self::Namespace(error) => Some(match error {
+                NamespaceError::AlreadyRegistered { namespace } => {
+                    json!({ "namespace": namespace })
+                }
+                NamespaceError::PosterNotAllowed { namespace, poster } => {
+                    json!({ "namespace": namespace, "poster": poster })
+                }
+                NamespaceError::GasPriceTooLow {
+                    namespace,
+                    required,
+                    actual,
+                } => json!({
+                    "namespace": namespace,
+                    "required_gas_price": required,
+                    "gas_price": actual,
+                }),
+            }),
+            Self::InvalidRange {
+                from_height,
+                to_height,
+                max_range,
+            } => Some(json!({
+                "from_height": from_height,
+                "to_height": to_height,
+                "max_range": max_range,
+            })),
+            Self::VoteNotFound {
+                block_height,
+                validator_address,
+            } => Some(json!({
+                "block_height": block_height,
+                "validator_address": validator_address,
+            })),
+            Self::BlobNotFound { commitment } => Some(json!({ "commitment": commitment })),
+            Self::Gas(error) => Some(match error {
+                GasError::IntrinsicGasTooLow { required, limit } => {
+                    json!({ "required_gas": required, "gas_limit": limit })
+                }
+                GasError::AboveBlockGasLimit {
+                    limit,
+                    block_gas_limit,
+                } => json!({ "gas_limit": limit, "block_gas_limit": block_gas_limit }),
+                GasError::OutOfGas {
+                    limit,
+                    used,
+                    requested,
+                } => json!({ "gas_limit": limit, "gas_used": used, "requested_gas": requested }),
+                GasError::BlockGasExceeded {
+                    used,
+                    block_gas_limit,
+                } => json!({ "gas_used": used, "block_gas_limit": block_gas_limit }),
+            }),
+            Self::NonceTooLow {
+                from,
+                expected,
+                nonce,
+            } => Some(json!({ "from": from, "expected_nonce": expected, "nonce": nonce })),
+            Self::TooManyBlobs {
+                max_blobs,
+                blob_count,
+            } => Some(json!({ "max_blobs": max_blobs, "blob_count": blob_count })),
+            Self::TransactionTooLarge { max_bytes, size } => {
+                Some(json!({ "max_bytes": max_bytes, "size": size }))
+            }
+            Self::BlobTooLarge { max_bytes, size } => {
+                Some(json!({ "max_bytes": max_bytes, "size": size }))
+            }
+            Self::Governance(error) => Some(match error {
+                GovernanceError::NotAValidator { sender } => json!({ "sender": sender }),
+                GovernanceError::ActivationNotInFuture { activation, height } => {
+                    json!({ "activation_height": activation, "height": height })
+                }
+            }),
+            Self::Liveness(error) => Some(match error {
+                LivenessError::NotAValidator { sender } => json!({ "sender": sender }),
+                LivenessError::NotJailed { validator } => json!({ "validator": validator }),
+                LivenessError::StillJailed { release, height } => {
+                    json!({ "release_height": release, "height": height })
+                }
+            }),
+            Self::Evidence(error) => match error {
+                EvidenceError::UnknownValidator { validator }
+                | EvidenceError::InvalidSignature { validator } => {
+                    Some(json!({ "validator": validator }))
+                }
+                EvidenceError::NotConflicting => None,
+            },
+            Self::SamplingBusy {
+                max_concurrent,
+                max_queued,
+            } => Some(json!({ "max_concurrent": max_concurrent, "max_queued": max_queued })),
+            Self::SamplingConnectionBusy { max_per_connection } => {
+                Some(json!({ "max_per_connection": max_per_connection }))
+            }
+            Self::SharesNotCommitted {
+                block_height,
+                version,
+            } => Some(json!({ "block_height": block_height, "version": version })),
+            Self::ByteRangeOutOfRange {
+                from_byte,
+                to_byte,
+                blob_len,
+            } => Some(json!({
+                "from_byte": from_byte,
+                "to_byte": to_byte,
+                "blob_len": blob_len,
+            })),
+            Self::Internal(_) => None,
+        }
+    }
+}
+
+impl From<MikanRpcError> for ErrorObjectOwned {
+    fn from(error: MikanRpcError) -> Self {
+        ErrorObject::owned(error.code(), error.to_string(), error.data())
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CommitCertificateError {
+    #[error("Signature from {0}, which is not in the validator set")]
+    UnknownValidator(Address),
+    #[error("Duplicate signature from {0}")]
+    DuplicateSignature(Address),
+    #[error("Invalid signature from {0}")]
+    InvalidSignature(Address),
+    #[error("Signatures carry {signed} of {total} voting power, more than 2/3 is required")]
+    NotEnoughVotingPower { signed: u64, total: u64 },
 }
 
 #[derive(Debug, Error)]
@@ -51,4 +547,171 @@ pub enum StateError {
     TransactionPoolEmpty,
     #[error("RPC server is not enabled")]
     RpcServerNotEnabled,
+    #[error("No decided block at height {0}")]
+    MissingBlock(Height),
+    #[error("Genesis block in the store does not match the genesis file")]
+    GenesisMismatch,
+    #[error("Chain diverged, halting: {0}")]
+    Fork(Divergence),
+    #[error("Invalid commit certificate at height {height}: {error}")]
+    InvalidCertificate {
+        height: Height,
+        error: CommitCertificateError,
+    },
+    #[error("Cannot propose, halting until the node is upgraded: {0}")]
+    UnsupportedVersion(UpgradeError),
+}
+
+#[derive(Debug, Error)]
+pub enum EventError {
+    #[error("Couldn't open {path}: {source}")]
+    Open {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Couldn't write to {path}: {source}")]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error(transparent)]
+    Store(#[from] StoreError),
+    #[error("Failed to encode/decode Protobuf: {0}")]
+    Protobuf(#[from] malachitebft_proto::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[cfg(feature = "sql")]
+    #[error("Couldn't connect to the indexer database: {0}")]
+    DatabaseConnection(sqlx::Error),
+    #[cfg(feature = "sql")]
+    #[error("Indexer database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("Couldn't connect to the message broker: {0}")]
+    BrokerConnection(String),
+    #[error("Couldn't publish to {topic}: {error}")]
+    Publish { topic: String, error: String },
+    #[error("Webhook secret not set in {0}")]
+    WebhookSecretNotSet(String),
+    #[cfg(feature = "webhook")]
+    #[error("Couldn't create the webhook client: {0}")]
+    WebhookClient(#[from] reqwest::Error),
+    #[error("Webhook queue is full, dropping notification")]
+    WebhookQueueFull,
+    #[error("The {0} event sink requires the `{0}` feature")]
+    FeatureDisabled(&'static str),
+}
+
+#[derive(Debug, Error)]
+pub enum RelayerError {
+    #[error("Relayer private key not set in {0}")]
+    PrivateKeyNotSet(String),
+    #[error("Invalid relayer {field}: {error}")]
+    InvalidConfig { field: &'static str, error: String },
+    #[cfg(feature = "relayer")]
+    #[error("Starknet provider error: {0}")]
+    Provider(#[from] starknet::providers::ProviderError),
+    #[error("Empty batch")]
+    EmptyBatch,
+    #[error("Missing decided block at height {0}")]
+    MissingBlock(u64),
+    #[error(transparent)]
+    Store(#[from] StoreError),
+    #[error("Failed to encode/decode Protobuf: {0}")]
+    Protobuf(#[from] malachitebft_proto::Error),
+    #[error("The relayer requires the `relayer` feature")]
+    FeatureDisabled,
+}
+
+#[derive(Debug, Error)]
+pub enum BuilderError {
+    #[cfg(feature = "builder")]
+    #[error("Block builder request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid hex transaction: {0}")]
+    Hex(#[from] hex::FromHexError),
+    #[error("Failed to decode Protobuf transaction: {0}")]
+    Protobuf(#[from] malachitebft_proto::Error),
+    #[error("The block builder requires the `builder` feature")]
+    FeatureDisabled,
+}
+
+#[derive(Debug, Error)]
+pub enum SamplingError {
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    #[error("Invalid SSZ header: {0}")]
+    Header(#[from] SszError),
+    #[error("Header at height {0} has an invalid hash")]
+    InvalidHash(u64),
+    #[error("Header at height {height} does not extend the header at height {parent}")]
+    NotExtending { height: u64, parent: u64 },
+}
+
+#[derive(Debug, Error)]
+pub enum WalError {
+    #[error("Couldn't open {path}: {source}")]
+    Open {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum DevnetError {
+    #[error("Couldn't run {binary}: {source}")]
+    Run {
+        binary: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("The testnet command failed with {0}")]
+    TestnetFailed(ExitStatus),
+    #[error("Couldn't start node {index} with {binary}: {source}")]
+    Spawn {
+        index: usize,
+        binary: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("All the nodes exited")]
+    AllNodesExited,
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum TestnetError {
+    #[error("Couldn't write {path}: {source}")]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rpc_error_carries_code_and_data() {
+        let error: ErrorObjectOwned = MikanRpcError::BlobIndexOutOfRange {
+            block_height: 3,
+            blob_index: 7,
+            blob_count: 4,
+        }
+        .into();
+
+        assert_eq!(error.code(), MikanRpcError::BLOB_INDEX_OUT_OF_RANGE);
+        let data: serde_json::Value = serde_json::from_str(error.data().unwrap().get()).unwrap();
+        assert_eq!(
+            data,
+            json!({ "block_height": 3, "blob_index": 7, "blob_count": 4 })
+        );
+    }
 }
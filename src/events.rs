@@ -0,0 +1,452 @@
+//! Structured chain events for indexers, emitted when a block is committed.
+//!
+//! Events go through a bounded queue to a background task that hands them to each configured
+//! [`EventSink`], so a slow sink drops events rather than holding up consensus. Besides JSONL
+//! files and logs, events can be written to a relational database, see `crate::indexer`, or
+//! published to Kafka or NATS, see [`crate::publisher`], and blocks notified to webhooks, see
+//! `crate::webhook`. Other sinks are plugged in by implementing [`EventSink`] and passing them
+//! to [`EventBus::start`].
+//!
+//! Finalized blocks are also published on a [`FinalityFeed`], which backs the
+//! `mikan_subscribeFinality` RPC subscription.
+
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
+
+use crate::block::Block;
+use crate::error::EventError;
+use crate::publisher::DEFAULT_TOPIC_PREFIX;
+use crate::store::Store;
+use crate::types::address::Address;
+use crate::types::value::ValueId;
+
+/// Notifications kept for subscribers that fall behind, older ones are skipped.
+pub const FINALITY_FEED_CAPACITY: usize = 64;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChainEvent {
+    BlockCommitted {
+        height: u64,
+        round: u32,
+        #[serde(with = "hex::serde")]
+        block_hash: [u8; 32],
+        timestamp: u64,
+        proposer: Address,
+        tx_count: usize,
+    },
+    TransactionIncluded {
+        height: u64,
+        index: usize,
+        #[serde(with = "hex::serde")]
+        tx_hash: [u8; 32],
+        from: Address,
+        nonce: u64,
+    },
+    BlobPosted {
+        height: u64,
+        #[serde(with = "hex::serde")]
+        tx_hash: [u8; 32],
+        blob_index: usize,
+        #[serde(with = "hex::serde")]
+        commitment: [u8; 32],
+        /// Size of the blob data in bytes
+        size: usize,
+    },
+    /// Validator set used from `height` on. Also emitted for the first committed block.
+    ValidatorSetChanged {
+        height: u64,
+        validators: Vec<(Address, u64)>,
+    },
+}
+
+/// Events of the block decided at `height` in `round`: the block, then each transaction followed
+/// by its blobs. Validator set changes are tracked by the caller.
+pub fn block_events(height: u64, round: u32, block: &Block) -> Vec<ChainEvent> {
+    let header = block.header();
+    let mut events = vec![ChainEvent::BlockCommitted {
+        height,
+        round,
+        block_hash: block.hash(),
+        timestamp: header.timestamp,
+        proposer: header.proposer_address,
+        tx_count: block.transactions().len(),
+    }];
+
+    for (index, tx) in block.transactions().iter().enumerate() {
+        events.push(ChainEvent::TransactionIncluded {
+            height,
+            index,
+            tx_hash: tx.hash(),
+            from: Address::from_public_key(&tx.from_()),
+            nonce: tx.nonce(),
+        });
+
+        for (blob_index, commitment) in tx.blob_commitments().iter().enumerate() {
+            events.push(ChainEvent::BlobPosted {
+                height,
+                tx_hash: tx.hash(),
+                blob_index,
+                commitment: *commitment,
+                size: tx
+                    .data()
+                    .get(blob_index)
+                    .map_or(0, |blob| blob.data().len()),
+            });
+        }
+    }
+
+    events
+}
+
+/// Destination of chain events.
+#[async_trait]
+pub trait EventSink: Send {
+    fn name(&self) -> &str;
+
+    async fn handle(&mut self, event: &ChainEvent) -> Result<(), EventError>;
+}
+
+/// Appends events to a file, one JSON object per line.
+pub struct JsonlSink {
+    path: PathBuf,
+    file: File,
+}
+
+impl JsonlSink {
+    pub async fn open(path: PathBuf) -> Result<Self, EventError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .map_err(|source| EventError::Open {
+                path: path.clone(),
+                source,
+            })?;
+        Ok(Self { path, file })
+    }
+}
+
+#[async_trait]
+impl EventSink for JsonlSink {
+    fn name(&self) -> &str {
+        "jsonl"
+    }
+
+    async fn handle(&mut self, event: &ChainEvent) -> Result<(), EventError> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+        self.file
+            .write_all(&line)
+            .await
+            .map_err(|source| EventError::Write {
+                path: self.path.clone(),
+                source,
+            })
+    }
+}
+
+/// Logs events, mostly useful to check what an indexer would receive.
+pub struct LogSink;
+
+#[async_trait]
+impl EventSink for LogSink {
+    fn name(&self) -> &str {
+        "log"
+    }
+
+    async fn handle(&mut self, event: &ChainEvent) -> Result<(), EventError> {
+        info!(?event, "Chain event");
+        Ok(())
+    }
+}
+
+/// A sink in the `[events]` section of the node configuration.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkConfig {
+    Jsonl {
+        path: PathBuf,
+    },
+    Log,
+    /// Relational database, see `crate::indexer::SqlSink`, requires the `sql` feature
+    Sql {
+        url: String,
+        /// Index the blocks of the store missing from the database on startup
+        #[serde(default)]
+        backfill: bool,
+    },
+    /// Kafka brokers, see [`crate::publisher::StreamSink`], requires the `kafka` feature
+    Kafka {
+        brokers: String,
+        #[serde(default = "default_topic_prefix")]
+        topic_prefix: String,
+    },
+    /// NATS JetStream server, see [`crate::publisher::StreamSink`], requires the `nats` feature
+    Nats {
+        url: String,
+        #[serde(default = "default_topic_prefix")]
+        topic_prefix: String,
+    },
+    /// URLs notified of each committed block, see `crate::webhook::WebhookSink`, requires the
+    /// `webhook` feature
+    Webhook {
+        urls: Vec<String>,
+        /// Environment variable holding the HMAC secret, unsigned if unset
+        #[serde(default)]
+        secret_env: Option<String>,
+        #[serde(default = "default_webhook_retries")]
+        max_retries: u32,
+    },
+}
+
+/// Retries of a webhook delivery when the configuration does not set it.
+const DEFAULT_WEBHOOK_RETRIES: u32 = 5;
+
+fn default_webhook_retries() -> u32 {
+    DEFAULT_WEBHOOK_RETRIES
+}
+
+fn default_topic_prefix() -> String {
+    DEFAULT_TOPIC_PREFIX.to_string()
+}
+
+/// `[events]` section of the node configuration, no sink by default.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventsConfig {
+    pub sinks: Vec<SinkConfig>,
+    /// Events waiting for the sinks beyond this number are dropped
+    pub queue_size: usize,
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        Self {
+            sinks: Vec::new(),
+            queue_size: 1024,
+        }
+    }
+}
+
+impl EventsConfig {
+    /// Opens the configured sinks, backfilling from `store` the ones that ask for it.
+    pub async fn open_sinks(&self, store: &Store) -> Result<Vec<Box<dyn EventSink>>, EventError> {
+        let mut sinks: Vec<Box<dyn EventSink>> = Vec::with_capacity(self.sinks.len());
+        for sink in &self.sinks {
+            match sink {
+                SinkConfig::Jsonl { path } => {
+                    sinks.push(Box::new(JsonlSink::open(path.clone()).await?))
+                }
+                SinkConfig::Log => sinks.push(Box::new(LogSink)),
+                SinkConfig::Sql { url, backfill } => {
+                    #[cfg(feature = "sql")]
+                    {
+                        let mut sink = crate::indexer::SqlSink::open(url).await?;
+                        if *backfill {
+                            sink.backfill(store).await?;
+                        }
+                        sinks.push(Box::new(sink))
+                    }
+                    #[cfg(not(feature = "sql"))]
+                    {
+                        let _ = (url, backfill, store);
+                        return Err(EventError::FeatureDisabled("sql"));
+                    }
+                }
+                SinkConfig::Kafka {
+                    brokers,
+                    topic_prefix,
+                } => {
+                    #[cfg(feature = "kafka")]
+                    {
+                        let publisher = crate::publisher::KafkaPublisher::new(brokers)?;
+                        let sink = crate::publisher::StreamSink::open(
+                            format!("kafka:{topic_prefix}"),
+                            topic_prefix.clone(),
+                            Box::new(publisher),
+                            store.clone(),
+                        )
+                        .await?;
+                        sinks.push(Box::new(sink))
+                    }
+                    #[cfg(not(feature = "kafka"))]
+                    {
+                        let _ = (brokers, topic_prefix);
+                        return Err(EventError::FeatureDisabled("kafka"));
+                    }
+                }
+                SinkConfig::Webhook {
+                    urls,
+                    secret_env,
+                    max_retries,
+                } => {
+                    #[cfg(feature = "webhook")]
+                    {
+                        let sink = crate::webhook::WebhookSink::start(
+                            urls.clone(),
+                            secret_env.as_deref(),
+                            *max_retries,
+                        )?;
+                        sinks.push(Box::new(sink))
+                    }
+                    #[cfg(not(feature = "webhook"))]
+                    {
+                        let _ = (urls, secret_env, max_retries);
+                        return Err(EventError::FeatureDisabled("webhook"));
+                    }
+                }
+                SinkConfig::Nats { url, topic_prefix } => {
+                    #[cfg(feature = "nats")]
+                    {
+                        let publisher = crate::publisher::NatsPublisher::connect(url).await?;
+                        let sink = crate::publisher::StreamSink::open(
+                            format!("nats:{topic_prefix}"),
+                            topic_prefix.clone(),
+                            Box::new(publisher),
+                            store.clone(),
+                        )
+                        .await?;
+                        sinks.push(Box::new(sink))
+                    }
+                    #[cfg(not(feature = "nats"))]
+                    {
+                        let _ = (url, topic_prefix);
+                        return Err(EventError::FeatureDisabled("nats"));
+                    }
+                }
+            }
+        }
+        Ok(sinks)
+    }
+}
+
+/// Handle to emit events, disabled when no sink is configured.
+#[derive(Clone, Debug, Default)]
+pub struct EventBus {
+    sender: Option<mpsc::Sender<ChainEvent>>,
+}
+
+impl EventBus {
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Spawns the task feeding `sinks`, must be called within a Tokio runtime.
+    pub fn start(sinks: Vec<Box<dyn EventSink>>, queue_size: usize) -> Self {
+        if sinks.is_empty() {
+            return Self::disabled();
+        }
+
+        let (sender, mut receiver) = mpsc::channel::<ChainEvent>(queue_size.max(1));
+        let mut sinks = sinks;
+        tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                for sink in sinks.iter_mut() {
+                    if let Err(e) = sink.handle(&event).await {
+                        warn!(sink = sink.name(), error = %e, "Event sink failed");
+                    }
+                }
+            }
+        });
+
+        Self {
+            sender: Some(sender),
+        }
+    }
+
+    /// Whether events are consumed, to skip building them otherwise.
+    pub fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    pub fn emit(&self, event: ChainEvent) {
+        if let Some(sender) = &self.sender {
+            if sender.try_send(event).is_err() {
+                warn!("Event queue is full, dropping event");
+            }
+        }
+    }
+}
+
+/// A block and a summary of the certificate that decided it, published once the commit is
+/// stored.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Finalized {
+    pub height: u64,
+    #[serde(with = "hex::serde")]
+    pub block_hash: [u8; 32],
+    pub round: u32,
+    pub value_id: ValueId,
+    /// Number of precommits in the certificate
+    pub signature_count: usize,
+    /// Voting power of those precommits
+    pub signed_voting_power: u64,
+}
+
+/// Publishes [`Finalized`] notifications to any number of subscribers.
+#[derive(Clone, Debug)]
+pub struct FinalityFeed {
+    sender: broadcast::Sender<Finalized>,
+}
+
+impl Default for FinalityFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FinalityFeed {
+    pub fn new() -> Self {
+        Self {
+            sender: broadcast::channel(FINALITY_FEED_CAPACITY).0,
+        }
+    }
+
+    pub fn publish(&self, finalized: Finalized) {
+        // No subscriber is not an error
+        let _ = self.sender.send(finalized);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Finalized> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_tagged_json() {
+        let event = ChainEvent::TransactionIncluded {
+            height: 3,
+            index: 0,
+            tx_hash: [0xab; 32],
+            from: Address::new([0xcd; 20]),
+            nonce: 0,
+        };
+
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "transaction_included");
+        assert_eq!(json["tx_hash"], hex::encode([0xab; 32]));
+        assert_eq!(serde_json::from_value::<ChainEvent>(json).unwrap(), event);
+    }
+
+    #[test]
+    fn disabled_bus_drops_events() {
+        let bus = EventBus::disabled();
+        assert!(!bus.is_enabled());
+        bus.emit(ChainEvent::ValidatorSetChanged {
+            height: 1,
+            validators: vec![],
+        });
+    }
+}
@@ -0,0 +1,377 @@
+//! Evidence of double signing, recorded for slashing.
+//!
+//! A validator double signs when it signs two votes of the same type for different values in the
+//! same round, or two different proposals for the same round. Nodes detect it locally, from
+//! proposals streamed twice by the same proposer with different contents and from precommits of a
+//! block's last commit conflicting with the certificate we decided, and anyone can submit the
+//! conflicting signatures they collected with `mikan_submitEvidence`.
+//!
+//! Evidence is verified against the validator set of its height and kept in the store by height
+//! and validator, never pruned.
+
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use malachitebft_core_types::{CommitCertificate, NilOrVal, Round};
+use serde::{Deserialize, Serialize};
+
+use crate::error::EvidenceError;
+use crate::types::address::Address;
+use crate::types::context::TestContext;
+use crate::types::height::Height;
+use crate::types::serde_hex;
+use crate::types::sign_bytes::vote_sign_bytes;
+use crate::types::signing::Signature;
+use crate::types::validator_set::ValidatorSet;
+use crate::types::value::ValueId;
+use crate::types::vote::Vote;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteKind {
+    Prevote,
+    Precommit,
+}
+
+/// A signed vote, as carried by evidence.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvidenceVote {
+    pub kind: VoteKind,
+    pub height: u64,
+    pub round: u32,
+    /// `None` for a vote for nil
+    pub value_id: Option<ValueId>,
+    pub validator_address: Address,
+    #[serde(with = "serde_hex::signature")]
+    pub signature: Signature,
+}
+
+impl EvidenceVote {
+    /// Precommit for `value_id` of the round decided by `certificate`, signed by `address`.
+    pub fn precommit(
+        certificate: &CommitCertificate<TestContext>,
+        address: Address,
+        signature: Signature,
+    ) -> Self {
+        Self {
+            kind: VoteKind::Precommit,
+            height: certificate.height.as_u64(),
+            round: certificate.round.as_u32().unwrap_or_default(),
+            value_id: Some(certificate.value_id),
+            validator_address: address,
+            signature,
+        }
+    }
+
+    /// The vote as signed, see [`vote_sign_bytes`].
+    pub fn to_vote(&self) -> Vote {
+        let height = Height::new(self.height);
+        let round = Round::new(self.round);
+        let value = self.value_id.map_or(NilOrVal::Nil, NilOrVal::Val);
+        match self.kind {
+            VoteKind::Prevote => Vote::new_prevote(height, round, value, self.validator_address),
+            VoteKind::Precommit => {
+                Vote::new_precommit(height, round, value, self.validator_address)
+            }
+        }
+    }
+}
+
+/// A signed proposal, as carried by evidence. The proposer signs the digest of the streamed
+/// parts, see [`crate::streaming::ProposalPartsHasher`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvidenceProposal {
+    pub height: u64,
+    pub round: u32,
+    pub proposer: Address,
+    #[serde(with = "hex::serde")]
+    pub digest: [u8; 32],
+    #[serde(with = "serde_hex::signature")]
+    pub signature: Signature,
+}
+
+/// Two conflicting messages signed by the same validator.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Evidence {
+    DuplicateVote {
+        first: EvidenceVote,
+        second: EvidenceVote,
+    },
+    DuplicateProposal {
+        first: EvidenceProposal,
+        second: EvidenceProposal,
+    },
+}
+
+impl Evidence {
+    /// Evidence of `first` and `second`, ordered so that the same conflict is recorded once
+    /// whichever message was seen first.
+    pub fn duplicate_vote(first: EvidenceVote, second: EvidenceVote) -> Self {
+        if first.value_id <= second.value_id {
+            Self::DuplicateVote { first, second }
+        } else {
+            Self::DuplicateVote {
+                first: second,
+                second: first,
+            }
+        }
+    }
+
+    /// See [`Self::duplicate_vote`].
+    pub fn duplicate_proposal(first: EvidenceProposal, second: EvidenceProposal) -> Self {
+        if first.digest <= second.digest {
+            Self::DuplicateProposal { first, second }
+        } else {
+            Self::DuplicateProposal {
+                first: second,
+                second: first,
+            }
+        }
+    }
+
+    pub fn height(&self) -> u64 {
+        match self {
+            Self::DuplicateVote { first, .. } => first.height,
+            Self::DuplicateProposal { first, .. } => first.height,
+        }
+    }
+
+    /// The validator that double signed.
+    pub fn validator(&self) -> Address {
+        match self {
+            Self::DuplicateVote { first, .. } => first.validator_address,
+            Self::DuplicateProposal { first, .. } => first.proposer,
+        }
+    }
+
+    /// Checks that both messages are signed by the same member of `validator_set`, the set of
+    /// the evidence height, and conflict.
+    pub fn verify(
+        &self,
+        chain_id: &str,
+        validator_set: &ValidatorSet,
+    ) -> Result<(), EvidenceError> {
+        let validator = self.validator();
+        let public_key = validator_set
+            .get_by_address(&validator)
+            .map(|v| v.public_key)
+            .ok_or(EvidenceError::UnknownValidator {
+                validator: validator.to_string(),
+            })?;
+
+        match self {
+            Self::DuplicateVote { first, second } => {
+                let conflicting = first.validator_address == second.validator_address
+                    && first.kind == second.kind
+                    && first.height == second.height
+                    && first.round == second.round
+                    && first.value_id != second.value_id;
+                if !conflicting {
+                    return Err(EvidenceError::NotConflicting);
+                }
+                for vote in [first, second] {
+                    let sign_bytes = vote_sign_bytes(chain_id, &vote.to_vote());
+                    if public_key.verify(&sign_bytes, &vote.signature).is_err() {
+                        return Err(EvidenceError::InvalidSignature {
+                            validator: validator.to_string(),
+                        });
+                    }
+                }
+            }
+            Self::DuplicateProposal { first, second } => {
+                let conflicting = first.proposer == second.proposer
+                    && first.height == second.height
+                    && first.round == second.round
+                    && first.digest != second.digest;
+                if !conflicting {
+                    return Err(EvidenceError::NotConflicting);
+                }
+                for proposal in [first, second] {
+                    if public_key
+                        .verify(&proposal.digest, &proposal.signature)
+                        .is_err()
+                    {
+                        return Err(EvidenceError::InvalidSignature {
+                            validator: validator.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvidenceSource {
+    /// Detected by this node
+    Local,
+    /// Submitted with `mikan_submitEvidence`
+    Submitted,
+}
+
+/// Evidence as persisted in the store.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvidenceRecord {
+    pub evidence: Evidence,
+    pub source: EvidenceSource,
+    /// Unix timestamp at which it was first recorded, in seconds
+    pub recorded_at: u64,
+}
+
+impl EvidenceRecord {
+    pub fn new(evidence: Evidence, source: EvidenceSource) -> Self {
+        let recorded_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        Self {
+            evidence,
+            source,
+            recorded_at,
+        }
+    }
+}
+
+/// Precommits of validators found in both certificates, which decided different values in the
+/// same round of the same height. Both certificates must have been verified.
+pub fn conflicting_precommits(
+    first: &CommitCertificate<TestContext>,
+    second: &CommitCertificate<TestContext>,
+) -> Vec<Evidence> {
+    if first.height != second.height
+        || first.round != second.round
+        || first.value_id == second.value_id
+    {
+        return Vec::new();
+    }
+
+    let mut evidence = Vec::new();
+    for signature in &first.aggregated_signature.signatures {
+        let other = second
+            .aggregated_signature
+            .signatures
+            .iter()
+            .find(|other| other.address == signature.address);
+        if let Some(other) = other {
+            evidence.push(Evidence::duplicate_vote(
+                EvidenceVote::precommit(first, signature.address, signature.signature),
+                EvidenceVote::precommit(second, other.address, other.signature),
+            ));
+        }
+    }
+    evidence
+}
+
+/// Proposals with a verified signature by height, round and proposer, to detect proposers
+/// streaming two different proposals for the same round.
+#[derive(Debug, Default)]
+pub struct ProposalWatch {
+    seen: BTreeMap<(u64, u32, Address), EvidenceProposal>,
+}
+
+impl ProposalWatch {
+    /// Records `proposal`, returning evidence if its proposer signed a different one for the
+    /// same round.
+    pub fn observe(&mut self, proposal: EvidenceProposal) -> Option<Evidence> {
+        let key = (proposal.height, proposal.round, proposal.proposer);
+        match self.seen.get(&key) {
+            Some(seen) if seen.digest != proposal.digest => {
+                Some(Evidence::duplicate_proposal(seen.clone(), proposal))
+            }
+            Some(_) => None,
+            None => {
+                self.seen.insert(key, proposal);
+                None
+            }
+        }
+    }
+
+    /// Drops the proposals of heights below `height`.
+    pub fn evict_below(&mut self, height: u64) {
+        self.seen = self.seen.split_off(&(height, 0, Address::new([0; 20])));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::sign_bytes::DEFAULT_CHAIN_ID;
+    use crate::types::validator_set::Validator;
+    use malachitebft_test::PrivateKey;
+    use rand::thread_rng;
+
+    fn signed_vote(key: &PrivateKey, value_id: Option<ValueId>) -> EvidenceVote {
+        let mut vote = EvidenceVote {
+            kind: VoteKind::Prevote,
+            height: 7,
+            round: 1,
+            value_id,
+            validator_address: Address::from_public_key(&key.public_key()),
+            signature: key.sign(&[]),
+        };
+        vote.signature = key.sign(&vote_sign_bytes(DEFAULT_CHAIN_ID, &vote.to_vote()));
+        vote
+    }
+
+    fn signed_proposal(key: &PrivateKey, digest: [u8; 32]) -> EvidenceProposal {
+        EvidenceProposal {
+            height: 7,
+            round: 0,
+            proposer: Address::from_public_key(&key.public_key()),
+            digest,
+            signature: key.sign(&digest),
+        }
+    }
+
+    #[test]
+    fn verifies_duplicate_votes() {
+        let key = PrivateKey::generate(thread_rng());
+        let validator_set = ValidatorSet::new([Validator::new(key.public_key(), 1)]);
+
+        let a = signed_vote(&key, Some(ValueId::new([1; 32])));
+        let b = signed_vote(&key, None);
+        let evidence = Evidence::duplicate_vote(a.clone(), b.clone());
+        assert_eq!(evidence, Evidence::duplicate_vote(b.clone(), a.clone()));
+        assert_eq!(evidence.verify(DEFAULT_CHAIN_ID, &validator_set), Ok(()));
+        assert_eq!(evidence.height(), 7);
+
+        // The same vote twice is not a conflict
+        assert_eq!(
+            Evidence::duplicate_vote(a.clone(), a.clone()).verify(DEFAULT_CHAIN_ID, &validator_set),
+            Err(EvidenceError::NotConflicting)
+        );
+
+        // Signatures are bound to the chain
+        assert!(matches!(
+            evidence.verify("other-chain", &validator_set),
+            Err(EvidenceError::InvalidSignature { .. })
+        ));
+
+        let other = PrivateKey::generate(thread_rng());
+        let other_set = ValidatorSet::new([Validator::new(other.public_key(), 1)]);
+        assert!(matches!(
+            evidence.verify(DEFAULT_CHAIN_ID, &other_set),
+            Err(EvidenceError::UnknownValidator { .. })
+        ));
+    }
+
+    #[test]
+    fn detects_duplicate_proposals() {
+        let key = PrivateKey::generate(thread_rng());
+        let validator_set = ValidatorSet::new([Validator::new(key.public_key(), 1)]);
+        let mut watch = ProposalWatch::default();
+
+        assert_eq!(watch.observe(signed_proposal(&key, [1; 32])), None);
+        // Streamed again, e.g. on a restart
+        assert_eq!(watch.observe(signed_proposal(&key, [1; 32])), None);
+
+        let evidence = watch.observe(signed_proposal(&key, [2; 32])).unwrap();
+        assert_eq!(evidence.verify(DEFAULT_CHAIN_ID, &validator_set), Ok(()));
+
+        watch.evict_below(8);
+        assert_eq!(watch.observe(signed_proposal(&key, [2; 32])), None);
+    }
+}
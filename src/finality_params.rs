@@ -0,0 +1,344 @@
+//! Finality of the parent block, carried by each block so that finality can be proven from block
+//! data alone, without the consensus store of a node.
+//!
+//! Block N embeds the [`CommitCertificate`] that decided block N-1, its last commit. The
+//! certificate is covered by the value id that the precommits for block N sign, so a chain of
+//! blocks ending with one certificate proves the finality of all of them. The genesis block is
+//! not decided, so block 1 carries no last commit.
+//!
+//! The precommits of a certificate are the leaves of the vote tree, see [`vote_tree_root`], so
+//! that the vote of one validator can be proven without the whole certificate.
+//!
+//! A [`FinalityProof`] bundles what a light client needs to accept one header as final.
+
+use bytes::Bytes;
+use malachitebft_core_types::{CommitCertificate, CommitSignature};
+use malachitebft_proto::Protobuf;
+use rs_merkle::{algorithms::Sha256, Hasher, MerkleProof, MerkleTree};
+use serde::{Deserialize, Serialize};
+
+use crate::block::Block;
+use crate::error::FinalityError;
+use crate::header::Header;
+use crate::types::address::Address;
+use crate::types::context::TestContext;
+use crate::types::genesis::Genesis;
+use crate::types::signing::verify_certificate;
+use crate::types::validator_set::ValidatorSet;
+use crate::types::value::Value;
+use crate::types::{certificate, serde_hex};
+
+/// What the last commit of a block is checked against.
+#[derive(Clone, Debug)]
+pub struct FinalityParams {
+    pub chain_id: String,
+    pub validator_set: ValidatorSet,
+}
+
+impl FinalityParams {
+    pub fn new(chain_id: impl Into<String>, validator_set: ValidatorSet) -> Self {
+        Self {
+            chain_id: chain_id.into(),
+            validator_set,
+        }
+    }
+
+    pub fn from_genesis(genesis: &Genesis) -> Self {
+        Self::new(genesis.chain_id.clone(), genesis.validator_set.clone())
+    }
+
+    /// Checks that `last_commit`, carried by the block at `height`, decided `prev_block` with
+    /// more than 2/3 of the voting power of the validator set.
+    pub fn verify(
+        &self,
+        height: u64,
+        last_commit: Option<&CommitCertificate<TestContext>>,
+        prev_block: &Block,
+    ) -> Result<(), FinalityError> {
+        let parent_height = height.saturating_sub(1);
+        let certificate = match last_commit {
+            None if parent_height == 0 => return Ok(()),
+            None => return Err(FinalityError::MissingLastCommit(parent_height)),
+            Some(_) if parent_height == 0 => {
+                return Err(FinalityError::UnexpectedLastCommit(height))
+            }
+            Some(certificate) => certificate,
+        };
+
+        if certificate.height.as_u64() != parent_height {
+            return Err(FinalityError::HeightMismatch {
+                expected: parent_height,
+                actual: certificate.height.as_u64(),
+            });
+        }
+
+        // The decided value is identified by the hash of the encoded block
+        let bytes = prev_block
+            .to_bytes()
+            .map_err(|_| FinalityError::ValueMismatch)?;
+        if certificate.value_id != Value::new(bytes).id() {
+            return Err(FinalityError::ValueMismatch);
+        }
+
+        verify_certificate(&self.chain_id, certificate, &self.validator_set)?;
+        Ok(())
+    }
+}
+
+/// Proof that the block at `height` is final, as one light client update.
+///
+/// The precommits sign the id of the encoded block rather than the header, so the proof carries
+/// the encoded block to bind the header to the certificate.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FinalityProof {
+    pub height: u64,
+    pub header: Header,
+    #[serde(with = "certificate")]
+    pub certificate: CommitCertificate<TestContext>,
+    pub validator_set: ValidatorSet,
+    /// Hashes of the validator sets from the trusted checkpoint to `height`, one per change, the
+    /// last one being the hash of `validator_set`, see [`ValidatorSet::hash`]. The set changes as
+    /// validators are jailed and unjailed, see [`crate::liveness`].
+    #[serde(with = "serde_hex::hashes")]
+    pub validator_set_hashes: Vec<[u8; 32]>,
+    /// Encoded block, whose hash is the decided value id
+    #[serde(with = "serde_hex::bytes")]
+    pub block: Bytes,
+}
+
+impl FinalityProof {
+    pub fn new(
+        certificate: CommitCertificate<TestContext>,
+        validator_set: ValidatorSet,
+        block: Bytes,
+    ) -> Result<Self, FinalityError> {
+        let header = Block::from_bytes(&block)
+            .map_err(|_| FinalityError::HeaderMismatch)?
+            .header()
+            .clone();
+
+        Ok(Self {
+            height: certificate.height.as_u64(),
+            header,
+            certificate,
+            validator_set_hashes: vec![validator_set.hash()],
+            validator_set,
+            block,
+        })
+    }
+
+    /// Replaces the hashes of the validator sets, a single one by default, when the set changed
+    /// since the checkpoint.
+    pub fn with_validator_set_hashes(mut self, hashes: Vec<[u8; 32]>) -> Self {
+        self.validator_set_hashes = hashes;
+        self
+    }
+
+    /// Checks that `header` was decided at `height` by more than 2/3 of the voting power of a
+    /// validator set chaining up to `trusted_validator_set_hash`.
+    pub fn verify(
+        &self,
+        chain_id: &str,
+        trusted_validator_set_hash: [u8; 32],
+    ) -> Result<(), FinalityError> {
+        if self.validator_set_hashes.first() != Some(&trusted_validator_set_hash)
+            || self.validator_set_hashes.last() != Some(&self.validator_set.hash())
+        {
+            return Err(FinalityError::ValidatorSetMismatch);
+        }
+
+        if self.certificate.height.as_u64() != self.height {
+            return Err(FinalityError::HeightMismatch {
+                expected: self.height,
+                actual: self.certificate.height.as_u64(),
+            });
+        }
+
+        if self.certificate.value_id != Value::new(self.block.clone()).id() {
+            return Err(FinalityError::ValueMismatch);
+        }
+
+        let block = Block::from_bytes(&self.block).map_err(|_| FinalityError::HeaderMismatch)?;
+        if block.header().block_number != self.height
+            || block.hash() != self.header.block_hash()
+            || self.header.compute_block_hash() != self.header.block_hash()
+        {
+            return Err(FinalityError::HeaderMismatch);
+        }
+
+        verify_certificate(chain_id, &self.certificate, &self.validator_set)?;
+        Ok(())
+    }
+}
+
+/// Leaf of the vote tree: SHA-256 of the validator address followed by its precommit signature.
+pub fn vote_leaf(commit_sig: &CommitSignature<TestContext>) -> [u8; 32] {
+    let mut bytes = commit_sig.address.into_inner().to_vec();
+    bytes.extend_from_slice(&commit_sig.signature.to_bytes());
+    Sha256::hash(&bytes)
+}
+
+fn vote_tree(certificate: &CommitCertificate<TestContext>) -> MerkleTree<Sha256> {
+    let leaves: Vec<[u8; 32]> = certificate
+        .aggregated_signature
+        .signatures
+        .iter()
+        .map(vote_leaf)
+        .collect();
+    MerkleTree::from_leaves(&leaves)
+}
+
+/// Merkle root of the precommits of `certificate`, in certificate order, zero without any.
+pub fn vote_tree_root(certificate: &CommitCertificate<TestContext>) -> [u8; 32] {
+    vote_tree(certificate).root().unwrap_or_default()
+}
+
+/// Index of the precommit of `validator` in `certificate` and proof that it is committed to by
+/// [`vote_tree_root`], `None` if the validator did not sign.
+pub fn proof_of_vote(
+    certificate: &CommitCertificate<TestContext>,
+    validator: &Address,
+) -> Option<(usize, MerkleProof<Sha256>)> {
+    let index = certificate
+        .aggregated_signature
+        .signatures
+        .iter()
+        .position(|commit_sig| commit_sig.address == *validator)?;
+
+    Some((index, vote_tree(certificate).proof(&[index])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::height::Height;
+    use crate::types::sign_bytes::DEFAULT_CHAIN_ID;
+    use crate::types::signing::{Ed25519Provider, PrivateKey};
+    use crate::types::validator_set::Validator;
+    use crate::types::vote::Vote;
+    use malachitebft_core_types::{
+        AggregatedSignature, CommitSignature, NilOrVal, Round, SigningProvider,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn commit(
+        signer: &Ed25519Provider,
+        height: u64,
+        block: &Block,
+    ) -> CommitCertificate<TestContext> {
+        let (height, round) = (Height::new(height), Round::new(0));
+        let value_id = Value::new(block.to_bytes().unwrap()).id();
+        let address = Address::from_public_key(&signer.private_key().public_key());
+        let vote = Vote::new_precommit(height, round, NilOrVal::Val(value_id), address);
+
+        CommitCertificate {
+            height,
+            round,
+            value_id,
+            aggregated_signature: AggregatedSignature {
+                signatures: vec![CommitSignature {
+                    address,
+                    signature: signer.sign_vote(vote).signature,
+                }],
+            },
+        }
+    }
+
+    #[test]
+    fn verifies_commit_of_the_parent_block() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let signer = Ed25519Provider::new(PrivateKey::generate(&mut rng), DEFAULT_CHAIN_ID);
+        let params = FinalityParams::new(
+            DEFAULT_CHAIN_ID,
+            ValidatorSet::new([Validator::new(signer.private_key().public_key(), 1)]),
+        );
+        let genesis = Block::genesis();
+        let parent = Block::new(1, 0, genesis.hash(), Address::default(), vec![]);
+
+        assert_eq!(params.verify(1, None, &genesis), Ok(()));
+        assert_eq!(
+            params.verify(1, Some(&commit(&signer, 0, &genesis)), &genesis),
+            Err(FinalityError::UnexpectedLastCommit(1))
+        );
+
+        assert_eq!(
+            params.verify(2, Some(&commit(&signer, 1, &parent)), &parent),
+            Ok(())
+        );
+        assert_eq!(
+            params.verify(2, None, &parent),
+            Err(FinalityError::MissingLastCommit(1))
+        );
+        assert_eq!(
+            params.verify(2, Some(&commit(&signer, 1, &genesis)), &parent),
+            Err(FinalityError::ValueMismatch)
+        );
+        assert_eq!(
+            params.verify(3, Some(&commit(&signer, 1, &parent)), &parent),
+            Err(FinalityError::HeightMismatch {
+                expected: 2,
+                actual: 1
+            })
+        );
+
+        let other_chain = FinalityParams::new("other-chain", params.validator_set.clone());
+        assert!(matches!(
+            other_chain.verify(2, Some(&commit(&signer, 1, &parent)), &parent),
+            Err(FinalityError::Certificate(_))
+        ));
+    }
+
+    #[test]
+    fn verifies_finality_proof() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let signer = Ed25519Provider::new(PrivateKey::generate(&mut rng), DEFAULT_CHAIN_ID);
+        let validator_set =
+            ValidatorSet::new([Validator::new(signer.private_key().public_key(), 1)]);
+        let block = Block::new(1, 0, Block::genesis().hash(), Address::default(), vec![]);
+
+        let proof = FinalityProof::new(
+            commit(&signer, 1, &block),
+            validator_set.clone(),
+            block.to_bytes().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(proof.verify(DEFAULT_CHAIN_ID, validator_set.hash()), Ok(()));
+        assert_eq!(
+            proof.verify(DEFAULT_CHAIN_ID, [0; 32]),
+            Err(FinalityError::ValidatorSetMismatch)
+        );
+
+        let mut forged = proof.clone();
+        forged.header = Block::genesis().header().clone();
+        assert_eq!(
+            forged.verify(DEFAULT_CHAIN_ID, validator_set.hash()),
+            Err(FinalityError::HeaderMismatch)
+        );
+    }
+
+    #[test]
+    fn proves_votes_of_the_commit() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let signers: Vec<_> = (0..3)
+            .map(|_| Ed25519Provider::new(PrivateKey::generate(&mut rng), DEFAULT_CHAIN_ID))
+            .collect();
+        let block = Block::genesis();
+        let mut certificate = commit(&signers[0], 1, &block);
+        for signer in &signers[1..] {
+            let other = commit(signer, 1, &block);
+            certificate
+                .aggregated_signature
+                .signatures
+                .extend(other.aggregated_signature.signatures);
+        }
+        let root = vote_tree_root(&certificate);
+        let signatures = &certificate.aggregated_signature.signatures;
+
+        let (index, proof) = proof_of_vote(&certificate, &signatures[2].address).unwrap();
+        assert_eq!(index, 2);
+        assert!(proof.verify(root, &[2], &[vote_leaf(&signatures[2])], 3));
+        assert!(!proof.verify(root, &[2], &[vote_leaf(&signatures[1])], 3));
+
+        assert!(proof_of_vote(&certificate, &Address::default()).is_none());
+    }
+}
@@ -0,0 +1,137 @@
+//! Detection of blocks that do not extend our chain.
+//!
+//! A proposal or a synced value whose parent is not our block at the height below is only
+//! recorded and rejected: it may come from a faulty proposer or sync peer. A decided value whose
+//! commit certificate checks out against our validator set and that does not extend our chain
+//! was decided by the network on top of another chain than ours, so the node halts rather than
+//! stalling on values it can never validate.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use malachitebft_app_channel::app::metrics::prometheus::metrics::counter::Counter;
+use malachitebft_app_channel::app::metrics::prometheus::metrics::gauge::Gauge;
+use malachitebft_app_channel::app::metrics::SharedRegistry;
+use serde::{Deserialize, Serialize};
+
+/// Number of divergences kept for `mikan_getForkStatus`.
+pub const MAX_RECORDED_DIVERGENCES: usize = 16;
+
+/// Where the diverging block came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DivergenceSource {
+    Proposal,
+    Sync,
+    /// A value decided with a valid commit certificate
+    Decided,
+}
+
+/// A block at `height` whose parent hash is not the hash of our block at `height - 1`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Divergence {
+    pub height: u64,
+    pub source: DivergenceSource,
+    /// Hash of our decided block at `height - 1`
+    #[serde(with = "hex::serde")]
+    pub local_parent: [u8; 32],
+    /// Parent hash claimed by the diverging block
+    #[serde(with = "hex::serde")]
+    pub block_parent: [u8; 32],
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} block at height {} extends {} instead of our block {}",
+            self.source,
+            self.height,
+            hex::encode(self.block_parent),
+            hex::encode(self.local_parent)
+        )
+    }
+}
+
+/// Divergences seen by the node, shared between the consensus loop and the RPC server.
+#[derive(Debug, Clone, Default)]
+pub struct ForkMonitor {
+    divergences: Arc<Mutex<VecDeque<Divergence>>>,
+    /// Number of divergences seen since startup
+    divergences_total: Counter,
+    /// Height of the last divergence, 0 if none
+    last_divergence_height: Gauge,
+}
+
+impl ForkMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(registry: &SharedRegistry) -> Self {
+        let monitor = Self::new();
+
+        registry.with_prefix("app_channel", |registry| {
+            registry.register(
+                "fork_divergences_total",
+                "Number of blocks seen that do not extend our chain",
+                monitor.divergences_total.clone(),
+            );
+
+            registry.register(
+                "fork_last_divergence_height",
+                "Height of the last block seen that does not extend our chain",
+                monitor.last_divergence_height.clone(),
+            );
+        });
+
+        monitor
+    }
+
+    pub fn record(&self, divergence: Divergence) {
+        self.divergences_total.inc();
+        self.last_divergence_height.set(divergence.height as i64);
+
+        let mut divergences = self.divergences.lock().unwrap();
+        if divergences.len() == MAX_RECORDED_DIVERGENCES {
+            divergences.pop_front();
+        }
+        divergences.push_back(divergence);
+    }
+
+    /// Recorded divergences, oldest first.
+    pub fn divergences(&self) -> Vec<Divergence> {
+        self.divergences.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn divergence(height: u64) -> Divergence {
+        Divergence {
+            height,
+            source: DivergenceSource::Sync,
+            local_parent: [1; 32],
+            block_parent: [2; 32],
+        }
+    }
+
+    #[test]
+    fn keeps_latest_divergences() {
+        let monitor = ForkMonitor::new();
+        for height in 0..MAX_RECORDED_DIVERGENCES as u64 + 2 {
+            monitor.record(divergence(height));
+        }
+
+        let divergences = monitor.divergences();
+        assert_eq!(divergences.len(), MAX_RECORDED_DIVERGENCES);
+        assert_eq!(divergences[0].height, 2);
+        assert_eq!(
+            monitor.divergences_total.get(),
+            MAX_RECORDED_DIVERGENCES as u64 + 2
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! Entry points for fuzzing the decoding of untrusted input: messages from peers, and entries of
+//! the WAL and the store.
+//!
+//! The `decode_*` functions take raw bytes and must return an error, never panic, whatever the
+//! input. The `roundtrip_*` functions check that a value decodes back from its encoding, and
+//! panic otherwise as fuzz targets expect. With the `arbitrary` feature, [`Block`],
+//! [`Transaction`], [`Vote`] and [`ProposalPart`] implement `arbitrary::Arbitrary`, for
+//! structure-aware fuzzing. With cargo-fuzz:
+//!
+//! ```ignore
+//! fuzz_target!(|data: &[u8]| {
+//!     let _ = mikan::fuzz::decode_block(data);
+//! });
+//!
+//! fuzz_target!(|block: mikan::block::Block| mikan::fuzz::roundtrip_block(&block));
+//! ```
+
+use bincode::error::DecodeError;
+use bytes::Bytes;
+use malachitebft_app::streaming::StreamMessage;
+use malachitebft_codec::Codec;
+use malachitebft_core_consensus::SignedConsensusMsg;
+use malachitebft_proto::{Error as ProtoError, Protobuf};
+
+use crate::block::Block;
+use crate::transactions::Transaction;
+use crate::types::codec::proto::ProtobufCodec;
+use crate::types::context::TestContext;
+use crate::types::proposal_part::ProposalPart;
+use crate::types::value::Value;
+use crate::types::vote::Vote;
+
+pub fn decode_block(data: &[u8]) -> Result<Block, ProtoError> {
+    Protobuf::from_bytes(data)
+}
+
+pub fn decode_transaction(data: &[u8]) -> Result<Transaction, ProtoError> {
+    Protobuf::from_bytes(data)
+}
+
+/// A vote as gossiped, see [`decode_vote_bincode`] for the WAL encoding.
+pub fn decode_vote(data: &[u8]) -> Result<Vote, ProtoError> {
+    Protobuf::from_bytes(data)
+}
+
+/// A vote as written to the WAL.
+pub fn decode_vote_bincode(data: &[u8]) -> Result<Vote, DecodeError> {
+    bincode::decode_from_slice(data, bincode::config::standard()).map(|(vote, _)| vote)
+}
+
+pub fn decode_proposal_part(data: &[u8]) -> Result<ProposalPart, ProtoError> {
+    Protobuf::from_bytes(data)
+}
+
+/// A signed vote or proposal, as received on the consensus channel.
+pub fn decode_consensus_message(
+    data: &[u8],
+) -> Result<SignedConsensusMsg<TestContext>, ProtoError> {
+    ProtobufCodec.decode(Bytes::copy_from_slice(data))
+}
+
+/// A decided value, as received from a sync peer.
+pub fn decode_value(data: &[u8]) -> Result<Value, ProtoError> {
+    crate::state::decode_value(Bytes::copy_from_slice(data))
+}
+
+/// A proposal part, as received on the proposal parts channel.
+pub fn decode_stream_message(data: &[u8]) -> Result<StreamMessage<ProposalPart>, ProtoError> {
+    ProtobufCodec.decode(Bytes::copy_from_slice(data))
+}
+
+pub fn roundtrip_block(block: &Block) {
+    roundtrip_proto(block, decode_block);
+}
+
+pub fn roundtrip_transaction(tx: &Transaction) {
+    roundtrip_proto(tx, decode_transaction);
+}
+
+/// Both encodings of `vote`. The bincode one does not carry the extension.
+pub fn roundtrip_vote(vote: &Vote) {
+    roundtrip_proto(vote, decode_vote);
+
+    let bytes = bincode::encode_to_vec(vote, bincode::config::standard()).expect("Encodable vote");
+    let decoded = decode_vote_bincode(&bytes).expect("Decodable vote");
+    assert_eq!(
+        decoded,
+        Vote {
+            extension: None,
+            ..vote.clone()
+        }
+    );
+}
+
+pub fn roundtrip_proposal_part(part: &ProposalPart) {
+    roundtrip_proto(part, decode_proposal_part);
+}
+
+/// Checks that `value` is encoded again to the same bytes once decoded, for types without
+/// `PartialEq`.
+fn roundtrip_proto<T: Protobuf>(value: &T, decode: impl Fn(&[u8]) -> Result<T, ProtoError>) {
+    let bytes = Protobuf::to_bytes(value).expect("Encodable value");
+    let decoded = decode(&bytes).expect("Decodable value");
+    assert_eq!(
+        Protobuf::to_bytes(&decoded).expect("Encodable value"),
+        bytes
+    );
+}
+
+#[cfg(feature = "arbitrary")]
+pub(crate) use generators::*;
+
+/// Generators of the foreign types found in the fuzzed types.
+#[cfg(feature = "arbitrary")]
+mod generators {
+    use arbitrary::{Result, Unstructured};
+    use malachitebft_core_types::{AggregatedSignature, CommitCertificate, CommitSignature, Round};
+
+    use crate::types::address::Address;
+    use crate::types::context::TestContext;
+    use crate::types::height::Height;
+    use crate::types::signing::{PrivateKey, PublicKey, Signature};
+    use crate::types::value::ValueId;
+
+    /// Nil rounds are never encoded.
+    pub(crate) fn arbitrary_round(u: &mut Unstructured<'_>) -> Result<Round> {
+        Ok(Round::new(u.arbitrary()?))
+    }
+
+    pub(crate) fn arbitrary_signature(u: &mut Unstructured<'_>) -> Result<Signature> {
+        Ok(Signature::from_bytes(u.arbitrary()?))
+    }
+
+    /// A valid key, the decoding of arbitrary bytes as a key being fuzzed by the `decode_*`
+    /// functions.
+    pub(crate) fn arbitrary_public_key(u: &mut Unstructured<'_>) -> Result<PublicKey> {
+        Ok(PrivateKey::from(u.arbitrary::<[u8; 32]>()?).public_key())
+    }
+
+    /// A certificate of up to 8 signatures, which are not valid.
+    pub(crate) fn arbitrary_certificate(
+        u: &mut Unstructured<'_>,
+    ) -> Result<CommitCertificate<TestContext>> {
+        let height = Height::new(u.arbitrary()?);
+        let round = arbitrary_round(u)?;
+        let value_id = ValueId::new(u.arbitrary()?);
+        let mut signatures = Vec::new();
+        for _ in 0..u.int_in_range(0..=8)? {
+            signatures.push(CommitSignature {
+                address: Address::new(u.arbitrary()?),
+                signature: arbitrary_signature(u)?,
+            });
+        }
+
+        Ok(CommitCertificate {
+            height,
+            round,
+            value_id,
+            aggregated_signature: AggregatedSignature { signatures },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::address::Address;
+    use crate::types::height::Height;
+    use crate::types::value::ValueId;
+    use malachitebft_core_types::{NilOrVal, Round};
+
+    #[test]
+    fn rejects_malformed_input() {
+        let inputs: [&[u8]; 5] = [
+            &[],
+            &[0xff; 64],
+            &[0x0a, 0xff, 0xff, 0xff, 0x0f],
+            &[0x08, 7],
+            &[2; 3],
+        ];
+        for input in inputs {
+            let _ = decode_block(input);
+            let _ = decode_transaction(input);
+            let _ = decode_vote(input);
+            let _ = decode_vote_bincode(input);
+            let _ = decode_proposal_part(input);
+            let _ = decode_consensus_message(input);
+            let _ = decode_value(input);
+            let _ = decode_stream_message(input);
+        }
+        assert!(decode_block(&[]).is_err());
+        assert!(decode_vote_bincode(&[]).is_err());
+    }
+
+    #[test]
+    fn roundtrips() {
+        roundtrip_block(&Block::new(1, 2, [3; 32], Address::new([4; 20]), vec![]));
+        roundtrip_vote(&Vote::new_precommit(
+            Height::new(5),
+            Round::new(0),
+            NilOrVal::Val(ValueId::new([6; 32])),
+            Address::new([7; 20]),
+        ));
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn roundtrips_arbitrary_values() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let data: Vec<u8> = (0..4096u32).map(|i| (i * 31 % 251) as u8).collect();
+        let mut u = Unstructured::new(&data);
+        roundtrip_block(&Block::arbitrary(&mut u).unwrap());
+        roundtrip_transaction(&Transaction::arbitrary(&mut u).unwrap());
+        roundtrip_vote(&Vote::arbitrary(&mut u).unwrap());
+        roundtrip_proposal_part(&ProposalPart::arbitrary(&mut u).unwrap());
+    }
+}
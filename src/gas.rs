@@ -0,0 +1,205 @@
+//! Gas metering: what transactions pay for with their gas price.
+//!
+//! Every transaction declares a gas limit, signed along with its gas price. Its gas is charged to
+//! a [`GasMeter`] holding that limit, following the [`GasSchedule`] of the genesis file: a base
+//! cost per transfer, a cost per byte of blob data and, for execution engines, a cost per
+//! execution step. A block must not use more than [`GasSchedule::block_gas_limit`].
+//!
+//! There is no execution engine yet, so the gas used by a transaction is its intrinsic gas, see
+//! [`GasSchedule::intrinsic_gas`].
+
+use serde::{Deserialize, Serialize};
+
+use crate::blob::Blob;
+use crate::error::GasError;
+use crate::transactions::Transaction;
+
+/// Gas costs, part of the genesis file since every validator must agree on them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct GasSchedule {
+    /// Base cost of every transaction
+    pub per_transfer: u64,
+    /// Cost of each byte of blob data
+    pub per_blob_byte: u64,
+    /// Cost of each step of an execution engine
+    pub per_execution_step: u64,
+    /// Maximum gas used by the transactions of a block, and so declared by one transaction
+    pub block_gas_limit: u64,
+}
+
+impl Default for GasSchedule {
+    fn default() -> Self {
+        Self {
+            per_transfer: 21_000,
+            per_blob_byte: 1,
+            per_execution_step: 1,
+            block_gas_limit: 30_000_000,
+        }
+    }
+}
+
+impl GasSchedule {
+    /// Gas of a transaction carrying `blobs`, before any execution.
+    pub fn intrinsic_gas(&self, blobs: &[Blob]) -> u64 {
+        let blob_bytes = blobs
+            .iter()
+            .map(|blob| blob.data().len() as u64)
+            .sum::<u64>();
+        self.per_blob_byte
+            .saturating_mul(blob_bytes)
+            .saturating_add(self.per_transfer)
+    }
+
+    /// Meter for `tx`, with its intrinsic gas charged. Execution engines charge their steps to it.
+    pub fn meter(&self, tx: &Transaction) -> Result<GasMeter, GasError> {
+        if tx.gas_limit() > self.block_gas_limit {
+            return Err(GasError::AboveBlockGasLimit {
+                limit: tx.gas_limit(),
+                block_gas_limit: self.block_gas_limit,
+            });
+        }
+
+        let mut meter = GasMeter::new(tx.gas_limit());
+        let required = self.intrinsic_gas(tx.data());
+        meter
+            .charge(required)
+            .map_err(|_| GasError::IntrinsicGasTooLow {
+                required,
+                limit: tx.gas_limit(),
+            })?;
+        Ok(meter)
+    }
+
+    /// Gas used by `tx`, checked against its gas limit.
+    pub fn gas_used(&self, tx: &Transaction) -> Result<u64, GasError> {
+        self.meter(tx).map(|meter| meter.used())
+    }
+
+    /// Gas used by the transactions of a block, checked against the block gas limit.
+    pub fn block_gas_used(&self, transactions: &[Transaction]) -> Result<u64, GasError> {
+        let mut used = 0u64;
+        for tx in transactions {
+            used = used.saturating_add(self.gas_used(tx)?);
+        }
+        if used > self.block_gas_limit {
+            return Err(GasError::BlockGasExceeded {
+                used,
+                block_gas_limit: self.block_gas_limit,
+            });
+        }
+        Ok(used)
+    }
+}
+
+/// Gas used by one transaction, up to its limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GasMeter {
+    limit: u64,
+    used: u64,
+}
+
+impl GasMeter {
+    pub fn new(limit: u64) -> Self {
+        Self { limit, used: 0 }
+    }
+
+    /// Charges `amount`, leaving the meter untouched if that would exceed the limit.
+    pub fn charge(&mut self, amount: u64) -> Result<(), GasError> {
+        match self.used.checked_add(amount) {
+            Some(used) if used <= self.limit => {
+                self.used = used;
+                Ok(())
+            }
+            _ => Err(GasError::OutOfGas {
+                limit: self.limit,
+                used: self.used,
+                requested: amount,
+            }),
+        }
+    }
+
+    /// Charges `steps` execution steps at the price of `schedule`.
+    pub fn charge_steps(&mut self, schedule: &GasSchedule, steps: u64) -> Result<(), GasError> {
+        self.charge(schedule.per_execution_step.saturating_mul(steps))
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.limit - self.used
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blob::BLOB_SIZE;
+    use crate::types::sign_bytes::DEFAULT_CHAIN_ID;
+    use malachitebft_test::PrivateKey;
+    use rand::thread_rng;
+
+    fn transfer(gas_limit: u64) -> Transaction {
+        let key = PrivateKey::generate(thread_rng());
+        Transaction::sign(
+            DEFAULT_CHAIN_ID,
+            &key,
+            key.public_key(),
+            1,
+            vec![Blob::random(); 4],
+            0,
+            1,
+            gas_limit,
+        )
+    }
+
+    #[test]
+    fn charges_transfers_and_blob_bytes() {
+        let schedule = GasSchedule::default();
+        let required = schedule.intrinsic_gas(transfer(0).data());
+        assert_eq!(required, 21_000 + 4 * BLOB_SIZE as u64);
+
+        assert_eq!(schedule.gas_used(&transfer(required)), Ok(required));
+        assert_eq!(
+            schedule.gas_used(&transfer(required - 1)),
+            Err(GasError::IntrinsicGasTooLow {
+                required,
+                limit: required - 1,
+            })
+        );
+        assert!(matches!(
+            schedule.gas_used(&transfer(schedule.block_gas_limit + 1)),
+            Err(GasError::AboveBlockGasLimit { .. })
+        ));
+
+        let txs = vec![transfer(required); 8];
+        assert!(matches!(
+            schedule.block_gas_used(&txs),
+            Err(GasError::BlockGasExceeded { .. })
+        ));
+        assert_eq!(schedule.block_gas_used(&txs[..7]), Ok(7 * required));
+    }
+
+    #[test]
+    fn meter_stops_at_the_limit() {
+        let schedule = GasSchedule::default();
+        let mut meter = GasMeter::new(10);
+        meter.charge_steps(&schedule, 7).unwrap();
+        assert_eq!(
+            meter.charge_steps(&schedule, 4),
+            Err(GasError::OutOfGas {
+                limit: 10,
+                used: 7,
+                requested: 4,
+            })
+        );
+        assert_eq!(meter.remaining(), 3);
+    }
+}
@@ -0,0 +1,314 @@
+//! On-chain governance of chain parameters.
+//!
+//! A transaction carrying a [`ParamChange`] is the vote of its sender, which must be a validator,
+//! for new values of some [`ChainParams`] from a future height on. Once validators holding
+//! [`GovernanceConfig::quorum_bps`] of the voting power voted for the same change, it is
+//! approved and applies from its height.
+//!
+//! Proposals take effect once decided and are persisted in the store, as pruning drops the blocks
+//! they were decided in.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use malachitebft_test::PublicKey;
+use serde::{Deserialize, Serialize};
+use sha3::Digest;
+
+use crate::consensus_params::ConsensusParams;
+use crate::error::GovernanceError;
+use crate::gas::GasSchedule;
+use crate::transactions::Transaction;
+use crate::types::sign_bytes::param_change_bytes;
+use crate::types::validator_set::ValidatorSet;
+
+/// Share of the voting power approving a change when the genesis file does not set one, in basis
+/// points.
+pub const DEFAULT_QUORUM_BPS: u64 = 6_667;
+
+/// `governance` section of the genesis file. The initial values of the parameters are those of
+/// the `consensus` section and the gas schedule of the genesis file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GovernanceConfig {
+    /// Share of the voting power that must vote for a change, in basis points
+    pub quorum_bps: u64,
+}
+
+impl Default for GovernanceConfig {
+    fn default() -> Self {
+        Self {
+            quorum_bps: DEFAULT_QUORUM_BPS,
+        }
+    }
+}
+
+/// Parameters in force at a height.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainParams {
+    /// Maximum encoded size of a block
+    pub max_block_bytes: u64,
+    /// Gas costs and block gas limit, see [`crate::gas`]
+    pub gas: GasSchedule,
+    /// Decided heights whose block data is retained, older ones are pruned
+    pub retain_heights: u64,
+}
+
+impl ChainParams {
+    /// Parameters before any change.
+    pub fn new(consensus: &ConsensusParams, gas: &GasSchedule) -> Self {
+        Self {
+            max_block_bytes: consensus.max_block_bytes,
+            gas: gas.clone(),
+            retain_heights: consensus.retain_heights,
+        }
+    }
+
+    fn apply(&mut self, change: &ParamChange) {
+        if let Some(max_block_bytes) = change.max_block_bytes {
+            self.max_block_bytes = max_block_bytes;
+        }
+        if let Some(gas) = &change.gas {
+            self.gas = gas.clone();
+        }
+        if let Some(retain_heights) = change.retain_heights {
+            self.retain_heights = retain_heights;
+        }
+    }
+}
+
+/// New values of some parameters, from `height` on.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct ParamChange {
+    pub height: u64,
+    #[serde(default)]
+    pub max_block_bytes: Option<u64>,
+    #[serde(default)]
+    pub gas: Option<GasSchedule>,
+    #[serde(default)]
+    pub retain_heights: Option<u64>,
+}
+
+impl ParamChange {
+    /// Identifies the change among the votes, see [`param_change_bytes`].
+    pub fn id(&self) -> [u8; 32] {
+        sha3::Keccak256::digest(param_change_bytes(self)).into()
+    }
+}
+
+/// Votes for a change.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proposal {
+    pub change: ParamChange,
+    pub voters: Vec<PublicKey>,
+    /// Whether the voters reached the quorum before the height of the change
+    pub approved: bool,
+}
+
+/// Decided proposals, shared between the mempool and block validation.
+#[derive(Debug, Clone)]
+pub struct Governance {
+    voting_powers: Arc<HashMap<[u8; 32], u64>>,
+    total_voting_power: u64,
+    quorum_bps: u64,
+    initial: ChainParams,
+    proposals: Arc<Mutex<HashMap<[u8; 32], Proposal>>>,
+    /// Last height whose votes were counted, the mempool checks votes against the next one
+    decided_height: Arc<AtomicU64>,
+}
+
+impl Default for Governance {
+    /// Without validators, so that every vote is rejected.
+    fn default() -> Self {
+        Self {
+            voting_powers: Default::default(),
+            total_voting_power: 0,
+            quorum_bps: DEFAULT_QUORUM_BPS,
+            initial: ChainParams::new(&ConsensusParams::default(), &GasSchedule::default()),
+            proposals: Default::default(),
+            decided_height: Default::default(),
+        }
+    }
+}
+
+impl Governance {
+    pub fn new(
+        validator_set: &ValidatorSet,
+        config: &GovernanceConfig,
+        consensus: &ConsensusParams,
+        gas: &GasSchedule,
+    ) -> Self {
+        let voting_powers = validator_set
+            .validators
+            .iter()
+            .map(|validator| (*validator.public_key.as_bytes(), validator.voting_power))
+            .collect();
+
+        Self {
+            voting_powers: Arc::new(voting_powers),
+            total_voting_power: validator_set.total_voting_power(),
+            quorum_bps: config.quorum_bps,
+            initial: ChainParams::new(consensus, gas),
+            proposals: Default::default(),
+            decided_height: Default::default(),
+        }
+    }
+
+    /// Adds proposals loaded from the store.
+    pub fn load(&self, proposals: impl IntoIterator<Item = ([u8; 32], Proposal)>) {
+        self.proposals.lock().unwrap().extend(proposals);
+    }
+
+    /// Sets the last decided height, e.g. on restart.
+    pub fn set_decided_height(&self, height: u64) {
+        self.decided_height.store(height, Ordering::Relaxed);
+    }
+
+    pub fn proposals(&self) -> Vec<Proposal> {
+        self.proposals.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Parameters of the block at `height`: the initial ones updated by the approved changes up
+    /// to `height`, in order of height.
+    pub fn params_at(&self, height: u64) -> ChainParams {
+        let proposals = self.proposals.lock().unwrap();
+        let mut changes: Vec<_> = proposals
+            .iter()
+            .filter(|(_, proposal)| proposal.approved && proposal.change.height <= height)
+            .map(|(id, proposal)| (proposal.change.height, *id, &proposal.change))
+            .collect();
+        changes.sort_by_key(|(height, id, _)| (*height, *id));
+
+        let mut params = self.initial.clone();
+        for (_, _, change) in changes {
+            params.apply(change);
+        }
+        params
+    }
+
+    /// Checks a vote included at `height`: its sender must be a validator and the change must
+    /// apply at a later height.
+    pub fn check(&self, tx: &Transaction, height: u64) -> Result<(), GovernanceError> {
+        let Some(change) = tx.governance() else {
+            return Ok(());
+        };
+
+        if !self.voting_powers.contains_key(tx.from_().as_bytes()) {
+            return Err(GovernanceError::NotAValidator {
+                sender: hex::encode(tx.from_().as_bytes()),
+            });
+        }
+        if change.height <= height {
+            return Err(GovernanceError::ActivationNotInFuture {
+                activation: change.height,
+                height,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks a vote submitted to the mempool, to be included after the last decided height.
+    pub fn check_pending(&self, tx: &Transaction) -> Result<(), GovernanceError> {
+        self.check(tx, self.decided_height.load(Ordering::Relaxed) + 1)
+    }
+
+    pub fn check_all(&self, txs: &[Transaction], height: u64) -> Result<(), GovernanceError> {
+        txs.iter().try_for_each(|tx| self.check(tx, height))
+    }
+
+    /// Counts the votes of a block decided at `height`, returns the updated proposals to be
+    /// persisted.
+    pub fn apply(&self, txs: &[Transaction], height: u64) -> Vec<([u8; 32], Proposal)> {
+        let mut proposals = self.proposals.lock().unwrap();
+        let mut updated = HashMap::new();
+        for tx in txs {
+            // Decided blocks were checked, like in validation
+            if self.check(tx, height).is_err() {
+                continue;
+            }
+            let Some(change) = tx.governance() else {
+                continue;
+            };
+
+            let id = change.id();
+            let proposal = proposals.entry(id).or_insert_with(|| Proposal {
+                change: change.clone(),
+                voters: Vec::new(),
+                approved: false,
+            });
+            if proposal.voters.contains(&tx.from_()) {
+                continue;
+            }
+            proposal.voters.push(tx.from_());
+            proposal.approved = proposal.approved || self.has_quorum(&proposal.voters);
+            updated.insert(id, proposal.clone());
+        }
+        self.set_decided_height(height);
+        updated.into_iter().collect()
+    }
+
+    fn has_quorum(&self, voters: &[PublicKey]) -> bool {
+        let power: u64 = voters
+            .iter()
+            .filter_map(|voter| self.voting_powers.get(voter.as_bytes()))
+            .sum();
+        u128::from(power) * 10_000
+            >= u128::from(self.total_voting_power) * u128::from(self.quorum_bps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::sign_bytes::DEFAULT_CHAIN_ID;
+    use crate::types::validator_set::Validator;
+    use malachitebft_test::PrivateKey;
+    use rand::thread_rng;
+
+    fn vote(key: &PrivateKey, change: &ParamChange) -> Transaction {
+        Transaction::sign_governance(DEFAULT_CHAIN_ID, key, change.clone(), 0, 1, 21_000)
+    }
+
+    #[test]
+    fn applies_changes_approved_by_a_quorum() {
+        let keys: Vec<_> = (0..3).map(|_| PrivateKey::generate(thread_rng())).collect();
+        let validator_set =
+            ValidatorSet::new(keys.iter().map(|key| Validator::new(key.public_key(), 1)));
+        let governance = Governance::new(
+            &validator_set,
+            &GovernanceConfig::default(),
+            &ConsensusParams::default(),
+            &GasSchedule::default(),
+        );
+        let change = ParamChange {
+            height: 10,
+            max_block_bytes: Some(1024),
+            gas: None,
+            retain_heights: None,
+        };
+
+        let outsider = PrivateKey::generate(thread_rng());
+        assert!(matches!(
+            governance.check(&vote(&outsider, &change), 1),
+            Err(GovernanceError::NotAValidator { .. })
+        ));
+        assert!(matches!(
+            governance.check(&vote(&keys[0], &change), 10),
+            Err(GovernanceError::ActivationNotInFuture { .. })
+        ));
+
+        // One vote out of three, twice, is below the quorum
+        let first = vote(&keys[0], &change);
+        governance.apply(&[first.clone(), first], 1);
+        assert_eq!(governance.params_at(10).max_block_bytes, 64 * 1024 * 1024);
+
+        let updated = governance.apply(&[vote(&keys[1], &change)], 2);
+        assert_eq!(updated.len(), 1);
+        assert!(updated[0].1.approved);
+        assert_eq!(governance.params_at(9).max_block_bytes, 64 * 1024 * 1024);
+        assert_eq!(governance.params_at(10).max_block_bytes, 1024);
+        assert_eq!(governance.params_at(10).gas, GasSchedule::default());
+    }
+}
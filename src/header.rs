@@ -1,27 +1,42 @@
-use crate::malachite_types::address::Address;
-use bincode::{Decode, Encode};
+use crate::types::address::Address;
+use crate::types::codec::proto::decode_hash;
+use crate::types::proto;
+use malachitebft_proto::{Error as ProtoError, Protobuf};
 use serde::{Deserialize, Serialize};
-use sha3::{Digest, Sha3_256};
 
+use crate::upgrades::DEFAULT_PROTOCOL_VERSION;
+use crate::verify::header_hash;
 use crate::{block::mock_make_validator, error::BlockError};
 
 #[allow(clippy::too_many_arguments, dead_code)]
-#[derive(Debug, Serialize, Deserialize, Encode, Decode)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Header {
     pub block_number: u64,
     pub timestamp: u64,
     /// Hash of current block
+    #[serde(with = "hex::serde")]
     pub block_hash: [u8; 32],
-    /// DA commitment for this block.
-    pub da_commitment: [[u8; 32]; 4],
+    /// Merkle root of the commitments of the blobs of this block, in order, see
+    /// [`crate::block::Block::blob_commitments`]. Zero for a block without blobs.
+    #[serde(with = "hex::serde")]
+    pub da_root: [u8; 32],
+    /// Number of leaves of `da_root`, needed to verify proofs against it
+    pub blob_count: u32,
     /// block of parent block.
+    #[serde(with = "hex::serde")]
     pub parent_hash: [u8; 32],
     /// Merkle root of the data in the current block.
     /// Leaves of this tree will be the raw bytes of each blob
+    #[serde(with = "hex::serde")]
     pub tx_commitment: [u8; 32],
     /// address of proposer of this block.
-    #[bincode(with_serde)]
     pub proposer_address: Address,
+    /// Protocol version the block was built under, see [`crate::upgrades`]
+    pub version: u32,
+    /// Merkle root of the lengths and share roots of the blobs of this block, see
+    /// [`crate::shares`]. Zero before [`crate::shares::SHARES_VERSION`] and without blobs.
+    #[serde(default, with = "hex::serde")]
+    pub share_root: [u8; 32],
 }
 impl Default for Header {
     fn default() -> Self {
@@ -29,10 +44,13 @@ impl Default for Header {
             block_number: 0,
             timestamp: 0,
             block_hash: [0; 32],
-            da_commitment: [[0; 32]; 4],
+            da_root: [0; 32],
+            blob_count: 0,
             parent_hash: [0; 32],
             tx_commitment: [0; 32],
             proposer_address: mock_make_validator(),
+            version: DEFAULT_PROTOCOL_VERSION,
+            share_root: [0; 32],
         }
     }
 }
@@ -45,16 +63,20 @@ impl Header {
         timestamp: u64,
         tx_commitment: [u8; 32],
         proposer_address: Address,
-        da_commitment: [[u8; 32]; 4],
+        da_root: [u8; 32],
+        blob_count: u32,
         parent_hash: [u8; 32],
     ) -> Self {
         let mut header = Header {
             block_number,
             timestamp,
-            da_commitment,
+            da_root,
+            blob_count,
             tx_commitment,
             proposer_address,
             parent_hash,
+            version: DEFAULT_PROTOCOL_VERSION,
+            share_root: [0; 32],
             block_hash: [0; 32],
         };
         header.block_hash = header.compute_block_hash();
@@ -75,16 +97,69 @@ impl Header {
         Ok(())
     }
 
-    ///Compute block hash
+    /// SSZ hash tree root of the header, for Ethereum-style light clients
+    pub fn hash_tree_root(&self) -> [u8; 32] {
+        crate::types::codec::ssz::hash_tree_root(self)
+    }
+
+    ///Compute block hash, see [`crate::verify::header_hash`]
     pub fn compute_block_hash(&self) -> [u8; 32] {
-        let mut hasher = Sha3_256::new();
+        header_hash(
+            self.block_number,
+            &self.parent_hash,
+            &self.tx_commitment,
+            &self.da_root,
+            self.blob_count,
+            &self.proposer_address.into_inner(),
+            self.version,
+            &self.share_root,
+        )
+    }
+}
+
+impl Protobuf for Header {
+    type Proto = proto::Header;
 
-        hasher.update(self.block_number.to_le_bytes());
-        hasher.update(self.parent_hash);
-        hasher.update(self.tx_commitment);
-        hasher.update(self.proposer_address.into_inner());
+    fn from_proto(proto: Self::Proto) -> Result<Self, ProtoError> {
+        Ok(Self {
+            block_number: proto.block_number,
+            timestamp: proto.timestamp,
+            block_hash: decode_hash(&proto.block_hash)?,
+            da_root: decode_hash(&proto.da_root)?,
+            blob_count: proto.blob_count,
+            parent_hash: decode_hash(&proto.parent_hash)?,
+            tx_commitment: decode_hash(&proto.tx_commitment)?,
+            proposer_address: proto
+                .proposer_address
+                .ok_or_else(|| ProtoError::missing_field::<Self::Proto>("proposer_address"))
+                .and_then(Address::from_proto)?,
+            version: proto.version,
+            share_root: if proto.share_root.is_empty() {
+                [0; 32]
+            } else {
+                decode_hash(&proto.share_root)?
+            },
+        })
+    }
 
-        hasher.finalize().into()
+    fn to_proto(&self) -> Result<Self::Proto, ProtoError> {
+        Ok(proto::Header {
+            block_number: self.block_number,
+            timestamp: self.timestamp,
+            block_hash: self.block_hash.to_vec().into(),
+            da_root: self.da_root.to_vec().into(),
+            blob_count: self.blob_count,
+            parent_hash: self.parent_hash.to_vec().into(),
+            tx_commitment: self.tx_commitment.to_vec().into(),
+            proposer_address: Some(self.proposer_address.to_proto()?),
+            version: self.version,
+            // Left out when zero, for blocks before shares to keep their encoding
+            share_root: if self.share_root == [0; 32] {
+                Default::default()
+            } else {
+                self.share_root.to_vec().into()
+            },
+        })
     }
 }
 
@@ -94,8 +169,9 @@ pub struct HeaderBuilder {
     pub timestamp: Option<u64>,
     /// Hash of current block
     pub block_hash: Option<[u8; 32]>,
-    /// DA commitment for this block.
-    pub da_commitment: Option<[[u8; 32]; 4]>,
+    /// Merkle root of the blob commitments of this block.
+    pub da_root: Option<[u8; 32]>,
+    pub blob_count: Option<u32>,
     /// block of parent block.
     pub parent_hash: Option<[u8; 32]>,
     /// Merkle root of the data in the current block.
@@ -103,6 +179,8 @@ pub struct HeaderBuilder {
     pub tx_commitment: Option<[u8; 32]>,
     /// address of proposer of this block.
     pub proposer_address: Option<Address>,
+    pub version: Option<u32>,
+    pub share_root: Option<[u8; 32]>,
 }
 
 impl HeaderBuilder {
@@ -124,8 +202,9 @@ impl HeaderBuilder {
         self.block_hash = Some(block_hash);
         self
     }
-    pub fn da_commitment(mut self, da_commitment: [[u8; 32]; 4]) -> Self {
-        self.da_commitment = Some(da_commitment);
+    pub fn da_root(mut self, da_root: [u8; 32], blob_count: u32) -> Self {
+        self.da_root = Some(da_root);
+        self.blob_count = Some(blob_count);
         self
     }
     pub fn parent_hash(mut self, parent_hash: [u8; 32]) -> Self {
@@ -141,15 +220,32 @@ impl HeaderBuilder {
         self.proposer_address = Some(proposer_address);
         self
     }
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = Some(version);
+        self
+    }
+    pub fn share_root(mut self, share_root: [u8; 32]) -> Self {
+        self.share_root = Some(share_root);
+        self
+    }
 
     pub fn build(&self) -> Header {
-        Header::new(
+        let mut header = Header::new(
             self.block_number.unwrap(),
             self.timestamp.unwrap(),
             self.tx_commitment.unwrap_or_default(),
             self.proposer_address.unwrap(),
-            self.da_commitment.unwrap(),
+            self.da_root.unwrap_or_default(),
+            self.blob_count.unwrap_or_default(),
             self.parent_hash.unwrap(),
-        )
+        );
+        if let Some(version) = self.version {
+            header.version = version;
+        }
+        if let Some(share_root) = self.share_root {
+            header.share_root = share_root;
+        }
+        header.block_hash = header.compute_block_hash();
+        header
     }
 }
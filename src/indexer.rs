@@ -0,0 +1,268 @@
+//! Chain event sink writing blocks, transactions and blob metadata to a relational database,
+//! PostgreSQL or SQLite, so explorers can query the chain with SQL instead of following it.
+//!
+//! The schema is created on startup if missing, and rows are inserted as events arrive. With
+//! `backfill` set, the sink first indexes the decided blocks still in the store from the highest
+//! indexed height on, so an indexer added to a running node, or restarted after missing events,
+//! catches up. Inserts ignore rows already present, which makes replays harmless.
+
+use async_trait::async_trait;
+use sqlx::any::{install_default_drivers, AnyPool, AnyPoolOptions};
+use tracing::{debug, info};
+
+use crate::block::Block;
+use crate::error::EventError;
+use crate::events::{block_events, ChainEvent, EventSink};
+use crate::store::Store;
+use crate::types::codec::proto::ProtobufCodec;
+use crate::types::height::Height;
+use malachitebft_app_channel::app::types::codec::Codec;
+
+/// Statements creating the schema, valid for both PostgreSQL and SQLite. Hashes, commitments and
+/// addresses are stored as they appear in the JSON-RPC API.
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS blocks (
+        height BIGINT PRIMARY KEY,
+        round BIGINT NOT NULL,
+        block_hash TEXT NOT NULL,
+        timestamp BIGINT NOT NULL,
+        proposer TEXT NOT NULL,
+        tx_count BIGINT NOT NULL
+    )",
+    "CREATE UNIQUE INDEX IF NOT EXISTS blocks_hash ON blocks (block_hash)",
+    "CREATE TABLE IF NOT EXISTS transactions (
+        height BIGINT NOT NULL,
+        tx_index BIGINT NOT NULL,
+        tx_hash TEXT NOT NULL,
+        sender TEXT NOT NULL,
+        nonce BIGINT NOT NULL,
+        PRIMARY KEY (height, tx_index)
+    )",
+    "CREATE INDEX IF NOT EXISTS transactions_hash ON transactions (tx_hash)",
+    "CREATE INDEX IF NOT EXISTS transactions_sender ON transactions (sender, nonce)",
+    "CREATE TABLE IF NOT EXISTS blobs (
+        height BIGINT NOT NULL,
+        tx_hash TEXT NOT NULL,
+        blob_index BIGINT NOT NULL,
+        commitment TEXT NOT NULL,
+        size BIGINT NOT NULL,
+        PRIMARY KEY (height, tx_hash, blob_index)
+    )",
+    "CREATE INDEX IF NOT EXISTS blobs_commitment ON blobs (commitment)",
+    "CREATE TABLE IF NOT EXISTS validator_sets (
+        height BIGINT NOT NULL,
+        address TEXT NOT NULL,
+        voting_power BIGINT NOT NULL,
+        PRIMARY KEY (height, address)
+    )",
+];
+
+/// Writes chain events to the database at `url`, e.g. `postgres://user@host/mikan` or
+/// `sqlite://indexer.db?mode=rwc`.
+pub struct SqlSink {
+    pool: AnyPool,
+}
+
+impl SqlSink {
+    pub async fn open(url: &str) -> Result<Self, EventError> {
+        install_default_drivers();
+        // Events are handled one at a time
+        let pool = AnyPoolOptions::new()
+            .max_connections(1)
+            .connect(url)
+            .await
+            .map_err(EventError::DatabaseConnection)?;
+
+        for statement in SCHEMA {
+            sqlx::query(statement).execute(&pool).await?;
+        }
+
+        Ok(Self { pool })
+    }
+
+    /// Highest height of an indexed block.
+    pub async fn indexed_height(&self) -> Result<Option<u64>, EventError> {
+        let height: Option<i64> = sqlx::query_scalar("SELECT MAX(height) FROM blocks")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(height.map(|height| height as u64))
+    }
+
+    /// Indexes the decided blocks of `store` from the highest indexed height, included as its
+    /// transactions may not all have been written, to the last decided one. Heights whose block
+    /// data was pruned are skipped, and validator sets are only indexed from events.
+    pub async fn backfill(&mut self, store: &Store) -> Result<(), EventError> {
+        let Some(max_height) = store.max_decided_value_height().await else {
+            return Ok(());
+        };
+        let min_height = store
+            .min_decided_value_height()
+            .await
+            .map_or(0, |height| height.as_u64());
+        let from_height = match self.indexed_height().await? {
+            Some(height) => height.max(min_height),
+            None => min_height,
+        };
+
+        let mut indexed = 0;
+        for height in from_height..=max_height.as_u64() {
+            let height = Height::new(height);
+            let (Some(decided), Some(data)) = (
+                store.get_decided_value(height).await?,
+                store.get_decided_block(height).await?,
+            ) else {
+                debug!(%height, "Block data not in the store, not indexed");
+                continue;
+            };
+
+            let block: Block = ProtobufCodec.decode(data)?;
+            let round = decided.certificate.round.as_u32().unwrap_or_default();
+            for event in block_events(height.as_u64(), round, &block) {
+                self.handle(&event).await?;
+            }
+            indexed += 1;
+        }
+
+        info!(from_height, to_height = %max_height, indexed, "Backfilled the indexer");
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventSink for SqlSink {
+    fn name(&self) -> &str {
+        "sql"
+    }
+
+    async fn handle(&mut self, event: &ChainEvent) -> Result<(), EventError> {
+        match event {
+            ChainEvent::BlockCommitted {
+                height,
+                round,
+                block_hash,
+                timestamp,
+                proposer,
+                tx_count,
+            } => {
+                sqlx::query(
+                    "INSERT INTO blocks (height, round, block_hash, timestamp, proposer, tx_count)
+                    VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT DO NOTHING",
+                )
+                .bind(*height as i64)
+                .bind(i64::from(*round))
+                .bind(hex::encode(block_hash))
+                .bind(*timestamp as i64)
+                .bind(proposer.to_string())
+                .bind(*tx_count as i64)
+                .execute(&self.pool)
+                .await?;
+            }
+            ChainEvent::TransactionIncluded {
+                height,
+                index,
+                tx_hash,
+                from,
+                nonce,
+            } => {
+                sqlx::query(
+                    "INSERT INTO transactions (height, tx_index, tx_hash, sender, nonce)
+                    VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING",
+                )
+                .bind(*height as i64)
+                .bind(*index as i64)
+                .bind(hex::encode(tx_hash))
+                .bind(from.to_string())
+                .bind(*nonce as i64)
+                .execute(&self.pool)
+                .await?;
+            }
+            ChainEvent::BlobPosted {
+                height,
+                tx_hash,
+                blob_index,
+                commitment,
+                size,
+            } => {
+                sqlx::query(
+                    "INSERT INTO blobs (height, tx_hash, blob_index, commitment, size)
+                    VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING",
+                )
+                .bind(*height as i64)
+                .bind(hex::encode(tx_hash))
+                .bind(*blob_index as i64)
+                .bind(hex::encode(commitment))
+                .bind(*size as i64)
+                .execute(&self.pool)
+                .await?;
+            }
+            ChainEvent::ValidatorSetChanged { height, validators } => {
+                for (address, voting_power) in validators {
+                    sqlx::query(
+                        "INSERT INTO validator_sets (height, address, voting_power)
+                        VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+                    )
+                    .bind(*height as i64)
+                    .bind(address.to_string())
+                    .bind(*voting_power as i64)
+                    .execute(&self.pool)
+                    .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::address::Address;
+
+    #[tokio::test]
+    async fn indexes_events_once() {
+        let mut sink = SqlSink::open("sqlite::memory:").await.unwrap();
+        assert_eq!(sink.indexed_height().await.unwrap(), None);
+
+        let events = [
+            ChainEvent::BlockCommitted {
+                height: 5,
+                round: 0,
+                block_hash: [1; 32],
+                timestamp: 1_700_000_000,
+                proposer: Address::new([2; 20]),
+                tx_count: 1,
+            },
+            ChainEvent::TransactionIncluded {
+                height: 5,
+                index: 0,
+                tx_hash: [3; 32],
+                from: Address::new([4; 20]),
+                nonce: 7,
+            },
+            ChainEvent::BlobPosted {
+                height: 5,
+                tx_hash: [3; 32],
+                blob_index: 0,
+                commitment: [5; 32],
+                size: 1024,
+            },
+        ];
+        // Replayed, e.g. by a backfill after a restart
+        for event in events.iter().chain(&events) {
+            sink.handle(event).await.unwrap();
+        }
+
+        assert_eq!(sink.indexed_height().await.unwrap(), Some(5));
+        let blobs: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM blobs")
+            .fetch_one(&sink.pool)
+            .await
+            .unwrap();
+        assert_eq!(blobs, 1);
+        let size: i64 = sqlx::query_scalar("SELECT size FROM blobs WHERE commitment = $1")
+            .bind(hex::encode([5; 32]))
+            .fetch_one(&sink.pool)
+            .await
+            .unwrap();
+        assert_eq!(size, 1024);
+    }
+}
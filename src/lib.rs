@@ -1,15 +1,105 @@
+//! With the default `std` feature off, only [`verify`] is built, with `core` and `alloc`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
 pub mod app;
+#[cfg(feature = "std")]
+pub mod bench;
+#[cfg(feature = "std")]
 pub mod blob;
+#[cfg(feature = "std")]
 pub mod block;
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "byzantine")]
+pub mod byzantine;
+#[cfg(feature = "std")]
+pub mod client;
+#[cfg(feature = "std")]
+pub mod compression;
+#[cfg(feature = "std")]
 pub mod config;
+#[cfg(feature = "std")]
+pub mod consensus_params;
+#[cfg(feature = "std")]
 pub mod error;
+#[cfg(feature = "std")]
+pub mod events;
+#[cfg(feature = "std")]
+pub mod evidence;
+#[cfg(feature = "std")]
+pub mod finality_params;
+#[cfg(feature = "std")]
+pub mod forks;
+#[cfg(feature = "std")]
+pub mod fuzz;
+#[cfg(feature = "std")]
+pub mod gas;
+#[cfg(feature = "std")]
+pub mod governance;
+#[cfg(feature = "std")]
 pub mod header;
-pub mod malachite_types;
+#[cfg(feature = "sql")]
+pub mod indexer;
+#[cfg(feature = "std")]
+pub mod liveness;
+#[cfg(feature = "std")]
 pub mod metrics;
+#[cfg(feature = "std")]
+pub mod namespaces;
+#[cfg(feature = "netsim")]
+pub mod netsim;
+#[cfg(feature = "std")]
 pub mod node;
+#[cfg(feature = "std")]
+pub mod orchestrator;
+#[cfg(feature = "std")]
+pub mod peers;
+#[cfg(feature = "std")]
+pub mod publisher;
+#[cfg(feature = "std")]
+pub mod relayer;
+#[cfg(feature = "std")]
+pub mod retrieval;
+#[cfg(feature = "std")]
+pub mod rng;
+#[cfg(feature = "std")]
+pub mod role;
+#[cfg(feature = "std")]
+pub mod round_state;
+#[cfg(feature = "std")]
 pub mod rpc;
+#[cfg(feature = "std")]
+pub mod sample_queue;
+#[cfg(feature = "std")]
+pub mod sampling;
+#[cfg(feature = "std")]
+pub mod shares;
+#[cfg(feature = "std")]
+pub mod shutdown;
+#[cfg(feature = "std")]
 pub mod state;
+#[cfg(feature = "std")]
 pub mod store;
+#[cfg(feature = "std")]
 pub mod streaming;
+#[cfg(feature = "std")]
 pub mod tables;
+#[cfg(feature = "std")]
+pub mod testnet;
+#[cfg(feature = "std")]
 pub mod transactions;
+#[cfg(feature = "std")]
+pub mod types;
+#[cfg(feature = "std")]
+pub mod upgrades;
+#[cfg(feature = "std")]
+pub mod uploads;
+pub mod verify;
+#[cfg(feature = "std")]
+pub mod wal;
+#[cfg(feature = "webhook")]
+pub mod webhook;
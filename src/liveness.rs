@@ -0,0 +1,371 @@
+//! Liveness of validators and jailing of the ones missing too many blocks.
+//!
+//! Each block carries the commit certificate of its parent, so the precommits it holds tell which
+//! validators signed the parent, the same on every node. Over each window of
+//! [`LivenessConfig::window`] heights, validators signing less than
+//! [`LivenessConfig::min_signed_bps`] of the blocks are jailed: their voting power is zero from
+//! the next height on, and they are skipped when rotating proposers. A jailed validator returns
+//! with an unjail transaction, valid once [`LivenessConfig::jail_heights`] heights have passed.
+//!
+//! Counters and jailings are persisted in the store, as pruning drops the blocks they were
+//! derived from.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::LivenessError;
+use crate::transactions::Transaction;
+use crate::types::address::Address;
+use crate::types::validator_set::{Validator, ValidatorSet};
+
+/// Heights over which signed blocks are counted when the genesis file does not set it.
+pub const DEFAULT_WINDOW: u64 = 100;
+
+/// Share of the blocks of a window a validator must sign when the genesis file does not set it,
+/// in basis points.
+pub const DEFAULT_MIN_SIGNED_BPS: u64 = 5_000;
+
+/// Heights a jailed validator stays jailed at least when the genesis file does not set it.
+pub const DEFAULT_JAIL_HEIGHTS: u64 = 1_000;
+
+/// `liveness` section of the genesis file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LivenessConfig {
+    /// Heights over which signed blocks are counted, validators are jailed at the end of each
+    pub window: u64,
+    /// Share of the blocks of a window a validator must sign, in basis points
+    pub min_signed_bps: u64,
+    /// Heights a jailed validator stays jailed at least, before it can unjail
+    pub jail_heights: u64,
+}
+
+impl Default for LivenessConfig {
+    fn default() -> Self {
+        Self {
+            window: DEFAULT_WINDOW,
+            min_signed_bps: DEFAULT_MIN_SIGNED_BPS,
+            jail_heights: DEFAULT_JAIL_HEIGHTS,
+        }
+    }
+}
+
+/// Heights at which a validator had no voting power.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Jailing {
+    /// First height without voting power
+    pub from: u64,
+    /// First height at which the validator can unjail
+    pub release: u64,
+    /// First height with voting power again, once unjailed
+    pub until: Option<u64>,
+}
+
+impl Jailing {
+    fn covers(&self, height: u64) -> bool {
+        self.from <= height && self.until.is_none_or(|until| height < until)
+    }
+}
+
+/// Blocks signed and missed by a validator in the current window, and its jailings.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidatorLiveness {
+    pub signed_blocks: u64,
+    pub missed_blocks: u64,
+    pub jailings: Vec<Jailing>,
+}
+
+impl ValidatorLiveness {
+    fn is_jailed_at(&self, height: u64) -> bool {
+        self.jailings.iter().any(|jailing| jailing.covers(height))
+    }
+}
+
+/// Liveness records of the validators, shared between the mempool, block validation and the
+/// validator sets handed to consensus.
+#[derive(Debug, Clone)]
+pub struct Liveness {
+    validator_set: ValidatorSet,
+    config: LivenessConfig,
+    records: Arc<Mutex<BTreeMap<Address, ValidatorLiveness>>>,
+    /// Last height whose block was counted, the mempool checks unjails against the next one
+    decided_height: Arc<AtomicU64>,
+}
+
+impl Default for Liveness {
+    /// Without validators, so that every unjail is rejected.
+    fn default() -> Self {
+        Self {
+            validator_set: ValidatorSet::from_validators(Vec::new()),
+            config: LivenessConfig::default(),
+            records: Default::default(),
+            decided_height: Default::default(),
+        }
+    }
+}
+
+impl Liveness {
+    pub fn new(validator_set: &ValidatorSet, config: &LivenessConfig) -> Self {
+        Self {
+            validator_set: validator_set.clone(),
+            config: config.clone(),
+            records: Default::default(),
+            decided_height: Default::default(),
+        }
+    }
+
+    /// Adds records loaded from the store.
+    pub fn load(&self, records: impl IntoIterator<Item = (Address, ValidatorLiveness)>) {
+        self.records.lock().unwrap().extend(records);
+    }
+
+    /// Sets the last decided height, e.g. on restart.
+    pub fn set_decided_height(&self, height: u64) {
+        self.decided_height.store(height, Ordering::Relaxed);
+    }
+
+    pub fn records(&self) -> BTreeMap<Address, ValidatorLiveness> {
+        self.records.lock().unwrap().clone()
+    }
+
+    /// Validator set of `height`: the one of the genesis file, with zero voting power for the
+    /// validators jailed at `height`.
+    pub fn validator_set_at(&self, height: u64) -> ValidatorSet {
+        let records = self.records.lock().unwrap();
+        let jailed = |validator: &Validator| {
+            records
+                .get(&validator.address)
+                .is_some_and(|record| record.is_jailed_at(height))
+        };
+        if !self.validator_set.validators.iter().any(jailed) {
+            return self.validator_set.clone();
+        }
+
+        ValidatorSet::new(self.validator_set.validators.iter().map(|validator| {
+            let mut validator = validator.clone();
+            if jailed(&validator) {
+                validator.voting_power = 0;
+            }
+            validator
+        }))
+    }
+
+    /// Hashes of the distinct validator sets from genesis to `height`, in order, see
+    /// [`ValidatorSet::hash`].
+    pub fn validator_set_hashes(&self, height: u64) -> Vec<[u8; 32]> {
+        let mut changes: Vec<u64> = {
+            let records = self.records.lock().unwrap();
+            records
+                .values()
+                .flat_map(|record| &record.jailings)
+                .flat_map(|jailing| [Some(jailing.from), jailing.until])
+                .flatten()
+                .filter(|change| *change <= height)
+                .collect()
+        };
+        changes.sort_unstable();
+        changes.dedup();
+
+        let mut hashes = vec![self.validator_set.hash()];
+        for change in changes {
+            let hash = self.validator_set_at(change).hash();
+            if hashes.last() != Some(&hash) {
+                hashes.push(hash);
+            }
+        }
+        hashes
+    }
+
+    /// Checks an unjail included at `height`: its sender must be a validator jailed at `height`,
+    /// for at least [`LivenessConfig::jail_heights`].
+    pub fn check(&self, tx: &Transaction, height: u64) -> Result<(), LivenessError> {
+        if !tx.unjail() {
+            return Ok(());
+        }
+
+        let sender = tx.from_();
+        let Some(validator) = self.validator_set.get_by_public_key(&sender) else {
+            return Err(LivenessError::NotAValidator {
+                sender: hex::encode(sender.as_bytes()),
+            });
+        };
+        let records = self.records.lock().unwrap();
+        let Some(jailing) = records
+            .get(&validator.address)
+            .and_then(|record| record.jailings.iter().find(|j| j.covers(height)))
+        else {
+            return Err(LivenessError::NotJailed {
+                validator: validator.address.to_string(),
+            });
+        };
+        if height < jailing.release {
+            return Err(LivenessError::StillJailed {
+                release: jailing.release,
+                height,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks an unjail submitted to the mempool, to be included after the last decided height.
+    pub fn check_pending(&self, tx: &Transaction) -> Result<(), LivenessError> {
+        self.check(tx, self.decided_height.load(Ordering::Relaxed) + 1)
+    }
+
+    pub fn check_all(&self, txs: &[Transaction], height: u64) -> Result<(), LivenessError> {
+        txs.iter().try_for_each(|tx| self.check(tx, height))
+    }
+
+    /// Counts the precommits of `signers` in the certificate of the parent of the block decided
+    /// at `height`, applies its unjails, and jails the validators below the threshold at the end
+    /// of a window. Returns the records to be persisted.
+    pub fn apply(
+        &self,
+        txs: &[Transaction],
+        signers: &[Address],
+        height: u64,
+    ) -> Vec<(Address, ValidatorLiveness)> {
+        // Checked before taking the lock, decided blocks were checked like in validation
+        let unjailed: Vec<Address> = txs
+            .iter()
+            .filter(|tx| tx.unjail() && self.check(tx, height).is_ok())
+            .filter_map(|tx| self.validator_set.get_by_public_key(&tx.from_()))
+            .map(|validator| validator.address)
+            .collect();
+        let end_of_window = self.config.window > 0 && height % self.config.window == 0;
+
+        let mut records = self.records.lock().unwrap();
+        // The first block has no parent certificate
+        if height > 1 {
+            for validator in self.validator_set.validators.iter() {
+                let record = records.entry(validator.address).or_default();
+                if record.is_jailed_at(height - 1) {
+                    continue;
+                }
+                if signers.contains(&validator.address) {
+                    record.signed_blocks += 1;
+                } else {
+                    record.missed_blocks += 1;
+                }
+            }
+        }
+
+        for address in &unjailed {
+            let record = records.entry(*address).or_default();
+            if let Some(jailing) = record.jailings.iter_mut().find(|j| j.until.is_none()) {
+                jailing.until = Some(height + 1);
+            }
+        }
+
+        if end_of_window {
+            self.jail_offline(&mut records, height + 1);
+        }
+
+        self.set_decided_height(height);
+        records
+            .iter()
+            .map(|(address, record)| (*address, record.clone()))
+            .collect()
+    }
+
+    /// Jails from `from` the validators that signed too few blocks of the window, unless that
+    /// would leave no voting power, and starts a new window.
+    fn jail_offline(&self, records: &mut BTreeMap<Address, ValidatorLiveness>, from: u64) {
+        let offline: Vec<Address> = records
+            .iter()
+            .filter(|(_, record)| !record.is_jailed_at(from))
+            .filter(|(_, record)| {
+                let counted = record.signed_blocks + record.missed_blocks;
+                counted > 0
+                    && u128::from(record.signed_blocks) * 10_000
+                        < u128::from(counted) * u128::from(self.config.min_signed_bps)
+            })
+            .map(|(address, _)| *address)
+            .collect();
+
+        let remaining_power: u64 = self
+            .validator_set
+            .validators
+            .iter()
+            .filter(|validator| !offline.contains(&validator.address))
+            .filter(|validator| {
+                !records
+                    .get(&validator.address)
+                    .is_some_and(|record| record.is_jailed_at(from))
+            })
+            .map(|validator| validator.voting_power)
+            .sum();
+        if remaining_power > 0 {
+            for address in offline {
+                if let Some(record) = records.get_mut(&address) {
+                    record.jailings.push(Jailing {
+                        from,
+                        release: from + self.config.jail_heights,
+                        until: None,
+                    });
+                }
+            }
+        }
+        for record in records.values_mut() {
+            record.signed_blocks = 0;
+            record.missed_blocks = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::sign_bytes::DEFAULT_CHAIN_ID;
+    use malachitebft_test::PrivateKey;
+    use rand::thread_rng;
+
+    #[test]
+    fn jails_offline_validators_until_they_unjail() {
+        let keys: Vec<_> = (0..3).map(|_| PrivateKey::generate(thread_rng())).collect();
+        let validator_set =
+            ValidatorSet::new(keys.iter().map(|key| Validator::new(key.public_key(), 1)));
+        let config = LivenessConfig {
+            window: 4,
+            min_signed_bps: 5_000,
+            jail_heights: 10,
+        };
+        let liveness = Liveness::new(&validator_set, &config);
+        let addresses: Vec<_> = keys
+            .iter()
+            .map(|key| Address::from_public_key(&key.public_key()))
+            .collect();
+        let offline = addresses[2];
+        let unjail = Transaction::sign_unjail(DEFAULT_CHAIN_ID, &keys[2], 0, 1, 21_000);
+
+        // The third validator signs none of the parents of heights 2 to 4
+        for height in 1..=4 {
+            liveness.apply(&[], &addresses[..2], height);
+        }
+        assert_eq!(liveness.validator_set_at(4), validator_set);
+        let jailed = liveness.validator_set_at(5);
+        assert_eq!(jailed.get_by_address(&offline).unwrap().voting_power, 0);
+        assert_eq!(jailed.total_voting_power(), 2);
+
+        assert!(matches!(
+            liveness.check(&unjail, 14),
+            Err(LivenessError::StillJailed { release: 15, .. })
+        ));
+        assert!(matches!(
+            liveness.check(&unjail, 4),
+            Err(LivenessError::NotJailed { .. })
+        ));
+        assert!(liveness.check(&unjail, 15).is_ok());
+
+        liveness.apply(&[unjail], &addresses[..2], 15);
+        assert_eq!(liveness.validator_set_at(15).total_voting_power(), 2);
+        assert_eq!(liveness.validator_set_at(16), validator_set);
+        assert_eq!(
+            liveness.validator_set_hashes(16),
+            vec![validator_set.hash(), jailed.hash(), validator_set.hash()]
+        );
+    }
+}
@@ -1,9 +1,6 @@
 //! Example application using channels
 
-use crate::malachite_types::codec::proto::ProtobufCodec;
-use crate::malachite_types::height::Height;
-
-use config::Config;
+use clap::Parser;
 use eyre::{eyre, Result};
 use malachitebft_app_channel::app::node::Node;
 use malachitebft_test_cli::args::{Args, Commands};
@@ -13,26 +10,17 @@ use malachitebft_test_cli::cmd::start::StartCmd;
 use malachitebft_test_cli::cmd::testnet::TestnetCmd;
 use malachitebft_test_cli::config::{LogFormat, LogLevel};
 use malachitebft_test_cli::{logging, runtime};
+use mikan::bench::BenchCmd;
+use mikan::config::{Config, NodeRole};
+use mikan::node::App;
+use mikan::orchestrator::DevnetCmd;
+use mikan::sampling;
+use mikan::testnet::{self as testnet_files, DOCKER_COMPOSE_FLAG};
+use mikan::types::codec::proto::ProtobufCodec;
+use mikan::types::height::Height;
+use mikan::wal::{DumpWalJsonCmd, RepairWalCmd};
 use tracing::info;
 
-pub mod app;
-pub mod blob;
-pub mod block;
-pub mod config;
-pub mod error;
-pub mod header;
-pub mod malachite_types;
-pub mod metrics;
-pub mod node;
-pub mod rpc;
-pub mod state;
-pub mod store;
-pub mod streaming;
-pub mod tables;
-pub mod transactions;
-
-use node::App;
-
 /// Main entry point for the application
 ///
 /// This function:
@@ -44,6 +32,36 @@ use node::App;
 fn main() -> Result<()> {
     color_eyre::install()?;
 
+    // `bench`, `devnet`, `repair-wal` and `dump-wal --json` are not Malachite CLI commands,
+    // handle them before parsing those
+    match std::env::args().nth(1).as_deref() {
+        Some("bench") => return BenchCmd::parse_from(std::env::args().skip(1)).run(),
+        Some("devnet") => {
+            let _guard = logging::init(LogLevel::Info, LogFormat::Plaintext);
+            return Ok(DevnetCmd::parse_from(std::env::args().skip(1)).run()?);
+        }
+        Some("repair-wal") => return Ok(RepairWalCmd::parse_from(std::env::args().skip(1)).run()?),
+        Some("dump-wal") if std::env::args().any(|arg| arg == "--json") => {
+            return Ok(DumpWalJsonCmd::parse_from(std::env::args().skip(1)).run()?)
+        }
+        _ => {}
+    }
+
+    // `testnet --docker-compose` runs the Malachite command, then writes the compose file and
+    // the port map next to the generated configurations
+    if std::env::args().nth(1).as_deref() == Some("testnet")
+        && std::env::args().any(|arg| arg == DOCKER_COMPOSE_FLAG)
+    {
+        let args = Args::parse_from(std::env::args().filter(|arg| arg != DOCKER_COMPOSE_FLAG));
+        if let Commands::Testnet(cmd) = &args.command {
+            testnet(&args, cmd)?;
+            return Ok(testnet_files::write_docker_compose(
+                &args.get_home_dir()?,
+                cmd.nodes,
+            )?);
+        }
+    }
+
     // Load command-line arguments and possible configuration file.
     let args = Args::new();
 
@@ -75,6 +93,15 @@ fn start(args: &Args, cmd: &StartCmd) -> Result<()> {
     let _guard = logging::init(config.logging.log_level, config.logging.log_format);
 
     let rt = runtime::build_runtime(config.runtime)?;
+
+    // Sampling nodes only talk to the RPC server of another node
+    if config.node.role == NodeRole::Sampling {
+        info!(moniker = %config.moniker, "Starting sampling node");
+        return rt
+            .block_on(sampling::run(&config.node))
+            .map_err(|error| eyre!("Failed to run the sampling node: {error}"));
+    }
+
     info!(moniker = %config.moniker, "Starting Malachite");
 
     // Start the node
@@ -1,7 +0,0 @@
-use super::validator_set::ValidatorSet;
-use serde::{Deserialize, Serialize};
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Genesis {
-    pub validator_set: ValidatorSet,
-}
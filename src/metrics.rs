@@ -3,12 +3,15 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use malachitebft_app_channel::app::metrics;
+use serde::Serialize;
 
 use metrics::prometheus::metrics::counter::Counter;
 use metrics::prometheus::metrics::gauge::Gauge;
 use metrics::prometheus::metrics::histogram::{exponential_buckets, Histogram};
 use metrics::SharedRegistry;
 
+use crate::block::Block;
+
 #[derive(Clone, Debug)]
 pub struct DbMetrics(Arc<Inner>);
 
@@ -180,8 +183,114 @@ impl DbMetrics {
     }
 }
 
+/// Values of the [`DbMetrics`] counters and gauges at one point in time, persisted on shutdown
+/// since the Prometheus endpoint goes away with the node.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DbMetricsSnapshot {
+    pub db_size: i64,
+    pub db_write_bytes: u64,
+    pub db_read_bytes: u64,
+    pub db_key_read_bytes: u64,
+    pub db_read_count: u64,
+    pub db_write_count: u64,
+    pub db_delete_count: u64,
+}
+
+impl DbMetrics {
+    pub fn snapshot(&self) -> DbMetricsSnapshot {
+        DbMetricsSnapshot {
+            db_size: self.db_size.get(),
+            db_write_bytes: self.db_write_bytes.get(),
+            db_read_bytes: self.db_read_bytes.get(),
+            db_key_read_bytes: self.db_key_read_bytes.get(),
+            db_read_count: self.db_read_count.get(),
+            db_write_count: self.db_write_count.get(),
+            db_delete_count: self.db_delete_count.get(),
+        }
+    }
+}
+
 impl Default for DbMetrics {
     fn default() -> Self {
         Self::new()
     }
 }
+
+/// Contents of the decided blocks, recorded at commit.
+#[derive(Clone, Debug)]
+pub struct BlockMetrics {
+    /// Size of the encoded block (bytes)
+    block_size: Histogram,
+
+    /// Number of transactions per block
+    block_transactions: Histogram,
+
+    /// Number of blobs per block
+    block_blobs: Histogram,
+
+    /// Blob data per block (bytes)
+    block_blob_bytes: Histogram,
+}
+
+impl BlockMetrics {
+    pub fn new() -> Self {
+        Self {
+            block_size: Histogram::new(exponential_buckets(1024.0, 4.0, 10)), // Start from 1KiB
+            block_transactions: Histogram::new(exponential_buckets(1.0, 2.0, 14)),
+            block_blobs: Histogram::new(exponential_buckets(1.0, 2.0, 10)),
+            block_blob_bytes: Histogram::new(exponential_buckets(1024.0, 4.0, 10)),
+        }
+    }
+
+    pub fn register(registry: &SharedRegistry) -> Self {
+        let metrics = Self::new();
+
+        registry.with_prefix("app_channel", |registry| {
+            registry.register(
+                "block_size",
+                "Size of the decided blocks (bytes)",
+                metrics.block_size.clone(),
+            );
+
+            registry.register(
+                "block_transactions",
+                "Number of transactions per decided block",
+                metrics.block_transactions.clone(),
+            );
+
+            registry.register(
+                "block_blobs",
+                "Number of blobs per decided block",
+                metrics.block_blobs.clone(),
+            );
+
+            registry.register(
+                "block_blob_bytes",
+                "Blob data per decided block (bytes)",
+                metrics.block_blob_bytes.clone(),
+            );
+        });
+
+        metrics
+    }
+
+    /// Records a decided block, `size` being the length of its encoding as stored.
+    pub fn observe(&self, block: &Block, size: usize) {
+        let blobs = block.transactions().iter().flat_map(|tx| tx.data());
+        let (blob_count, blob_bytes) = blobs.fold((0, 0), |(count, bytes), blob| {
+            (count + 1, bytes + blob.data().len())
+        });
+
+        self.block_size.observe(size as f64);
+        self.block_transactions
+            .observe(block.transactions().len() as f64);
+        self.block_blobs.observe(blob_count as f64);
+        self.block_blob_bytes.observe(blob_bytes as f64);
+    }
+}
+
+impl Default for BlockMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
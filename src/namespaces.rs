@@ -0,0 +1,276 @@
+//! Namespace registration, so that rollups can reserve their namespace on a shared chain.
+//!
+//! Blobs are namespaced by the recipient of their transaction. A transaction carrying a
+//! [`NamespacePolicy`] registers its recipient as a namespace owned by its sender, or updates the
+//! policy if the sender already owns it. Transactions to a registered namespace, except those of
+//! its owner, must then be sent by an allowed poster and pay the namespace gas price.
+//!
+//! Registrations take effect once decided and are persisted in the store, as pruning drops the
+//! blocks they were decided in.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use malachitebft_test::PublicKey;
+use serde::{Deserialize, Serialize};
+
+use crate::error::NamespaceError;
+use crate::transactions::Transaction;
+
+/// Gas price scaled by [`NamespacePolicy::fee_multiplier`]. There is no chain-wide fee market yet,
+/// so the multiplier is the minimum gas price in the namespace.
+pub const BASE_GAS_PRICE: u64 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamespacePolicy {
+    /// Minimum gas price of transactions to the namespace, in units of [`BASE_GAS_PRICE`]
+    pub fee_multiplier: u64,
+    /// Senders allowed to post to the namespace besides its owner, anyone if empty
+    pub allowed_posters: Vec<PublicKey>,
+}
+
+impl Default for NamespacePolicy {
+    fn default() -> Self {
+        Self {
+            fee_multiplier: 1,
+            allowed_posters: Vec::new(),
+        }
+    }
+}
+
+impl NamespacePolicy {
+    pub fn min_gas_price(&self) -> u64 {
+        BASE_GAS_PRICE.saturating_mul(self.fee_multiplier)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Registration {
+    pub owner: PublicKey,
+    pub policy: NamespacePolicy,
+}
+
+/// Decided namespace registrations, shared between the mempool and block validation.
+#[derive(Debug, Clone, Default)]
+pub struct NamespaceRegistry {
+    registrations: Arc<Mutex<HashMap<[u8; 32], Registration>>>,
+}
+
+impl NamespaceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds registrations loaded from the store.
+    pub fn load(&self, registrations: impl IntoIterator<Item = ([u8; 32], Registration)>) {
+        self.registrations.lock().unwrap().extend(registrations);
+    }
+
+    pub fn get(&self, namespace: &PublicKey) -> Option<Registration> {
+        self.registrations
+            .lock()
+            .unwrap()
+            .get(namespace.as_bytes())
+            .cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.registrations.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Checks a transaction against the decided registrations, for mempool admission.
+    pub fn check(&self, tx: &Transaction) -> Result<(), NamespaceError> {
+        check(tx, self.get(&tx.to()).as_ref())
+    }
+
+    /// Checks the transactions of a block in order, each one seeing the registrations of the
+    /// previous ones.
+    pub fn check_all(&self, txs: &[Transaction]) -> Result<(), NamespaceError> {
+        let mut pending = HashMap::new();
+        for tx in txs {
+            self.check_pending(tx, &mut pending)?;
+        }
+        Ok(())
+    }
+
+    /// Applies the registrations of a decided block, returns them to be persisted.
+    pub fn apply(&self, txs: &[Transaction]) -> Vec<([u8; 32], Registration)> {
+        let mut pending = HashMap::new();
+        for tx in txs {
+            // Decided blocks were checked, an error would come from a block decided before the
+            // transaction it depends on and is skipped like in validation
+            let _ = self.check_pending(tx, &mut pending);
+        }
+
+        let applied: Vec<_> = pending.into_iter().collect();
+        self.load(applied.clone());
+        applied
+    }
+
+    fn check_pending(
+        &self,
+        tx: &Transaction,
+        pending: &mut HashMap<[u8; 32], Registration>,
+    ) -> Result<(), NamespaceError> {
+        let namespace = *tx.to().as_bytes();
+        let registration = pending
+            .get(&namespace)
+            .cloned()
+            .or_else(|| self.get(&tx.to()));
+
+        check(tx, registration.as_ref())?;
+
+        if let Some(policy) = tx.registration() {
+            pending.insert(
+                namespace,
+                Registration {
+                    owner: tx.from_(),
+                    policy: policy.clone(),
+                },
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn check(tx: &Transaction, registration: Option<&Registration>) -> Result<(), NamespaceError> {
+    let Some(registration) = registration else {
+        return Ok(());
+    };
+
+    if registration.owner == tx.from_() {
+        return Ok(());
+    }
+
+    let namespace = hex::encode(tx.to().as_bytes());
+    if tx.registration().is_some() {
+        return Err(NamespaceError::AlreadyRegistered { namespace });
+    }
+
+    let policy = &registration.policy;
+    if !policy.allowed_posters.is_empty() && !policy.allowed_posters.contains(&tx.from_()) {
+        return Err(NamespaceError::PosterNotAllowed {
+            namespace,
+            poster: hex::encode(tx.from_().as_bytes()),
+        });
+    }
+
+    if tx.gas_price() < policy.min_gas_price() {
+        return Err(NamespaceError::GasPriceTooLow {
+            namespace,
+            required: policy.min_gas_price(),
+            actual: tx.gas_price(),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::sign_bytes::DEFAULT_CHAIN_ID;
+    use malachitebft_test::PrivateKey;
+    use rand::thread_rng;
+
+    /// Covers four blobs under the default gas schedule
+    const GAS_LIMIT: u64 = 5_000_000;
+
+    fn post(from: &PrivateKey, namespace: &PrivateKey, gas_price: u64) -> Transaction {
+        Transaction::sign(
+            DEFAULT_CHAIN_ID,
+            from,
+            namespace.public_key(),
+            0,
+            Default::default(),
+            0,
+            gas_price,
+            GAS_LIMIT,
+        )
+    }
+
+    #[test]
+    fn enforces_policy_of_registered_namespaces() {
+        let (owner, poster, squatter) = (
+            PrivateKey::generate(thread_rng()),
+            PrivateKey::generate(thread_rng()),
+            PrivateKey::generate(thread_rng()),
+        );
+        let namespace = PrivateKey::generate(thread_rng());
+        let policy = NamespacePolicy {
+            fee_multiplier: 10,
+            allowed_posters: vec![poster.public_key()],
+        };
+        let register = Transaction::sign_registration(
+            DEFAULT_CHAIN_ID,
+            &owner,
+            namespace.public_key(),
+            policy,
+            0,
+            1,
+            GAS_LIMIT,
+        );
+
+        let registry = NamespaceRegistry::new();
+        assert!(registry.check(&post(&squatter, &namespace, 1)).is_ok());
+        assert_eq!(registry.apply(&[register]).len(), 1);
+
+        assert!(registry.check(&post(&owner, &namespace, 1)).is_ok());
+        assert!(registry.check(&post(&poster, &namespace, 10)).is_ok());
+        assert!(matches!(
+            registry.check(&post(&poster, &namespace, 9)),
+            Err(NamespaceError::GasPriceTooLow { required: 10, .. })
+        ));
+        assert!(matches!(
+            registry.check(&post(&squatter, &namespace, 10)),
+            Err(NamespaceError::PosterNotAllowed { .. })
+        ));
+
+        let claim = Transaction::sign_registration(
+            DEFAULT_CHAIN_ID,
+            &squatter,
+            namespace.public_key(),
+            NamespacePolicy::default(),
+            0,
+            1,
+            GAS_LIMIT,
+        );
+        assert!(matches!(
+            registry.check(&claim),
+            Err(NamespaceError::AlreadyRegistered { .. })
+        ));
+    }
+
+    #[test]
+    fn first_registration_in_a_block_wins() {
+        let (first, second) = (
+            PrivateKey::generate(thread_rng()),
+            PrivateKey::generate(thread_rng()),
+        );
+        let namespace = PrivateKey::generate(thread_rng()).public_key();
+        let claim = |key: &PrivateKey| {
+            Transaction::sign_registration(
+                DEFAULT_CHAIN_ID,
+                key,
+                namespace,
+                NamespacePolicy::default(),
+                0,
+                1,
+                GAS_LIMIT,
+            )
+        };
+
+        let registry = NamespaceRegistry::new();
+        let txs = vec![claim(&first), claim(&second)];
+        assert!(registry.check_all(&txs[..1]).is_ok());
+        assert!(registry.check_all(&txs).is_err());
+
+        registry.apply(&txs);
+        assert_eq!(registry.get(&namespace).unwrap().owner, first.public_key());
+    }
+}
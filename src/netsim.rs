@@ -0,0 +1,109 @@
+//! Adverse network conditions for testing, only compiled with the `netsim` feature.
+//!
+//! There is no in-process network to hook into: peers talk over the libp2p network of the
+//! consensus engine. Conditions are therefore applied where the application sees traffic, on the
+//! proposal parts it receives. Votes and sync messages are not affected, but dropping or delaying
+//! proposal parts is enough to make rounds time out and force nodes to catch up through sync.
+//!
+//! A partition between two groups of nodes is set up by listing the peer ids of the other group
+//! in `blocked_peers` on each side.
+
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// `[netsim]` section of the node configuration, a perfect network by default.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetsimConfig {
+    /// Probability in `[0, 1]` of dropping each received proposal part
+    pub drop_rate: f64,
+    /// Delay in milliseconds added before handling each received proposal part
+    pub latency_ms: u64,
+    /// Maximum random delay in milliseconds added on top of `latency_ms`
+    pub jitter_ms: u64,
+    /// Peers whose proposal parts are all dropped, base58 encoded
+    pub blocked_peers: Vec<String>,
+}
+
+/// What to do with a received proposal part.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkAction {
+    Drop,
+    Deliver { delay: Duration },
+}
+
+/// Link conditions applied to received proposal parts.
+pub struct LinkConditions {
+    config: NetsimConfig,
+    rng: StdRng,
+}
+
+impl LinkConditions {
+    /// The RNG is seeded from [`crate::rng::SEED_ENV`], so that a run can be replayed.
+    pub fn new(config: NetsimConfig) -> Self {
+        Self::with_rng(config, crate::rng::seeded_rng())
+    }
+
+    pub fn with_rng(config: NetsimConfig, rng: StdRng) -> Self {
+        Self { config, rng }
+    }
+
+    /// `from` is the base58 encoded id of the sending peer.
+    pub fn on_receive(&mut self, from: &str) -> LinkAction {
+        if self.config.blocked_peers.iter().any(|peer| peer == from) {
+            return LinkAction::Drop;
+        }
+
+        if self.config.drop_rate > 0.0 && self.rng.gen_bool(self.config.drop_rate.min(1.0)) {
+            return LinkAction::Drop;
+        }
+
+        let jitter = match self.config.jitter_ms {
+            0 => 0,
+            jitter => self.rng.gen_range(0..=jitter),
+        };
+
+        LinkAction::Deliver {
+            delay: Duration::from_millis(self.config.latency_ms + jitter),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn perfect_network_by_default() {
+        let mut link = LinkConditions::with_rng(NetsimConfig::default(), StdRng::seed_from_u64(0));
+        assert_eq!(
+            link.on_receive("12D3KooWPeer"),
+            LinkAction::Deliver {
+                delay: Duration::ZERO
+            }
+        );
+    }
+
+    #[test]
+    fn drops_blocked_peers_and_adds_latency() {
+        let config = NetsimConfig {
+            latency_ms: 100,
+            jitter_ms: 50,
+            blocked_peers: vec!["12D3KooWBlocked".to_string()],
+            ..NetsimConfig::default()
+        };
+        let mut link = LinkConditions::with_rng(config, StdRng::seed_from_u64(0));
+
+        assert_eq!(link.on_receive("12D3KooWBlocked"), LinkAction::Drop);
+        match link.on_receive("12D3KooWPeer") {
+            LinkAction::Deliver { delay } => {
+                assert!((100..=150).contains(&(delay.as_millis() as u64)))
+            }
+            LinkAction::Drop => panic!("unexpected drop"),
+        }
+    }
+}
@@ -4,9 +4,10 @@
 use std::path::PathBuf;
 
 use async_trait::async_trait;
+use eyre::eyre;
 use rand::{CryptoRng, RngCore};
 use tokio::task::JoinHandle;
-use tracing::Instrument;
+use tracing::{info, warn, Instrument};
 
 use malachitebft_app_channel::app::events::{RxEvent, TxEvent};
 use malachitebft_app_channel::app::metrics::SharedRegistry;
@@ -14,26 +15,44 @@ use malachitebft_app_channel::app::node::{
     CanGeneratePrivateKey, CanMakeConfig, CanMakeGenesis, CanMakePrivateKeyFile, EngineHandle,
     MakeConfigSettings, Node, NodeHandle,
 };
-use malachitebft_app_channel::app::types::core::{Height as _, VotingPower};
+use malachitebft_app_channel::app::types::core::VotingPower;
 use malachitebft_app_channel::app::types::Keypair;
 use malachitebft_signing_ed25519::{PrivateKey, PublicKey};
 
-use crate::block::Block;
 // Use the same types used for integration tests.
 // A real application would use its own types and context instead.
-use crate::malachite_types::codec::proto::ProtobufCodec;
-use crate::malachite_types::signing::Ed25519Provider;
-use crate::malachite_types::{
+use crate::consensus_params::ConsensusParams;
+use crate::events::EventBus;
+use crate::forks::ForkMonitor;
+use crate::gas::GasSchedule;
+use crate::governance::{Governance, GovernanceConfig};
+use crate::liveness::{Liveness, LivenessConfig};
+use crate::peers::PeerBook;
+use crate::shutdown::Shutdown;
+use crate::transactions::pool::TransactionPool;
+use crate::transactions::DEFAULT_MAX_BLOBS_PER_TX;
+use crate::types::codec::proto::ProtobufCodec;
+use crate::types::sign_bytes::DEFAULT_CHAIN_ID;
+use crate::types::signing::Ed25519Provider;
+use crate::types::{
     address::Address, context::TestContext, genesis::Genesis, height::Height,
     validator_set::Validator, validator_set::ValidatorSet,
 };
-use crate::transactions::pool::TransactionPool;
+use crate::upgrades::{ProtocolSchedule, PROTOCOL_VERSION};
 use malachitebft_test_cli::metrics;
 
-use crate::config::{load_config, Config};
-use crate::metrics::DbMetrics;
+use crate::builder::BlockBuilder;
+use crate::config::{
+    load_config, BlockConfig, BuilderConfig, CompressionConfig, Config, EventsConfig,
+    MempoolExchangeConfig, NodeRole, NodeRoleConfig, PeerAccessConfig, ProtocolConfig,
+    RelayerConfig, RetrievalConfig, SamplingConfig, StorageConfig, StreamingConfig,
+};
+use crate::metrics::{BlockMetrics, DbMetrics};
+use crate::retrieval::BlockRetrieval;
 use crate::state::State;
 use crate::store::Store;
+use crate::streaming::StreamMetrics;
+use crate::wal::{self, Recovery};
 
 /// Main application struct implementing the consensus node functionality
 #[derive(Clone)]
@@ -46,6 +65,42 @@ pub struct App {
     pub enable_rpc: bool,
 }
 
+impl App {
+    /// Height to resume consensus at, after the last height the store decided, see
+    /// [`wal::recover`].
+    async fn recover(&self, store: &Store) -> eyre::Result<Height> {
+        let decided_height = store.max_decided_value_height().await;
+        let wal_path = self.get_home_dir().join("wal").join("consensus.wal");
+        let wal_height = if wal_path.exists() {
+            Some(wal::scan(&wal_path)?.sequence)
+        } else {
+            None
+        };
+
+        let recovery = wal::recover(wal_height, decided_height.map(|height| height.as_u64()));
+        match &recovery {
+            Recovery::Resume { .. } => {}
+            Recovery::Stale { height, wal_height } => {
+                info!(
+                    height,
+                    wal_height, "The WAL is for a decided height, discarding it"
+                );
+            }
+            Recovery::Gap { height, wal_height } => {
+                let backup = wal::keep_ahead(&wal_path)?;
+                warn!(
+                    height,
+                    wal_height,
+                    backup = %backup.display(),
+                    "The WAL is ahead of the store, replaying the missing heights from peers"
+                );
+            }
+        }
+
+        Ok(Height::new(recovery.height()))
+    }
+}
+
 pub struct Handle {
     pub app: JoinHandle<()>,
     pub engine: EngineHandle,
@@ -80,7 +135,7 @@ impl Node for App {
     }
 
     fn load_config(&self) -> eyre::Result<Self::Config> {
-        load_config(&self.config_file, Some("MALACHITE"))
+        Ok(load_config(&self.config_file, Some("MALACHITE"))?)
     }
 
     fn get_address(&self, pk: &PublicKey) -> Address {
@@ -105,7 +160,13 @@ impl Node for App {
     }
 
     fn get_signing_provider(&self, private_key: PrivateKey) -> Self::SigningProvider {
-        Ed25519Provider::new(private_key)
+        // The engine builds its own signing provider, so the chain id has to come from genesis here
+        let chain_id = self
+            .load_genesis()
+            .map(|genesis| genesis.chain_id)
+            .unwrap_or_else(|_| DEFAULT_CHAIN_ID.to_string());
+
+        Ed25519Provider::new(private_key, chain_id)
     }
 
     fn load_genesis(&self) -> eyre::Result<Self::Genesis> {
@@ -114,19 +175,46 @@ impl Node for App {
     }
 
     async fn start(&self) -> eyre::Result<Handle> {
-        let config = self.load_config()?;
+        let mut config = self.load_config()?;
+        // Don't dial the peers we would reject anyway
+        let p2p = config.p2p.clone();
+        config
+            .consensus
+            .p2p
+            .persistent_peers
+            .retain(|addr| p2p.is_dialable(&addr.to_string()));
 
         let span = tracing::error_span!("node", moniker = %config.moniker);
         let _enter = span.enter();
 
-        let private_key_file = self.load_private_key_file()?;
-        let private_key = self.load_private_key(private_key_file);
+        let private_key = match config.node.role {
+            NodeRole::Validator => self.load_private_key(self.load_private_key_file()?),
+            NodeRole::Full => {
+                info!("Full node, running with an ephemeral key");
+                self.generate_private_key(rand::thread_rng())
+            }
+            NodeRole::Sampling => {
+                return Err(eyre!(
+                    "Sampling nodes do not run consensus, see `sampling::run`"
+                ))
+            }
+        };
         let public_key = self.get_public_key(&private_key);
         let address = self.get_address(&public_key);
         let signing_provider = self.get_signing_provider(private_key);
         let ctx = TestContext::new();
 
-        let genesis = self.load_genesis()?;
+        let mut genesis = self.load_genesis()?;
+        genesis.protocol.schedule(&config.protocol.upgrades)?;
+        genesis.protocol.activate(&config.protocol.forks)?;
+        genesis.consensus.check()?;
+        if let Some(height) = genesis.protocol.unsupported_from() {
+            warn!(
+                height,
+                supported = PROTOCOL_VERSION,
+                "This node does not support the protocol version scheduled from this height, it will stop there until it is upgraded"
+            );
+        }
         let initial_validator_set = genesis.validator_set.clone();
 
         let codec = ProtobufCodec;
@@ -146,17 +234,78 @@ impl Node for App {
         let registry = SharedRegistry::global().with_moniker(&config.moniker);
         let metrics = DbMetrics::register(&registry);
 
+        let shutdown = Shutdown::new();
+        tokio::spawn(shutdown.clone().on_signal());
+
         if config.metrics.enabled {
-            tokio::spawn(metrics::serve(config.metrics.listen_addr));
+            let shutdown = shutdown.clone();
+            let listen_addr = config.metrics.listen_addr;
+            tokio::spawn(async move { shutdown.run_until(metrics::serve(listen_addr)).await });
         }
 
         let db_dir = self.get_home_dir().join("db");
         std::fs::create_dir_all(&db_dir)?;
 
-        let store = Store::open(db_dir.join("store.db"), metrics)?;
-        let start_height = self.start_height.unwrap_or(Height::INITIAL);
-        let transaction_pool = TransactionPool::new();
-
+        let store = Store::open(
+            db_dir.join("store.db"),
+            metrics,
+            &config.compression,
+            &config.storage,
+        )?;
+        let completed = store.complete_commits().await?;
+        if !completed.is_empty() {
+            info!(heights = ?completed, "Completed the commits interrupted by a crash");
+        }
+        let start_height = match self.start_height {
+            Some(height) => height,
+            None => self.recover(&store).await?,
+        };
+
+        if config.relayer.enabled {
+            #[cfg(feature = "relayer")]
+            {
+                let relayer = crate::relayer::run(config.relayer.clone(), store.clone());
+                let shutdown = shutdown.clone();
+                tokio::spawn(async move {
+                    if let Some(Err(e)) = shutdown.run_until(relayer).await {
+                        tracing::error!(%e, "Data root relayer stopped");
+                    }
+                });
+            }
+            #[cfg(not(feature = "relayer"))]
+            return Err(crate::error::RelayerError::FeatureDisabled.into());
+        }
+        let governance = Governance::new(
+            &genesis.validator_set,
+            &genesis.governance,
+            &genesis.consensus,
+            &genesis.gas,
+        );
+        governance.load(store.get_governance().await?);
+        if let Some(height) = store.max_decided_value_height().await {
+            governance.set_decided_height(height.as_u64());
+        }
+        let liveness = Liveness::new(&genesis.validator_set, &genesis.liveness);
+        liveness.load(store.get_liveness().await?);
+        if let Some(height) = store.max_decided_value_height().await {
+            liveness.set_decided_height(height.as_u64());
+        }
+        let transaction_pool = TransactionPool::new(&genesis.chain_id)
+            .with_gas_schedule(genesis.gas.clone())
+            .with_governance(governance)
+            .with_liveness(liveness)
+            .with_max_blobs_per_tx(genesis.max_blobs_per_tx)
+            .with_max_blob_bytes(genesis.consensus.max_blob_bytes)
+            .with_max_tx_bytes(config.block.max_tx_bytes());
+        transaction_pool
+            .namespaces()
+            .load(store.get_namespaces().await?);
+        transaction_pool
+            .account_nonces()
+            .load(store.get_account_nonces().await?);
+
+        let event_sinks = config.events.open_sinks(&store).await?;
+        let retrieval = BlockRetrieval::new(&config.retrieval, &genesis.chain_id)?;
         let mut state = State::new(
             genesis,
             ctx,
@@ -165,22 +314,40 @@ impl Node for App {
             start_height,
             store,
             transaction_pool,
+            PeerBook::register(&registry).with_access(config.p2p.clone()),
+            ForkMonitor::register(&registry),
+            config.compression.clone(),
+            config.streaming.clone(),
+            StreamMetrics::register(&registry),
+            BlockMetrics::register(&registry),
+            config.block.clone(),
+            EventBus::start(event_sinks, config.events.queue_size),
+            config.mempool_exchange.clone(),
+            config.sampling.clone(),
+            retrieval,
             self.enable_rpc,
+            shutdown.clone(),
         )
         .await;
+        state.builder = BlockBuilder::new(&config.builder)?;
+
+        #[cfg(feature = "byzantine")]
+        {
+            state.byzantine = config.byzantine.clone();
+        }
+
+        #[cfg(feature = "netsim")]
+        {
+            state.link_conditions = Some(crate::netsim::LinkConditions::new(config.netsim.clone()));
+        }
+
+        state.bootstrap_genesis().await?;
+        state.load_undecided_proposals().await?;
 
         let span = tracing::error_span!("node", moniker = %config.moniker);
         let app_handle = tokio::spawn(
             async move {
-                state
-                    .store
-                    .store_decided_block_data(
-                        start_height - 1,
-                        Block::genesis().to_bytes().unwrap(),
-                    )
-                    .await
-                    .unwrap();
-                if let Err(e) = crate::app::run(&mut state, &mut channels).await {
+                if let Err(e) = crate::app::run(&mut state, &mut channels, &shutdown).await {
                     tracing::error!(%e, "Application error");
                 }
             }
@@ -195,8 +362,10 @@ impl Node for App {
     }
 
     async fn run(self) -> eyre::Result<()> {
-        let handles = self.start().await?;
-        handles.app.await.map_err(Into::into)
+        let mut handles = self.start().await?;
+        // The application returns on shutdown, once the state is flushed
+        (&mut handles.app).await?;
+        handles.kill(Some("shutdown".to_string())).await
     }
 }
 
@@ -208,7 +377,17 @@ impl CanMakeGenesis for App {
 
         let validator_set = ValidatorSet::new(validators);
 
-        Genesis { validator_set }
+        Genesis {
+            chain_id: DEFAULT_CHAIN_ID.to_string(),
+            genesis_time: chrono::Utc::now().timestamp() as u64,
+            validator_set,
+            gas: GasSchedule::default(),
+            max_blobs_per_tx: DEFAULT_MAX_BLOBS_PER_TX,
+            protocol: ProtocolSchedule::default(),
+            consensus: ConsensusParams::default(),
+            governance: GovernanceConfig::default(),
+            liveness: LivenessConfig::default(),
+        }
     }
 }
 
@@ -241,14 +420,14 @@ fn make_config(index: usize, total: usize, settings: MakeConfigSettings) -> Conf
 
     use malachitebft_app_channel::app::config::*;
 
-    const CONSENSUS_BASE_PORT: usize = 27000;
-    const METRICS_BASE_PORT: usize = 29000;
+    use crate::testnet::{CONSENSUS_BASE_PORT, METRICS_BASE_PORT};
 
-    let consensus_port = CONSENSUS_BASE_PORT + index;
-    let metrics_port = METRICS_BASE_PORT + index;
+    let consensus_port = CONSENSUS_BASE_PORT as usize + index;
+    let metrics_port = METRICS_BASE_PORT as usize + index;
 
     Config {
         moniker: format!("app-{}", index),
+        node: NodeRoleConfig::default(),
         consensus: ConsensusConfig {
             // Current channel app does not support proposal-only value payload properly as Init does not include valid_round
             value_payload: ValuePayload::ProposalAndParts,
@@ -276,7 +455,7 @@ fn make_config(index: usize, total: usize, settings: MakeConfigSettings) -> Conf
                         .map(|index| {
                             settings
                                 .transport
-                                .multiaddr("127.0.0.1", CONSENSUS_BASE_PORT + index)
+                                .multiaddr("127.0.0.1", CONSENSUS_BASE_PORT as usize + index)
                         })
                         .collect()
                 } else {
@@ -285,7 +464,7 @@ fn make_config(index: usize, total: usize, settings: MakeConfigSettings) -> Conf
                         .map(|j| {
                             settings
                                 .transport
-                                .multiaddr("127.0.0.1", CONSENSUS_BASE_PORT + j)
+                                .multiaddr("127.0.0.1", CONSENSUS_BASE_PORT as usize + j)
                         })
                         .collect()
                 },
@@ -300,5 +479,21 @@ fn make_config(index: usize, total: usize, settings: MakeConfigSettings) -> Conf
         runtime: settings.runtime,
         logging: LoggingConfig::default(),
         value_sync: ValueSyncConfig::default(),
+        compression: CompressionConfig::default(),
+        streaming: StreamingConfig::default(),
+        block: BlockConfig::default(),
+        builder: BuilderConfig::default(),
+        events: EventsConfig::default(),
+        relayer: RelayerConfig::default(),
+        storage: StorageConfig::default(),
+        protocol: ProtocolConfig::default(),
+        mempool_exchange: MempoolExchangeConfig::default(),
+        p2p: PeerAccessConfig::default(),
+        sampling: SamplingConfig::default(),
+        retrieval: RetrievalConfig::default(),
+        #[cfg(feature = "byzantine")]
+        byzantine: Default::default(),
+        #[cfg(feature = "netsim")]
+        netsim: Default::default(),
     }
 }
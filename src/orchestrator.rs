@@ -0,0 +1,283 @@
+//! `mikan devnet`: runs a local testnet as child processes of a single [`Orchestrator`].
+//!
+//! The orchestrator generates the node homes with the `testnet` command when they are missing,
+//! starts one `mikan start` process per node with its output in `<home>/<i>/logs/node.log`,
+//! restarts the nodes that exit on their own and stops all of them on shutdown.
+
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use clap::Parser;
+use tokio::process::{Child, Command};
+use tracing::{error, info, warn};
+
+use crate::error::DevnetError;
+use crate::shutdown::Shutdown;
+
+/// Interval between two checks of the node processes.
+const SUPERVISION_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Time given to the nodes to flush their state on shutdown before they are killed.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "mikan devnet",
+    about = "Run and supervise a local testnet until ctrl-c"
+)]
+pub struct DevnetCmd {
+    /// Number of nodes
+    #[arg(long, default_value_t = 3)]
+    pub nodes: usize,
+
+    /// Directory of the node homes, generated if missing
+    #[arg(long, default_value = "nodes")]
+    pub home: PathBuf,
+
+    /// Node binary, the current executable by default
+    #[arg(long)]
+    pub binary: Option<PathBuf>,
+
+    /// Keep the databases and WALs of a previous run
+    #[arg(long)]
+    pub no_reset: bool,
+
+    /// Number of times a crashed node is restarted before it is given up on
+    #[arg(long, default_value_t = 5)]
+    pub max_restarts: u32,
+}
+
+impl DevnetCmd {
+    pub fn run(&self) -> Result<(), DevnetError> {
+        let binary = match &self.binary {
+            Some(binary) => binary.clone(),
+            None => std::env::current_exe()?,
+        };
+        let orchestrator = Orchestrator::new(binary, &self.home, self.nodes)
+            .reset(!self.no_reset)
+            .max_restarts(self.max_restarts);
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        rt.block_on(async {
+            let shutdown = Shutdown::new();
+            tokio::spawn(shutdown.clone().on_signal());
+            orchestrator.run(shutdown).await
+        })
+    }
+}
+
+/// Launches and supervises the nodes of a local testnet.
+#[derive(Clone, Debug)]
+pub struct Orchestrator {
+    binary: PathBuf,
+    home: PathBuf,
+    nodes: usize,
+    reset: bool,
+    max_restarts: u32,
+    restart_delay: Duration,
+}
+
+impl Orchestrator {
+    pub fn new(binary: impl Into<PathBuf>, home: impl Into<PathBuf>, nodes: usize) -> Self {
+        Self {
+            binary: binary.into(),
+            home: home.into(),
+            nodes,
+            reset: true,
+            max_restarts: 5,
+            restart_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// Whether the databases and WALs are wiped before the nodes first start.
+    pub fn reset(mut self, reset: bool) -> Self {
+        self.reset = reset;
+        self
+    }
+
+    pub fn max_restarts(mut self, max_restarts: u32) -> Self {
+        self.max_restarts = max_restarts;
+        self
+    }
+
+    pub fn restart_delay(mut self, restart_delay: Duration) -> Self {
+        self.restart_delay = restart_delay;
+        self
+    }
+
+    fn node_home(&self, index: usize) -> PathBuf {
+        self.home.join(index.to_string())
+    }
+
+    /// Runs the nodes until `shutdown` is triggered, or fails once every node has been given up on.
+    pub async fn run(self, shutdown: Shutdown) -> Result<(), DevnetError> {
+        self.generate_homes().await?;
+
+        let mut nodes = Vec::with_capacity(self.nodes);
+        for index in 0..self.nodes {
+            self.prepare_home(index)?;
+            let child = self.spawn(index)?;
+            info!(node = index, log = %self.log_file(index).display(), "Spawned node");
+            nodes.push(NodeProcess {
+                index,
+                child: Some(child),
+                restarts: 0,
+            });
+        }
+        info!(nodes = self.nodes, "Press Ctrl+C to stop the nodes");
+
+        let mut interval = tokio::time::interval(SUPERVISION_INTERVAL);
+        loop {
+            tokio::select! {
+                () = shutdown.triggered() => break,
+                _ = interval.tick() => {}
+            }
+
+            for node in nodes.iter_mut() {
+                let Some(child) = node.child.as_mut() else {
+                    continue;
+                };
+                let Some(status) = child.try_wait()? else {
+                    continue;
+                };
+
+                if node.restarts >= self.max_restarts {
+                    error!(node = node.index, %status, "Node exited too many times, giving up");
+                    node.child = None;
+                    continue;
+                }
+
+                node.restarts += 1;
+                warn!(
+                    node = node.index,
+                    %status,
+                    restarts = node.restarts,
+                    "Node exited, restarting"
+                );
+                tokio::time::sleep(self.restart_delay).await;
+                node.child = Some(self.spawn(node.index)?);
+            }
+
+            if nodes.iter().all(|node| node.child.is_none()) {
+                return Err(DevnetError::AllNodesExited);
+            }
+        }
+
+        info!("Stopping all nodes...");
+        for node in nodes.iter_mut() {
+            if let Some(child) = node.child.take() {
+                stop(node.index, child).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs `<binary> testnet` unless the home of every node already exists.
+    async fn generate_homes(&self) -> Result<(), DevnetError> {
+        let missing = (0..self.nodes).any(|index| !self.node_home(index).join("config").is_dir());
+        if !missing {
+            return Ok(());
+        }
+
+        info!(home = %self.home.display(), "Generating the node homes");
+        let status = Command::new(&self.binary)
+            .arg("testnet")
+            .arg("--nodes")
+            .arg(self.nodes.to_string())
+            .arg("--home")
+            .arg(&self.home)
+            .status()
+            .await
+            .map_err(|source| DevnetError::Run {
+                binary: self.binary.clone(),
+                source,
+            })?;
+        if !status.success() {
+            return Err(DevnetError::TestnetFailed(status));
+        }
+        Ok(())
+    }
+
+    fn prepare_home(&self, index: usize) -> Result<(), DevnetError> {
+        let home = self.node_home(index);
+        let mut dirs = vec!["logs", "traces"];
+        if self.reset {
+            dirs.extend(["db", "wal"]);
+        }
+
+        for dir in dirs {
+            let dir = home.join(dir);
+            if dir.exists() {
+                std::fs::remove_dir_all(&dir)?;
+            }
+            std::fs::create_dir_all(&dir)?;
+        }
+        Ok(())
+    }
+
+    fn log_file(&self, index: usize) -> PathBuf {
+        self.node_home(index).join("logs").join("node.log")
+    }
+
+    fn spawn(&self, index: usize) -> Result<Child, DevnetError> {
+        // Appending keeps the output of the previous runs of a restarted node
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.log_file(index))?;
+
+        let mut command = Command::new(&self.binary);
+        command
+            .arg("start")
+            .arg("--home")
+            .arg(self.node_home(index))
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .kill_on_drop(true);
+        if std::env::var_os("RUST_LOG").is_none() {
+            command.env("RUST_LOG", "debug");
+        }
+
+        command.spawn().map_err(|source| DevnetError::Spawn {
+            index,
+            binary: self.binary.clone(),
+            source,
+        })
+    }
+}
+
+struct NodeProcess {
+    index: usize,
+    child: Option<Child>,
+    restarts: u32,
+}
+
+/// Waits for the node to exit, then kills it after [`SHUTDOWN_GRACE_PERIOD`].
+///
+/// Ctrl-c reaches the nodes too as they share the process group of the orchestrator, which lets
+/// them flush their state, see [`crate::shutdown`].
+async fn stop(index: usize, mut child: Child) {
+    match tokio::time::timeout(SHUTDOWN_GRACE_PERIOD, child.wait()).await {
+        Ok(Ok(status)) => info!(node = index, %status, "Node stopped"),
+        Ok(Err(e)) => warn!(node = index, %e, "Couldn't wait for node"),
+        Err(_) => {
+            warn!(node = index, "Node did not stop in time, killing it");
+            if let Err(e) = child.kill().await {
+                warn!(node = index, %e, "Couldn't kill node");
+            }
+        }
+    }
+}
+
+/// Path of the `mikan` binary built by `cargo build -r` from the crate root.
+pub fn release_binary() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("release")
+        .join("mikan")
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use malachitebft_app_channel::app::metrics::prometheus::metrics::counter::Counter;
+use malachitebft_app_channel::app::metrics::prometheus::metrics::family::Family;
+use malachitebft_app_channel::app::metrics::SharedRegistry;
+use malachitebft_app_channel::app::types::PeerId;
+use serde::{Deserialize, Serialize};
+
+use crate::types::height::Height;
+
+/// Direction of the connection to a peer, as far as the application layer knows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerDirection {
+    /// We dialed the peer (it is one of our persistent peers).
+    Outbound,
+    /// The peer dialed us.
+    Inbound,
+    /// The network layer did not tell us.
+    Unknown,
+}
+
+/// What we know about a peer in our local view of the network.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerInfo {
+    /// libp2p peer id, base58 encoded
+    pub peer_id: String,
+    pub direction: PeerDirection,
+    /// Unix timestamp (seconds) at which the peer joined our view
+    pub connected_at: u64,
+    /// Unix timestamp (seconds) of the last message received from the peer
+    pub last_seen_at: u64,
+    /// Highest height for which the peer sent us a proposal part
+    pub last_seen_height: Option<u64>,
+    /// Proposal parts received from the peer since it joined our view
+    pub proposal_traffic: ProposalTraffic,
+}
+
+/// Counters of the proposal parts received from a peer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProposalTraffic {
+    pub parts_received: u64,
+    /// Data bytes carried by the parts
+    pub bytes_received: u64,
+    /// Complete proposals whose signature did not verify
+    pub invalid_signatures: u64,
+    pub completed_streams: u64,
+}
+
+impl PeerInfo {
+    fn new(peer_id: PeerId, direction: PeerDirection) -> Self {
+        let now = Utc::now().timestamp() as u64;
+        Self {
+            peer_id: peer_id.to_string(),
+            direction,
+            connected_at: now,
+            last_seen_at: now,
+            last_seen_height: None,
+            proposal_traffic: ProposalTraffic::default(),
+        }
+    }
+}
+
+/// `[p2p]` section of the node configuration: which peers the node talks to, for permissioned
+/// devnets and for banning a misbehaving peer.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PeerAccessConfig {
+    /// When not empty, only these peer ids are accepted
+    pub allowed_peers: Vec<String>,
+    /// Peer ids always rejected, even when allowed
+    pub denied_peers: Vec<String>,
+}
+
+impl PeerAccessConfig {
+    pub fn is_allowed(&self, peer_id: &PeerId) -> bool {
+        let peer_id = peer_id.to_string();
+        !self.denied_peers.contains(&peer_id)
+            && (self.allowed_peers.is_empty() || self.allowed_peers.contains(&peer_id))
+    }
+
+    /// Whether to dial `addr`, a persistent peer address. Only addresses ending with a
+    /// `/p2p/<peer id>` component can be filtered, the others are dialed.
+    pub fn is_dialable(&self, addr: &str) -> bool {
+        match addr.rsplit_once("/p2p/") {
+            Some((_, peer_id)) => {
+                !self.denied_peers.iter().any(|denied| denied == peer_id)
+                    && (self.allowed_peers.is_empty()
+                        || self.allowed_peers.iter().any(|allowed| allowed == peer_id))
+            }
+            None => true,
+        }
+    }
+}
+
+/// Label set of the per-peer metrics.
+type PeerLabels = Vec<(String, String)>;
+
+/// Per-peer proposal traffic counters, labelled by peer id.
+#[derive(Debug, Clone, Default)]
+struct PeerMetrics {
+    parts_received: Family<PeerLabels, Counter>,
+    bytes_received: Family<PeerLabels, Counter>,
+    invalid_signatures: Family<PeerLabels, Counter>,
+    completed_streams: Family<PeerLabels, Counter>,
+}
+
+fn peer_labels(peer_id: &PeerId) -> PeerLabels {
+    vec![("peer_id".to_string(), peer_id.to_string())]
+}
+
+/// Peers currently connected to the node, shared between the consensus loop and the RPC server.
+#[derive(Debug, Clone, Default)]
+pub struct PeerBook {
+    peers: Arc<Mutex<HashMap<PeerId, PeerInfo>>>,
+    metrics: PeerMetrics,
+    access: PeerAccessConfig,
+}
+
+impl PeerBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(registry: &SharedRegistry) -> Self {
+        let book = Self::new();
+
+        registry.with_prefix("app_channel", |registry| {
+            registry.register(
+                "peer_proposal_parts_received_total",
+                "Number of proposal parts received, per peer",
+                book.metrics.parts_received.clone(),
+            );
+
+            registry.register(
+                "peer_proposal_bytes_received_total",
+                "Data bytes of the proposal parts received, per peer",
+                book.metrics.bytes_received.clone(),
+            );
+
+            registry.register(
+                "peer_proposal_invalid_signatures_total",
+                "Number of complete proposals with an invalid signature, per peer",
+                book.metrics.invalid_signatures.clone(),
+            );
+
+            registry.register(
+                "peer_proposal_streams_completed_total",
+                "Number of complete proposal streams, per peer",
+                book.metrics.completed_streams.clone(),
+            );
+        });
+
+        book
+    }
+
+    /// Restricts the peers the node talks to, see [`PeerAccessConfig`].
+    pub fn with_access(mut self, access: PeerAccessConfig) -> Self {
+        self.access = access;
+        self
+    }
+
+    /// Whether messages from `peer_id` are handled at all.
+    pub fn is_allowed(&self, peer_id: &PeerId) -> bool {
+        self.access.is_allowed(peer_id)
+    }
+
+    pub fn insert(&self, peer_id: PeerId, direction: PeerDirection) {
+        self.peers
+            .lock()
+            .unwrap()
+            .entry(peer_id)
+            .or_insert_with(|| PeerInfo::new(peer_id, direction));
+    }
+
+    pub fn remove(&self, peer_id: &PeerId) {
+        self.peers.lock().unwrap().remove(peer_id);
+    }
+
+    pub fn contains(&self, peer_id: &PeerId) -> bool {
+        self.peers.lock().unwrap().contains_key(peer_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Records that `peer_id` just sent us something about `height`.
+    pub fn observe_height(&self, peer_id: PeerId, height: Height) {
+        let mut peers = self.peers.lock().unwrap();
+        let info = peers
+            .entry(peer_id)
+            .or_insert_with(|| PeerInfo::new(peer_id, PeerDirection::Unknown));
+
+        info.last_seen_at = Utc::now().timestamp() as u64;
+        info.last_seen_height = Some(
+            info.last_seen_height
+                .map_or(height.as_u64(), |h| h.max(height.as_u64())),
+        );
+    }
+
+    /// Records a proposal part carrying `bytes` data bytes received from `peer_id`.
+    pub fn record_proposal_part(&self, peer_id: PeerId, bytes: usize) {
+        let labels = peer_labels(&peer_id);
+        self.metrics.parts_received.get_or_create(&labels).inc();
+        self.metrics
+            .bytes_received
+            .get_or_create(&labels)
+            .inc_by(bytes as u64);
+
+        self.update_traffic(peer_id, |traffic| {
+            traffic.parts_received += 1;
+            traffic.bytes_received += bytes as u64;
+        });
+    }
+
+    /// Records that all the parts of a proposal from `peer_id` were received.
+    pub fn record_completed_stream(&self, peer_id: PeerId) {
+        let labels = peer_labels(&peer_id);
+        self.metrics.completed_streams.get_or_create(&labels).inc();
+        self.update_traffic(peer_id, |traffic| traffic.completed_streams += 1);
+    }
+
+    /// Records a complete proposal from `peer_id` whose signature did not verify.
+    pub fn record_invalid_signature(&self, peer_id: PeerId) {
+        let labels = peer_labels(&peer_id);
+        self.metrics.invalid_signatures.get_or_create(&labels).inc();
+        self.update_traffic(peer_id, |traffic| traffic.invalid_signatures += 1);
+    }
+
+    fn update_traffic(&self, peer_id: PeerId, update: impl FnOnce(&mut ProposalTraffic)) {
+        let mut peers = self.peers.lock().unwrap();
+        let info = peers
+            .entry(peer_id)
+            .or_insert_with(|| PeerInfo::new(peer_id, PeerDirection::Unknown));
+        update(&mut info.proposal_traffic);
+    }
+
+    pub fn peers(&self) -> Vec<PeerInfo> {
+        let mut peers: Vec<_> = self.peers.lock().unwrap().values().cloned().collect();
+        peers.sort_by(|a, b| a.peer_id.cmp(&b.peer_id));
+        peers
+    }
+}
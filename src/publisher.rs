@@ -0,0 +1,314 @@
+//! Chain event sink publishing committed blocks and included transactions to Kafka or NATS
+//! JetStream, for data pipelines.
+//!
+//! Delivery is at least once. Events only wake the sink up: it publishes the decided blocks of
+//! the store from the height after its offset, waits for the broker to acknowledge each message,
+//! then records the height as its offset in the store. Blocks whose events were dropped from the
+//! event queue are published with the next one, and a restarted node resumes after the last
+//! acknowledged block, publishing again the messages of a block interrupted halfway. A new stream
+//! starts with the first block committed once it is configured.
+//!
+//! Block events go to the `<topic_prefix>.blocks` topic keyed by height, transaction events to
+//! `<topic_prefix>.transactions` keyed by transaction hash, both as JSON. The Kafka and NATS
+//! clients are behind the `kafka` and `nats` features.
+
+use async_trait::async_trait;
+use tracing::{debug, info, warn};
+
+use crate::block::Block;
+use crate::error::EventError;
+use crate::events::{block_events, ChainEvent, EventSink};
+use crate::store::Store;
+use crate::types::codec::proto::ProtobufCodec;
+use crate::types::height::Height;
+use malachitebft_app_channel::app::types::codec::Codec;
+
+/// Topic prefix when the configuration does not set one.
+pub const DEFAULT_TOPIC_PREFIX: &str = "mikan";
+
+/// Client of a message broker.
+#[async_trait]
+pub trait Publisher: Send {
+    /// Publishes `payload` to `topic`, returning once the broker acknowledged it.
+    async fn publish(&mut self, topic: &str, key: &str, payload: Vec<u8>)
+        -> Result<(), EventError>;
+}
+
+/// Publishes the decided blocks of the store, see the module documentation.
+pub struct StreamSink {
+    /// Key of the offset in the store, from the broker and the topic prefix
+    name: String,
+    topic_prefix: String,
+    publisher: Box<dyn Publisher>,
+    store: Store,
+    /// Last height acknowledged by the broker
+    offset: Option<u64>,
+}
+
+impl StreamSink {
+    pub async fn open(
+        name: String,
+        topic_prefix: String,
+        publisher: Box<dyn Publisher>,
+        store: Store,
+    ) -> Result<Self, EventError> {
+        let offset = store.get_stream_offset(name.clone()).await?;
+        let mut sink = Self {
+            name,
+            topic_prefix,
+            publisher,
+            store,
+            offset,
+        };
+
+        // Blocks committed while the node was down
+        if let (Some(offset), Some(height)) = (offset, sink.store.max_decided_value_height().await)
+        {
+            info!(stream = %sink.name, offset, %height, "Resuming stream");
+            // Retried with the next committed block
+            if let Err(e) = sink.publish_up_to(height.as_u64()).await {
+                warn!(stream = %sink.name, error = %e, "Couldn't resume stream");
+            }
+        }
+
+        Ok(sink)
+    }
+
+    /// Publishes the blocks after the offset up to `height`, advancing the offset after each.
+    async fn publish_up_to(&mut self, height: u64) -> Result<(), EventError> {
+        let from_height = self.offset.map_or(height, |offset| offset + 1);
+        for height in from_height..=height {
+            self.publish_height(height).await?;
+            self.store
+                .store_stream_offset(self.name.clone(), height)
+                .await?;
+            self.offset = Some(height);
+        }
+        Ok(())
+    }
+
+    async fn publish_height(&mut self, height: u64) -> Result<(), EventError> {
+        let (Some(decided), Some(data)) = (
+            self.store.get_decided_value(Height::new(height)).await?,
+            self.store.get_decided_block(Height::new(height)).await?,
+        ) else {
+            warn!(stream = %self.name, height, "Block pruned before it was published, skipping");
+            return Ok(());
+        };
+
+        let block: Block = ProtobufCodec.decode(data)?;
+        let round = decided.certificate.round.as_u32().unwrap_or_default();
+        for event in block_events(height, round, &block) {
+            let (topic, key) = match &event {
+                ChainEvent::BlockCommitted { height, .. } => ("blocks", height.to_string()),
+                ChainEvent::TransactionIncluded { tx_hash, .. } => {
+                    ("transactions", hex::encode(tx_hash))
+                }
+                _ => continue,
+            };
+            let topic = format!("{}.{topic}", self.topic_prefix);
+            self.publisher
+                .publish(&topic, &key, serde_json::to_vec(&event)?)
+                .await?;
+        }
+
+        debug!(stream = %self.name, height, "Published block");
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventSink for StreamSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn handle(&mut self, event: &ChainEvent) -> Result<(), EventError> {
+        if let ChainEvent::BlockCommitted { height, .. } = event {
+            self.publish_up_to(*height).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaPublisher;
+
+#[cfg(feature = "kafka")]
+mod kafka {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use rdkafka::config::ClientConfig;
+    use rdkafka::producer::{FutureProducer, FutureRecord};
+
+    use super::Publisher;
+    use crate::error::EventError;
+
+    /// How long a message may wait for room in the producer queue.
+    const QUEUE_TIMEOUT: Duration = Duration::from_secs(30);
+
+    pub struct KafkaPublisher {
+        producer: FutureProducer,
+    }
+
+    impl KafkaPublisher {
+        pub fn new(brokers: &str) -> Result<Self, EventError> {
+            let producer = ClientConfig::new()
+                .set("bootstrap.servers", brokers)
+                // Acknowledged once replicated, and not duplicated by the client retries
+                .set("acks", "all")
+                .set("enable.idempotence", "true")
+                .create()
+                .map_err(|e| EventError::BrokerConnection(e.to_string()))?;
+            Ok(Self { producer })
+        }
+    }
+
+    #[async_trait]
+    impl Publisher for KafkaPublisher {
+        async fn publish(
+            &mut self,
+            topic: &str,
+            key: &str,
+            payload: Vec<u8>,
+        ) -> Result<(), EventError> {
+            let record = FutureRecord::to(topic).key(key).payload(&payload);
+            self.producer
+                .send(record, QUEUE_TIMEOUT)
+                .await
+                .map_err(|(e, _)| EventError::Publish {
+                    topic: topic.to_string(),
+                    error: e.to_string(),
+                })?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "nats")]
+pub use nats::NatsPublisher;
+
+#[cfg(feature = "nats")]
+mod nats {
+    use async_nats::jetstream;
+    use async_trait::async_trait;
+
+    use super::Publisher;
+    use crate::error::EventError;
+
+    /// Publishes to JetStream, whose streams must cover the topics.
+    pub struct NatsPublisher {
+        context: jetstream::Context,
+    }
+
+    impl NatsPublisher {
+        pub async fn connect(url: &str) -> Result<Self, EventError> {
+            let client = async_nats::connect(url)
+                .await
+                .map_err(|e| EventError::BrokerConnection(e.to_string()))?;
+            Ok(Self {
+                context: jetstream::new(client),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl Publisher for NatsPublisher {
+        async fn publish(
+            &mut self,
+            topic: &str,
+            _key: &str,
+            payload: Vec<u8>,
+        ) -> Result<(), EventError> {
+            let publish_error = |error: String| EventError::Publish {
+                topic: topic.to_string(),
+                error,
+            };
+            self.context
+                .publish(topic.to_string(), payload.into())
+                .await
+                .map_err(|e| publish_error(e.to_string()))?
+                .await
+                .map_err(|e| publish_error(e.to_string()))?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use crate::compression::CompressionConfig;
+    use crate::metrics::DbMetrics;
+    use crate::store::StorageConfig;
+
+    /// Topics and keys of the published messages.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        published: Arc<Mutex<Vec<(String, String)>>>,
+    }
+
+    #[async_trait]
+    impl Publisher for Recorder {
+        async fn publish(
+            &mut self,
+            topic: &str,
+            key: &str,
+            _payload: Vec<u8>,
+        ) -> Result<(), EventError> {
+            let mut published = self.published.lock().unwrap();
+            published.push((topic.to_string(), key.to_string()));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn resumes_after_the_last_acknowledged_block() {
+        let dir = std::env::temp_dir().join(format!("mikan-publisher-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = Store::open(
+            dir.join("store.db"),
+            DbMetrics::new(),
+            &CompressionConfig::default(),
+            &StorageConfig::default(),
+        )
+        .unwrap();
+
+        // Nothing decided yet, the stream starts at the next block
+        let recorder = Recorder::default();
+        let mut sink = StreamSink::open(
+            "test".to_string(),
+            DEFAULT_TOPIC_PREFIX.to_string(),
+            Box::new(recorder.clone()),
+            store.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(sink.offset, None);
+
+        // Heights without block data are skipped rather than blocking the stream
+        sink.publish_up_to(3).await.unwrap();
+        assert_eq!(sink.offset, Some(3));
+        assert_eq!(
+            store.get_stream_offset("test".to_string()).await.unwrap(),
+            Some(3)
+        );
+        assert!(recorder.published.lock().unwrap().is_empty());
+
+        // Reopened after a restart
+        let sink = StreamSink::open(
+            "test".to_string(),
+            DEFAULT_TOPIC_PREFIX.to_string(),
+            Box::new(Recorder::default()),
+            store,
+        )
+        .await
+        .unwrap();
+        assert_eq!(sink.offset, Some(3));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,254 @@
+//! Relays data roots to a Starknet contract, so that rollups settling on Starknet can check that
+//! their data was made available on Mikan.
+//!
+//! Decided heights are grouped in batches of `batch_size`. The data root of each height
+//! ([`Block::da_root`]) is a leaf of a Merkle tree, whose root is submitted with the height range
+//! by calling `entrypoint(from_height, to_height, root)` on the contract, `root` being a `u256`.
+//! A rollup proves its blob was posted with [`DataRootBatch::proof`] from the batch root to the
+//! data root of its height, and [`Block::proof_of_blob`] from there to the blob commitment.
+//!
+//! Submitting the batches requires the `relayer` feature, building them and their proofs does
+//! not.
+
+use malachitebft_proto::Protobuf;
+use rs_merkle::{algorithms::Sha256, MerkleProof, MerkleTree};
+use serde::{Deserialize, Serialize};
+
+use crate::block::Block;
+use crate::error::RelayerError;
+use crate::store::Store;
+use crate::types::height::Height;
+
+/// `[relayer]` section of the node configuration, disabled by default.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelayerConfig {
+    pub enabled: bool,
+    /// Starknet JSON-RPC endpoint
+    pub rpc_url: String,
+    /// Contract receiving the data roots, hex encoded
+    pub contract_address: String,
+    /// Function of the contract called with each batch
+    pub entrypoint: String,
+    /// Account paying for the transactions, hex encoded
+    pub account_address: String,
+    /// Environment variable holding the private key of the account, which is never read from
+    /// the configuration file
+    pub private_key_env: String,
+    /// Number of heights per batch
+    pub batch_size: u64,
+    /// First height to relay
+    pub start_height: u64,
+    /// Delay in milliseconds between two checks for a complete batch
+    pub poll_interval_ms: u64,
+}
+
+impl Default for RelayerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rpc_url: "http://127.0.0.1:5050".to_string(),
+            contract_address: String::new(),
+            entrypoint: "submit_data_roots".to_string(),
+            account_address: String::new(),
+            private_key_env: "MIKAN_RELAYER_PRIVATE_KEY".to_string(),
+            batch_size: 16,
+            start_height: 1,
+            poll_interval_ms: 5000,
+        }
+    }
+}
+
+/// Data roots of consecutive heights, starting at `from`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DataRootBatch {
+    pub from: u64,
+    pub data_roots: Vec<[u8; 32]>,
+}
+
+impl DataRootBatch {
+    /// Last height of the batch.
+    pub fn to(&self) -> u64 {
+        self.from + self.data_roots.len() as u64 - 1
+    }
+
+    /// Merkle root of the data roots, `None` for an empty batch.
+    pub fn root(&self) -> Option<[u8; 32]> {
+        MerkleTree::<Sha256>::from_leaves(&self.data_roots).root()
+    }
+
+    /// Proof that the data root of `height` is committed to by [`Self::root`].
+    pub fn proof(&self, height: u64) -> Option<MerkleProof<Sha256>> {
+        let index = usize::try_from(height.checked_sub(self.from)?).ok()?;
+        if index >= self.data_roots.len() {
+            return None;
+        }
+
+        Some(MerkleTree::<Sha256>::from_leaves(&self.data_roots).proof(&[index]))
+    }
+}
+
+/// Reads the data roots of heights `from..=to` from the store.
+pub async fn load_batch(store: &Store, from: u64, to: u64) -> Result<DataRootBatch, RelayerError> {
+    let mut data_roots = Vec::new();
+    for height in from..=to {
+        let bytes = store
+            .get_decided_block(Height::new(height))
+            .await?
+            .ok_or(RelayerError::MissingBlock(height))?;
+        let block = <Block as Protobuf>::from_bytes(&bytes)?;
+        data_roots.push(block.da_root());
+    }
+
+    Ok(DataRootBatch { from, data_roots })
+}
+
+#[cfg(feature = "relayer")]
+pub use starknet_client::run;
+
+#[cfg(feature = "relayer")]
+mod starknet_client {
+    use std::time::Duration;
+
+    use starknet::accounts::{Account, ExecutionEncoding, SingleOwnerAccount};
+    use starknet::core::types::{Call, Felt};
+    use starknet::core::utils::get_selector_from_name;
+    use starknet::providers::jsonrpc::{HttpTransport, JsonRpcClient};
+    use starknet::providers::{Provider, Url};
+    use starknet::signers::{LocalWallet, SigningKey};
+    use tracing::{info, warn};
+
+    use super::{load_batch, DataRootBatch, RelayerConfig};
+    use crate::error::RelayerError;
+    use crate::store::Store;
+
+    impl DataRootBatch {
+        /// Calldata of `entrypoint(from_height, to_height, root)`, with `root` split in the low and
+        /// high 128 bits of a Cairo `u256`.
+        pub(super) fn calldata(&self) -> Result<Vec<Felt>, RelayerError> {
+            let root = self.root().ok_or(RelayerError::EmptyBatch)?;
+            let (high, low) = root.split_at(16);
+            let limb =
+                |half: &[u8]| u128::from_be_bytes(half.try_into().expect("half of 32 bytes"));
+
+            Ok(vec![
+                Felt::from(self.from),
+                Felt::from(self.to()),
+                Felt::from(limb(low)),
+                Felt::from(limb(high)),
+            ])
+        }
+    }
+
+    /// Submits a batch each time `batch_size` new heights are decided. Runs until the configuration
+    /// is found invalid, failed submissions are retried at the next poll.
+    pub async fn run(config: RelayerConfig, store: Store) -> Result<(), RelayerError> {
+        let private_key = std::env::var(&config.private_key_env)
+            .map_err(|_| RelayerError::PrivateKeyNotSet(config.private_key_env.clone()))?;
+
+        let rpc_url = Url::parse(&config.rpc_url).map_err(|e| invalid("rpc_url", e))?;
+        let provider = JsonRpcClient::new(HttpTransport::new(rpc_url));
+        let chain_id = provider.chain_id().await?;
+        let private_key = Felt::from_hex(&private_key).map_err(|e| invalid("private key", e))?;
+        let signer = LocalWallet::from(SigningKey::from_secret_scalar(private_key));
+        let account_address =
+            Felt::from_hex(&config.account_address).map_err(|e| invalid("account_address", e))?;
+        let account = SingleOwnerAccount::new(
+            provider,
+            signer,
+            account_address,
+            chain_id,
+            ExecutionEncoding::New,
+        );
+
+        let contract_address =
+            Felt::from_hex(&config.contract_address).map_err(|e| invalid("contract_address", e))?;
+        let selector =
+            get_selector_from_name(&config.entrypoint).map_err(|e| invalid("entrypoint", e))?;
+        let poll_interval = Duration::from_millis(config.poll_interval_ms);
+        let batch_size = config.batch_size.max(1);
+
+        info!(contract = %config.contract_address, batch_size, "Starting data root relayer");
+
+        let mut next_height = config.start_height;
+        loop {
+            let latest = store
+                .max_decided_value_height()
+                .await
+                .map_or(0, |h| h.as_u64());
+
+            while latest >= next_height + batch_size - 1 {
+                let batch = load_batch(&store, next_height, next_height + batch_size - 1).await?;
+                let call = Call {
+                    to: contract_address,
+                    selector,
+                    calldata: batch.calldata()?,
+                };
+
+                match account.execute_v3(vec![call]).send().await {
+                    Ok(result) => {
+                        info!(
+                            from = batch.from,
+                            to = batch.to(),
+                            tx_hash = %format!("{:#x}", result.transaction_hash),
+                            "Relayed data roots"
+                        );
+                        next_height = batch.to() + 1;
+                    }
+                    Err(e) => {
+                        warn!(from = batch.from, to = batch.to(), %e, "Failed to relay data roots");
+                        break;
+                    }
+                }
+            }
+
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+
+    /// Error for a value of the `[relayer]` section that does not parse.
+    fn invalid(field: &'static str, error: impl std::fmt::Display) -> RelayerError {
+        RelayerError::InvalidConfig {
+            field,
+            error: error.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proves_data_roots_of_the_batch() {
+        let batch = DataRootBatch {
+            from: 10,
+            data_roots: vec![[1; 32], [2; 32], [3; 32]],
+        };
+        let root = batch.root().unwrap();
+
+        assert_eq!(batch.to(), 12);
+        let proof = batch.proof(11).unwrap();
+        assert!(proof.verify(root, &[1], &[[2; 32]], 3));
+        assert!(batch.proof(9).is_none());
+        assert!(batch.proof(13).is_none());
+    }
+
+    #[cfg(feature = "relayer")]
+    #[test]
+    fn splits_root_in_u256_limbs() {
+        use starknet::core::types::Felt;
+
+        let batch = DataRootBatch {
+            from: 1,
+            data_roots: vec![[7; 32]],
+        };
+        let calldata = batch.calldata().unwrap();
+
+        assert_eq!(calldata.len(), 4);
+        assert_eq!(calldata[0], Felt::from(1u64));
+        assert_eq!(calldata[1], Felt::from(1u64));
+        assert_eq!(calldata[2], Felt::from(u128::from_be_bytes([7; 16])));
+        assert_eq!(calldata[3], Felt::from(u128::from_be_bytes([7; 16])));
+    }
+}
@@ -0,0 +1,195 @@
+//! Retrieval of pruned blocks from peers, so that a node keeping a short history can still serve
+//! `mikan_getBlob` for older heights.
+//!
+//! The consensus network only carries consensus messages and proposal parts, so blocks are
+//! fetched from the RPC servers of the `peers` listed in the `[retrieval]` section, in order,
+//! with `mikan_getFinalityProof`. The proof carries the encoded block whose id the validators
+//! signed: the block is only served once the proof checks against the validator sets the node
+//! knows for that height, so a peer cannot make the node serve data that was not decided.
+//! Retrieved blocks are not stored, the node keeps its retention.
+
+use std::time::Duration;
+
+use malachitebft_proto::Protobuf;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::block::Block;
+use crate::client::{ClientError, MikanClient};
+use crate::error::FinalityError;
+use crate::finality_params::FinalityProof;
+
+/// `[retrieval]` section of the node configuration, disabled by default.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetrievalConfig {
+    pub enabled: bool,
+    /// RPC URLs of the peers to fetch pruned blocks from, tried in order
+    pub peers: Vec<String>,
+    /// A peer not answering within this many milliseconds is skipped
+    pub timeout_ms: u64,
+}
+
+impl Default for RetrievalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            peers: Vec::new(),
+            timeout_ms: 5_000,
+        }
+    }
+}
+
+/// Clients of the peers pruned blocks are fetched from.
+#[derive(Debug, Clone)]
+pub struct BlockRetrieval {
+    chain_id: String,
+    peers: Vec<(String, MikanClient)>,
+    timeout: Duration,
+}
+
+impl BlockRetrieval {
+    /// `None` if retrieval is disabled or no peer is listed.
+    pub fn new(config: &RetrievalConfig, chain_id: &str) -> Result<Option<Self>, ClientError> {
+        if !config.enabled || config.peers.is_empty() {
+            return Ok(None);
+        }
+        let peers = config
+            .peers
+            .iter()
+            .map(|url| Ok((url.clone(), MikanClient::new(url)?)))
+            .collect::<Result<_, ClientError>>()?;
+
+        Ok(Some(Self {
+            chain_id: chain_id.to_string(),
+            peers,
+            timeout: Duration::from_millis(config.timeout_ms),
+        }))
+    }
+
+    /// Block decided at `height`, from the first peer proving it, with the URL of that peer.
+    /// `validator_set_hashes` are the hashes of the validator sets from genesis to `height`.
+    pub async fn fetch(
+        &self,
+        height: u64,
+        validator_set_hashes: &[[u8; 32]],
+    ) -> Option<(Block, String)> {
+        for (url, client) in &self.peers {
+            let proof =
+                match tokio::time::timeout(self.timeout, client.get_finality_proof(height)).await {
+                    Ok(Ok(proof)) => proof,
+                    Ok(Err(e)) => {
+                        debug!(%url, height, error = %e, "Peer cannot serve pruned block");
+                        continue;
+                    }
+                    Err(_) => {
+                        warn!(%url, height, "Peer timed out serving pruned block");
+                        continue;
+                    }
+                };
+
+            match verified_block(&proof, &self.chain_id, height, validator_set_hashes) {
+                Ok(block) => return Some((block, url.clone())),
+                Err(e) => warn!(%url, height, error = %e, "Peer served an unproven block"),
+            }
+        }
+
+        None
+    }
+}
+
+/// Block of `proof`, once checked to be decided at `height` by the validator sets of
+/// `validator_set_hashes`.
+pub fn verified_block(
+    proof: &FinalityProof,
+    chain_id: &str,
+    height: u64,
+    validator_set_hashes: &[[u8; 32]],
+) -> Result<Block, FinalityError> {
+    if proof.height != height {
+        return Err(FinalityError::HeightMismatch {
+            expected: height,
+            actual: proof.height,
+        });
+    }
+    let trusted = validator_set_hashes
+        .first()
+        .ok_or(FinalityError::ValidatorSetMismatch)?;
+    if proof.validator_set_hashes != validator_set_hashes {
+        return Err(FinalityError::ValidatorSetMismatch);
+    }
+    proof.verify(chain_id, *trusted)?;
+
+    Block::from_bytes(&proof.block).map_err(|_| FinalityError::HeaderMismatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::address::Address;
+    use crate::types::context::TestContext;
+    use crate::types::height::Height;
+    use crate::types::sign_bytes::DEFAULT_CHAIN_ID;
+    use crate::types::signing::{Ed25519Provider, PrivateKey};
+    use crate::types::validator_set::{Validator, ValidatorSet};
+    use crate::types::value::Value;
+    use crate::types::vote::Vote;
+    use malachitebft_core_types::{
+        AggregatedSignature, CommitCertificate, CommitSignature, NilOrVal, Round, SigningProvider,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn proof(signer: &Ed25519Provider, validator_set: &ValidatorSet) -> FinalityProof {
+        let block = Block::new(1, 0, Block::genesis().hash(), Address::default(), vec![]);
+        let data = block.to_bytes().unwrap();
+        let (height, round) = (Height::new(1), Round::new(0));
+        let value_id = Value::new(data.clone()).id();
+        let address = Address::from_public_key(&signer.private_key().public_key());
+        let vote = Vote::new_precommit(height, round, NilOrVal::Val(value_id), address);
+        let certificate = CommitCertificate::<TestContext> {
+            height,
+            round,
+            value_id,
+            aggregated_signature: AggregatedSignature {
+                signatures: vec![CommitSignature {
+                    address,
+                    signature: signer.sign_vote(vote).signature,
+                }],
+            },
+        };
+
+        FinalityProof::new(certificate, validator_set.clone(), data).unwrap()
+    }
+
+    #[test]
+    fn serves_only_proven_blocks() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let signer = Ed25519Provider::new(PrivateKey::generate(&mut rng), DEFAULT_CHAIN_ID);
+        let validator_set =
+            ValidatorSet::new([Validator::new(signer.private_key().public_key(), 1)]);
+        let hashes = [validator_set.hash()];
+        let proof = proof(&signer, &validator_set);
+
+        let block = verified_block(&proof, DEFAULT_CHAIN_ID, 1, &hashes).unwrap();
+        assert_eq!(block.header().block_number, 1);
+
+        assert!(matches!(
+            verified_block(&proof, DEFAULT_CHAIN_ID, 2, &hashes),
+            Err(FinalityError::HeightMismatch { .. })
+        ));
+        assert_eq!(
+            verified_block(&proof, DEFAULT_CHAIN_ID, 1, &[[0; 32]]).err(),
+            Some(FinalityError::ValidatorSetMismatch)
+        );
+
+        let other = Ed25519Provider::new(PrivateKey::generate(&mut rng), DEFAULT_CHAIN_ID);
+        let forged = proof(
+            &other,
+            &ValidatorSet::new([Validator::new(other.private_key().public_key(), 1)]),
+        );
+        assert_eq!(
+            verified_block(&forged, DEFAULT_CHAIN_ID, 1, &hashes).err(),
+            Some(FinalityError::ValidatorSetMismatch)
+        );
+    }
+}
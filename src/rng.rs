@@ -0,0 +1,39 @@
+//! Seedable randomness for test fixtures and the load generator, so that a failing run can be
+//! replayed with the same blobs, keys and transactions.
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Environment variable holding the seed, e.g. `MIKAN_SEED=42 cargo test`.
+pub const SEED_ENV: &str = "MIKAN_SEED";
+
+/// Returns the seed from [`SEED_ENV`], or a fresh random one if it is unset or invalid.
+pub fn seed_from_env() -> u64 {
+    std::env::var(SEED_ENV)
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(rand::random)
+}
+
+/// RNG seeded from [`SEED_ENV`]. The seed is printed so that the run can be replayed.
+pub fn seeded_rng() -> StdRng {
+    let seed = seed_from_env();
+    eprintln!("Using random seed {SEED_ENV}={seed}");
+    StdRng::seed_from_u64(seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transactions::Transaction;
+
+    #[test]
+    fn same_seed_same_fixtures() {
+        let first = Transaction::random_with_rng(&mut StdRng::seed_from_u64(7));
+        let second = Transaction::random_with_rng(&mut StdRng::seed_from_u64(7));
+        assert_eq!(first, second);
+
+        let other = Transaction::random_with_rng(&mut StdRng::seed_from_u64(8));
+        assert_ne!(first, other);
+    }
+}
@@ -0,0 +1,41 @@
+//! What a node process does, from the `[node]` section of the configuration.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeRole {
+    /// Runs consensus and signs votes and proposals with its private key
+    #[default]
+    Validator,
+    /// Follows consensus and serves data over RPC, without a key: an ephemeral one is generated
+    /// at startup for the network identity, which is never in the validator set
+    Full,
+    /// Does not run consensus: tracks headers and samples blobs through the RPC server of
+    /// another node
+    Sampling,
+}
+
+/// `[node]` section of the node configuration.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NodeRoleConfig {
+    pub role: NodeRole,
+    /// RPC endpoint of the node a sampling node follows
+    pub rpc_url: String,
+    /// Sampling proofs requested per blob by a sampling node
+    pub samples_per_blob: usize,
+    /// Delay in milliseconds between two polls of the latest height by a sampling node
+    pub poll_interval_ms: u64,
+}
+
+impl Default for NodeRoleConfig {
+    fn default() -> Self {
+        Self {
+            role: NodeRole::Validator,
+            rpc_url: "http://127.0.0.1:8545".to_string(),
+            samples_per_blob: 1,
+            poll_interval_ms: 1000,
+        }
+    }
+}
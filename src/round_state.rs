@@ -0,0 +1,248 @@
+//! Snapshot of the round consensus is in, for `mikan_getConsensusState`, so that operators can
+//! tell where the chain stalls mid-round.
+//!
+//! The snapshot is fed from the messages `app::run` receives: the round consensus started and its
+//! proposer, the proposals we built or assembled from their parts, and the votes the application
+//! sees. The channel API hands prevotes and the precommits of other validators to consensus
+//! without going through the application, so the votes of a round are only our own precommit;
+//! the precommits of other validators show up in `last_commit` once a height is decided.
+
+use std::sync::{Arc, Mutex};
+
+use malachitebft_app_channel::app::types::core::{CommitCertificate, Round, Validity};
+use malachitebft_app_channel::app::types::ProposedValue;
+use serde::{Deserialize, Serialize};
+
+use crate::types::address::Address;
+use crate::types::context::TestContext;
+use crate::types::height::Height;
+use crate::types::validator_set::ValidatorSet;
+use crate::types::value::ValueId;
+
+/// A proposal known for the current round.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoundProposal {
+    pub proposer: Address,
+    pub value_id: ValueId,
+    pub valid: bool,
+    /// Whether we built it
+    pub local: bool,
+}
+
+/// Votes of the current round for a value, as seen by the application.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VoteTally {
+    pub value_id: ValueId,
+    pub voters: Vec<Address>,
+    pub voting_power: u64,
+}
+
+/// Precommits of the certificate of the last decided height.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitTally {
+    pub height: u64,
+    pub round: u32,
+    pub value_id: ValueId,
+    pub signature_count: usize,
+    pub signed_voting_power: u64,
+    pub total_voting_power: u64,
+}
+
+/// Returned by `mikan_getConsensusState`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsensusState {
+    pub height: u64,
+    /// `None` until consensus starts a round at `height`
+    pub round: Option<u32>,
+    pub proposer: Option<Address>,
+    pub proposals: Vec<RoundProposal>,
+    pub precommits: Vec<VoteTally>,
+    pub total_voting_power: u64,
+    pub last_commit: Option<CommitTally>,
+}
+
+/// Current round state, shared between the consensus loop and the RPC server.
+#[derive(Debug, Clone)]
+pub struct RoundState {
+    /// Our address, to tell our proposals apart
+    address: Address,
+    state: Arc<Mutex<ConsensusState>>,
+}
+
+impl RoundState {
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            state: Arc::default(),
+        }
+    }
+
+    /// Consensus started `round` at `height`, forgetting the proposals and votes of the previous
+    /// one.
+    pub fn started_round(
+        &self,
+        height: Height,
+        round: Round,
+        proposer: Address,
+        validator_set: &ValidatorSet,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        state.height = height.as_u64();
+        state.round = round.as_u32();
+        state.proposer = Some(proposer);
+        state.proposals.clear();
+        state.precommits.clear();
+        state.total_voting_power = validator_set.total_voting_power();
+    }
+
+    /// Records a proposal, ignored unless it is for the current round.
+    pub fn proposal(&self, proposal: &ProposedValue<TestContext>) {
+        let mut state = self.state.lock().unwrap();
+        if !state.is_current(proposal.height, proposal.round) {
+            return;
+        }
+
+        let value_id = proposal.value.id();
+        if state
+            .proposals
+            .iter()
+            .any(|known| known.proposer == proposal.proposer && known.value_id == value_id)
+        {
+            return;
+        }
+        state.proposals.push(RoundProposal {
+            proposer: proposal.proposer,
+            value_id,
+            valid: proposal.validity == Validity::Valid,
+            local: proposal.proposer == self.address,
+        });
+    }
+
+    /// Records a precommit of `voter` for `value_id`, ignored unless it is for the current round.
+    pub fn precommit(
+        &self,
+        height: Height,
+        round: Round,
+        value_id: ValueId,
+        voter: Address,
+        voting_power: u64,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        if !state.is_current(height, round) {
+            return;
+        }
+
+        let index = match state
+            .precommits
+            .iter()
+            .position(|tally| tally.value_id == value_id)
+        {
+            Some(index) => index,
+            None => {
+                state.precommits.push(VoteTally {
+                    value_id,
+                    voters: Vec::new(),
+                    voting_power: 0,
+                });
+                state.precommits.len() - 1
+            }
+        };
+        let tally = &mut state.precommits[index];
+        if !tally.voters.contains(&voter) {
+            tally.voters.push(voter);
+            tally.voting_power += voting_power;
+        }
+    }
+
+    /// Consensus decided a value, with the precommits of `certificate`.
+    pub fn decided(
+        &self,
+        certificate: &CommitCertificate<TestContext>,
+        validator_set: &ValidatorSet,
+    ) {
+        let signatures = &certificate.aggregated_signature.signatures;
+        let signed_voting_power = signatures
+            .iter()
+            .filter_map(|sig| validator_set.get_by_address(&sig.address))
+            .map(|validator| validator.voting_power)
+            .sum();
+
+        self.state.lock().unwrap().last_commit = Some(CommitTally {
+            height: certificate.height.as_u64(),
+            round: certificate.round.as_u32().unwrap_or_default(),
+            value_id: certificate.value_id,
+            signature_count: signatures.len(),
+            signed_voting_power,
+            total_voting_power: validator_set.total_voting_power(),
+        });
+    }
+
+    pub fn snapshot(&self) -> ConsensusState {
+        self.state.lock().unwrap().clone()
+    }
+}
+
+impl ConsensusState {
+    fn is_current(&self, height: Height, round: Round) -> bool {
+        self.height == height.as_u64() && self.round.is_some() && self.round == round.as_u32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::signing::PrivateKey;
+    use crate::types::validator_set::Validator;
+    use crate::types::value::Value;
+    use bytes::Bytes;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn validators(count: usize) -> (Vec<Address>, ValidatorSet) {
+        let mut rng = StdRng::seed_from_u64(0x42);
+        let validators: Vec<Validator> = (0..count)
+            .map(|_| Validator::new(PrivateKey::generate(&mut rng).public_key(), 1))
+            .collect();
+        let addresses = validators.iter().map(|v| v.address).collect();
+        (addresses, ValidatorSet::new(validators))
+    }
+
+    fn proposal(height: u64, round: u32, proposer: Address) -> ProposedValue<TestContext> {
+        ProposedValue {
+            height: Height::new(height),
+            round: Round::new(round),
+            valid_round: Round::Nil,
+            proposer,
+            value: Value::new(Bytes::from_static(b"block")),
+            validity: Validity::Valid,
+        }
+    }
+
+    #[test]
+    fn tracks_the_current_round() {
+        let (addresses, validator_set) = validators(3);
+        let rounds = RoundState::new(addresses[0]);
+        assert_eq!(rounds.snapshot().round, None);
+
+        rounds.started_round(Height::new(5), Round::new(0), addresses[0], &validator_set);
+        rounds.proposal(&proposal(5, 0, addresses[0]));
+        rounds.proposal(&proposal(5, 0, addresses[0]));
+        rounds.proposal(&proposal(5, 1, addresses[1]));
+        rounds.proposal(&proposal(4, 0, addresses[2]));
+
+        let value_id = proposal(5, 0, addresses[0]).value.id();
+        rounds.precommit(Height::new(5), Round::new(0), value_id, addresses[1], 1);
+        rounds.precommit(Height::new(5), Round::new(0), value_id, addresses[1], 1);
+
+        let state = rounds.snapshot();
+        assert_eq!((state.height, state.round), (5, Some(0)));
+        assert_eq!(state.proposer, Some(addresses[0]));
+        assert_eq!(state.proposals.len(), 1);
+        assert!(state.proposals[0].local);
+        assert_eq!(state.precommits[0].voting_power, 1);
+        assert_eq!(state.total_voting_power, 3);
+
+        rounds.started_round(Height::new(5), Round::new(1), addresses[1], &validator_set);
+        let state = rounds.snapshot();
+        assert!(state.proposals.is_empty() && state.precommits.is_empty());
+    }
+}
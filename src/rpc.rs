@@ -1,35 +1,114 @@
 use async_trait::async_trait;
 use frieda::proof::{FriConfig, PcsConfig, Proof};
-use jsonrpsee::core::RpcResult;
+use jsonrpsee::core::{RpcResult, SubscriptionResult};
 use jsonrpsee::proc_macros::rpc;
 use jsonrpsee::server::{ServerBuilder, ServerHandle};
-use jsonrpsee::types::error::INTERNAL_ERROR_CODE;
-use jsonrpsee::types::ErrorObject;
+use jsonrpsee::{PendingSubscriptionSink, SubscriptionMessage};
+use malachitebft_app_channel::app::types::codec::Codec;
+use malachitebft_app_channel::app::types::core::CommitCertificate;
 use serde::{Deserialize, Serialize};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use tracing::info;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
 
 use crate::blob::Blob;
+use crate::block::Block;
+use crate::consensus_params::ConsensusParams;
+use crate::error::{MikanRpcError, Result, TransactionError};
+use crate::events::{FinalityFeed, Finalized};
+use crate::evidence::{Evidence, EvidenceRecord, EvidenceSource};
+use crate::finality_params::{proof_of_vote, vote_tree_root, FinalityProof};
+use crate::forks::{Divergence, ForkMonitor};
+use crate::gas::GasSchedule;
+use crate::governance::{ChainParams, ParamChange};
+use crate::header::Header;
+use crate::liveness::Jailing;
+use crate::namespaces::NamespacePolicy;
+use crate::peers::{PeerBook, PeerInfo};
+use crate::retrieval::BlockRetrieval;
+use crate::round_state::{ConsensusState, RoundState};
+use crate::sample_queue::{
+    current_connection, ConnectionLayer, InFlight, Rejection, SampleQueue, SamplingConfig,
+};
+use crate::sampling::SeedDerivation;
+use crate::shares::{prove_shares, SHARES_VERSION, SHARE_SIZE};
+use crate::shutdown::Shutdown;
 use crate::store::Store;
 use crate::transactions::{pool::TransactionPool, Transaction};
+use crate::types::address::Address;
+use crate::types::certificate;
+use crate::types::codec::proto::ProtobufCodec;
+use crate::types::codec::ssz::{HeaderEncoding, SszCodec};
+use crate::types::context::TestContext;
+use crate::types::genesis::Genesis;
+use crate::types::height::Height;
+use crate::types::serde_hex;
+use crate::types::value::ValueId;
+use crate::upgrades::{Feature, Upgrade, PROTOCOL_VERSION};
+use crate::uploads::BlobUploads;
 use frieda::api::generate_proof;
 use malachitebft_test::{PublicKey, Signature};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct RpcTransaction {
     pub from: PublicKey,
     pub to: PublicKey,
+    #[serde(with = "serde_hex::signature")]
     pub signature: Signature,
     pub value: u64,
     pub nonce: u64,
     pub gas_price: u64,
-    pub data: [Blob; 4],
+    pub gas_limit: u64,
+    /// At most the `max_blobs_per_tx` of the genesis file
+    #[serde(default)]
+    pub data: Vec<Blob>,
+    /// Registers `to` as a namespace owned by `from`, see [`crate::namespaces`]
+    #[serde(default)]
+    pub registration: Option<NamespacePolicy>,
+    /// Vote of `from`, a validator, for a parameter change, see [`crate::governance`]
+    #[serde(default)]
+    pub governance: Option<ParamChange>,
+    /// Returns `from`, a jailed validator, to the validator set, see [`crate::liveness`]
+    #[serde(default)]
+    pub unjail: bool,
+}
+
+/// Room in a request body for everything but the hex-encoded blobs of a transaction.
+const REQUEST_OVERHEAD_BYTES: usize = 64 * 1024;
+
+/// Largest request body accepted by the server: a transaction of `max_tx_bytes`, hex encoded.
+/// Larger payloads are rejected before being parsed.
+fn max_request_body_size(max_tx_bytes: usize) -> u32 {
+    max_tx_bytes
+        .saturating_mul(2)
+        .saturating_add(REQUEST_OVERHEAD_BYTES)
+        .try_into()
+        .unwrap_or(u32::MAX)
 }
 
 impl RpcTransaction {
     pub fn random() -> Self {
         Transaction::random().into()
     }
+
+    /// Checks the signature against `chain_id` and builds the transaction.
+    pub fn into_transaction(self, chain_id: &str) -> Result<Transaction, TransactionError> {
+        Transaction::new(
+            chain_id,
+            self.from,
+            self.to,
+            self.signature,
+            self.value,
+            self.data,
+            self.nonce,
+            self.gas_price,
+            self.gas_limit,
+            self.registration,
+            self.governance,
+            self.unjail,
+        )
+    }
 }
 
 impl From<Transaction> for RpcTransaction {
@@ -41,217 +120,657 @@ impl From<Transaction> for RpcTransaction {
             value: tx.value(),
             nonce: tx.nonce(),
             gas_price: tx.gas_price(),
-            data: tx.data().clone(),
+            gas_limit: tx.gas_limit(),
+            data: tx.data().to_vec(),
+            registration: tx.registration().cloned(),
+            governance: tx.governance().cloned(),
+            unjail: tx.unjail(),
         }
     }
 }
 
-impl Serialize for RpcTransaction {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        use serde::ser::SerializeStruct;
-
-        let mut state = serializer.serialize_struct("RpcTransaction", 7)?;
-        state.serialize_field("from", &self.from)?;
-        state.serialize_field("to", &self.to)?;
-        state.serialize_field("signature", &hex::encode(self.signature.to_bytes()))?;
-        state.serialize_field("value", &self.value)?;
-        state.serialize_field("nonce", &self.nonce)?;
-        state.serialize_field("gas_price", &self.gas_price)?;
-
-        // Serialize blobs as an array of hex strings
-        let blob_hexes: Vec<String> = self
-            .data
+/// A transaction whose blobs were uploaded beforehand with `mikan_beginBlobUpload` and
+/// `mikan_uploadBlobChunk`, referenced by upload id.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadedTransaction {
+    pub from: PublicKey,
+    pub to: PublicKey,
+    #[serde(with = "serde_hex::signature")]
+    pub signature: Signature,
+    pub value: u64,
+    pub nonce: u64,
+    pub gas_price: u64,
+    pub gas_limit: u64,
+    /// One upload per blob
+    pub uploads: Vec<u64>,
+}
+
+/// Parameters of the FRIEDA proofs returned by `mikan_sampleBlob`
+pub fn sampling_pcs_config() -> PcsConfig {
+    PcsConfig {
+        pow_bits: 20,
+        fri_config: FriConfig {
+            log_blowup_factor: 4,
+            log_last_layer_degree_bound: 0,
+            n_queries: 20,
+        },
+    }
+}
+
+/// Chain identity and range of heights whose blocks can be retrieved from the node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainInfo {
+    pub chain_id: String,
+    #[serde(with = "hex::serde")]
+    pub genesis_hash: [u8; 32],
+    /// Latest decided height, 0 before the first decision
+    pub latest_height: u64,
+    /// Earliest decided height still retained, older ones were pruned. 0 before the first
+    /// decision
+    pub earliest_height: u64,
+    /// Protocol version of the next height, see [`crate::upgrades`]
+    pub protocol_version: u32,
+    /// Highest protocol version the node supports
+    pub supported_protocol_version: u32,
+    /// First upgrade scheduled after the latest height
+    pub next_upgrade: Option<Upgrade>,
+    /// Limits of block validation from the genesis file, identical on every node
+    pub consensus: ConsensusParams,
+    /// Parameters of the next height, as voted by governance
+    pub params: ChainParams,
+}
+
+/// Genesis document of the chain, see [`MikanApiServer::get_genesis`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenesisDocument {
+    /// Hash of the genesis block, to compare with [`ChainInfo::genesis_hash`] of other nodes
+    #[serde(with = "hex::serde")]
+    pub genesis_hash: [u8; 32],
+    #[serde(flatten)]
+    pub genesis: Genesis,
+}
+
+/// Heights whose data the node still serves, see [`MikanApiServer::get_data_window`]. The block
+/// data of height `h` is pruned once `h + retain_heights + 1` is decided, or earlier when the
+/// store grows beyond `max_db_size`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataWindow {
+    /// Decided heights whose block data is retained, as voted by governance or more if the node
+    /// is configured to
+    pub retain_heights: u64,
+    /// Cap in bytes on the store of this node, 0 for none
+    pub max_db_size: u64,
+    /// Latest decided height, 0 before the first decision
+    pub latest_height: u64,
+    /// Earliest height whose blocks and blobs can be retrieved
+    pub earliest_block_height: Option<u64>,
+    /// Earliest height whose header and commit certificate can be retrieved
+    pub earliest_certificate_height: Option<u64>,
+}
+
+/// Transactions of the pool, see [`TransactionPool`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxPoolStatus {
+    /// Transactions that can be included in the next block
+    pub pending: usize,
+    /// Transactions waiting for the previous nonces of their sender
+    pub queued: usize,
+}
+
+/// Liveness of a validator, see [`crate::liveness`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidatorStatus {
+    pub address: Address,
+    /// Voting power at the next height, zero while jailed
+    pub voting_power: u64,
+    /// Blocks signed and missed in the current window
+    pub signed_blocks: u64,
+    pub missed_blocks: u64,
+    pub jailings: Vec<Jailing>,
+}
+
+/// Most heights `mikan_getNamespaceData` returns at once, each of them can hold several MiB of
+/// blobs.
+pub const MAX_NAMESPACE_RANGE: u64 = 64;
+
+/// Most heights `mikan_getEvidence` spans at once.
+pub const MAX_EVIDENCE_RANGE: u64 = 10_000;
+
+/// Outcome of a decided transaction. Blocks are not executed: a transaction in a decided block
+/// succeeded, and used the intrinsic gas of its blobs under the gas schedule of its height.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionReceipt {
+    #[serde(with = "hex::serde")]
+    pub tx_hash: [u8; 32],
+    pub block_height: u64,
+    #[serde(with = "hex::serde")]
+    pub block_hash: [u8; 32],
+    /// Position in the block
+    pub index: usize,
+    pub from: Address,
+    pub nonce: u64,
+    /// `None` before gas metering is active
+    pub gas_used: Option<u64>,
+    /// `gas_used` times the gas price
+    pub fee: Option<u64>,
+    #[serde(with = "serde_hex::hashes")]
+    pub blob_commitments: Vec<[u8; 32]>,
+}
+
+impl TransactionReceipt {
+    /// Receipts of the transactions of `block`, decided at `block_height` under `gas`, if gas
+    /// metering was active.
+    pub fn for_block(block: &Block, block_height: u64, gas: Option<&GasSchedule>) -> Vec<Self> {
+        block
+            .transactions()
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| {
+                // Decided transactions pay for their gas, see `Block::is_valid`
+                let gas_used = gas.and_then(|gas| gas.gas_used(tx).ok());
+                Self {
+                    tx_hash: tx.hash(),
+                    block_height,
+                    block_hash: block.hash(),
+                    index,
+                    from: Address::from_public_key(&tx.from_()),
+                    nonce: tx.nonce(),
+                    gas_used,
+                    fee: gas_used.map(|used| used.saturating_mul(tx.gas_price())),
+                    blob_commitments: tx.blob_commitments().to_vec(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// A decided block as structured JSON, returned by `mikan_getBlockByHeight`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcBlock {
+    pub header: Header,
+    pub transactions: Vec<BlockTransaction>,
+    #[serde(with = "serde_hex::hashes")]
+    pub blob_commitments: Vec<[u8; 32]>,
+    /// Certificate of the parent, `None` for the first block
+    #[serde(default, with = "certificate::option")]
+    pub last_commit: Option<CommitCertificate<TestContext>>,
+}
+
+/// A transaction of an [`RpcBlock`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlockTransaction {
+    #[serde(with = "hex::serde")]
+    pub hash: [u8; 32],
+    /// Its `data` is empty unless blobs were requested
+    pub transaction: RpcTransaction,
+    #[serde(with = "serde_hex::hashes")]
+    pub blob_commitments: Vec<[u8; 32]>,
+}
+
+impl RpcBlock {
+    /// `block` with the payloads of its blobs if `include_blobs`.
+    pub fn new(block: &Block, include_blobs: bool) -> Self {
+        let transactions = block
+            .transactions()
             .iter()
-            .map(|blob| hex::encode(blob.data()))
+            .map(|tx| {
+                let mut transaction = RpcTransaction::from(tx.clone());
+                if !include_blobs {
+                    transaction.data.clear();
+                }
+                BlockTransaction {
+                    hash: tx.hash(),
+                    transaction,
+                    blob_commitments: tx.blob_commitments().to_vec(),
+                }
+            })
             .collect();
 
-        state.serialize_field("data", &blob_hexes)?;
-        state.end()
+        Self {
+            header: block.header().clone(),
+            transactions,
+            blob_commitments: block.blob_commitments().to_vec(),
+            last_commit: block.last_commit().cloned(),
+        }
     }
 }
 
-impl<'de> Deserialize<'de> for RpcTransaction {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        use bytes::Bytes;
-        use serde::de::{self, MapAccess, Visitor};
-        use std::fmt;
+/// A decided transaction with its receipt, returned by `mikan_getTransactionByHash`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DecidedTransaction {
+    pub transaction: RpcTransaction,
+    pub receipt: TransactionReceipt,
+}
 
-        struct RpcTransactionVisitor;
+/// A transaction of a namespace, with its inclusion proof.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NamespaceTransaction {
+    /// Position in the block, and leaf index in the transaction tree
+    pub index: usize,
+    pub transaction: RpcTransaction,
+    /// Proof from the transaction hash to `tx_commitment`, see
+    /// [`rs_merkle::MerkleProof::to_bytes`]
+    #[serde(with = "hex::serde")]
+    pub proof: Vec<u8>,
+}
 
-        impl<'de> Visitor<'de> for RpcTransactionVisitor {
-            type Value = RpcTransaction;
+/// Data of a namespace at one height.
+///
+/// Transactions are namespaced by their recipient: a rollup posts its blobs in transactions sent
+/// to its own key.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NamespaceData {
+    pub height: u64,
+    #[serde(with = "hex::serde")]
+    pub tx_commitment: [u8; 32],
+    /// Number of transactions in the block, i.e. of leaves in the transaction tree
+    pub tx_count: usize,
+    pub transactions: Vec<NamespaceTransaction>,
+    /// Hex encoded signing payloads of all transactions of the block, in order, when none is
+    /// sent to the namespace. They hash to the leaves of the transaction tree and contain the
+    /// recipient, proving the absence of the namespace.
+    pub absence_proof: Option<Vec<String>>,
+}
 
-            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("struct RpcTransaction")
-            }
+impl NamespaceData {
+    pub fn new(block: &Block, namespace: &PublicKey, chain_id: &str) -> Self {
+        let transactions: Vec<NamespaceTransaction> = block
+            .transactions()
+            .iter()
+            .enumerate()
+            .filter(|(_, tx)| tx.to() == *namespace)
+            .map(|(index, tx)| NamespaceTransaction {
+                index,
+                transaction: tx.clone().into(),
+                proof: block
+                    .proof_of_transaction(index)
+                    .expect("index of a transaction of the block")
+                    .to_bytes(),
+            })
+            .collect();
 
-            fn visit_map<V>(self, mut map: V) -> Result<RpcTransaction, V::Error>
-            where
-                V: MapAccess<'de>,
-            {
-                let mut from = None;
-                let mut to = None;
-                let mut signature = None;
-                let mut value = None;
-                let mut nonce = None;
-                let mut gas_price = None;
-                let mut data = None;
-
-                while let Some(key) = map.next_key::<String>()? {
-                    match key.as_str() {
-                        "from" => {
-                            if from.is_some() {
-                                return Err(de::Error::duplicate_field("from"));
-                            }
-                            from = Some(map.next_value()?);
-                        }
-                        "to" => {
-                            if to.is_some() {
-                                return Err(de::Error::duplicate_field("to"));
-                            }
-                            to = Some(map.next_value()?);
-                        }
-                        "signature" => {
-                            if signature.is_some() {
-                                return Err(de::Error::duplicate_field("signature"));
-                            }
-                            // Deserialize signature from hex string
-                            let sig_hex: String = map.next_value()?;
-                            let sig_bytes = hex::decode(sig_hex).map_err(|e| {
-                                de::Error::custom(format!("Invalid signature hex: {}", e))
-                            })?;
-                            let signature_obj = Signature::from_bytes(
-                                sig_bytes.try_into().map_err(|e: Vec<u8>| {
-                                    de::Error::custom(format!(
-                                        "Invalid signature bytes len: {}",
-                                        e.len()
-                                    ))
-                                })?,
-                            );
-                            signature = Some(signature_obj);
-                        }
-                        "value" => {
-                            if value.is_some() {
-                                return Err(de::Error::duplicate_field("value"));
-                            }
-                            value = Some(map.next_value()?);
-                        }
-                        "nonce" => {
-                            if nonce.is_some() {
-                                return Err(de::Error::duplicate_field("nonce"));
-                            }
-                            nonce = Some(map.next_value()?);
-                        }
-                        "gas_price" => {
-                            if gas_price.is_some() {
-                                return Err(de::Error::duplicate_field("gas_price"));
-                            }
-                            gas_price = Some(map.next_value()?);
-                        }
-                        "data" => {
-                            if data.is_some() {
-                                return Err(de::Error::duplicate_field("data"));
-                            }
-
-                            // Deserialize blob data from hex strings
-                            let hex_strings: Vec<String> = map.next_value()?;
-                            if hex_strings.len() != 4 {
-                                return Err(de::Error::custom("Expected 4 blobs in data array"));
-                            }
-
-                            let mut blobs = [
-                                Blob::default(),
-                                Blob::default(),
-                                Blob::default(),
-                                Blob::default(),
-                            ];
-                            for (i, hex) in hex_strings.iter().enumerate() {
-                                let bytes = hex::decode(hex).map_err(|e| {
-                                    de::Error::custom(format!("Invalid hex in blob {}: {}", i, e))
-                                })?;
-                                blobs[i] = Blob::new(Bytes::from(bytes));
-                            }
-
-                            data = Some(blobs);
-                        }
-                        _ => {
-                            // Skip unknown fields
-                            let _ = map.next_value::<serde::de::IgnoredAny>()?;
-                        }
-                    }
-                }
+        let absence_proof = transactions.is_empty().then(|| {
+            block
+                .transactions()
+                .iter()
+                .map(|tx| hex::encode(tx.sign_bytes(chain_id)))
+                .collect()
+        });
 
-                let from = from.ok_or_else(|| de::Error::missing_field("from"))?;
-                let to = to.ok_or_else(|| de::Error::missing_field("to"))?;
-                let signature = signature.ok_or_else(|| de::Error::missing_field("signature"))?;
-                let value = value.ok_or_else(|| de::Error::missing_field("value"))?;
-                let nonce = nonce.ok_or_else(|| de::Error::missing_field("nonce"))?;
-                let gas_price = gas_price.ok_or_else(|| de::Error::missing_field("gas_price"))?;
-                let data = data.ok_or_else(|| de::Error::missing_field("data"))?;
-
-                Ok(RpcTransaction {
-                    from,
-                    to,
-                    signature,
-                    value,
-                    nonce,
-                    gas_price,
-                    data,
-                })
-            }
+        Self {
+            height: block.header().block_number,
+            tx_commitment: block.header().tx_commitment,
+            tx_count: block.transactions().len(),
+            transactions,
+            absence_proof,
         }
+    }
+}
 
-        deserializer.deserialize_map(RpcTransactionVisitor)
+/// Precommit of a validator in the commit of a block, with its proof in the vote tree.
+///
+/// The precommit signs the height, round and value id, see
+/// [`crate::types::sign_bytes::vote_sign_bytes`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VoteProof {
+    pub height: u64,
+    pub round: u32,
+    pub value_id: ValueId,
+    pub validator_address: Address,
+    #[serde(with = "serde_hex::signature")]
+    pub signature: Signature,
+    /// Leaf index of the precommit, see [`crate::finality_params::vote_leaf`]
+    pub index: usize,
+    /// Number of precommits in the commit, i.e. of leaves in the vote tree
+    pub vote_count: usize,
+    #[serde(with = "hex::serde")]
+    pub vote_root: [u8; 32],
+    /// Proof from the leaf to `vote_root`, see [`rs_merkle::MerkleProof::to_bytes`]
+    #[serde(with = "hex::serde")]
+    pub proof: Vec<u8>,
+}
+
+/// Sampling proof of a blob with the seed it was sampled with.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlobSample {
+    pub sampling_seed: u64,
+    /// How the seed was derived, `None` if the sampler picked it
+    pub derivation: Option<SeedDerivation>,
+    pub proof: Proof,
+}
+
+impl BlobSample {
+    fn new(
+        blob: &Blob,
+        block: &Block,
+        blob_index: usize,
+        sampling_seed: Option<u64>,
+        sampler_nonce: Option<u64>,
+    ) -> Self {
+        let derivation = sampling_seed.is_none().then(|| SeedDerivation {
+            block_hash: block.hash(),
+            blob_index,
+            nonce: sampler_nonce.unwrap_or_default(),
+        });
+        let sampling_seed =
+            sampling_seed.unwrap_or_else(|| derivation.as_ref().map_or(0, SeedDerivation::seed));
+
+        Self {
+            sampling_seed,
+            proof: generate_proof(&blob.padded(), Some(sampling_seed), sampling_pcs_config()),
+            derivation,
+        }
+    }
+
+    /// Whether the seed was derived from `derivation`, to be checked against a header known to
+    /// the sampler before trusting the proof.
+    pub fn is_derived_from(&self, derivation: &SeedDerivation) -> bool {
+        self.derivation.as_ref() == Some(derivation) && derivation.seed() == self.sampling_seed
     }
 }
 
-#[rpc(server, namespace = "mikan")]
+/// Blob returned by `mikan_getBlob`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RetrievedBlob {
+    pub data: Blob,
+    /// Whether the blob was read from the store of the node rather than fetched from a peer
+    pub local: bool,
+    /// RPC URL of the peer the block was fetched from, the node having pruned it, see
+    /// [`crate::retrieval`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer: Option<String>,
+}
+
+/// Sampling proof of a blob found by its commitment, see `mikan_getBlobProofByCommitment`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlobCommitmentProof {
+    /// Height of the first decided block carrying the blob
+    pub block_height: u64,
+    pub blob_index: usize,
+    #[serde(flatten)]
+    pub sample: BlobSample,
+}
+
+/// Shares covering a byte range of a blob, see `mikan_getShares`, to be checked with
+/// [`crate::verify::verify_shares`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlobShares {
+    pub block_height: u64,
+    pub blob_index: usize,
+    /// Share root of the header, over `blob_count` blobs
+    #[serde(with = "hex::serde")]
+    pub share_root: [u8; 32],
+    pub blob_count: usize,
+    pub blob_len: u64,
+    #[serde(with = "hex::serde")]
+    pub blob_share_root: [u8; 32],
+    /// Proof of the blob against `share_root`
+    #[serde(with = "hex::serde")]
+    pub blob_proof: Vec<u8>,
+    /// Index of the first share, shares being [`SHARE_SIZE`] bytes long
+    pub first_share: usize,
+    /// Hex encoded shares
+    pub shares: Vec<String>,
+    /// Proof of the shares against `blob_share_root`
+    #[serde(with = "hex::serde")]
+    pub shares_proof: Vec<u8>,
+}
+
+#[rpc(server, client, namespace = "mikan")]
 pub trait MikanApi {
     #[method(name = "sendTransaction")]
     async fn send_transaction(&self, tx: RpcTransaction) -> RpcResult<String>;
 
+    /// Starts a chunked blob upload and returns its id.
+    #[method(name = "beginBlobUpload")]
+    async fn begin_blob_upload(&self) -> RpcResult<u64>;
+
+    /// Appends a hex-encoded chunk at `offset` and returns the number of bytes received so far.
+    #[method(name = "uploadBlobChunk")]
+    async fn upload_blob_chunk(
+        &self,
+        upload_id: u64,
+        offset: usize,
+        chunk: String,
+    ) -> RpcResult<usize>;
+
+    /// Assembles the uploaded blobs and submits the transaction. The uploads are consumed even if
+    /// the transaction is rejected.
+    #[method(name = "sendUploadedTransaction")]
+    async fn send_uploaded_transaction(&self, tx: UploadedTransaction) -> RpcResult<String>;
+
+    /// Sampling proof of a blob. Without `sampling_seed`, the seed is derived from the block hash
+    /// and `sampler_nonce`, see [`SeedDerivation`]. Fails with `SamplingBusy` when too many
+    /// proofs are being generated, see [`crate::sample_queue`].
     #[method(name = "sampleBlob")]
     async fn sample_blob(
         &self,
         block_height: u64,
         blob_index: usize,
         sampling_seed: Option<u64>,
-    ) -> RpcResult<Proof>;
+        sampler_nonce: Option<u64>,
+    ) -> RpcResult<BlobSample>;
+
+    /// Sampling proof of the blob committed to by the hex-encoded `commitment`, wherever it was
+    /// decided, as long as its block data is kept. Seeds as in `mikan_sampleBlob`.
+    #[method(name = "getBlobProofByCommitment")]
+    async fn get_blob_proof_by_commitment(
+        &self,
+        commitment: String,
+        sampling_seed: Option<u64>,
+        sampler_nonce: Option<u64>,
+    ) -> RpcResult<BlobCommitmentProof>;
 
     #[method(name = "blockNumber")]
-    async fn block_number(&self) -> u64;
+    async fn block_number(&self) -> RpcResult<u64>;
+
+    #[method(name = "chainInfo")]
+    async fn chain_info(&self) -> RpcResult<ChainInfo>;
+
+    /// Genesis document of the chain, for light clients and new operators to bootstrap from a
+    /// node they trust rather than from a file distributed out of band. It includes the upgrades
+    /// scheduled in the configuration of the node, which every node must schedule too.
+    #[method(name = "getGenesis")]
+    async fn get_genesis(&self) -> RpcResult<GenesisDocument>;
+
+    /// Retention of this node, for samplers and rollups to fetch their blobs before pruning.
+    #[method(name = "getDataWindow")]
+    async fn get_data_window(&self) -> RpcResult<DataWindow>;
+
+    #[method(name = "txPoolStatus")]
+    async fn tx_pool_status(&self) -> RpcResult<TxPoolStatus>;
+
+    /// Hashes of the pending and queued transactions, exchanged with peers joining the network,
+    /// see [`crate::transactions::exchange`].
+    #[method(name = "txPoolHashes")]
+    async fn tx_pool_hashes(&self) -> RpcResult<Vec<String>>;
+
+    /// Pending or queued transaction with `hash`, if still in the pool.
+    #[method(name = "getPoolTransaction")]
+    async fn get_pool_transaction(&self, hash: String) -> RpcResult<Option<RpcTransaction>>;
+
+    /// Decided transaction with `hash` and its receipt, `None` if it was never decided or the
+    /// block data of its height was pruned.
+    #[method(name = "getTransactionByHash")]
+    async fn get_transaction_by_hash(&self, hash: String) -> RpcResult<Option<DecidedTransaction>>;
 
+    #[method(name = "getBlock")]
+    async fn get_block(&self, block_height: u64) -> RpcResult<Block>;
+
+    /// Block at `block_height` as structured JSON, without the blob payloads unless
+    /// `include_blobs`.
+    #[method(name = "getBlockByHeight")]
+    async fn get_block_by_height(
+        &self,
+        block_height: u64,
+        include_blobs: Option<bool>,
+    ) -> RpcResult<RpcBlock>;
+
+    /// Receipts of all the transactions of the block at `block_height`, in order.
+    #[method(name = "getBlockReceipts")]
+    async fn get_block_receipts(&self, block_height: u64) -> RpcResult<Vec<TransactionReceipt>>;
+
+    /// Blob at `blob_index` in the block at `block_height`, fetched from a peer if the node
+    /// pruned the block and `[retrieval]` is enabled.
     #[method(name = "getBlob")]
-    async fn get_blob(&self, block_height: u64, blob_index: usize) -> RpcResult<Blob>;
+    async fn get_blob(&self, block_height: u64, blob_index: usize) -> RpcResult<RetrievedBlob>;
+
+    /// Shares covering bytes `from_byte..to_byte` of a blob with their proofs, for blocks built
+    /// under [`SHARES_VERSION`] or later.
+    #[method(name = "getShares")]
+    async fn get_shares(
+        &self,
+        block_height: u64,
+        blob_index: usize,
+        from_byte: usize,
+        to_byte: usize,
+    ) -> RpcResult<BlobShares>;
+
+    #[method(name = "getHeader")]
+    async fn get_header(
+        &self,
+        block_height: u64,
+        encoding: Option<HeaderEncoding>,
+    ) -> RpcResult<String>;
+
+    /// Transactions sent to `namespace` in `from_height..=to_height` with inclusion proofs, or an
+    /// absence proof for heights without any. At most [`MAX_NAMESPACE_RANGE`] heights.
+    #[method(name = "getNamespaceData")]
+    async fn get_namespace_data(
+        &self,
+        namespace: PublicKey,
+        from_height: u64,
+        to_height: u64,
+    ) -> RpcResult<Vec<NamespaceData>>;
+
+    /// Proof that the precommit of `validator_address` is part of the commit that decided the
+    /// block at `block_height`.
+    #[method(name = "getVoteProof")]
+    async fn get_vote_proof(
+        &self,
+        block_height: u64,
+        validator_address: Address,
+    ) -> RpcResult<VoteProof>;
+
+    /// Header of the block at `block_height` with what proves it final, from the genesis
+    /// validator set as checkpoint.
+    #[method(name = "getFinalityProof")]
+    async fn get_finality_proof(&self, block_height: u64) -> RpcResult<FinalityProof>;
+
+    #[method(name = "getPeers")]
+    async fn get_peers(&self) -> RpcResult<Vec<PeerInfo>>;
+
+    /// Signed and missed blocks of each validator, and its jailings.
+    #[method(name = "getValidatorLiveness")]
+    async fn get_validator_liveness(&self) -> RpcResult<Vec<ValidatorStatus>>;
+
+    /// Double-sign evidence recorded for heights `from_height..=to_height`, at most
+    /// [`MAX_EVIDENCE_RANGE`] heights.
+    #[method(name = "getEvidence")]
+    async fn get_evidence(
+        &self,
+        from_height: u64,
+        to_height: u64,
+    ) -> RpcResult<Vec<EvidenceRecord>>;
+
+    /// Verifies and records double-sign evidence, returns `false` if it was already recorded.
+    #[method(name = "submitEvidence")]
+    async fn submit_evidence(&self, evidence: Evidence) -> RpcResult<bool>;
+
+    /// Recent blocks seen that do not extend our chain, oldest first.
+    #[method(name = "getForkStatus")]
+    async fn get_fork_status(&self) -> RpcResult<Vec<Divergence>>;
+
+    /// Height and round consensus is in, with the proposals and votes seen for the round.
+    #[method(name = "getConsensusState")]
+    async fn get_consensus_state(&self) -> RpcResult<ConsensusState>;
+
+    /// Notifies each block once its commit is stored, over WebSocket. Subscribers falling more
+    /// than [`crate::events::FINALITY_FEED_CAPACITY`] blocks behind skip the oldest ones.
+    #[subscription(
+        name = "subscribeFinality" => "finality",
+        unsubscribe = "unsubscribeFinality",
+        item = Finalized
+    )]
+    async fn subscribe_finality(&self) -> SubscriptionResult;
 }
 
 #[derive(Clone)]
 pub struct MikanRpcObj {
     transaction_pool: TransactionPool,
     store: Store,
+    peers: PeerBook,
+    forks: ForkMonitor,
+    genesis_hash: [u8; 32],
+    genesis: Arc<Genesis>,
+    finality: FinalityFeed,
+    round_state: RoundState,
+    uploads: BlobUploads,
+    samples: SampleQueue,
+    /// Peers pruned blocks are fetched from, if enabled
+    retrieval: Option<BlockRetrieval>,
+    /// Keyed by height, blob index, seed and nonce
+    sampling: InFlight<(u64, usize, Option<u64>, Option<u64>), RpcResult<BlobSample>>,
 }
 
 impl MikanRpcObj {
-    pub fn new(transaction_pool: TransactionPool, store: Store) -> Self {
+    pub fn new(
+        transaction_pool: TransactionPool,
+        store: Store,
+        peers: PeerBook,
+        forks: ForkMonitor,
+        genesis: &Genesis,
+        finality: FinalityFeed,
+        round_state: RoundState,
+    ) -> Self {
         Self {
             transaction_pool,
             store,
+            peers,
+            forks,
+            genesis_hash: genesis.block().hash(),
+            genesis: Arc::new(genesis.clone()),
+            finality,
+            round_state,
+            uploads: BlobUploads::new(),
+            samples: SampleQueue::new(SamplingConfig::default()),
+            retrieval: None,
+            sampling: InFlight::new(),
         }
     }
 
-    pub async fn start(self, port: u16) -> eyre::Result<(ServerHandle, Self)> {
+    /// Limits on the sampling proofs generated at the same time.
+    pub fn with_sampling(mut self, config: SamplingConfig) -> Self {
+        self.samples = SampleQueue::new(config);
+        self
+    }
+
+    /// Peers to fetch the blocks pruned by the node from, see [`crate::retrieval`].
+    pub fn with_retrieval(mut self, retrieval: Option<BlockRetrieval>) -> Self {
+        self.retrieval = retrieval;
+        self
+    }
+
+    /// Starts the server on `port`, it stops by itself once `shutdown` is triggered.
+    pub async fn start(self, port: u16, shutdown: Shutdown) -> Result<(ServerHandle, Self)> {
         let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port);
-        let server = ServerBuilder::default().build(addr).await?;
+        let server = ServerBuilder::default()
+            .max_request_body_size(max_request_body_size(self.transaction_pool.max_tx_bytes()))
+            .set_middleware(tower::ServiceBuilder::new().layer(ConnectionLayer::default()))
+            .build(addr)
+            .await?;
 
         let handle = server.start(self.clone().into_rpc());
         info!("RPC server started on {}", addr);
 
+        let server_handle = handle.clone();
+        tokio::spawn(async move {
+            shutdown.triggered().await;
+            if server_handle.stop().is_ok() {
+                info!("RPC server stopped");
+            }
+        });
+
         Ok((handle, self))
     }
     pub fn get_top_transaction(&self) -> Option<Transaction> {
@@ -260,19 +779,224 @@ impl MikanRpcObj {
     pub fn get_transactions(&self, count: usize) -> Vec<Transaction> {
         self.transaction_pool.get_transactions(count)
     }
+
+    fn submit_transaction(&self, tx: RpcTransaction) -> RpcResult<String> {
+        let from = hex::encode(tx.from.as_bytes());
+        let tx_error = |e| match e {
+            TransactionError::InvalidSignature => {
+                MikanRpcError::InvalidSignature { from: from.clone() }
+            }
+            TransactionError::PoolFull(capacity) | TransactionError::QueueFull(capacity) => {
+                MikanRpcError::MempoolFull { capacity }
+            }
+            TransactionError::NonceTooLow { expected, actual } => MikanRpcError::NonceTooLow {
+                from: from.clone(),
+                expected,
+                nonce: actual,
+            },
+            TransactionError::Namespace(error) => MikanRpcError::Namespace(error),
+            TransactionError::Gas(error) => MikanRpcError::Gas(error),
+            TransactionError::Governance(error) => MikanRpcError::Governance(error),
+            TransactionError::Liveness(error) => MikanRpcError::Liveness(error),
+            TransactionError::BlobTooLarge { max, actual } => MikanRpcError::BlobTooLarge {
+                max_bytes: max,
+                size: actual,
+            },
+            TransactionError::TooManyBlobs { max, actual } => MikanRpcError::TooManyBlobs {
+                max_blobs: max,
+                blob_count: actual,
+            },
+            TransactionError::TooLarge { max, actual } => MikanRpcError::TransactionTooLarge {
+                max_bytes: max,
+                size: actual,
+            },
+        };
+
+        // The blob data alone, checked before computing the commitments of the blobs
+        let blob_bytes = tx.data.iter().map(|blob| blob.data().len()).sum::<usize>();
+        let max_tx_bytes = self.transaction_pool.max_tx_bytes();
+        if blob_bytes > max_tx_bytes {
+            return Err(MikanRpcError::TransactionTooLarge {
+                max_bytes: max_tx_bytes,
+                size: blob_bytes,
+            }
+            .into());
+        }
+
+        let tx = tx
+            .into_transaction(self.transaction_pool.chain_id())
+            .map_err(&tx_error)?;
+        self.transaction_pool
+            .add_transaction(tx.clone())
+            .map_err(tx_error)?;
+        info!("Transaction sent: {}", hex::encode(tx.hash()));
+        Ok(hex::encode(tx.hash()))
+    }
+
+    async fn decided_block(&self, block_height: u64) -> RpcResult<Block> {
+        let height = Height::new(block_height);
+
+        let block_data = self
+            .store
+            .get_decided_block(height)
+            .await
+            .map_err(|e| MikanRpcError::Internal(e.to_string()))?
+            .ok_or(MikanRpcError::BlockNotFound { block_height })?;
+
+        let block = ProtobufCodec
+            .decode(block_data)
+            .map_err(|e| MikanRpcError::Decode {
+                what: "block",
+                reason: e.to_string(),
+            })?;
+
+        Ok(block)
+    }
+
+    /// Receipts of the transactions of `block`, under the gas schedule in force at `block_height`
+    /// if gas metering was active.
+    fn receipts(&self, block: &Block, block_height: u64) -> Vec<TransactionReceipt> {
+        let params = self.transaction_pool.governance().params_at(block_height);
+        let gas = self
+            .genesis
+            .protocol
+            .is_active(Feature::GasMetering, block_height)
+            .then_some(&params.gas);
+        TransactionReceipt::for_block(block, block_height, gas)
+    }
+
+    /// Block at `block_height` fetched from a peer, if retrieval is enabled and the node decided
+    /// the height and pruned it. Returns the block and the URL of the peer.
+    async fn pruned_block(&self, block_height: u64) -> Option<(Block, String)> {
+        let retrieval = self.retrieval.as_ref()?;
+        let latest = self.store.max_decided_value_height().await?;
+        if block_height == 0 || block_height > latest.as_u64() {
+            return None;
+        }
+
+        let hashes = self
+            .transaction_pool
+            .liveness()
+            .validator_set_hashes(block_height);
+        retrieval.fetch(block_height, &hashes).await
+    }
+
+    /// Identical requests share the proof being generated.
+    async fn sample(
+        &self,
+        block_height: u64,
+        blob_index: usize,
+        sampling_seed: Option<u64>,
+        sampler_nonce: Option<u64>,
+    ) -> RpcResult<BlobSample> {
+        self.sampling
+            .run(
+                (block_height, blob_index, sampling_seed, sampler_nonce),
+                self.generate_sample(block_height, blob_index, sampling_seed, sampler_nonce),
+            )
+            .await
+    }
+
+    async fn generate_sample(
+        &self,
+        block_height: u64,
+        blob_index: usize,
+        sampling_seed: Option<u64>,
+        sampler_nonce: Option<u64>,
+    ) -> RpcResult<BlobSample> {
+        let block = self.decided_block(block_height).await?;
+        let blob = blob_at(block.blobs(), block_height, blob_index)?;
+
+        let config = self.samples.config();
+        let permit =
+            self.samples.acquire(current_connection()).await.map_err(
+                |rejection| match rejection {
+                    Rejection::Connection => MikanRpcError::SamplingConnectionBusy {
+                        max_per_connection: config.max_per_connection,
+                    },
+                    Rejection::Busy => MikanRpcError::SamplingBusy {
+                        max_concurrent: config.max_concurrent,
+                        max_queued: config.max_queued,
+                    },
+                },
+            )?;
+
+        // Generate a FRIEDA proof for the blob, off the RPC worker threads
+        let sample = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            BlobSample::new(&blob, &block, blob_index, sampling_seed, sampler_nonce)
+        })
+        .await
+        .map_err(|e| MikanRpcError::Internal(e.to_string()))?;
+
+        Ok(sample)
+    }
+}
+
+fn blob_at(mut blobs: Vec<Blob>, block_height: u64, blob_index: usize) -> RpcResult<Blob> {
+    if blob_index >= blobs.len() {
+        return Err(MikanRpcError::BlobIndexOutOfRange {
+            block_height,
+            blob_index,
+            blob_count: blobs.len(),
+        }
+        .into());
+    }
+
+    Ok(blobs.swap_remove(blob_index))
 }
 
 #[async_trait]
 impl MikanApiServer for MikanRpcObj {
     async fn send_transaction(&self, tx: RpcTransaction) -> RpcResult<String> {
-        let tx = Transaction::from(tx);
+        self.submit_transaction(tx)
+    }
 
-        self.transaction_pool.add_transaction(tx.clone());
-        info!("Transaction sent: {}", hex::encode(tx.hash()));
-        Ok(hex::encode(tx.hash()))
+    async fn begin_blob_upload(&self) -> RpcResult<u64> {
+        Ok(self.uploads.begin().map_err(MikanRpcError::from)?)
+    }
+
+    async fn upload_blob_chunk(
+        &self,
+        upload_id: u64,
+        offset: usize,
+        chunk: String,
+    ) -> RpcResult<usize> {
+        let chunk = hex::decode(chunk).map_err(|e| MikanRpcError::Decode {
+            what: "chunk",
+            reason: e.to_string(),
+        })?;
+
+        Ok(self
+            .uploads
+            .append(upload_id, offset, &chunk)
+            .map_err(MikanRpcError::from)?)
+    }
+
+    async fn send_uploaded_transaction(&self, tx: UploadedTransaction) -> RpcResult<String> {
+        let data: Vec<Blob> = tx
+            .uploads
+            .iter()
+            .map(|&upload_id| self.uploads.finish(upload_id))
+            .collect::<Result<_, _>>()
+            .map_err(MikanRpcError::from)?;
+
+        self.submit_transaction(RpcTransaction {
+            from: tx.from,
+            to: tx.to,
+            signature: tx.signature,
+            value: tx.value,
+            nonce: tx.nonce,
+            gas_price: tx.gas_price,
+            gas_limit: tx.gas_limit,
+            data,
+            registration: None,
+            governance: None,
+            unjail: false,
+        })
     }
 
-    async fn block_number(&self) -> u64 {
+    async fn block_number(&self) -> RpcResult<u64> {
         // Get the latest block height from the store
         let height = self
             .store
@@ -280,7 +1004,120 @@ impl MikanApiServer for MikanRpcObj {
             .await
             .unwrap_or_default();
 
-        height.as_u64()
+        Ok(height.as_u64())
+    }
+
+    async fn chain_info(&self) -> RpcResult<ChainInfo> {
+        let latest_height = self.store.max_decided_value_height().await;
+        let earliest_height = self.store.min_decided_value_height().await;
+        let latest_height = latest_height.map_or(0, |height| height.as_u64());
+
+        Ok(ChainInfo {
+            chain_id: self.transaction_pool.chain_id().to_string(),
+            genesis_hash: self.genesis_hash,
+            latest_height,
+            earliest_height: earliest_height.map_or(0, |height| height.as_u64()),
+            protocol_version: self.genesis.protocol.version_at(latest_height + 1),
+            supported_protocol_version: PROTOCOL_VERSION,
+            next_upgrade: self.genesis.protocol.next_upgrade(latest_height),
+            consensus: self.genesis.consensus.clone(),
+            params: self
+                .transaction_pool
+                .governance()
+                .params_at(latest_height + 1),
+        })
+    }
+
+    async fn get_genesis(&self) -> RpcResult<GenesisDocument> {
+        Ok(GenesisDocument {
+            genesis_hash: self.genesis_hash,
+            genesis: Genesis::clone(&self.genesis),
+        })
+    }
+
+    async fn get_data_window(&self) -> RpcResult<DataWindow> {
+        let latest_height = self
+            .store
+            .max_decided_value_height()
+            .await
+            .map_or(0, |height| height.as_u64());
+        let earliest_block_height = self.store.min_decided_block_height().await;
+        let earliest_certificate_height = self.store.min_certificate_height().await;
+
+        Ok(DataWindow {
+            retain_heights: self.store.retain_heights(
+                self.transaction_pool
+                    .governance()
+                    .params_at(latest_height + 1)
+                    .retain_heights,
+            ),
+            max_db_size: self.store.max_db_size(),
+            latest_height,
+            earliest_block_height: earliest_block_height.map(|height| height.as_u64()),
+            earliest_certificate_height: earliest_certificate_height.map(|height| height.as_u64()),
+        })
+    }
+
+    async fn tx_pool_status(&self) -> RpcResult<TxPoolStatus> {
+        Ok(TxPoolStatus {
+            pending: self.transaction_pool.tx_count(),
+            queued: self.transaction_pool.queued_count(),
+        })
+    }
+
+    async fn tx_pool_hashes(&self) -> RpcResult<Vec<String>> {
+        Ok(self
+            .transaction_pool
+            .hashes()
+            .iter()
+            .map(hex::encode)
+            .collect())
+    }
+
+    async fn get_pool_transaction(&self, hash: String) -> RpcResult<Option<RpcTransaction>> {
+        let bytes =
+            hex::decode(hash.trim_start_matches("0x")).map_err(|e| MikanRpcError::Decode {
+                what: "transaction hash",
+                reason: e.to_string(),
+            })?;
+        let hash: [u8; 32] = bytes.try_into().map_err(|_| MikanRpcError::Decode {
+            what: "transaction hash",
+            reason: "expected 32 bytes".to_string(),
+        })?;
+
+        Ok(self.transaction_pool.get(&hash).map(RpcTransaction::from))
+    }
+
+    async fn get_transaction_by_hash(&self, hash: String) -> RpcResult<Option<DecidedTransaction>> {
+        let bytes =
+            hex::decode(hash.trim_start_matches("0x")).map_err(|e| MikanRpcError::Decode {
+                what: "transaction hash",
+                reason: e.to_string(),
+            })?;
+        let hash: [u8; 32] = bytes.try_into().map_err(|_| MikanRpcError::Decode {
+            what: "transaction hash",
+            reason: "expected 32 bytes".to_string(),
+        })?;
+
+        let Some((height, index)) = self
+            .store
+            .get_transaction_location(hash)
+            .await
+            .map_err(|e| MikanRpcError::Internal(e.to_string()))?
+        else {
+            return Ok(None);
+        };
+        let block_height = height.as_u64();
+        let block = self.decided_block(block_height).await?;
+
+        let transaction = block.transactions().get(index).cloned();
+        let receipt = self.receipts(&block, block_height).into_iter().nth(index);
+        Ok(transaction
+            .zip(receipt)
+            .map(|(transaction, receipt)| DecidedTransaction {
+                transaction: RpcTransaction::from(transaction),
+                receipt,
+            }))
     }
 
     async fn sample_blob(
@@ -288,107 +1125,334 @@ impl MikanApiServer for MikanRpcObj {
         block_height: u64,
         blob_index: usize,
         sampling_seed: Option<u64>,
-    ) -> RpcResult<Proof> {
-        let height = crate::malachite_types::height::Height::new(block_height);
-
-        // Get the block data
-        let block_data = self.store.get_decided_block(height).await.map_err(|_| {
-            ErrorObject::owned(
-                INTERNAL_ERROR_CODE,
-                "Couldn't find block",
-                Option::<String>::None,
-            )
+        sampler_nonce: Option<u64>,
+    ) -> RpcResult<BlobSample> {
+        self.sample(block_height, blob_index, sampling_seed, sampler_nonce)
+            .await
+    }
+
+    async fn get_blob_proof_by_commitment(
+        &self,
+        commitment: String,
+        sampling_seed: Option<u64>,
+        sampler_nonce: Option<u64>,
+    ) -> RpcResult<BlobCommitmentProof> {
+        let bytes = hex::decode(commitment.trim_start_matches("0x")).map_err(|e| {
+            MikanRpcError::Decode {
+                what: "commitment",
+                reason: e.to_string(),
+            }
+        })?;
+        let commitment: [u8; 32] = bytes.try_into().map_err(|_| MikanRpcError::Decode {
+            what: "commitment",
+            reason: "expected 32 bytes".to_string(),
         })?;
 
-        let block_data = block_data.ok_or(ErrorObject::owned(
-            INTERNAL_ERROR_CODE,
-            "Couldn't find block",
-            Option::<String>::None,
-        ))?;
-
-        // Decode the block
-        let (block, _): (crate::block::Block, _) =
-            bincode::borrow_decode_from_slice(&block_data, bincode::config::standard()).map_err(
-                |_| {
-                    ErrorObject::owned(
-                        INTERNAL_ERROR_CODE,
-                        "Couldn't decode block",
-                        Option::<String>::None,
-                    )
-                },
-            )?;
+        let (height, blob_index) = self
+            .store
+            .get_blob_location(commitment)
+            .await
+            .map_err(|e| MikanRpcError::Internal(e.to_string()))?
+            .ok_or(MikanRpcError::BlobNotFound {
+                commitment: hex::encode(commitment),
+            })?;
+        let sample = self
+            .sample(height.as_u64(), blob_index, sampling_seed, sampler_nonce)
+            .await?;
 
-        // Get all blobs from the block
-        let blobs = block.blobs();
+        Ok(BlobCommitmentProof {
+            block_height: height.as_u64(),
+            blob_index,
+            sample,
+        })
+    }
 
-        // Check if the requested blob index is valid
-        if blob_index >= blobs.len() {
-            return Err(ErrorObject::owned(
-                INTERNAL_ERROR_CODE,
-                "Blob index out of bounds",
-                Option::<String>::None,
-            ));
+    async fn get_blob(&self, block_height: u64, blob_index: usize) -> RpcResult<RetrievedBlob> {
+        let (block, peer) = match self.decided_block(block_height).await {
+            Ok(block) => (block, None),
+            Err(e) => match self.pruned_block(block_height).await {
+                Some((block, url)) => {
+                    info!(block_height, %url, "Serving a pruned blob fetched from a peer");
+                    (block, Some(url))
+                }
+                None => return Err(e),
+            },
         };
 
-        // Generate a FRIEDA proof for the blob
-        let proof = generate_proof(
-            blobs[blob_index].data(),
-            sampling_seed,
-            PcsConfig {
-                pow_bits: 20,
-                fri_config: FriConfig {
-                    log_blowup_factor: 4,
-                    log_last_layer_degree_bound: 0,
-                    n_queries: 20,
-                },
+        Ok(RetrievedBlob {
+            data: blob_at(block.blobs(), block_height, blob_index)?,
+            local: peer.is_none(),
+            peer,
+        })
+    }
+
+    async fn get_shares(
+        &self,
+        block_height: u64,
+        blob_index: usize,
+        from_byte: usize,
+        to_byte: usize,
+    ) -> RpcResult<BlobShares> {
+        let block = self.decided_block(block_height).await?;
+        let header = block.header();
+        if header.version < SHARES_VERSION {
+            return Err(MikanRpcError::SharesNotCommitted {
+                block_height,
+                version: header.version,
+            }
+            .into());
+        }
+
+        let blobs = block.blobs();
+        let blob_len = blobs
+            .get(blob_index)
+            .ok_or(MikanRpcError::BlobIndexOutOfRange {
+                block_height,
+                blob_index,
+                blob_count: blobs.len(),
+            })?
+            .data()
+            .len();
+        let proof = prove_shares(&blobs, blob_index, from_byte..to_byte).ok_or(
+            MikanRpcError::ByteRangeOutOfRange {
+                from_byte,
+                to_byte,
+                blob_len,
             },
-        );
+        )?;
 
-        // Return the proof as a hex string
-        Ok(proof)
+        Ok(BlobShares {
+            block_height,
+            blob_index,
+            share_root: header.share_root,
+            blob_count: blobs.len(),
+            blob_len: proof.blob_len,
+            blob_share_root: proof.blob_share_root,
+            blob_proof: proof.blob_proof,
+            first_share: proof.first_share,
+            shares: proof.shares.iter().map(hex::encode).collect(),
+            shares_proof: proof.shares_proof,
+        })
     }
-    async fn get_blob(&self, block_height: u64, blob_index: usize) -> RpcResult<Blob> {
-        let height = crate::malachite_types::height::Height::new(block_height);
+    async fn get_header(
+        &self,
+        block_height: u64,
+        encoding: Option<HeaderEncoding>,
+    ) -> RpcResult<String> {
+        let header = match self
+            .store
+            .get_pruned_header(Height::new(block_height))
+            .await
+            .map_err(|e| MikanRpcError::Internal(e.to_string()))?
+        {
+            Some(header) => header,
+            None => self.decided_block(block_height).await?.header().clone(),
+        };
 
-        // Get the block data
-        let block_data = self.store.get_decided_block(height).await.map_err(|_| {
-            ErrorObject::owned(
-                INTERNAL_ERROR_CODE,
-                "Couldn't find block",
-                Option::<String>::None,
-            )
-        })?;
+        let encoded = match encoding.unwrap_or_default() {
+            HeaderEncoding::Protobuf => ProtobufCodec
+                .encode(&header)
+                .map_err(|e| MikanRpcError::Internal(format!("Couldn't encode header: {e}")))?,
+            HeaderEncoding::Ssz => SszCodec
+                .encode(&header)
+                .map_err(|e| MikanRpcError::Internal(format!("Couldn't encode header: {e}")))?,
+        };
 
-        let block_data = block_data.ok_or(ErrorObject::owned(
-            INTERNAL_ERROR_CODE,
-            "Couldn't find block",
-            Option::<String>::None,
-        ))?;
-
-        // Decode the block
-        let (block, _): (crate::block::Block, _) =
-            bincode::borrow_decode_from_slice(&block_data, bincode::config::standard()).map_err(
-                |_| {
-                    ErrorObject::owned(
-                        INTERNAL_ERROR_CODE,
-                        "Couldn't decode block",
-                        Option::<String>::None,
-                    )
-                },
-            )?;
+        Ok(hex::encode(encoded))
+    }
 
-        // Get all blobs from the block
-        let blobs = block.blobs();
+    async fn get_block(&self, block_height: u64) -> RpcResult<Block> {
+        self.decided_block(block_height).await
+    }
 
-        // Check if the requested blob index is valid
-        if blob_index >= blobs.len() {
-            return Err(ErrorObject::owned(
-                INTERNAL_ERROR_CODE,
-                "Blob index out of bounds",
-                Option::<String>::None,
-            ));
-        };
+    async fn get_block_by_height(
+        &self,
+        block_height: u64,
+        include_blobs: Option<bool>,
+    ) -> RpcResult<RpcBlock> {
+        let block = self.decided_block(block_height).await?;
+        Ok(RpcBlock::new(&block, include_blobs.unwrap_or(false)))
+    }
+
+    async fn get_block_receipts(&self, block_height: u64) -> RpcResult<Vec<TransactionReceipt>> {
+        let block = self.decided_block(block_height).await?;
+        Ok(self.receipts(&block, block_height))
+    }
+
+    async fn get_namespace_data(
+        &self,
+        namespace: PublicKey,
+        from_height: u64,
+        to_height: u64,
+    ) -> RpcResult<Vec<NamespaceData>> {
+        if from_height > to_height || to_height - from_height >= MAX_NAMESPACE_RANGE {
+            return Err(MikanRpcError::InvalidRange {
+                from_height,
+                to_height,
+                max_range: MAX_NAMESPACE_RANGE,
+            }
+            .into());
+        }
+
+        let chain_id = self.transaction_pool.chain_id();
+        let mut data = Vec::new();
+        for block_height in from_height..=to_height {
+            let block = self.decided_block(block_height).await?;
+            data.push(NamespaceData::new(&block, &namespace, chain_id));
+        }
+
+        Ok(data)
+    }
+
+    async fn get_vote_proof(
+        &self,
+        block_height: u64,
+        validator_address: Address,
+    ) -> RpcResult<VoteProof> {
+        // Certificates outlive the block data pruned for size
+        let certificate = self
+            .store
+            .get_certificate(Height::new(block_height))
+            .await
+            .map_err(|e| MikanRpcError::Internal(e.to_string()))?
+            .ok_or(MikanRpcError::BlockNotFound { block_height })?;
+
+        let (index, proof) =
+            proof_of_vote(&certificate, &validator_address).ok_or(MikanRpcError::VoteNotFound {
+                block_height,
+                validator_address: validator_address.to_string(),
+            })?;
+        let signatures = &certificate.aggregated_signature.signatures;
+
+        Ok(VoteProof {
+            height: block_height,
+            round: certificate.round.as_u32().unwrap_or_default(),
+            value_id: certificate.value_id,
+            validator_address,
+            signature: signatures[index].signature,
+            index,
+            vote_count: signatures.len(),
+            vote_root: vote_tree_root(&certificate),
+            proof: proof.to_bytes(),
+        })
+    }
+
+    async fn get_finality_proof(&self, block_height: u64) -> RpcResult<FinalityProof> {
+        let height = Height::new(block_height);
+        let certificate = self
+            .store
+            .get_certificate(height)
+            .await
+            .map_err(|e| MikanRpcError::Internal(e.to_string()))?
+            .ok_or(MikanRpcError::BlockNotFound { block_height })?;
+        let block = self
+            .store
+            .get_decided_block(height)
+            .await
+            .map_err(|e| MikanRpcError::Internal(e.to_string()))?
+            .ok_or(MikanRpcError::BlockNotFound { block_height })?;
+
+        let liveness = self.transaction_pool.liveness();
+        Ok(
+            FinalityProof::new(certificate, liveness.validator_set_at(block_height), block)
+                .map_err(|e| MikanRpcError::Internal(e.to_string()))?
+                .with_validator_set_hashes(liveness.validator_set_hashes(block_height)),
+        )
+    }
+
+    async fn get_peers(&self) -> RpcResult<Vec<PeerInfo>> {
+        Ok(self.peers.peers())
+    }
+
+    async fn get_validator_liveness(&self) -> RpcResult<Vec<ValidatorStatus>> {
+        let next_height = self
+            .store
+            .max_decided_value_height()
+            .await
+            .map_or(1, |height| height.as_u64() + 1);
+        let liveness = self.transaction_pool.liveness();
+        let mut records = liveness.records();
+
+        Ok(liveness
+            .validator_set_at(next_height)
+            .validators
+            .iter()
+            .map(|validator| {
+                let record = records.remove(&validator.address).unwrap_or_default();
+                ValidatorStatus {
+                    address: validator.address,
+                    voting_power: validator.voting_power,
+                    signed_blocks: record.signed_blocks,
+                    missed_blocks: record.missed_blocks,
+                    jailings: record.jailings,
+                }
+            })
+            .collect())
+    }
+
+    async fn get_evidence(
+        &self,
+        from_height: u64,
+        to_height: u64,
+    ) -> RpcResult<Vec<EvidenceRecord>> {
+        if from_height > to_height || to_height - from_height >= MAX_EVIDENCE_RANGE {
+            return Err(MikanRpcError::InvalidRange {
+                from_height,
+                to_height,
+                max_range: MAX_EVIDENCE_RANGE,
+            }
+            .into());
+        }
+
+        Ok(self
+            .store
+            .get_evidence(from_height, to_height)
+            .await
+            .map_err(|e| MikanRpcError::Internal(e.to_string()))?)
+    }
+
+    async fn submit_evidence(&self, evidence: Evidence) -> RpcResult<bool> {
+        let validator_set = self
+            .transaction_pool
+            .liveness()
+            .validator_set_at(evidence.height());
+        evidence
+            .verify(self.transaction_pool.chain_id(), &validator_set)
+            .map_err(MikanRpcError::from)?;
+
+        let record = EvidenceRecord::new(evidence, EvidenceSource::Submitted);
+        Ok(self
+            .store
+            .store_evidence(record)
+            .await
+            .map_err(|e| MikanRpcError::Internal(e.to_string()))?)
+    }
+
+    async fn get_fork_status(&self) -> RpcResult<Vec<Divergence>> {
+        Ok(self.forks.divergences())
+    }
+
+    async fn get_consensus_state(&self) -> RpcResult<ConsensusState> {
+        Ok(self.round_state.snapshot())
+    }
+
+    async fn subscribe_finality(&self, pending: PendingSubscriptionSink) -> SubscriptionResult {
+        let mut receiver = self.finality.subscribe();
+        let sink = pending.accept().await?;
+
+        loop {
+            tokio::select! {
+                _ = sink.closed() => break,
+                finalized = receiver.recv() => match finalized {
+                    Ok(finalized) => sink.send(SubscriptionMessage::from_json(&finalized)?).await?,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(skipped, "Finality subscriber is lagging, skipping blocks");
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
 
-        Ok(blobs[blob_index].clone())
+        Ok(())
     }
 }
@@ -0,0 +1,455 @@
+//! Admission of `mikan_sampleBlob` and `mikan_getBlobProofByCommitment` requests, each FRI proof
+//! taking seconds of CPU.
+//!
+//! At most `max_concurrent` proofs are generated at once. Up to `max_queued` more requests wait
+//! for their turn, for at most `queue_timeout_ms`, and the others are rejected right away with
+//! [`MikanRpcError::SamplingBusy`](crate::error::MikanRpcError::SamplingBusy), which clients
+//! should treat as an HTTP 429 and retry later. These limits are independent of the other RPC
+//! methods.
+//!
+//! A single RPC connection has at most `max_per_connection` requests running or queued, beyond
+//! which its requests are rejected with
+//! [`MikanRpcError::SamplingConnectionBusy`](crate::error::MikanRpcError::SamplingConnectionBusy),
+//! so that one client cannot fill the queue of the others. Connections are told apart by
+//! [`ConnectionLayer`]. The handlers of WebSocket connections run outside of it and are only
+//! subject to the global limits.
+//!
+//! Identical requests arriving while a proof is being generated, typically light clients
+//! sampling the newest block at the same time, wait for it through [`InFlight`] rather than
+//! taking a slot of their own.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
+use tokio::task::futures::TaskLocalFuture;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamplingConfig {
+    /// Maximum number of sampling proofs generated at the same time
+    pub max_concurrent: usize,
+    /// Maximum number of requests waiting for a proof slot
+    pub max_queued: usize,
+    /// Queued requests still waiting after this many milliseconds are rejected
+    pub queue_timeout_ms: u64,
+    /// Maximum number of requests of one RPC connection running or queued
+    pub max_per_connection: usize,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 4,
+            max_queued: 16,
+            queue_timeout_ms: 10_000,
+            max_per_connection: 2,
+        }
+    }
+}
+
+/// Why a sampling request was not admitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// Its connection already has `max_per_connection` requests
+    Connection,
+    /// The queue is full, or the wait timed out
+    Busy,
+}
+
+/// Slot for generating one proof, released when dropped.
+#[derive(Debug)]
+pub struct SamplePermit {
+    _permit: OwnedSemaphorePermit,
+    _admission: Admission,
+    _connection: Option<ConnectionAdmission>,
+}
+
+/// Counts a request as admitted, running or queued, until dropped.
+#[derive(Debug)]
+struct Admission(Arc<AtomicUsize>);
+
+impl Drop for Admission {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Counts a request of a connection as admitted until dropped.
+#[derive(Debug)]
+struct ConnectionAdmission {
+    connections: Arc<Mutex<HashMap<ConnectionId, usize>>>,
+    connection: ConnectionId,
+}
+
+impl Drop for ConnectionAdmission {
+    fn drop(&mut self) {
+        let mut connections = self.connections.lock().unwrap();
+        if let Some(admitted) = connections.get_mut(&self.connection) {
+            *admitted -= 1;
+            if *admitted == 0 {
+                connections.remove(&self.connection);
+            }
+        }
+    }
+}
+
+/// Sampling requests being served or waiting, shared by all the RPC connections.
+#[derive(Debug, Clone)]
+pub struct SampleQueue {
+    config: SamplingConfig,
+    slots: Arc<Semaphore>,
+    admitted: Arc<AtomicUsize>,
+    /// Requests admitted per connection
+    connections: Arc<Mutex<HashMap<ConnectionId, usize>>>,
+}
+
+impl SampleQueue {
+    pub fn new(config: SamplingConfig) -> Self {
+        Self {
+            slots: Arc::new(Semaphore::new(config.max_concurrent.max(1))),
+            admitted: Arc::new(AtomicUsize::new(0)),
+            connections: Arc::default(),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &SamplingConfig {
+        &self.config
+    }
+
+    /// Waits for a free slot for a request of `connection`, if known.
+    pub async fn acquire(
+        &self,
+        connection: Option<ConnectionId>,
+    ) -> Result<SamplePermit, Rejection> {
+        let connection = connection
+            .map(|connection| self.admit_connection(connection))
+            .transpose()?;
+
+        let capacity = self.config.max_concurrent.max(1) + self.config.max_queued;
+        self.admitted
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |admitted| {
+                (admitted < capacity).then_some(admitted + 1)
+            })
+            .map_err(|_| Rejection::Busy)?;
+        let admission = Admission(self.admitted.clone());
+
+        let timeout = Duration::from_millis(self.config.queue_timeout_ms);
+        let permit = tokio::time::timeout(timeout, self.slots.clone().acquire_owned())
+            .await
+            .map_err(|_| Rejection::Busy)?
+            .map_err(|_| Rejection::Busy)?;
+
+        Ok(SamplePermit {
+            _permit: permit,
+            _admission: admission,
+            _connection: connection,
+        })
+    }
+
+    fn admit_connection(&self, connection: ConnectionId) -> Result<ConnectionAdmission, Rejection> {
+        let mut connections = self.connections.lock().unwrap();
+        let admitted = connections.entry(connection).or_default();
+        if *admitted >= self.config.max_per_connection.max(1) {
+            return Err(Rejection::Connection);
+        }
+        *admitted += 1;
+
+        Ok(ConnectionAdmission {
+            connections: self.connections.clone(),
+            connection,
+        })
+    }
+
+    /// Number of requests being served or waiting.
+    pub fn len(&self) -> usize {
+        self.admitted.load(Ordering::Acquire)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Identifies an RPC connection, see [`ConnectionLayer`].
+pub type ConnectionId = u64;
+
+tokio::task_local! {
+    static CONNECTION: ConnectionId;
+}
+
+/// Connection the RPC request being handled came over, `None` outside of [`ConnectionLayer`].
+pub fn current_connection() -> Option<ConnectionId> {
+    CONNECTION.try_with(|connection| *connection).ok()
+}
+
+/// Middleware of the RPC server giving each connection an id, which the requests it carries see
+/// through [`current_connection`]. The server wraps every new connection in the layer.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionLayer {
+    next: Arc<AtomicU64>,
+}
+
+impl<S> tower::Layer<S> for ConnectionLayer {
+    type Service = ConnectionService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConnectionService {
+            inner,
+            connection: self.next.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConnectionService<S> {
+    inner: S,
+    connection: ConnectionId,
+}
+
+impl<S, Request> tower::Service<Request> for ConnectionService<S>
+where
+    S: tower::Service<Request>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = TaskLocalFuture<ConnectionId, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        CONNECTION.scope(self.connection, self.inner.call(request))
+    }
+}
+
+/// Results being computed, keyed by request, shared with the identical requests arriving
+/// meanwhile.
+#[derive(Debug)]
+pub struct InFlight<K, V> {
+    requests: Arc<Mutex<HashMap<K, watch::Receiver<Option<V>>>>>,
+}
+
+impl<K, V> Clone for InFlight<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            requests: self.requests.clone(),
+        }
+    }
+}
+
+impl<K, V> Default for InFlight<K, V> {
+    fn default() -> Self {
+        Self {
+            requests: Arc::default(),
+        }
+    }
+}
+
+/// Forgets a request once its result is out, or once it is cancelled.
+struct Completion<K: Eq + Hash, V> {
+    requests: Arc<Mutex<HashMap<K, watch::Receiver<Option<V>>>>>,
+    key: K,
+}
+
+impl<K: Eq + Hash, V> Drop for Completion<K, V> {
+    fn drop(&mut self) {
+        self.requests.lock().unwrap().remove(&self.key);
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> InFlight<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `compute`, unless a request for `key` is already running, in which case its result
+    /// is returned. If that request is cancelled, `compute` runs after all.
+    pub async fn run(&self, key: K, compute: impl Future<Output = V>) -> V {
+        let running = {
+            let mut requests = self.requests.lock().unwrap();
+            match requests.get(&key) {
+                Some(result) => Err(result.clone()),
+                None => {
+                    let (sender, result) = watch::channel(None);
+                    requests.insert(key.clone(), result);
+                    Ok(sender)
+                }
+            }
+        };
+
+        match running {
+            Ok(sender) => {
+                let _completion = Completion {
+                    requests: self.requests.clone(),
+                    key,
+                };
+                let value = compute.await;
+                sender.send_replace(Some(value.clone()));
+                value
+            }
+            Err(mut result) => {
+                let shared = result
+                    .wait_for(Option::is_some)
+                    .await
+                    .ok()
+                    .and_then(|value| value.clone());
+                match shared {
+                    Some(value) => value,
+                    None => compute.await,
+                }
+            }
+        }
+    }
+
+    /// Number of distinct requests running.
+    pub fn len(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(max_concurrent: usize, max_queued: usize, queue_timeout_ms: u64) -> SampleQueue {
+        SampleQueue::new(SamplingConfig {
+            max_concurrent,
+            max_queued,
+            queue_timeout_ms,
+            max_per_connection: 1,
+        })
+    }
+
+    #[tokio::test]
+    async fn rejects_beyond_the_queue() {
+        let queue = queue(1, 1, 10_000);
+        let running = queue.acquire(None).await.unwrap();
+
+        let waiting = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.acquire(None).await.is_ok() })
+        };
+        tokio::task::yield_now().await;
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.acquire(None).await.err(), Some(Rejection::Busy));
+
+        drop(running);
+        assert!(waiting.await.unwrap());
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn times_out_in_the_queue() {
+        let queue = queue(1, 4, 10);
+        let _running = queue.acquire(None).await.unwrap();
+
+        assert_eq!(queue.acquire(None).await.err(), Some(Rejection::Busy));
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn limits_each_connection() {
+        let queue = queue(2, 0, 10_000);
+        let first = queue.acquire(Some(0)).await.unwrap();
+
+        assert_eq!(
+            queue.acquire(Some(0)).await.err(),
+            Some(Rejection::Connection)
+        );
+        let second = queue.acquire(Some(1)).await.unwrap();
+        assert_eq!(queue.len(), 2);
+
+        drop(first);
+        drop(second);
+        assert!(queue.acquire(Some(0)).await.is_ok());
+    }
+
+    /// Service answering with the connection its requests are handled for, once polled like
+    /// the handlers of the RPC server.
+    struct Echo;
+
+    impl tower::Service<()> for Echo {
+        type Response = Option<ConnectionId>;
+        type Error = std::convert::Infallible;
+        type Future =
+            std::pin::Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: ()) -> Self::Future {
+            Box::pin(async { Ok(current_connection()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn tells_connections_apart() {
+        use tower::{Layer, Service};
+
+        let layer = ConnectionLayer::default();
+        let mut first = layer.layer(Echo);
+        let mut second = layer.layer(Echo);
+
+        let a = first.call(()).await.unwrap();
+        let b = first.call(()).await.unwrap();
+        let c = second.call(()).await.unwrap();
+        assert!(a.is_some());
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(current_connection(), None);
+    }
+
+    #[tokio::test]
+    async fn shares_identical_requests() {
+        let in_flight = InFlight::new();
+        let computed = AtomicUsize::new(0);
+        let compute = |value: u64| {
+            let computed = &computed;
+            async move {
+                tokio::task::yield_now().await;
+                computed.fetch_add(1, Ordering::Relaxed);
+                value
+            }
+        };
+
+        let (first, second, other) = tokio::join!(
+            in_flight.run((1, 0), compute(7)),
+            in_flight.run((1, 0), compute(8)),
+            in_flight.run((1, 1), compute(9)),
+        );
+        assert_eq!((first, second, other), (7, 7, 9));
+        assert_eq!(computed.load(Ordering::Relaxed), 2);
+        assert!(in_flight.is_empty());
+    }
+
+    #[tokio::test]
+    async fn computes_when_the_shared_request_is_cancelled() {
+        let in_flight = InFlight::new();
+        let cancelled = in_flight.run(0, std::future::pending::<u64>());
+        let mut waiting = Box::pin(in_flight.run(0, async { 3 }));
+
+        // Both are registered, then the first one is dropped
+        tokio::select! {
+            biased;
+            _ = cancelled => unreachable!(),
+            _ = &mut waiting => unreachable!(),
+            _ = tokio::task::yield_now() => {}
+        }
+        assert!(in_flight.is_empty());
+        assert_eq!(waiting.await, 3);
+    }
+}
@@ -0,0 +1,179 @@
+//! Sampling node: follows the chain through the RPC server of another node, checks that headers
+//! link up, and samples every blob they commit to.
+//!
+//! Unless the sampler picks the seed, the server derives it from the block hash and a nonce of
+//! the sampler, see [`SeedDerivation`], so that an attestation of availability shows the seeds
+//! were not cherry-picked.
+
+use std::time::Duration;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use tracing::{info, warn};
+
+use crate::client::MikanClient;
+use crate::error::SamplingError;
+use crate::header::Header;
+use crate::role::NodeRoleConfig;
+use crate::types::codec::ssz::{decode_header, HeaderEncoding};
+
+/// Domain tag of the sampling seed derivation.
+const SEED_DOMAIN: &[u8] = b"mikan/sampling-seed/v1";
+
+/// Inputs of a sampling seed that anyone can recompute.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeedDerivation {
+    #[serde(with = "hex::serde")]
+    pub block_hash: [u8; 32],
+    pub blob_index: usize,
+    /// Supplied by the sampler, 0 if none
+    pub nonce: u64,
+}
+
+impl SeedDerivation {
+    /// First 8 bytes, big-endian, of
+    /// `keccak256(domain_tag || block_hash || blob_index (u64) || nonce (u64))`.
+    pub fn seed(&self) -> u64 {
+        let digest = Keccak256::new()
+            .chain_update(SEED_DOMAIN)
+            .chain_update(self.block_hash)
+            .chain_update((self.blob_index as u64).to_be_bytes())
+            .chain_update(self.nonce.to_be_bytes())
+            .finalize();
+        u64::from_be_bytes(digest[..8].try_into().expect("digest is 32 bytes"))
+    }
+}
+
+/// Runs until an RPC call fails or a header does not extend the previous one.
+pub async fn run(config: &NodeRoleConfig) -> Result<(), SamplingError> {
+    let client = MikanClient::new(&config.rpc_url)?;
+    let mut rng = crate::rng::seeded_rng();
+    let poll_interval = Duration::from_millis(config.poll_interval_ms);
+
+    info!(rpc_url = %config.rpc_url, "Starting sampling node");
+
+    // Start from the tip, history is only sampled going forward
+    let mut last_header: Option<Header> = None;
+    loop {
+        let latest = client.block_number().await?;
+        let mut height = last_header
+            .as_ref()
+            .map_or(latest, |header| header.block_number + 1);
+
+        while height <= latest {
+            let encoded = client.get_header(height, HeaderEncoding::Ssz).await?;
+            let header = decode_header(&encoded)?;
+            check_header(&header, last_header.as_ref())?;
+
+            let (sampled, failed) =
+                sample_header(&client, &header, config.samples_per_blob, &mut rng).await?;
+            if failed > 0 {
+                warn!(%height, sampled, failed, "Blob samples failed to verify");
+            } else {
+                info!(%height, sampled, "Sampled block");
+            }
+
+            last_header = Some(header);
+            height += 1;
+        }
+
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+fn check_header(header: &Header, parent: Option<&Header>) -> Result<(), SamplingError> {
+    if header.compute_block_hash() != header.block_hash() {
+        return Err(SamplingError::InvalidHash(header.block_number));
+    }
+
+    if let Some(parent) = parent {
+        if header.parent_hash() != parent.block_hash() {
+            return Err(SamplingError::NotExtending {
+                height: header.block_number,
+                parent: parent.block_number,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Samples every blob committed to by `header`, returns the number of samples taken and the
+/// number of those that failed to verify.
+async fn sample_header(
+    client: &MikanClient,
+    header: &Header,
+    samples_per_blob: usize,
+    rng: &mut impl Rng,
+) -> Result<(usize, usize), SamplingError> {
+    let (mut sampled, mut failed) = (0, 0);
+
+    for blob_index in 0..header.blob_count as usize {
+        for _ in 0..samples_per_blob {
+            let nonce = rng.gen();
+            let sample = client
+                .sample_blob(header.block_number, blob_index, None, Some(nonce))
+                .await?;
+            let derivation = SeedDerivation {
+                block_hash: header.block_hash(),
+                blob_index,
+                nonce,
+            };
+            let verified = sample.is_derived_from(&derivation)
+                && frieda::api::verify(sample.proof, Some(sample.sampling_seed));
+
+            sampled += 1;
+            if !verified {
+                failed += 1;
+            }
+        }
+    }
+
+    Ok((sampled, failed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+
+    #[test]
+    fn headers_must_link_up() {
+        let genesis = Block::genesis();
+        let child = Block::new(1, 1, genesis.hash(), Default::default(), vec![]);
+        let orphan = Block::new(1, 1, [1; 32], Default::default(), vec![]);
+
+        assert!(check_header(child.header(), Some(genesis.header())).is_ok());
+        assert!(check_header(orphan.header(), Some(genesis.header())).is_err());
+        assert!(check_header(orphan.header(), None).is_ok());
+    }
+
+    #[test]
+    fn seeds_depend_on_every_input() {
+        let derivation = SeedDerivation {
+            block_hash: [1; 32],
+            blob_index: 0,
+            nonce: 7,
+        };
+        assert_eq!(derivation.seed(), derivation.clone().seed());
+
+        let others = [
+            SeedDerivation {
+                block_hash: [2; 32],
+                ..derivation.clone()
+            },
+            SeedDerivation {
+                blob_index: 1,
+                ..derivation.clone()
+            },
+            SeedDerivation {
+                nonce: 8,
+                ..derivation.clone()
+            },
+        ];
+        for other in others {
+            assert_ne!(other.seed(), derivation.seed());
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Shares of the blobs of a block, committed to by the `share_root` of the header from
+//! [`SHARES_VERSION`] on, so that light clients can fetch and check byte ranges of a blob with
+//! `mikan_getShares` rather than download the whole blob.
+//!
+//! Each blob is split into shares of [`SHARE_SIZE`] bytes, whose hashes are the leaves of the
+//! share tree of the blob. The leaves of the `share_root` are, for every blob of the block in
+//! order, its length and the root of its share tree, see [`blob_share_leaf`]. Proofs are checked
+//! with [`crate::verify::verify_shares`].
+
+use std::ops::Range;
+
+use bytes::Bytes;
+use rs_merkle::{algorithms::Sha256, MerkleTree};
+
+use crate::blob::Blob;
+pub use crate::verify::{blob_share_leaf, share_count, share_leaf, SHARES_VERSION, SHARE_SIZE};
+
+/// Shares of a blob and the proofs that they belong to it, and it to the block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SharesProof {
+    pub blob_len: u64,
+    pub blob_share_root: [u8; 32],
+    /// Proof of the [`blob_share_leaf`] of the blob against the `share_root`
+    pub blob_proof: Vec<u8>,
+    pub first_share: usize,
+    pub shares: Vec<Bytes>,
+    /// Proof of the shares against `blob_share_root`
+    pub shares_proof: Vec<u8>,
+}
+
+fn share_tree(data: &[u8]) -> MerkleTree<Sha256> {
+    let leaves: Vec<[u8; 32]> = data.chunks(SHARE_SIZE).map(share_leaf).collect();
+    MerkleTree::from_leaves(&leaves)
+}
+
+/// Merkle root of the shares of a blob, zero for an empty blob.
+pub fn blob_share_root(data: &[u8]) -> [u8; 32] {
+    share_tree(data).root().unwrap_or_default()
+}
+
+fn blob_share_leaves(blobs: &[Blob]) -> Vec<[u8; 32]> {
+    blobs
+        .iter()
+        .map(|blob| blob_share_leaf(blob.data().len() as u64, &blob_share_root(blob.data())))
+        .collect()
+}
+
+/// Share root of a block carrying `blobs`, zero without blobs.
+pub fn compute_share_root(blobs: &[Blob]) -> [u8; 32] {
+    MerkleTree::<Sha256>::from_leaves(&blob_share_leaves(blobs))
+        .root()
+        .unwrap_or_default()
+}
+
+/// Shares covering the bytes `range` of the blob at `blob_index`, `None` if the range is empty
+/// or not within the blob.
+pub fn prove_shares(blobs: &[Blob], blob_index: usize, range: Range<usize>) -> Option<SharesProof> {
+    let blob = blobs.get(blob_index)?;
+    let data = Bytes::copy_from_slice(blob.data());
+    if range.is_empty() || range.end > data.len() {
+        return None;
+    }
+
+    let shares = range.start / SHARE_SIZE..(range.end - 1) / SHARE_SIZE + 1;
+    let tree = share_tree(&data);
+    let blob_proof = MerkleTree::<Sha256>::from_leaves(&blob_share_leaves(blobs))
+        .proof(&[blob_index])
+        .to_bytes();
+    let shares_proof = tree.proof(&shares.clone().collect::<Vec<_>>()).to_bytes();
+
+    Some(SharesProof {
+        blob_len: data.len() as u64,
+        blob_share_root: tree.root().unwrap_or_default(),
+        blob_proof,
+        first_share: shares.start,
+        shares: shares
+            .map(|index| {
+                let start = index * SHARE_SIZE;
+                data.slice(start..(start + SHARE_SIZE).min(data.len()))
+            })
+            .collect(),
+        shares_proof,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verify::{verify_shares, VerifyError};
+
+    fn blob(len: usize, byte: u8) -> Blob {
+        Blob::try_new(Bytes::from(vec![byte; len])).unwrap()
+    }
+
+    fn verify(root: &[u8; 32], blob_count: usize, index: usize, proof: &SharesProof) -> bool {
+        let shares: Vec<&[u8]> = proof.shares.iter().map(|share| share.as_ref()).collect();
+        verify_shares(
+            root,
+            blob_count,
+            index,
+            proof.blob_len,
+            &proof.blob_share_root,
+            &proof.blob_proof,
+            proof.first_share,
+            &shares,
+            &proof.shares_proof,
+        ) == Ok(())
+    }
+
+    #[test]
+    fn proves_byte_ranges() {
+        let blobs = [
+            blob(3 * SHARE_SIZE + 10, 1),
+            blob(100, 2),
+            blob(SHARE_SIZE, 3),
+        ];
+        let root = compute_share_root(&blobs);
+
+        let proof = prove_shares(&blobs, 0, SHARE_SIZE - 1..3 * SHARE_SIZE + 1).unwrap();
+        assert_eq!(proof.first_share, 0);
+        assert_eq!(proof.shares.len(), 4);
+        assert_eq!(proof.shares[3].len(), 10);
+        assert!(verify(&root, 3, 0, &proof));
+
+        let proof = prove_shares(&blobs, 1, 10..20).unwrap();
+        assert!(verify(&root, 3, 1, &proof));
+        assert!(!verify(&root, 3, 2, &proof));
+
+        let proof = prove_shares(&blobs, 2, 0..SHARE_SIZE).unwrap();
+        assert_eq!(proof.shares.len(), 1);
+        assert!(verify(&root, 3, 2, &proof));
+
+        assert!(prove_shares(&blobs, 1, 10..101).is_none());
+        assert!(prove_shares(&blobs, 1, 10..10).is_none());
+        assert!(prove_shares(&blobs, 3, 0..1).is_none());
+    }
+
+    #[test]
+    fn rejects_altered_shares() {
+        let blobs = [blob(2 * SHARE_SIZE, 1)];
+        let root = compute_share_root(&blobs);
+        let mut proof = prove_shares(&blobs, 0, 0..1).unwrap();
+
+        proof.shares[0] = Bytes::from(vec![2; SHARE_SIZE]);
+        assert!(!verify(&root, 1, 0, &proof));
+
+        proof.shares[0] = Bytes::from(vec![1; SHARE_SIZE - 1]);
+        let shares = [proof.shares[0].as_ref()];
+        assert_eq!(
+            verify_shares(
+                &root,
+                1,
+                0,
+                proof.blob_len,
+                &proof.blob_share_root,
+                &proof.blob_proof,
+                0,
+                &shares,
+                &proof.shares_proof
+            ),
+            Err(VerifyError::MalformedShares)
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Graceful shutdown, coordinated through a [`Shutdown`] token shared by the application loop,
+//! the RPC server and the background tasks.
+//!
+//! The token is triggered once, by SIGINT or SIGTERM, see [`Shutdown::on_signal`]. The
+//! application loop then stops handling consensus messages and flushes the state, see
+//! [`crate::state::State::shutdown`], while the other tasks return.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+#[derive(Clone, Debug)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            sender: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Resolves once the shutdown is triggered, immediately if it already was.
+    pub async fn triggered(&self) {
+        let mut receiver = self.sender.subscribe();
+        // The sender lives as long as `self`, so waiting cannot fail
+        let _ = receiver.wait_for(|triggered| *triggered).await;
+    }
+
+    /// Runs `task` until it completes or the shutdown is triggered, returning `None` in the latter
+    /// case.
+    pub async fn run_until<F: Future>(&self, task: F) -> Option<F::Output> {
+        tokio::select! {
+            output = task => Some(output),
+            () = self.triggered() => None,
+        }
+    }
+
+    /// Triggers the shutdown on the first SIGINT or SIGTERM.
+    pub async fn on_signal(self) {
+        let ctrl_c = tokio::signal::ctrl_c();
+
+        #[cfg(unix)]
+        let terminate = async {
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(mut signal) => {
+                    signal.recv().await;
+                }
+                Err(e) => {
+                    warn!(%e, "Cannot listen for SIGTERM");
+                    std::future::pending::<()>().await;
+                }
+            }
+        };
+        #[cfg(not(unix))]
+        let terminate = std::future::pending::<()>();
+
+        tokio::select! {
+            _ = ctrl_c => info!("Received SIGINT, shutting down"),
+            () = terminate => info!("Received SIGTERM, shutting down"),
+        }
+        self.trigger();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn triggers_every_clone() {
+        let shutdown = Shutdown::new();
+        let clone = shutdown.clone();
+        assert!(!clone.is_triggered());
+
+        let waiter = tokio::spawn(async move { clone.triggered().await });
+        shutdown.trigger();
+        waiter.await.unwrap();
+
+        assert!(shutdown.is_triggered());
+        assert_eq!(shutdown.run_until(std::future::pending::<()>()).await, None);
+    }
+}
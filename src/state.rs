@@ -1,47 +1,70 @@
 //! Internal state of the application. This is a simplified abstract to keep it simple.
 //! A regular application would have mempool implemented, a proper database and input methods like RPC.
 
-use crate::block::Block;
-use crate::error::StateError;
-use crate::malachite_types::codec::proto::ProtobufCodec;
-use crate::malachite_types::signing::Ed25519Provider;
-use crate::malachite_types::value::Value;
-use crate::malachite_types::{
+use crate::block::{median_timestamp, Block, BlockConfig, BLOCK_OVERHEAD_BYTES};
+use crate::builder::{BlockBuilder, BuildRequest};
+use crate::compression::{Compression, CompressionConfig};
+use crate::consensus_params::ConsensusParams;
+use crate::error::{CompressionError, Result, StateError};
+use crate::events::{block_events, ChainEvent, EventBus, FinalityFeed, Finalized};
+use crate::evidence::{
+    conflicting_precommits, Evidence, EvidenceProposal, EvidenceRecord, EvidenceSource,
+    ProposalWatch,
+};
+use crate::finality_params::FinalityParams;
+use crate::forks::{Divergence, DivergenceSource, ForkMonitor};
+use crate::governance::Governance;
+use crate::liveness::Liveness;
+use crate::metrics::BlockMetrics;
+use crate::namespaces::NamespaceRegistry;
+use crate::peers::PeerBook;
+use crate::retrieval::BlockRetrieval;
+use crate::round_state::RoundState;
+use crate::rpc::MikanRpcObj;
+use crate::sample_queue::SamplingConfig;
+use crate::shutdown::Shutdown;
+use crate::store::{DecidedValue, DerivedState, Store};
+use crate::streaming::{
+    PartStreamsMap, ProposalParts, ProposalStream, StreamMetrics, StreamingConfig,
+};
+use crate::testnet::NodePorts;
+use crate::transactions::exchange::{self, MempoolExchangeConfig};
+use crate::transactions::{cache::ValidationCache, pool::TransactionPool, Transaction};
+use crate::types::codec::proto::ProtobufCodec;
+use crate::types::sign_bytes::ProposalPartsHasher;
+use crate::types::signing::Ed25519Provider;
+use crate::types::value::{Value, ValueId};
+use crate::types::{
     address::Address,
     context::TestContext,
-    genesis::Genesis,
+    genesis::{Genesis, GENESIS_HEIGHT},
     height::Height,
-    proposal_part::{ProposalData, ProposalFin, ProposalInit, ProposalPart},
+    proposal_part::{ProposalInit, ProposalPart},
     validator_set::ValidatorSet,
 };
-use crate::rpc::MikanRpcObj;
-use crate::store::{DecidedValue, Store};
-use crate::streaming::{PartStreamsMap, ProposalParts};
-use crate::transactions::pool::TransactionPool;
-use bincode::config::standard;
+use crate::upgrades::{Feature, ProtocolSchedule};
 use bytes::Bytes;
 use chrono::Utc;
-use color_eyre::eyre;
-use eyre::Result;
 use jsonrpsee::server::ServerHandle;
-use malachitebft_app_channel::app::streaming::{StreamContent, StreamId, StreamMessage};
+use malachitebft_app_channel::app::streaming::{StreamId, StreamMessage};
 use malachitebft_app_channel::app::types::codec::Codec;
 use malachitebft_app_channel::app::types::core::{CommitCertificate, Round, Validity};
 use malachitebft_app_channel::app::types::{LocallyProposedValue, PeerId, ProposedValue};
-use sha3::Digest;
-use std::collections::HashSet;
+use malachitebft_proto::Error as ProtoError;
+use std::collections::BTreeMap;
 use std::mem::size_of;
 use std::time::{Duration, Instant};
-use tracing::{debug, error, info};
-
-/// Size of chunks in which the data is split for streaming
-const CHUNK_SIZE: usize = 128 * 1024; // 128 KiB
+use tracing::{debug, error, info, warn};
 
 // Path to the file containing the genesis
 // const GENESIS_PATH: &str = "./data/genesis.json";
 
-/// Maximum number of blocks to keep in history
-const MAX_HISTORY_LENGTH: u64 = 25;
+/// Maximum time spent looking for a valid transaction in the mempool
+const TX_SELECTION_TIME: Duration = Duration::from_secs(1);
+
+/// How often the mempool is checked while waiting for a transaction, see
+/// [`wait_for_transaction`]
+const TX_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 /// Represents the internal state of the application node
 /// Contains information about current height, round, proposals and blocks
@@ -53,16 +76,51 @@ pub struct State {
     pub store: Store,
     stream_nonce: u32,
     streams_map: PartStreamsMap,
+    compression: CompressionConfig,
+    block: BlockConfig,
+    block_metrics: BlockMetrics,
+    events: EventBus,
+    /// Fetching the pools of joining peers
+    mempool_exchange: MempoolExchangeConfig,
+    /// Feed of `mikan_subscribeFinality`
+    finality: FinalityFeed,
+    /// Validator set announced to event sinks, see [`ChainEvent::ValidatorSetChanged`]
+    announced_validator_set: Option<ValidatorSet>,
+    /// When the previous height was decided, to pace block production
+    last_decided_at: Option<Instant>,
+    /// Timestamps of recently decided blocks by height, see [`Self::recent_timestamps`]
+    block_timestamps: BTreeMap<u64, u64>,
+    validation_cache: ValidationCache,
+    /// Decided namespace registrations, shared with the transaction pool
+    namespaces: NamespaceRegistry,
+    /// Decided votes and the chain parameters they set, shared with the transaction pool
+    governance: Governance,
+    /// Decided jailings and the validator sets they make, shared with the transaction pool
+    liveness: Liveness,
+    /// Proposals seen for recent heights, to detect proposers signing two of them
+    proposal_watch: ProposalWatch,
+    /// Undecided proposals of the current height by round, loaded from the store on startup,
+    /// see [`Self::load_undecided_proposals`]
+    undecided: BTreeMap<Round, ProposedValue<TestContext>>,
+    /// External block builder, see [`crate::builder`]
+    pub builder: Option<BlockBuilder>,
+    #[cfg(feature = "byzantine")]
+    pub byzantine: crate::byzantine::ByzantineConfig,
+    #[cfg(feature = "netsim")]
+    pub link_conditions: Option<crate::netsim::LinkConditions>,
     // block_proposer: BlockProposer,
     // block_executor: BlockExecutor,
     pub rpc_server: Option<MikanRpcObj>,
     pub rpc_server_handle: Option<ServerHandle>,
-    // TODO: replace this wiith rpc server
-    // pub transaction_pool: TransactionPool,
+    /// Shared with the RPC server, re-validated after each commit
+    pub transaction_pool: TransactionPool,
     pub current_height: Height,
     pub current_round: Round,
     pub current_proposer: Option<Address>,
-    pub peers: HashSet<PeerId>,
+    pub peers: PeerBook,
+    pub forks: ForkMonitor,
+    /// Round consensus is in, for `mikan_getConsensusState`
+    pub round_state: RoundState,
 }
 
 /// Represents errors that can occur during the verification of a proposal's signature.
@@ -78,6 +136,19 @@ enum SignatureVerificationError {
     InvalidSignature,
 }
 
+/// Reasons for rejecting a proposal stream as soon as its `Init` part is received.
+#[derive(Debug)]
+enum InitValidationError {
+    /// The proposal is for a height that was already decided.
+    Outdated,
+
+    /// The proposer is not in the validator set.
+    ProposerNotFound,
+
+    /// The proposer is not the one consensus expects for the current round.
+    UnexpectedProposer,
+}
+
 impl State {
     #[allow(clippy::too_many_arguments)]
     /// Creates a new State instance with the given validator address and starting height
@@ -89,7 +160,19 @@ impl State {
         height: Height,
         store: Store,
         transaction_pool: TransactionPool,
+        peers: PeerBook,
+        forks: ForkMonitor,
+        compression: CompressionConfig,
+        streaming: StreamingConfig,
+        stream_metrics: StreamMetrics,
+        block_metrics: BlockMetrics,
+        block: BlockConfig,
+        events: EventBus,
+        mempool_exchange: MempoolExchangeConfig,
+        sampling: SamplingConfig,
+        retrieval: Option<BlockRetrieval>,
         enable_rpc: bool,
+        shutdown: Shutdown,
     ) -> Self {
         // Get the node's home directory from the store path
         let store_path = store.get_path();
@@ -110,15 +193,39 @@ impl State {
         // let eth_genesis: EthGenesis = serde_json::from_str(&eth_genesis_json).unwrap();
 
         // let block_executor = BlockExecutor::new(db_path, eth_genesis.clone()).unwrap();
+        let finality = FinalityFeed::new();
+        let round_state = RoundState::new(address);
+        let validation_cache = transaction_pool.validation_cache().clone();
+        let namespaces = transaction_pool.namespaces().clone();
+        let governance = transaction_pool.governance().clone();
+        let liveness = transaction_pool.liveness().clone();
+        let pool = transaction_pool.clone();
+
         println!("enable_rpc: {}", enable_rpc);
         let rpc_server = if enable_rpc {
-            MikanRpcObj::new(transaction_pool, store.clone())
-                .start(8545 + node_index as u16)
-                .await
-                .ok()
+            MikanRpcObj::new(
+                transaction_pool,
+                store.clone(),
+                peers.clone(),
+                forks.clone(),
+                &genesis,
+                finality.clone(),
+                round_state.clone(),
+            )
+            .with_sampling(sampling)
+            .with_retrieval(retrieval)
+            .start(NodePorts::new(node_index).rpc, shutdown)
+            .await
+            .ok()
         } else {
             None
         };
+        let streams_map = PartStreamsMap::new(
+            genesis.chain_id.clone(),
+            streaming,
+            genesis.consensus.chunk_size,
+            stream_metrics,
+        );
 
         Self {
             genesis,
@@ -130,67 +237,551 @@ impl State {
             address,
             store,
             stream_nonce: 0,
-            streams_map: PartStreamsMap::new(),
-            peers: HashSet::new(),
+            streams_map,
+            compression,
+            block,
+            block_metrics,
+            events,
+            mempool_exchange,
+            finality,
+            announced_validator_set: None,
+            last_decided_at: None,
+            block_timestamps: BTreeMap::new(),
+            validation_cache,
+            namespaces,
+            governance,
+            liveness,
+            proposal_watch: ProposalWatch::default(),
+            undecided: BTreeMap::new(),
+            builder: None,
+            #[cfg(feature = "byzantine")]
+            byzantine: Default::default(),
+            #[cfg(feature = "netsim")]
+            link_conditions: None,
+            peers,
+            forks,
+            round_state,
             rpc_server: rpc_server.clone().map(|(_, rpc_server)| rpc_server),
             rpc_server_handle: rpc_server.map(|(handle, _)| handle),
+            transaction_pool: pool,
             // block_proposer: BlockProposer::new(&blocks_file).unwrap(),
             // block_executor,
             // rpc_server,
         }
     }
 
-    pub async fn make_block(&mut self) -> eyre::Result<Bytes> {
+    /// Flushes the state once the shutdown is triggered: stops the RPC server, persists a final
+    /// snapshot of the database metrics next to the store and logs a summary.
+    pub async fn shutdown(&mut self) -> Result<()> {
+        if let Some(handle) = self.rpc_server_handle.take() {
+            // Already stopped if the server saw the shutdown first
+            let _ = handle.stop();
+            handle.stopped().await;
+        }
+
+        let db_size = self.store.db_size()?;
+        self.store.metrics().set_db_size(db_size as usize);
+        let snapshot = self.store.metrics().snapshot();
+        let snapshot_path = self.store.get_path().with_file_name("metrics.json");
+        tokio::fs::write(&snapshot_path, serde_json::to_vec_pretty(&snapshot)?).await?;
+
+        let last_height = self
+            .store
+            .max_decided_value_height()
+            .await
+            .map_or(0, |height| height.as_u64());
+        info!(
+            last_height,
+            db_size,
+            metrics = %snapshot_path.display(),
+            "Shutdown complete"
+        );
+
+        Ok(())
+    }
+
+    /// Persists the genesis block on first start, so that proposals and validation at the first
+    /// height have a parent. On later starts, checks that the stored one matches the genesis file.
+    pub async fn bootstrap_genesis(&self) -> Result<()> {
+        let genesis_block = self.genesis.block().to_bytes()?;
+
+        match self.store.get_decided_block(GENESIS_HEIGHT).await? {
+            None => {
+                info!(hash = %hex::encode(self.genesis.block().hash()), "Storing genesis block");
+                self.store
+                    .store_decided_block_data(GENESIS_HEIGHT, genesis_block)
+                    .await?;
+            }
+            Some(stored) if stored == genesis_block => {}
+            Some(_) => return Err(StateError::GenesisMismatch.into()),
+        }
+
+        Ok(())
+    }
+
+    /// Loads the undecided proposals of the current height, those we built or received before a
+    /// restart, so that they are replayed to consensus from their round on, see
+    /// [`Self::undecided_proposal`].
+    pub async fn load_undecided_proposals(&mut self) -> Result<()> {
+        let proposals = self
+            .store
+            .get_undecided_proposals(self.current_height)
+            .await?;
+        if !proposals.is_empty() {
+            info!(
+                height = %self.current_height,
+                count = proposals.len(),
+                "Loaded undecided proposals"
+            );
+        }
+        self.undecided = proposals
+            .into_iter()
+            .map(|proposal| (proposal.round, proposal))
+            .collect();
+
+        Ok(())
+    }
+
+    /// Stores an undecided proposal and its block data, and keeps it for the rounds to come if it
+    /// is for the current height.
+    async fn store_undecided(
+        &mut self,
+        proposal: ProposedValue<TestContext>,
+        data: Bytes,
+    ) -> Result<()> {
+        let (height, round) = (proposal.height, proposal.round);
+        self.store
+            .store_undecided_proposal(proposal.clone())
+            .await?;
+        self.store
+            .store_undecided_block_data(height, round, data)
+            .await?;
+
+        if height == self.current_height {
+            self.undecided.entry(round).or_insert(proposal);
+        }
+
+        Ok(())
+    }
+
+    /// Value to replay to consensus when it starts `round` at `height`: the proposal already
+    /// known for that round or, if we are its `proposer`, the last value we built at an earlier
+    /// round of the height, proposed again rather than building a new block.
+    pub async fn undecided_proposal(
+        &mut self,
+        height: Height,
+        round: Round,
+        proposer: Address,
+    ) -> Result<Option<ProposedValue<TestContext>>> {
+        if height != self.current_height {
+            return Ok(self.store.get_undecided_proposal(height, round).await?);
+        }
+        if let Some(proposal) = self.undecided.get(&round) {
+            return Ok(Some(proposal.clone()));
+        }
+        if proposer != self.address {
+            return Ok(None);
+        }
+
+        let Some(built_round) = self
+            .undecided
+            .range(..round)
+            .rev()
+            .find(|(_, proposal)| proposal.proposer == self.address)
+            .map(|(round, _)| *round)
+        else {
+            return Ok(None);
+        };
+        let Some(data) = self.store.get_block_data(height, built_round).await? else {
+            return Ok(None);
+        };
+
+        info!(%height, %round, %built_round, "Proposing again the value built at an earlier round");
+        self.propose_value(height, round, data).await?;
+        Ok(self.undecided.get(&round).cloned())
+    }
+
+    /// Value we already built for `round` at the current height, with its block data, to answer
+    /// consensus without building a new block.
+    pub async fn built_value(
+        &self,
+        height: Height,
+        round: Round,
+    ) -> Result<Option<(LocallyProposedValue<TestContext>, Bytes)>> {
+        if height != self.current_height {
+            return Ok(None);
+        }
+        let Some(proposal) = self
+            .undecided
+            .get(&round)
+            .filter(|proposal| proposal.proposer == self.address)
+        else {
+            return Ok(None);
+        };
+        let Some(data) = self.store.get_block_data(height, round).await? else {
+            return Ok(None);
+        };
+
+        Ok(Some((
+            LocallyProposedValue::new(height, round, proposal.value.clone()),
+            data,
+        )))
+    }
+
+    /// Fetches the transactions of a joining peer missing from our pool, see [`exchange`].
+    pub fn exchange_mempool(&self, peer_id: PeerId) {
+        exchange::spawn(
+            &self.mempool_exchange,
+            self.transaction_pool.clone(),
+            peer_id,
+        );
+    }
+
+    /// Builds the block to propose at the current height. When there is no transaction to include
+    /// and the `[block]` policy says not to propose an empty block yet, waits for a transaction
+    /// as long as consensus allows, and proposes an empty block only if the heartbeat fell due in
+    /// the meantime. Returns `None` otherwise. Fails if the node does not support the protocol
+    /// version of the height.
+    ///
+    /// `timeout` is the time consensus waits for the value, the answer is paced by the target
+    /// block time within it.
+    pub async fn make_block(&mut self, timeout: Duration) -> Result<Option<Bytes>> {
         let start = Instant::now();
+        // Without support for the scheduled version, stop until the node is upgraded
+        let version = self
+            .genesis
+            .protocol
+            .version_at(self.current_height.as_u64());
+        self.genesis
+            .protocol
+            .check_version(self.current_height.as_u64(), version)
+            .map_err(StateError::UnsupportedVersion)?;
+        let parent_height = self.current_height - 1;
         let prev_block = self
             .store
-            .get_decided_block(self.current_height - 1)
-            .await?;
-        let prev_block = prev_block.unwrap();
-        let (prev_block, _): (Block, usize) =
-            bincode::borrow_decode_from_slice(prev_block.as_ref(), standard())?;
+            .get_decided_block(parent_height)
+            .await?
+            .ok_or(StateError::MissingBlock(parent_height))?;
+        let prev_block: Block = ProtobufCodec.decode(prev_block)?;
+        let recent_timestamps = self.recent_timestamps().await?;
 
         let rpc_serv = self
             .rpc_server
             .as_ref()
             .ok_or(StateError::RpcServerNotEnabled)?;
 
-        let tx = loop {
-            let tx = rpc_serv.get_top_transaction();
-            if start.elapsed() > Duration::from_secs(1) || tx.is_none() {
-                info!("No transaction to add to block");
-                break None;
-            }
-            if tx.is_some() {
-                let tx = tx.unwrap();
-                if tx.validate() {
-                    break Some(tx);
-                } else {
-                    info!("Invalid transaction, skipping");
+        let params = self.governance.params_at(self.current_height.as_u64());
+        // Bounded by both the configuration and the size voted by governance
+        let max_tx_bytes = self.block.max_tx_bytes().min(
+            usize::try_from(params.max_block_bytes)
+                .unwrap_or(usize::MAX)
+                .saturating_sub(BLOCK_OVERHEAD_BYTES),
+        );
+        let mut txs = match self
+            .built_transactions(&prev_block, version, max_tx_bytes)
+            .await
+        {
+            Some(txs) => txs,
+            None => loop {
+                let tx = rpc_serv.get_top_transaction();
+                if start.elapsed() > TX_SELECTION_TIME || tx.is_none() {
+                    info!("No transaction to add to block");
+                    break vec![];
                 }
-            }
+                if let Some(tx) = tx {
+                    if self.can_include(&tx, max_tx_bytes) {
+                        info!(
+                            "Valid transaction, {} adding to block",
+                            hex::encode(tx.hash())
+                        );
+                        break vec![tx];
+                    } else {
+                        info!("Invalid transaction, skipping");
+                    }
+                }
+            },
         };
-        while start.elapsed() < Duration::from_secs(1) {}
 
-        let txs = if let Some(tx) = tx {
-            info!(
-                "Valid transaction, {} adding to block",
-                hex::encode(tx.hash())
-            );
-            vec![tx]
-        } else {
-            vec![]
-        };
-        let block = Block::new(
+        let parent_timestamp = prev_block.header().timestamp;
+        let now = Utc::now().timestamp() as u64;
+        if txs.is_empty() && !self.block.propose_empty(parent_timestamp, now) {
+            // Until the heartbeat falls due, within the first half of the timeout
+            let heartbeat_due = parent_timestamp.saturating_add(self.block.heartbeat_interval_secs);
+            let heartbeat_at =
+                Instant::now() + Duration::from_secs(heartbeat_due.saturating_sub(now));
+            let deadline = (start + timeout / 2).min(heartbeat_at);
+            info!("No transaction to propose, waiting for one until the propose deadline");
+            let tx = wait_for_transaction(deadline, || {
+                rpc_serv
+                    .get_top_transaction()
+                    .filter(|tx| self.can_include(tx, max_tx_bytes))
+            })
+            .await;
+            match tx {
+                Some(tx) => txs.push(tx),
+                // The heartbeat fell due while waiting
+                None if deadline == heartbeat_at => {}
+                None => return Ok(None),
+            }
+        }
+        tokio::time::sleep_until(self.block_deadline(start, timeout).into()).await;
+
+        // Never behind the parent, even with a lagging clock, nor too far ahead of the median of
+        // recent blocks, even with a clock ahead of the chain, or the block would be invalid
+        let mut timestamp = (Utc::now().timestamp() as u64).max(prev_block.header().timestamp);
+        if let Some(median) = median_timestamp(&recent_timestamps) {
+            let upper_bound = median.saturating_add(self.genesis.consensus.max_timestamp_drift);
+            timestamp = timestamp.min(upper_bound);
+        }
+        let mut block = Block::new(
             self.current_height.as_u64(),
-            Utc::now().timestamp() as u64,
+            timestamp,
             prev_block.hash(),
             self.address,
             txs,
+        )
+        .with_version(version);
+        // Genesis is not decided, every other parent is
+        if let Some(decided) = self.store.get_decided_value(parent_height).await? {
+            block = block.with_last_commit(decided.certificate);
+        }
+
+        Ok(Some(ProtobufCodec.encode(&block)?))
+    }
+
+    /// Whether `tx` can be included in the block proposed at the current height.
+    fn can_include(&self, tx: &Transaction, max_tx_bytes: usize) -> bool {
+        let (protocol, height) = (&self.genesis.protocol, self.current_height.as_u64());
+        let params = self.governance.params_at(height);
+
+        tx.data().len() <= self.genesis.max_blobs_per_tx
+            && tx
+                .data()
+                .iter()
+                .all(|blob| blob.data().len() <= self.genesis.consensus.max_blob_bytes)
+            && tx.encoded_len() <= max_tx_bytes
+            && self.validation_cache.validate(tx)
+            && (!protocol.is_active(Feature::Namespaces, height)
+                || self.namespaces.check(tx).is_ok())
+            && (!protocol.is_active(Feature::GasMetering, height)
+                || params.gas.gas_used(tx).is_ok())
+            && (tx.governance().is_none()
+                || (protocol.is_active(Feature::Governance, height)
+                    && self.governance.check(tx, height).is_ok()))
+            && (!tx.unjail()
+                || (protocol.is_active(Feature::Jailing, height)
+                    && self.liveness.check(tx, height).is_ok()))
+    }
+
+    /// Transactions picked by the external builder, `None` to build from the pool instead, see
+    /// [`crate::builder`].
+    async fn built_transactions(
+        &self,
+        prev_block: &Block,
+        version: u32,
+        max_tx_bytes: usize,
+    ) -> Option<Vec<Transaction>> {
+        let builder = self.builder.as_ref()?;
+        let request = BuildRequest {
+            chain_id: self.genesis.chain_id.clone(),
+            height: self.current_height.as_u64(),
+            parent_hash: prev_block.hash(),
+            parent_timestamp: prev_block.header().timestamp,
+            version,
+            proposer: self.address,
+            max_bytes: max_tx_bytes,
+            max_blobs_per_tx: self.genesis.max_blobs_per_tx,
+        };
+
+        let txs = match builder.build(&request).await {
+            Ok(txs) => txs,
+            Err(e) => {
+                warn!(
+                    url = builder.url(),
+                    "Block builder failed, building locally: {e}"
+                );
+                return None;
+            }
+        };
+        let total_bytes: usize = txs.iter().map(Transaction::encoded_len).sum();
+        if total_bytes > max_tx_bytes {
+            warn!(
+                url = builder.url(),
+                total_bytes, "Built transactions do not fit in a block, building locally"
+            );
+            return None;
+        }
+        if let Some(tx) = txs.iter().find(|tx| !self.can_include(tx, max_tx_bytes)) {
+            warn!(
+                url = builder.url(),
+                "Built transaction {} cannot be included, building locally",
+                hex::encode(tx.hash())
+            );
+            return None;
+        }
+
+        info!(
+            url = builder.url(),
+            count = txs.len(),
+            "Using built transactions"
         );
+        Some(txs)
+    }
 
-        let block_data = bincode::encode_to_vec(&block, standard())?;
-        Ok(Bytes::from(block_data))
+    /// When to answer consensus with a block requested at `start`: the target block time after
+    /// the previous decision, but within the first half of `timeout`, leaving the other half to
+    /// stream the proposal to our peers.
+    fn block_deadline(&self, start: Instant, timeout: Duration) -> Instant {
+        let latest = start + timeout / 2;
+        match self.last_decided_at {
+            Some(decided_at) => (decided_at + self.block.target_block_time()).min(latest),
+            None => start,
+        }
+    }
+
+    /// Timestamps of the last [`ConsensusParams::median_time_span`] decided blocks below the current height,
+    /// oldest first, read from the kept header of blocks pruned for size. Cached, since reading
+    /// them from the store means decoding whole blocks.
+    async fn recent_timestamps(&mut self) -> Result<Vec<u64>> {
+        let end = self.current_height.as_u64();
+        let span = self.genesis.consensus.median_time_span;
+        let start = end.saturating_sub(span as u64);
+        self.block_timestamps.retain(|height, _| *height >= start);
+
+        let mut timestamps = Vec::with_capacity(span);
+        for height in start..end {
+            if let Some(timestamp) = self.block_timestamps.get(&height) {
+                timestamps.push(*timestamp);
+                continue;
+            }
+
+            // Only the header is kept of blocks pruned for size
+            let timestamp = match self.store.get_decided_block(Height::new(height)).await? {
+                Some(data) => {
+                    let block: Block = ProtobufCodec.decode(data)?;
+                    block.header().timestamp
+                }
+                None => match self.store.get_pruned_header(Height::new(height)).await? {
+                    Some(header) => header.timestamp,
+                    // Genesis, or not synced yet
+                    None => continue,
+                },
+            };
+            self.block_timestamps.insert(height, timestamp);
+            timestamps.push(timestamp);
+        }
+
+        Ok(timestamps)
+    }
+
+    /// Checks that a value received through sync is a valid block extending our chain, and stores
+    /// it so that it can be committed once consensus decides it. Otherwise the value is returned
+    /// as invalid, a block that does not extend our chain being recorded as a divergence.
+    pub async fn process_synced_value(
+        &mut self,
+        height: Height,
+        round: Round,
+        proposer: Address,
+        value_bytes: Bytes,
+    ) -> Result<ProposedValue<TestContext>> {
+        let value = match decode_value(value_bytes.clone()) {
+            Ok(value) => value,
+            Err(e) => {
+                error!(%height, %round, error = %e, "Failed to decode synced value");
+                return Ok(ProposedValue {
+                    height,
+                    round,
+                    valid_round: Round::Nil,
+                    proposer,
+                    value: Value::new(value_bytes),
+                    validity: Validity::Invalid,
+                });
+            }
+        };
+
+        let mut proposal = ProposedValue {
+            height,
+            round,
+            valid_round: Round::Nil,
+            proposer,
+            value: value.clone(),
+            validity: Validity::Valid,
+        };
+
+        let block: Block = match ProtobufCodec.decode(value.extensions.clone()) {
+            Ok(block) => block,
+            Err(e) => {
+                error!(%height, %round, error = %e, "Failed to decode synced block");
+                proposal.validity = Validity::Invalid;
+                return Ok(proposal);
+            }
+        };
+
+        let Some(prev_block) = self.store.get_decided_block(height - 1).await? else {
+            error!(%height, %round, "Previous block not found, cannot validate synced block");
+            proposal.validity = Validity::Invalid;
+            return Ok(proposal);
+        };
+
+        let prev_block: Block = ProtobufCodec.decode(prev_block)?;
+        if block.parent_hash() != prev_block.hash() {
+            let divergence = Divergence {
+                height: height.as_u64(),
+                source: DivergenceSource::Sync,
+                local_parent: prev_block.hash(),
+                block_parent: block.parent_hash(),
+            };
+            // Only halting once decided with a valid certificate, see `Self::commit`
+            error!(%divergence, "Received synced block that does not extend our chain");
+            self.forks.record(divergence);
+            proposal.validity = Validity::Invalid;
+            return Ok(proposal);
+        }
+
+        // The sync peer is not trusted: check the block like a proposal, its certificate is
+        // checked once decided, see `Self::commit`
+        let recent_timestamps = self.recent_timestamps().await?;
+        let validation_cache = self.validation_cache.clone();
+        let namespaces = self.namespaces.clone();
+        let governance = self.governance.clone();
+        let liveness = self.liveness.clone();
+        let consensus = self.genesis.consensus.clone();
+        let max_blobs_per_tx = self.genesis.max_blobs_per_tx;
+        let protocol = self.genesis.protocol.clone();
+        let finality = FinalityParams::new(
+            self.genesis.chain_id.clone(),
+            self.get_validator_set(height - 1),
+        );
+        let valid = tokio::task::spawn_blocking(move || {
+            block.header().proposer_address == proposer
+                && matches!(
+                    block.is_valid(
+                        height.as_u64(),
+                        &prev_block,
+                        &recent_timestamps,
+                        &validation_cache,
+                        &namespaces,
+                        &governance,
+                        &liveness,
+                        &consensus,
+                        max_blobs_per_tx,
+                        &protocol,
+                        &finality,
+                    ),
+                    Ok(true)
+                )
+        })
+        .await?;
+        if !valid {
+            error!(%height, %round, %proposer, "Received invalid synced block");
+            proposal.validity = Validity::Invalid;
+            return Ok(proposal);
+        }
+
+        self.store_undecided(proposal.clone(), value.extensions)
+            .await?;
+
+        Ok(proposal)
     }
 
     /// Returns the earliest height available in the state
@@ -207,13 +798,45 @@ impl State {
         &mut self,
         from: PeerId,
         part: StreamMessage<ProposalPart>,
-    ) -> eyre::Result<Option<ProposedValue<TestContext>>> {
+    ) -> Result<Option<ProposedValue<TestContext>>> {
         let sequence = part.sequence;
+        let data_len = part
+            .content
+            .as_data()
+            .and_then(|p| p.as_data())
+            .map_or(0, |data| data.bytes.len());
+        self.peers.record_proposal_part(from, data_len);
+
+        if let Some(init) = part.content.as_data().and_then(|p| p.as_init()) {
+            self.peers.observe_height(from, init.height);
+
+            // Don't buffer the data of a proposal we would reject once complete
+            if let Err(e) = self.validate_proposal_init(init) {
+                warn!(
+                    %from,
+                    height = %self.current_height,
+                    round = %self.current_round,
+                    part.height = %init.height,
+                    part.round = %init.round,
+                    error = ?e,
+                    "Received invalid proposal init, ignoring stream"
+                );
 
-        // Check if we have a full proposal - for now we are assuming that the network layer will stop spam/DOS
-        let Some(parts) = self.streams_map.insert(from, part) else {
-            return Ok(None);
+                self.streams_map.reject(from, part.stream_id);
+                return Ok(None);
+            }
+        }
+
+        // Check if we have a full proposal
+        let parts = match self.streams_map.insert(from, part) {
+            Ok(Some(parts)) => parts,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                warn!(%from, %sequence, error = %e, "Rejected proposal part");
+                return Ok(None);
+            }
         };
+        self.peers.record_completed_stream(from);
 
         // Check if the proposal is outdated
         if parts.height < self.current_height {
@@ -229,23 +852,33 @@ impl State {
             return Ok(None);
         }
 
-        if let Err(e) = self.verify_proposal_signature(&parts) {
+        // The digest was computed while assembling the parts, checking the signature is cheap
+        if let Err(e) = verify_proposal_signature(&self.get_validator_set(parts.height), &parts) {
             error!(
-                height = %self.current_height,
-                round = %self.current_round,
+                %from,
+                height = %parts.height,
+                round = %parts.round,
                 error = ?e,
                 "Received proposal with invalid signature, ignoring"
             );
+            self.peers.record_invalid_signature(from);
 
             return Ok(None);
         }
 
-        let part_height = parts.height;
-        let part_round = parts.round;
+        if let Some(signature) = parts.signature {
+            let proposal = EvidenceProposal {
+                height: parts.height.as_u64(),
+                round: parts.round.as_u32().unwrap_or_default(),
+                proposer: parts.proposer,
+                digest: parts.digest,
+                signature,
+            };
+            if let Some(evidence) = self.proposal_watch.observe(proposal) {
+                self.record_evidence(evidence).await?;
+            }
+        }
 
-        // Re-assemble the proposal from its parts
-        let (value, data) = assemble_value_from_parts(parts);
-        let (block, _): (Block, usize) = bincode::borrow_decode_from_slice(&data, standard())?;
         let prev_block = self
             .store
             .get_decided_block(self.current_height - 1)
@@ -254,27 +887,58 @@ impl State {
             error!("Previous block not found");
             return Ok(None);
         };
-        let (prev_block, _) = bincode::borrow_decode_from_slice(prev_block.as_ref(), standard())?;
-        if !block.is_valid(self.current_height.as_u64(), &prev_block)? {
-            error!("Invalid block");
+
+        // Decompression, decoding and blob commitments take long enough for large blocks
+        // that they must not hold up the consensus task
+        let recent_timestamps = self.recent_timestamps().await?;
+        let chain_id = self.genesis.chain_id.clone();
+        // The last commit of the block decided the previous height
+        let validator_set = self.get_validator_set(self.current_height - 1);
+        let validation_cache = self.validation_cache.clone();
+        let namespaces = self.namespaces.clone();
+        let governance = self.governance.clone();
+        let liveness = self.liveness.clone();
+        let consensus = self.genesis.consensus.clone();
+        let max_blobs_per_tx = self.genesis.max_blobs_per_tx;
+        let protocol = self.genesis.protocol.clone();
+        let forks = self.forks.clone();
+        let height = self.current_height;
+        let verified = tokio::task::spawn_blocking(move || {
+            verify_proposal(
+                &chain_id,
+                &validator_set,
+                &validation_cache,
+                &namespaces,
+                &governance,
+                &liveness,
+                &consensus,
+                max_blobs_per_tx,
+                &protocol,
+                &forks,
+                height,
+                parts,
+                prev_block,
+                recent_timestamps,
+            )
+        })
+        .await??;
+
+        let Some((value, data)) = verified else {
             return Ok(None);
-        }
+        };
 
         // Log first 32 bytes of proposal data and total size
         if data.len() >= 32 {
             info!(
-                "Proposal data[0..32]: {}, total_size: {} bytes, id: {:x}",
+                "Proposal data[0..32]: {}, total_size: {} bytes, id: {}",
                 hex::encode(&data[..32]),
                 data.len(),
-                value.value.id().as_u64()
+                value.value.id()
             );
         }
 
         // Store the proposal and its data
-        self.store.store_undecided_proposal(value.clone()).await?;
-        self.store
-            .store_undecided_block_data(part_height, part_round, data)
-            .await?;
+        self.store_undecided(value.clone(), data).await?;
 
         Ok(Some(value))
     }
@@ -286,16 +950,26 @@ impl State {
 
     /// Commits a value with the given certificate, updating internal state
     /// and moving to the next height
-    pub async fn commit(
-        &mut self,
-        certificate: CommitCertificate<TestContext>,
-    ) -> eyre::Result<()> {
+    pub async fn commit(&mut self, certificate: CommitCertificate<TestContext>) -> Result<()> {
         info!(
             height = %certificate.height,
             round = %certificate.round,
             "Looking for certificate"
         );
 
+        // Synced values reach us with the certificate only once decided, never persist a value
+        // that is not backed by our validator set
+        if let Err(error) = self
+            .signing_provider
+            .verify_certificate(&certificate, &self.get_validator_set(certificate.height))
+        {
+            return Err(StateError::InvalidCertificate {
+                height: certificate.height,
+                error,
+            }
+            .into());
+        }
+
         let proposal = self
             .store
             .get_undecided_proposal(certificate.height, certificate.round)
@@ -315,49 +989,186 @@ impl State {
             Err(e) => return Err(e.into()),
         };
 
-        self.store
-            .store_decided_value(&certificate, proposal.value)
-            .await?;
+        // Backed by our validator set, a value that does not extend our chain was decided by the
+        // network on top of another chain than ours
+        let block: Block = ProtobufCodec.decode(proposal.value.extensions.clone())?;
+        if let Some(parent) = self.store.get_decided_block(certificate.height - 1).await? {
+            let parent: Block = ProtobufCodec.decode(parent)?;
+            if block.parent_hash() != parent.hash() {
+                let divergence = Divergence {
+                    height: certificate.height.as_u64(),
+                    source: DivergenceSource::Decided,
+                    local_parent: parent.hash(),
+                    block_parent: block.parent_hash(),
+                };
+                self.forks.record(divergence.clone());
+                return Err(StateError::Fork(divergence).into());
+            }
+        }
+
+        let height = certificate.height.as_u64();
+        let mut derived = DerivedState::default();
+
+        // Registrations outlive the blocks they were decided in, which get pruned
+        if self.genesis.protocol.is_active(Feature::Namespaces, height) {
+            derived.namespaces = self.namespaces.apply(block.transactions());
+        }
+
+        if self.genesis.protocol.is_active(Feature::Governance, height) {
+            derived.governance = self.governance.apply(block.transactions(), height);
+        }
+
+        // Jailings change the validator set handed to consensus for the next height
+        if self.genesis.protocol.is_active(Feature::Jailing, height) {
+            let signers: Vec<Address> = block
+                .last_commit()
+                .map(|parent| {
+                    parent
+                        .aggregated_signature
+                        .signatures
+                        .iter()
+                        .map(|sig| sig.address)
+                        .collect()
+                })
+                .unwrap_or_default();
+            derived.liveness = self.liveness.apply(block.transactions(), &signers, height);
+        }
 
-        // Store block data for decided value
-        let block_data = self
+        derived.account_nonces = self
+            .transaction_pool
+            .account_nonces()
+            .apply(block.transactions());
+
+        // The decided value, its certificate, its block data and the state derived from it are
+        // persisted at once, before the height is acknowledged to consensus by starting the next
+        // one. The node stops if this fails, and reloads the derived state from the store.
+        let data = self
             .store
-            .get_block_data(certificate.height, certificate.round)
+            .commit_decided(&certificate, proposal.value, derived)
             .await?;
 
-        if let Some(data) = block_data {
-            self.store
-                .store_decided_block_data(certificate.height, data.clone())
-                .await?;
-
-            // Only execute blocks if this node is running the RPC server
-            if !data.is_empty()
-            // && self.rpc_server.is_some() rpc is not implemented yet
-            {
-                // Execute the block in the background
-                // let executor = self.block_executor.clone();
-                // let height = certificate.height;
-                // tokio::task::spawn_blocking(move || match executor.next_block(&data) {
-                //     Ok(_) => info!(height = %height, "Successfully executed block"),
-                //     Err(e) => {
-                //         error!(height = %height, "Failed to execute block: {}. Continuing with consensus...", e)
-                //     }
-                // });
+        let block_hash = block.hash();
+        self.block_metrics.observe(&block, data.len());
+
+        // The last commit may decide the parent with other precommits than the ones we
+        // decided it with, validators found in both signed two values in the same round
+        if let Some(parent) = block.last_commit() {
+            if let Some(decided) = self.store.get_certificate(parent.height).await? {
+                for evidence in conflicting_precommits(&decided, parent) {
+                    self.record_evidence(evidence).await?;
+                }
             }
         }
 
-        // Prune the store
-        let retain_height = Height::new(
-            certificate
-                .height
-                .as_u64()
-                .saturating_sub(MAX_HISTORY_LENGTH),
-        );
+        // Another proposer may have included our pending transactions, and the decided
+        // registrations and nonces may have made others invalid
+        let included = self.transaction_pool.remove_decided(block.transactions());
+        let evicted = self.transaction_pool.revalidate();
+        if included + evicted > 0 {
+            info!(
+                height = %certificate.height,
+                included,
+                evicted,
+                "Pruned the transaction pool"
+            );
+        }
+
+        if self.events.is_enabled() {
+            self.emit_commit_events(&certificate, data.clone())?;
+        }
+
+        // Only execute blocks if this node is running the RPC server
+        if !data.is_empty()
+        // && self.rpc_server.is_some() rpc is not implemented yet
+        {
+            // Execute the block in the background
+            // let executor = self.block_executor.clone();
+            // let height = certificate.height;
+            // tokio::task::spawn_blocking(move || match executor.next_block(&data) {
+            //     Ok(_) => info!(height = %height, "Successfully executed block"),
+            //     Err(e) => {
+            //         error!(height = %height, "Failed to execute block: {}. Continuing with consensus...", e)
+            //     }
+            // });
+        }
+
+        // Prune the consensus artifacts, then the decided data, keeping the number of heights
+        // voted by governance or more if this node is configured to
+        let consensus_retain_height =
+            Height::new(height.saturating_sub(self.store.consensus_retain_heights()));
+        self.store.prune_undecided(consensus_retain_height).await?;
+
+        // The timestamps of the last blocks bound the next one, their headers are always kept
+        let retain_heights = self
+            .store
+            .retain_heights(self.governance.params_at(height).retain_heights)
+            .max(self.genesis.consensus.median_time_span as u64);
+        let retain_height = Height::new(height.saturating_sub(retain_heights));
         self.store.prune(retain_height).await?;
+        self.store.prune_to_size().await?;
 
         // Move to next height
+        self.last_decided_at = Some(Instant::now());
         self.current_height = self.current_height.increment();
         self.current_round = Round::new(0);
+        self.streams_map.evict_outdated(self.current_height);
+        self.proposal_watch
+            .evict_below(self.current_height.as_u64());
+        self.undecided.clear();
+
+        self.publish_finalized(&certificate, block_hash);
+
+        Ok(())
+    }
+
+    fn publish_finalized(
+        &self,
+        certificate: &CommitCertificate<TestContext>,
+        block_hash: [u8; 32],
+    ) {
+        let signatures = &certificate.aggregated_signature.signatures;
+        let validator_set = self.get_validator_set(certificate.height);
+        let signed_voting_power = signatures
+            .iter()
+            .filter_map(|sig| validator_set.get_by_address(&sig.address))
+            .map(|validator| validator.voting_power)
+            .sum();
+
+        self.finality.publish(Finalized {
+            height: certificate.height.as_u64(),
+            block_hash,
+            round: certificate.round.as_u32().unwrap_or_default(),
+            value_id: certificate.value_id,
+            signature_count: signatures.len(),
+            signed_voting_power,
+        });
+    }
+
+    fn emit_commit_events(
+        &mut self,
+        certificate: &CommitCertificate<TestContext>,
+        data: Bytes,
+    ) -> Result<()> {
+        let block: Block = ProtobufCodec.decode(data)?;
+        let height = certificate.height.as_u64();
+
+        let validator_set = self.get_validator_set(certificate.height);
+        if self.announced_validator_set.as_ref() != Some(&validator_set) {
+            self.events.emit(ChainEvent::ValidatorSetChanged {
+                height,
+                validators: validator_set
+                    .validators
+                    .iter()
+                    .map(|v| (v.address, v.voting_power))
+                    .collect(),
+            });
+            self.announced_validator_set = Some(validator_set);
+        }
+
+        let round = certificate.round.as_u32().unwrap_or_default();
+        for event in block_events(height, round, &block) {
+            self.events.emit(event);
+        }
 
         Ok(())
     }
@@ -369,7 +1180,7 @@ impl State {
         height: Height,
         round: Round,
         data: Bytes,
-    ) -> eyre::Result<LocallyProposedValue<TestContext>> {
+    ) -> Result<LocallyProposedValue<TestContext>> {
         assert_eq!(height, self.current_height);
         assert_eq!(round, self.current_round);
 
@@ -385,15 +1196,10 @@ impl State {
             validity: Validity::Valid, // Our proposals are de facto valid
         };
 
-        // Insert the new proposal into the undecided proposals.
-        self.store
-            .store_undecided_proposal(proposal.clone())
-            .await?;
+        self.round_state.proposal(&proposal);
 
-        // Also store the block data
-        self.store
-            .store_undecided_block_data(height, round, data)
-            .await?;
+        // Insert the new proposal and its block data into the undecided proposals.
+        self.store_undecided(proposal.clone(), data).await?;
 
         Ok(LocallyProposedValue::new(
             proposal.height,
@@ -411,136 +1217,217 @@ impl State {
         StreamId::new(bytes.into())
     }
 
-    /// Creates a stream message containing a proposal part.
-    /// Updates internal sequence number and current proposal.
+    /// Splits a proposal into a stream of parts, produced lazily as the stream is consumed.
     pub fn stream_proposal(
         &mut self,
         value: LocallyProposedValue<TestContext>,
         data: Bytes,
-    ) -> impl Iterator<Item = StreamMessage<ProposalPart>> {
-        let parts = self.make_proposal_parts(value, data);
-
+    ) -> ProposalStream {
         let stream_id = self.stream_id();
 
-        let mut msgs = Vec::with_capacity(parts.len() + 1);
-        let mut sequence = 0;
-
-        for part in parts {
-            let msg = StreamMessage::new(stream_id.clone(), sequence, StreamContent::Data(part));
-            sequence += 1;
-            msgs.push(msg);
-        }
+        // Fall back to sending the data uncompressed rather than not proposing at all
+        let (compression, data) = match self
+            .compression
+            .proposals
+            .compress(data.clone(), self.compression.level)
+        {
+            Ok(compressed) => (self.compression.proposals, compressed),
+            Err(e) => {
+                error!(error = %e, "Failed to compress proposal data, sending it uncompressed");
+                (Compression::None, data)
+            }
+        };
 
-        msgs.push(StreamMessage::new(stream_id, sequence, StreamContent::Fin));
-        msgs.into_iter()
+        let hasher = ProposalPartsHasher::new(
+            &self.genesis.chain_id,
+            value.height,
+            value.round,
+            &self.address,
+            compression,
+        );
+        let init = ProposalInit::new(value.height, value.round, self.address, compression);
+
+        ProposalStream::new(
+            stream_id,
+            init,
+            data,
+            self.genesis.consensus.chunk_size,
+            hasher,
+            &self.signing_provider,
+        )
     }
 
-    fn make_proposal_parts(
-        &self,
-        value: LocallyProposedValue<TestContext>,
-        data: Bytes,
-    ) -> Vec<ProposalPart> {
-        let mut hasher = sha3::Keccak256::new();
-        let mut parts = Vec::new();
-
-        // Init
-        {
-            parts.push(ProposalPart::Init(ProposalInit::new(
-                value.height,
-                value.round,
-                self.address,
-            )));
-
-            hasher.update(value.height.as_u64().to_be_bytes().as_slice());
-            hasher.update(value.round.as_i64().to_be_bytes().as_slice());
+    /// Checks the `Init` part of a proposal stream against the current consensus state.
+    /// Proposals for future heights or rounds are accepted, the expected proposer can only be
+    /// checked for the current round.
+    fn validate_proposal_init(&self, init: &ProposalInit) -> Result<(), InitValidationError> {
+        if init.height < self.current_height {
+            return Err(InitValidationError::Outdated);
         }
 
-        // Data
+        // Jailed validators keep their place in the set, without voting power
+        if self
+            .get_validator_set(init.height)
+            .get_by_address(&init.proposer)
+            .is_none_or(|proposer| proposer.voting_power == 0)
         {
-            for chunk in data.chunks(CHUNK_SIZE) {
-                let chunk_data = ProposalData::new(Bytes::copy_from_slice(chunk));
-                parts.push(ProposalPart::Data(chunk_data));
-                hasher.update(chunk);
-            }
+            return Err(InitValidationError::ProposerNotFound);
         }
 
-        {
-            let hash = hasher.finalize().to_vec();
-            let signature = self.signing_provider.sign(&hash);
-            parts.push(ProposalPart::Fin(ProposalFin::new(signature)));
+        let is_current_round =
+            init.height == self.current_height && init.round == self.current_round;
+        if is_current_round && self.current_proposer.is_some_and(|p| p != init.proposer) {
+            return Err(InitValidationError::UnexpectedProposer);
         }
 
-        parts
+        Ok(())
     }
 
-    /// Returns the set of validators.
-    pub fn get_validator_set(&self) -> &ValidatorSet {
-        &self.genesis.validator_set
+    /// Persists double-sign evidence detected by this node.
+    async fn record_evidence(&self, evidence: Evidence) -> Result<()> {
+        let height = evidence.height();
+        let validator = evidence.validator();
+        let record = EvidenceRecord::new(evidence, EvidenceSource::Local);
+        if self.store.store_evidence(record).await? {
+            warn!(%height, %validator, "Recorded double-sign evidence");
+        }
+        Ok(())
     }
 
-    /// Verifies the signature of the proposal.
-    /// Returns `Ok(())` if the signature is valid, or an appropriate `SignatureVerificationError`.
-    fn verify_proposal_signature(
-        &self,
-        parts: &ProposalParts,
-    ) -> Result<(), SignatureVerificationError> {
-        let mut hasher = sha3::Keccak256::new();
-        let mut signature = None;
-
-        // Recreate the hash and extract the signature during traversal
-        for part in &parts.parts {
-            match part {
-                ProposalPart::Init(init) => {
-                    hasher.update(init.height.as_u64().to_be_bytes());
-                    hasher.update(init.round.as_i64().to_be_bytes());
-                }
-                ProposalPart::Data(data) => {
-                    hasher.update(data.bytes.as_ref());
-                }
-                ProposalPart::Fin(fin) => {
-                    signature = Some(&fin.signature);
-                }
-            }
-        }
+    /// Returns the set of validators at `height`, see [`Liveness::validator_set_at`].
+    pub fn get_validator_set(&self, height: Height) -> ValidatorSet {
+        self.liveness.validator_set_at(height.as_u64())
+    }
 
-        let hash = hasher.finalize();
-        let signature = signature.ok_or(SignatureVerificationError::MissingFinPart)?;
+    /// Records our precommit for `value_id` in the round state.
+    pub fn record_precommit(&self, height: Height, round: Round, value_id: ValueId) {
+        let voting_power = self
+            .get_validator_set(height)
+            .get_by_address(&self.address)
+            .map_or(0, |validator| validator.voting_power);
+        self.round_state
+            .precommit(height, round, value_id, self.address, voting_power);
+    }
+}
 
-        // Retrieve the public key of the proposer
-        let public_key = self
-            .get_validator_set()
-            .get_by_address(&parts.proposer)
-            .map(|v| v.public_key);
+/// Verifies the signature of the proposal, over the digest computed while assembling its parts.
+/// Returns `Ok(())` if the signature is valid, or an appropriate `SignatureVerificationError`.
+fn verify_proposal_signature(
+    validator_set: &ValidatorSet,
+    parts: &ProposalParts,
+) -> Result<(), SignatureVerificationError> {
+    let signature = parts
+        .signature
+        .as_ref()
+        .ok_or(SignatureVerificationError::MissingFinPart)?;
+
+    // Retrieve the public key of the proposer
+    let public_key = validator_set
+        .get_by_address(&parts.proposer)
+        .map(|v| v.public_key)
+        .ok_or(SignatureVerificationError::ProposerNotFound)?;
+
+    // Verify the signature
+    if public_key.verify(&parts.digest, signature).is_err() {
+        return Err(SignatureVerificationError::InvalidSignature);
+    }
 
-        let public_key = public_key.ok_or(SignatureVerificationError::ProposerNotFound)?;
+    Ok(())
+}
 
-        // Verify the signature
-        if !self.signing_provider.verify(&hash, signature, &public_key) {
-            return Err(SignatureVerificationError::InvalidSignature);
+/// Checks a complete proposal received for `height`, whose signature was verified: decompression,
+/// decoding and validity of the block on top of `prev_block`, including its blob commitments.
+/// Returns `None` if the proposal must be ignored.
+///
+/// CPU bound, meant to run on the blocking thread pool.
+fn verify_proposal(
+    chain_id: &str,
+    validator_set: &ValidatorSet,
+    validation_cache: &ValidationCache,
+    namespaces: &NamespaceRegistry,
+    governance: &Governance,
+    liveness: &Liveness,
+    consensus: &ConsensusParams,
+    max_blobs_per_tx: usize,
+    protocol: &ProtocolSchedule,
+    forks: &ForkMonitor,
+    height: Height,
+    parts: ProposalParts,
+    prev_block: Bytes,
+    recent_timestamps: Vec<u64>,
+) -> Result<Option<(ProposedValue<TestContext>, Bytes)>> {
+    let part_height = parts.height;
+    let part_round = parts.round;
+
+    // Re-assemble the proposal from its parts
+    let (value, data) = match assemble_value_from_parts(parts) {
+        Ok(assembled) => assembled,
+        Err(e) => {
+            error!(
+                height = %part_height,
+                round = %part_round,
+                error = %e,
+                "Failed to decompress proposal data, ignoring"
+            );
+            return Ok(None);
         }
+    };
 
-        Ok(())
+    // Signed by a validator but not necessarily well-formed, never stop on the proposed data
+    let block: Block = match ProtobufCodec.decode(data.clone()) {
+        Ok(block) => block,
+        Err(e) => {
+            error!(%height, error = %e, "Failed to decode proposed block, ignoring");
+            return Ok(None);
+        }
+    };
+    let prev_block: Block = ProtobufCodec.decode(prev_block)?;
+    if block.parent_hash() != prev_block.hash() {
+        let divergence = Divergence {
+            height: height.as_u64(),
+            source: DivergenceSource::Proposal,
+            local_parent: prev_block.hash(),
+            block_parent: block.parent_hash(),
+        };
+        warn!(%divergence, "Received proposal that does not extend our chain, ignoring");
+        forks.record(divergence);
+        return Ok(None);
     }
-}
-
-/// Re-assemble a [`ProposedValue`] from its [`ProposalParts`].
-fn assemble_value_from_parts(parts: ProposalParts) -> (ProposedValue<TestContext>, Bytes) {
-    // Calculate total size and allocate buffer
-    let total_size: usize = parts
-        .parts
-        .iter()
-        .filter_map(|part| part.as_data())
-        .map(|data| data.bytes.len())
-        .sum();
 
-    let mut data = Vec::with_capacity(total_size);
-    // Concatenate all chunks
-    for part in parts.parts.iter().filter_map(|part| part.as_data()) {
-        data.extend_from_slice(&part.bytes);
+    let validity = block.is_valid(
+        height.as_u64(),
+        &prev_block,
+        &recent_timestamps,
+        validation_cache,
+        namespaces,
+        governance,
+        liveness,
+        consensus,
+        max_blobs_per_tx,
+        protocol,
+        &FinalityParams::new(chain_id, validator_set.clone()),
+    );
+    match validity {
+        Ok(true) => {}
+        Ok(false) => {
+            error!("Invalid block");
+            return Ok(None);
+        }
+        Err(e) => {
+            error!(%height, error = %e, "Failed to validate proposed block, ignoring");
+            return Ok(None);
+        }
     }
 
-    // Convert the concatenated data vector into Bytes
-    let data = Bytes::from(data);
+    Ok(Some((value, data)))
+}
+
+/// Re-assemble a [`ProposedValue`] from its [`ProposalParts`], decompressing the data if needed.
+fn assemble_value_from_parts(
+    parts: ProposalParts,
+) -> Result<(ProposedValue<TestContext>, Bytes), CompressionError> {
+    // The chunks were concatenated as they arrived
+    let data = parts.compression.decompress(parts.data)?;
 
     let proposed_value = ProposedValue {
         height: parts.height,
@@ -551,10 +1438,58 @@ fn assemble_value_from_parts(parts: ProposalParts) -> (ProposedValue<TestContext
         validity: Validity::Valid,
     };
 
-    (proposed_value, data)
+    Ok((proposed_value, data))
 }
 
 /// Decodes a Value from its byte representation using ProtobufCodec
-pub fn decode_value(bytes: Bytes) -> Value {
-    ProtobufCodec.decode(bytes).unwrap()
+pub fn decode_value(bytes: Bytes) -> Result<Value, ProtoError> {
+    ProtobufCodec.decode(bytes)
+}
+
+/// Polls `next` for a transaction until one is found or `deadline` passes.
+async fn wait_for_transaction<T>(
+    deadline: Instant,
+    mut next: impl FnMut() -> Option<T>,
+) -> Option<T> {
+    loop {
+        if let Some(tx) = next() {
+            return Some(tx);
+        }
+        if Instant::now() + TX_POLL_INTERVAL > deadline {
+            return None;
+        }
+        tokio::time::sleep(TX_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn gives_up_at_the_deadline_without_transactions() {
+        let start = Instant::now();
+        let deadline = start + 3 * TX_POLL_INTERVAL;
+
+        let tx: Option<Transaction> = wait_for_transaction(deadline, || None).await;
+
+        // Consensus then gets an empty block if the heartbeat is due, no answer otherwise
+        assert!(tx.is_none());
+        assert!(start.elapsed() >= 2 * TX_POLL_INTERVAL);
+        assert!(Instant::now() <= deadline + TX_POLL_INTERVAL);
+    }
+
+    #[tokio::test]
+    async fn returns_a_transaction_once_available() {
+        let deadline = Instant::now() + 10 * TX_POLL_INTERVAL;
+        let mut polls = 0;
+
+        let tx = wait_for_transaction(deadline, || {
+            polls += 1;
+            (polls == 3).then_some(polls)
+        })
+        .await;
+
+        assert_eq!(tx, Some(3));
+    }
 }
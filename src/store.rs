@@ -1,34 +1,90 @@
+use std::collections::VecDeque;
 use std::mem::size_of;
 use std::ops::RangeBounds;
 use std::path::Path;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{mpsc, Arc};
 use std::time::Instant;
 
 use bytes::Bytes;
 use prost::Message;
 use redb::ReadableTable;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tracing::error;
+use tracing::{error, info, warn};
 
-use crate::malachite_types::codec::proto as codec;
-use crate::malachite_types::codec::proto::ProtobufCodec;
-use crate::malachite_types::proto;
-use crate::malachite_types::{context::TestContext, height::Height, value::Value};
+use crate::types::codec::proto as codec;
+use crate::types::codec::proto::ProtobufCodec;
+use crate::types::proto;
+use crate::types::{address::Address, context::TestContext, height::Height, value::Value};
 use malachitebft_app_channel::app::types::codec::Codec;
 use malachitebft_app_channel::app::types::core::{CommitCertificate, Round};
 use malachitebft_app_channel::app::types::ProposedValue;
 use malachitebft_proto::{Error as ProtoError, Protobuf};
 
+use crate::block::Block;
+use crate::compression::{Compression, CompressionConfig};
+use crate::error::CompressionError;
+use crate::evidence::EvidenceRecord;
+use crate::governance::Proposal;
+use crate::header::Header;
+use crate::liveness::ValidatorLiveness;
 use crate::metrics::DbMetrics;
-use crate::tables::keys::{HeightKey, UndecidedValueKey};
+use crate::namespaces::Registration;
+use crate::tables::keys::{HeightKey, UndecidedKey};
+
+/// `[storage]` section of the node configuration.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    /// Cap in bytes on the pages in use in the store, 0 for none. Above it, the block data of
+    /// the oldest decided heights is pruned, regardless of the height-based retention. Their
+    /// certificates and headers are kept.
+    pub max_db_size: u64,
+    /// Decided heights whose block data, values and certificates this node retains, when more
+    /// than the `retain_heights` of the chain, e.g. to serve blobs for longer. 0 for the chain's.
+    pub da_retain_heights: u64,
+    /// Heights whose undecided proposals and block data are retained. Consensus only needs the
+    /// ones of the current height, so they are pruned well before the decided data.
+    pub consensus_retain_heights: u64,
+}
+
+/// Undecided heights retained when the configuration does not set it.
+pub const DEFAULT_CONSENSUS_RETAIN_HEIGHTS: u64 = 2;
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            max_db_size: 0,
+            da_retain_heights: 0,
+            consensus_retain_heights: DEFAULT_CONSENSUS_RETAIN_HEIGHTS,
+        }
+    }
+}
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DecidedValue {
     pub value: Value,
+    #[serde(with = "crate::types::certificate")]
     pub certificate: CommitCertificate<TestContext>,
 }
 
+/// State derived from a decided block, persisted in the transaction that decides the block so
+/// that a restart never finds one without the other.
+#[derive(Clone, Debug, Default)]
+pub struct DerivedState {
+    pub namespaces: Vec<([u8; 32], Registration)>,
+    pub governance: Vec<([u8; 32], Proposal)>,
+    pub liveness: Vec<(Address, ValidatorLiveness)>,
+    pub account_nonces: Vec<([u8; 32], u64)>,
+}
+
+/// Decided block data is stored compressed or not depending on the configuration at the time it
+/// was written, detect which one it is.
+fn decode_block_data(bytes: Vec<u8>) -> Result<Bytes, CompressionError> {
+    Compression::detect(&bytes).decompress(Bytes::from(bytes))
+}
+
 fn decode_certificate(bytes: &[u8]) -> Result<CommitCertificate<TestContext>, ProtoError> {
     let proto = proto::CommitCertificate::decode(bytes)?;
     codec::decode_certificate(proto)
@@ -61,6 +117,30 @@ pub enum StoreError {
 
     #[error("Failed to join on task: {0}")]
     TaskJoin(#[from] tokio::task::JoinError),
+
+    #[error("Failed to (de)compress block data: {0}")]
+    Compression(#[from] CompressionError),
+
+    #[error("Failed to encode/decode JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Store {0} is in use by another process, e.g. a running node")]
+    Locked(PathBuf),
+
+    #[error("Store is opened read-only")]
+    ReadOnly,
+
+    #[error("Store writer stopped")]
+    WriterStopped,
+
+    #[error("Write aborted with its transaction: {0}")]
+    Aborted(String),
+
+    #[error("No block data for the value decided at height {0}")]
+    MissingBlockData(Height),
 }
 
 const CERTIFICATES_TABLE: redb::TableDefinition<HeightKey, Vec<u8>> =
@@ -69,25 +149,248 @@ const CERTIFICATES_TABLE: redb::TableDefinition<HeightKey, Vec<u8>> =
 const DECIDED_VALUES_TABLE: redb::TableDefinition<HeightKey, Vec<u8>> =
     redb::TableDefinition::new("decided_values");
 
-const UNDECIDED_PROPOSALS_TABLE: redb::TableDefinition<UndecidedValueKey, Vec<u8>> =
+const UNDECIDED_PROPOSALS_TABLE: redb::TableDefinition<UndecidedKey, Vec<u8>> =
     redb::TableDefinition::new("undecided_values");
 
 const DECIDED_BLOCK_DATA_TABLE: redb::TableDefinition<HeightKey, Vec<u8>> =
     redb::TableDefinition::new("decided_block_data");
 
-const UNDECIDED_BLOCK_DATA_TABLE: redb::TableDefinition<UndecidedValueKey, Vec<u8>> =
+const UNDECIDED_BLOCK_DATA_TABLE: redb::TableDefinition<UndecidedKey, Vec<u8>> =
     redb::TableDefinition::new("undecided_block_data");
 
+/// Headers of the heights whose block data was pruned to stay under
+/// [`StorageConfig::max_db_size`], pruned with the certificates
+const PRUNED_HEADERS_TABLE: redb::TableDefinition<HeightKey, Vec<u8>> =
+    redb::TableDefinition::new("pruned_headers");
+
+/// Height and index of the first decided blob with a given commitment, pruned with the block data
+const BLOB_INDEX_TABLE: redb::TableDefinition<&[u8], (u64, u64)> =
+    redb::TableDefinition::new("blob_index");
+
+/// Height and index of the decided transactions by hash, pruned with the block data
+const TX_INDEX_TABLE: redb::TableDefinition<&[u8], (u64, u64)> =
+    redb::TableDefinition::new("tx_index");
+
+/// Namespace registrations by namespace, never pruned
+const NAMESPACES_TABLE: redb::TableDefinition<&[u8], Vec<u8>> =
+    redb::TableDefinition::new("namespaces");
+
+/// Votes for parameter changes by change id, never pruned
+const GOVERNANCE_TABLE: redb::TableDefinition<&[u8], Vec<u8>> =
+    redb::TableDefinition::new("governance");
+
+/// Signed and missed blocks and jailings by validator address, never pruned
+const LIVENESS_TABLE: redb::TableDefinition<&[u8], Vec<u8>> =
+    redb::TableDefinition::new("liveness");
+
+/// Double-sign evidence by height and validator address, never pruned
+const EVIDENCE_TABLE: redb::TableDefinition<(u64, [u8; 20]), Vec<u8>> =
+    redb::TableDefinition::new("evidence");
+
+/// Last height published by each streaming sink, see [`crate::publisher`]
+const STREAM_OFFSETS_TABLE: redb::TableDefinition<&str, u64> =
+    redb::TableDefinition::new("stream_offsets");
+
+/// Next nonce of each sender, never pruned
+const ACCOUNT_NONCES_TABLE: redb::TableDefinition<&[u8], u64> =
+    redb::TableDefinition::new("account_nonces");
+
+/// Indexes the blobs and transactions of the block decided at `height` within `tx`.
+fn index_block(tx: &redb::WriteTransaction, height: Height, data: &[u8]) -> Result<(), StoreError> {
+    let block = Block::from_bytes(data)?;
+    index_blobs(&mut tx.open_table(BLOB_INDEX_TABLE)?, height, &block)?;
+    index_transactions(&mut tx.open_table(TX_INDEX_TABLE)?, height, &block)
+}
+
+/// Indexes the blobs of the block decided at `height`, keeping earlier entries for the same
+/// commitment.
+fn index_blobs(
+    table: &mut redb::Table<&'static [u8], (u64, u64)>,
+    height: Height,
+    block: &Block,
+) -> Result<(), StoreError> {
+    for (index, commitment) in block.blob_commitments().iter().enumerate() {
+        if table.get(commitment.as_slice())?.is_none() {
+            table.insert(commitment.as_slice(), (height.as_u64(), index as u64))?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes the entries of the blobs of `block`, decided at `height`, from the blob index.
+fn unindex_blobs(
+    table: &mut redb::Table<&'static [u8], (u64, u64)>,
+    height: Height,
+    block: &Block,
+) -> Result<(), StoreError> {
+    for commitment in block.blob_commitments() {
+        let indexed_height = table.get(commitment.as_slice())?.map(|v| v.value().0);
+        if indexed_height == Some(height.as_u64()) {
+            table.remove(commitment.as_slice())?;
+        }
+    }
+    Ok(())
+}
+
+/// Indexes the transactions of `block`, decided at `height`. Nonces keep a transaction from
+/// being decided twice, earlier entries are kept all the same.
+fn index_transactions(
+    table: &mut redb::Table<&'static [u8], (u64, u64)>,
+    height: Height,
+    block: &Block,
+) -> Result<(), StoreError> {
+    for (index, tx) in block.transactions().iter().enumerate() {
+        let hash = tx.hash();
+        if table.get(hash.as_slice())?.is_none() {
+            table.insert(hash.as_slice(), (height.as_u64(), index as u64))?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes the entries of the transactions of `block`, decided at `height`, from the
+/// transaction index.
+fn unindex_transactions(
+    table: &mut redb::Table<&'static [u8], (u64, u64)>,
+    height: Height,
+    block: &Block,
+) -> Result<(), StoreError> {
+    for tx in block.transactions() {
+        let hash = tx.hash();
+        let indexed_height = table.get(hash.as_slice())?.map(|v| v.value().0);
+        if indexed_height == Some(height.as_u64()) {
+            table.remove(hash.as_slice())?;
+        }
+    }
+    Ok(())
+}
+
+/// Persists namespace registrations within `tx`, replacing previous ones for the same
+/// namespaces.
+fn insert_namespaces(
+    tx: &redb::WriteTransaction,
+    registrations: Vec<([u8; 32], Registration)>,
+    write_bytes: &mut u64,
+) -> Result<(), StoreError> {
+    let mut table = tx.open_table(NAMESPACES_TABLE)?;
+    for (namespace, registration) in registrations {
+        let value = serde_json::to_vec(&registration)?;
+        *write_bytes += value.len() as u64;
+        table.insert(namespace.as_slice(), value)?;
+    }
+    Ok(())
+}
+
+/// Persists votes for parameter changes within `tx`, replacing previous ones for the same
+/// changes.
+fn insert_governance(
+    tx: &redb::WriteTransaction,
+    proposals: Vec<([u8; 32], Proposal)>,
+    write_bytes: &mut u64,
+) -> Result<(), StoreError> {
+    let mut table = tx.open_table(GOVERNANCE_TABLE)?;
+    for (id, proposal) in proposals {
+        let value = serde_json::to_vec(&proposal)?;
+        *write_bytes += value.len() as u64;
+        table.insert(id.as_slice(), value)?;
+    }
+    Ok(())
+}
+
+/// Persists liveness records within `tx`, replacing previous ones for the same validators.
+fn insert_liveness(
+    tx: &redb::WriteTransaction,
+    records: Vec<(Address, ValidatorLiveness)>,
+    write_bytes: &mut u64,
+) -> Result<(), StoreError> {
+    let mut table = tx.open_table(LIVENESS_TABLE)?;
+    for (address, record) in records {
+        let value = serde_json::to_vec(&record)?;
+        *write_bytes += value.len() as u64;
+        table.insert(address.into_inner().as_slice(), value)?;
+    }
+    Ok(())
+}
+
+/// Persists the next nonces of senders within `tx`, replacing previous ones.
+fn insert_account_nonces(
+    tx: &redb::WriteTransaction,
+    nonces: Vec<([u8; 32], u64)>,
+    write_bytes: &mut u64,
+) -> Result<(), StoreError> {
+    let mut table = tx.open_table(ACCOUNT_NONCES_TABLE)?;
+    for (sender, nonce) in &nonces {
+        table.insert(sender.as_slice(), nonce)?;
+    }
+    // A 32-byte key and a u64 per sender
+    *write_bytes += nonces.len() as u64 * 40;
+    Ok(())
+}
+
+/// A database opened by [`Store::open`], or by [`Store::open_read_only`] which rejects writes.
+enum Backend {
+    ReadWrite(redb::Database),
+    ReadOnly(redb::ReadOnlyDatabase),
+}
+
+impl Backend {
+    fn begin_read(&self) -> Result<redb::ReadTransaction, redb::TransactionError> {
+        match self {
+            Self::ReadWrite(db) => db.begin_read(),
+            Self::ReadOnly(db) => db.begin_read(),
+        }
+    }
+
+    fn begin_write(&self) -> Result<redb::WriteTransaction, StoreError> {
+        match self {
+            Self::ReadWrite(db) => Ok(db.begin_write()?),
+            Self::ReadOnly(_) => Err(StoreError::ReadOnly),
+        }
+    }
+}
+
 struct Db {
-    db: redb::Database,
+    db: Backend,
     metrics: DbMetrics,
+    /// Compression applied to decided block data
+    compression: Compression,
+    compression_level: i32,
+    max_db_size: u64,
+    da_retain_heights: u64,
+    consensus_retain_heights: u64,
 }
 
 impl Db {
-    fn new(path: impl AsRef<Path>, metrics: DbMetrics) -> Result<Self, StoreError> {
-        Ok(Self {
-            db: redb::Database::create(path).map_err(StoreError::Database)?,
+    fn new(
+        db: redb::Database,
+        metrics: DbMetrics,
+        compression: &CompressionConfig,
+        storage: &StorageConfig,
+    ) -> Self {
+        Self {
+            db: Backend::ReadWrite(db),
             metrics,
+            compression: compression.storage,
+            compression_level: compression.level,
+            max_db_size: storage.max_db_size,
+            da_retain_heights: storage.da_retain_heights,
+            consensus_retain_heights: storage.consensus_retain_heights,
+        }
+    }
+
+    fn read_only(path: &Path) -> Result<Self, StoreError> {
+        let db = redb::ReadOnlyDatabase::open(path).map_err(|e| match e {
+            redb::DatabaseError::DatabaseAlreadyOpen => StoreError::Locked(path.to_path_buf()),
+            e => StoreError::Database(e),
+        })?;
+        Ok(Self {
+            db: Backend::ReadOnly(db),
+            metrics: DbMetrics::new(),
+            compression: Compression::default(),
+            compression_level: 0,
+            max_db_size: 0,
+            da_retain_heights: 0,
+            consensus_retain_heights: DEFAULT_CONSENSUS_RETAIN_HEIGHTS,
         })
     }
 
@@ -128,12 +431,22 @@ impl Db {
         Ok(decided_value)
     }
 
-    fn insert_decided_value(&self, decided_value: DecidedValue) -> Result<(), StoreError> {
-        let start = Instant::now();
+    /// Persists the value decided in `round` with its certificate, and moves the block data of
+    /// that round to the decided table, dropping the data of the other rounds at that height. All
+    /// within `tx`, so that a height is either fully decided in the store or not at all, along
+    /// with the state `derived` from its block. Falls back to data already decided at that
+    /// height, e.g. on a replayed commit. Returns the uncompressed block data, and fails without
+    /// it, which aborts `tx`.
+    fn commit_decided(
+        &self,
+        tx: &redb::WriteTransaction,
+        decided_value: DecidedValue,
+        round: Round,
+        derived: DerivedState,
+    ) -> Result<Bytes, StoreError> {
         let mut write_bytes = 0;
 
         let height = decided_value.certificate.height;
-        let tx = self.db.begin_write()?;
 
         {
             let mut values = tx.open_table(DECIDED_VALUES_TABLE)?;
@@ -149,12 +462,61 @@ impl Db {
             certificates.insert(height, encoded_certificate)?;
         }
 
-        tx.commit()?;
+        // Never record a decision whose block cannot be served
+        let Some(data) = self.decide_block_data(tx, height, round, &mut write_bytes)? else {
+            return Err(StoreError::MissingBlockData(height));
+        };
+
+        insert_namespaces(tx, derived.namespaces, &mut write_bytes)?;
+        insert_governance(tx, derived.governance, &mut write_bytes)?;
+        insert_liveness(tx, derived.liveness, &mut write_bytes)?;
+        insert_account_nonces(tx, derived.account_nonces, &mut write_bytes)?;
 
-        self.metrics.observe_write_time(start.elapsed());
         self.metrics.add_write_bytes(write_bytes);
 
-        Ok(())
+        Ok(data)
+    }
+
+    /// Heights whose certificate is stored without their block data, decided by a version that
+    /// committed them in two transactions and stopped in between.
+    fn incomplete_commits(
+        &self,
+        tx: &redb::WriteTransaction,
+    ) -> Result<Vec<(Height, Round)>, StoreError> {
+        let values = tx.open_table(DECIDED_VALUES_TABLE)?;
+        let certificates = tx.open_table(CERTIFICATES_TABLE)?;
+        let decided_block_data = tx.open_table(DECIDED_BLOCK_DATA_TABLE)?;
+
+        // Heights pruned for size have no decided value anymore
+        let mut incomplete = Vec::new();
+        for entry in values.iter()? {
+            let (key, _) = entry?;
+            let height = key.value();
+            if decided_block_data.get(&height)?.is_some() {
+                continue;
+            }
+            if let Some(certificate) = certificates.get(&height)? {
+                let certificate = decode_certificate(&certificate.value())?;
+                incomplete.push((height, certificate.round));
+            }
+        }
+
+        Ok(incomplete)
+    }
+
+    /// Moves the block data of `round` at `height` out of the undecided table, for the heights
+    /// of [`Self::incomplete_commits`].
+    fn complete_commit(
+        &self,
+        tx: &redb::WriteTransaction,
+        height: Height,
+        round: Round,
+    ) -> Result<Option<Bytes>, StoreError> {
+        let mut write_bytes = 0;
+        let data = self.decide_block_data(tx, height, round, &mut write_bytes)?;
+        self.metrics.add_write_bytes(write_bytes);
+
+        Ok(data)
     }
 
     #[tracing::instrument(skip(self))]
@@ -169,7 +531,7 @@ impl Db {
         let tx = self.db.begin_read()?;
         let table = tx.open_table(UNDECIDED_PROPOSALS_TABLE)?;
 
-        let value = if let Ok(Some(value)) = table.get(&(height, round)) {
+        let value = if let Ok(Some(value)) = table.get(&UndecidedKey::new(height, round)) {
             let bytes = value.value();
             read_bytes += bytes.len() as u64;
 
@@ -190,26 +552,49 @@ impl Db {
         Ok(value)
     }
 
+    /// Undecided proposals of all the rounds at `height`, by round.
+    fn get_undecided_proposals(
+        &self,
+        height: Height,
+    ) -> Result<Vec<ProposedValue<TestContext>>, StoreError> {
+        let start = Instant::now();
+        let mut read_bytes = 0;
+
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(UNDECIDED_PROPOSALS_TABLE)?;
+
+        let mut proposals = Vec::new();
+        for entry in table.range(UndecidedKey::all_rounds_of(height))? {
+            let (_, value) = entry?;
+            let bytes = value.value();
+            read_bytes += bytes.len() as u64;
+
+            let proposal = ProtobufCodec
+                .decode(Bytes::from(bytes))
+                .map_err(StoreError::Protobuf)?;
+            proposals.push(proposal);
+        }
+
+        self.metrics.observe_read_time(start.elapsed());
+        self.metrics.add_read_bytes(read_bytes);
+
+        Ok(proposals)
+    }
+
     fn insert_undecided_proposal(
         &self,
+        tx: &redb::WriteTransaction,
         proposal: ProposedValue<TestContext>,
     ) -> Result<(), StoreError> {
-        let start = Instant::now();
-
-        let key = (proposal.height, proposal.round);
+        let key = UndecidedKey::new(proposal.height, proposal.round);
         let value = ProtobufCodec.encode(&proposal)?;
 
-        let tx = self.db.begin_write()?;
-        {
-            let mut table = tx.open_table(UNDECIDED_PROPOSALS_TABLE)?;
-            // Only insert if no value exists at this key
-            if table.get(&key)?.is_none() {
-                table.insert(key, value.to_vec())?;
-            }
+        let mut table = tx.open_table(UNDECIDED_PROPOSALS_TABLE)?;
+        // Only insert if no value exists at this key
+        if table.get(&key)?.is_none() {
+            table.insert(&key, value.to_vec())?;
         }
-        tx.commit()?;
 
-        self.metrics.observe_write_time(start.elapsed());
         self.metrics.add_write_bytes(value.len() as u64);
 
         Ok(())
@@ -233,10 +618,10 @@ impl Db {
     fn undecided_proposals_range<Table>(
         &self,
         table: &Table,
-        range: impl RangeBounds<(Height, Round)>,
-    ) -> Result<Vec<(Height, Round)>, StoreError>
+        range: impl RangeBounds<UndecidedKey>,
+    ) -> Result<Vec<UndecidedKey>, StoreError>
     where
-        Table: redb::ReadableTable<UndecidedValueKey, Vec<u8>>,
+        Table: redb::ReadableTable<UndecidedKey, Vec<u8>>,
     {
         Ok(table
             .range(range)?
@@ -248,10 +633,10 @@ impl Db {
     fn block_data_range<Table>(
         &self,
         table: &Table,
-        range: impl RangeBounds<(Height, Round)>,
-    ) -> Result<Vec<(Height, Round)>, StoreError>
+        range: impl RangeBounds<UndecidedKey>,
+    ) -> Result<Vec<UndecidedKey>, StoreError>
     where
-        Table: redb::ReadableTable<UndecidedValueKey, Vec<u8>>,
+        Table: redb::ReadableTable<UndecidedKey, Vec<u8>>,
     {
         Ok(table
             .range(range)?
@@ -260,46 +645,204 @@ impl Db {
             .collect::<Vec<_>>())
     }
 
-    fn prune(&self, retain_height: Height) -> Result<Vec<Height>, StoreError> {
+    /// Removes the undecided proposals and block data below `retain_height`.
+    fn prune_undecided(
+        &self,
+        tx: &redb::WriteTransaction,
+        retain_height: Height,
+    ) -> Result<(), StoreError> {
         let start = Instant::now();
 
-        let tx = self.db.begin_write().unwrap();
-
-        let pruned = {
+        {
             let mut undecided = tx.open_table(UNDECIDED_PROPOSALS_TABLE)?;
-            let keys = self.undecided_proposals_range(&undecided, ..(retain_height, Round::Nil))?;
+            let keys = self.undecided_proposals_range(
+                &undecided,
+                UndecidedKey::all_rounds_below(retain_height),
+            )?;
             for key in keys {
                 undecided.remove(key)?;
             }
 
             let mut undecided_block_data = tx.open_table(UNDECIDED_BLOCK_DATA_TABLE)?;
-            let keys =
-                self.block_data_range(&undecided_block_data, ..(retain_height, Round::Nil))?;
+            let keys = self.block_data_range(
+                &undecided_block_data,
+                UndecidedKey::all_rounds_below(retain_height),
+            )?;
             for key in &keys {
                 undecided_block_data.remove(key)?;
             }
+        }
 
+        self.metrics.observe_delete_time(start.elapsed());
+
+        Ok(())
+    }
+
+    /// Removes the decided values, certificates and block data below `retain_height`.
+    fn prune(
+        &self,
+        tx: &redb::WriteTransaction,
+        retain_height: Height,
+    ) -> Result<Vec<Height>, StoreError> {
+        let start = Instant::now();
+
+        let pruned = {
             let mut decided = tx.open_table(DECIDED_VALUES_TABLE)?;
             let mut certificates = tx.open_table(CERTIFICATES_TABLE)?;
             let mut decided_block_data = tx.open_table(DECIDED_BLOCK_DATA_TABLE)?;
 
-            let keys = self.height_range(&decided, ..retain_height)?;
+            let mut pruned_headers = tx.open_table(PRUNED_HEADERS_TABLE)?;
+            let mut blob_index = tx.open_table(BLOB_INDEX_TABLE)?;
+            let mut tx_index = tx.open_table(TX_INDEX_TABLE)?;
+
+            let mut keys = self.height_range(&decided, ..retain_height)?;
             for key in &keys {
                 decided.remove(key)?;
                 certificates.remove(key)?;
-                decided_block_data.remove(key)?;
+                if let Some(data) = decided_block_data.remove(key)? {
+                    let block = Block::from_bytes(&decode_block_data(data.value())?)?;
+                    unindex_blobs(&mut blob_index, *key, &block)?;
+                    unindex_transactions(&mut tx_index, *key, &block)?;
+                }
             }
 
+            // Heights pruned for size have no decided value anymore, and are below the others
+            let pruned_for_size = self.height_range(&certificates, ..retain_height)?;
+            for key in &pruned_for_size {
+                certificates.remove(key)?;
+                pruned_headers.remove(key)?;
+            }
+
+            keys.splice(0..0, pruned_for_size);
             keys
         };
 
+        self.metrics.observe_delete_time(start.elapsed());
+
+        Ok(pruned)
+    }
+
+    /// Bytes of the pages in use. Freed pages are reused before the file grows, so this is what
+    /// pruning can bring down, unlike the file size.
+    fn used_size(&self) -> Result<u64, StoreError> {
+        let tx = self.db.begin_write()?;
+        let stats = tx.stats()?;
+        tx.abort()?;
+        Ok(stats.allocated_pages() * stats.page_size() as u64)
+    }
+
+    /// Prunes the block data of the oldest decided height, keeping its certificate and header.
+    /// The latest decided height is never pruned, it is the parent of the next block. Returns
+    /// the pruned height, if any.
+    fn prune_oldest_block_data(&self) -> Result<Option<Height>, StoreError> {
+        let tx = self.db.begin_write()?;
+
+        let pruned = {
+            let mut decided = tx.open_table(DECIDED_VALUES_TABLE)?;
+            let mut decided_block_data = tx.open_table(DECIDED_BLOCK_DATA_TABLE)?;
+            let mut pruned_headers = tx.open_table(PRUNED_HEADERS_TABLE)?;
+            let mut blob_index = tx.open_table(BLOB_INDEX_TABLE)?;
+            let mut tx_index = tx.open_table(TX_INDEX_TABLE)?;
+
+            let oldest = decided.first()?.map(|(key, _)| key.value());
+            let latest = decided.last()?.map(|(key, _)| key.value());
+            match oldest.zip(latest) {
+                Some((oldest, latest)) if oldest < latest => {
+                    decided.remove(&oldest)?;
+                    if let Some(data) = decided_block_data.remove(&oldest)? {
+                        let block = Block::from_bytes(&decode_block_data(data.value())?)?;
+                        pruned_headers.insert(oldest, block.header().to_bytes()?.to_vec())?;
+                        unindex_blobs(&mut blob_index, oldest, &block)?;
+                        unindex_transactions(&mut tx_index, oldest, &block)?;
+                    }
+                    Some(oldest)
+                }
+                _ => None,
+            }
+        };
+
         tx.commit()?;
+        Ok(pruned)
+    }
 
-        self.metrics.observe_delete_time(start.elapsed());
+    /// Prunes the block data of the oldest decided heights until the store uses at most
+    /// `max_db_size` bytes. Returns the pruned heights.
+    fn prune_to_size(&self) -> Result<Vec<Height>, StoreError> {
+        let start = Instant::now();
+        let mut pruned = Vec::new();
+
+        if self.max_db_size == 0 {
+            return Ok(pruned);
+        }
+
+        while self.used_size()? > self.max_db_size {
+            match self.prune_oldest_block_data()? {
+                Some(height) => pruned.push(height),
+                None => {
+                    warn!(
+                        max_db_size = self.max_db_size,
+                        "Store above its size cap with no block data left to prune"
+                    );
+                    break;
+                }
+            }
+        }
+
+        if let (Some(first), Some(last)) = (pruned.first(), pruned.last()) {
+            info!(%first, %last, "Pruned block data to stay under the store size cap");
+            self.metrics.observe_delete_time(start.elapsed());
+        }
 
         Ok(pruned)
     }
 
+    fn get_certificate(
+        &self,
+        height: Height,
+    ) -> Result<Option<CommitCertificate<TestContext>>, StoreError> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(CERTIFICATES_TABLE)?;
+        let certificate = table.get(&height)?;
+        Ok(certificate
+            .map(|value| decode_certificate(&value.value()))
+            .transpose()?)
+    }
+
+    fn get_blob_location(
+        &self,
+        commitment: [u8; 32],
+    ) -> Result<Option<(Height, usize)>, StoreError> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(BLOB_INDEX_TABLE)?;
+        let location = table.get(commitment.as_slice())?;
+        Ok(location.map(|value| {
+            let (height, index) = value.value();
+            (Height::new(height), index as usize)
+        }))
+    }
+
+    fn get_transaction_location(
+        &self,
+        hash: [u8; 32],
+    ) -> Result<Option<(Height, usize)>, StoreError> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(TX_INDEX_TABLE)?;
+        let location = table.get(hash.as_slice())?;
+        Ok(location.map(|value| {
+            let (height, index) = value.value();
+            (Height::new(height), index as usize)
+        }))
+    }
+
+    fn get_pruned_header(&self, height: Height) -> Result<Option<Header>, StoreError> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(PRUNED_HEADERS_TABLE)?;
+        let header = table.get(&height)?;
+        Ok(header
+            .map(|value| Header::from_bytes(&value.value()))
+            .transpose()?)
+    }
+
     fn min_decided_value_height(&self) -> Option<Height> {
         let start = Instant::now();
 
@@ -321,6 +864,22 @@ impl Db {
         Some(key.value())
     }
 
+    /// Lowest height whose block data is stored, which [`Self::prune_to_size`] may have removed
+    /// before the decided value.
+    fn min_decided_block_height(&self) -> Option<Height> {
+        let tx = self.db.begin_read().ok()?;
+        let table = tx.open_table(DECIDED_BLOCK_DATA_TABLE).ok()?;
+        let (key, _) = table.first().ok()??;
+        Some(key.value())
+    }
+
+    fn min_certificate_height(&self) -> Option<Height> {
+        let tx = self.db.begin_read().ok()?;
+        let table = tx.open_table(CERTIFICATES_TABLE).ok()?;
+        let (key, _) = table.first().ok()??;
+        Some(key.value())
+    }
+
     fn create_tables(&self) -> Result<(), StoreError> {
         let tx = self.db.begin_write()?;
 
@@ -330,6 +889,15 @@ impl Db {
         let _ = tx.open_table(UNDECIDED_PROPOSALS_TABLE)?;
         let _ = tx.open_table(DECIDED_BLOCK_DATA_TABLE)?;
         let _ = tx.open_table(UNDECIDED_BLOCK_DATA_TABLE)?;
+        let _ = tx.open_table(NAMESPACES_TABLE)?;
+        let _ = tx.open_table(GOVERNANCE_TABLE)?;
+        let _ = tx.open_table(LIVENESS_TABLE)?;
+        let _ = tx.open_table(EVIDENCE_TABLE)?;
+        let _ = tx.open_table(ACCOUNT_NONCES_TABLE)?;
+        let _ = tx.open_table(STREAM_OFFSETS_TABLE)?;
+        let _ = tx.open_table(PRUNED_HEADERS_TABLE)?;
+        let _ = tx.open_table(BLOB_INDEX_TABLE)?;
+        let _ = tx.open_table(TX_INDEX_TABLE)?;
 
         tx.commit()?;
 
@@ -343,7 +911,7 @@ impl Db {
 
         // Try undecided block data first
         let undecided_table = tx.open_table(UNDECIDED_BLOCK_DATA_TABLE)?;
-        if let Some(data) = undecided_table.get(&(height, round))? {
+        if let Some(data) = undecided_table.get(&UndecidedKey::new(height, round))? {
             let bytes = data.value();
             let read_bytes = bytes.len() as u64;
             self.metrics.observe_read_time(start.elapsed());
@@ -361,7 +929,7 @@ impl Db {
             self.metrics.observe_read_time(start.elapsed());
             self.metrics.add_read_bytes(read_bytes);
             self.metrics.add_key_read_bytes(size_of::<Height>() as u64);
-            return Ok(Some(Bytes::copy_from_slice(&bytes)));
+            return Ok(Some(decode_block_data(bytes)?));
         }
 
         self.metrics.observe_read_time(start.elapsed());
@@ -370,50 +938,222 @@ impl Db {
 
     fn insert_undecided_block_data(
         &self,
+        tx: &redb::WriteTransaction,
         height: Height,
         round: Round,
         data: Bytes,
     ) -> Result<(), StoreError> {
-        let start = Instant::now();
-        let write_bytes = data.len() as u64;
-
-        let tx = self.db.begin_write()?;
-        {
-            let mut table = tx.open_table(UNDECIDED_BLOCK_DATA_TABLE)?;
-            let key = (height, round);
-            // Only insert if no value exists at this key
-            if table.get(&key)?.is_none() {
-                table.insert(key, data.to_vec())?;
-            }
+        let mut table = tx.open_table(UNDECIDED_BLOCK_DATA_TABLE)?;
+        let key = UndecidedKey::new(height, round);
+        // Only insert if no value exists at this key
+        if table.get(&key)?.is_none() {
+            table.insert(&key, data.to_vec())?;
         }
-        tx.commit()?;
 
-        self.metrics.observe_write_time(start.elapsed());
-        self.metrics.add_write_bytes(write_bytes);
+        self.metrics.add_write_bytes(data.len() as u64);
 
         Ok(())
     }
 
-    fn insert_decided_block_data(&self, height: Height, data: Bytes) -> Result<(), StoreError> {
-        let start = Instant::now();
-        let write_bytes = data.len() as u64;
+    fn insert_decided_block_data(
+        &self,
+        tx: &redb::WriteTransaction,
+        height: Height,
+        data: Bytes,
+    ) -> Result<(), StoreError> {
+        let compressed = self
+            .compression
+            .compress(data.clone(), self.compression_level)?;
 
-        let tx = self.db.begin_write()?;
         {
             let mut table = tx.open_table(DECIDED_BLOCK_DATA_TABLE)?;
             // Only insert if no value exists at this key
             if table.get(&height)?.is_none() {
-                table.insert(height, data.to_vec())?;
+                table.insert(height, compressed.to_vec())?;
+                index_block(tx, height, &data)?;
             }
         }
-        tx.commit()?;
 
-        self.metrics.observe_write_time(start.elapsed());
-        self.metrics.add_write_bytes(write_bytes);
+        self.metrics.add_write_bytes(compressed.len() as u64);
+
+        Ok(())
+    }
+
+    /// Moves the block data of the decided `round` at `height` to the decided table within `tx`,
+    /// and drops the data of the other rounds at that height. Falls back to data already decided
+    /// at `height`. Returns the uncompressed data.
+    fn decide_block_data(
+        &self,
+        tx: &redb::WriteTransaction,
+        height: Height,
+        round: Round,
+        write_bytes: &mut u64,
+    ) -> Result<Option<Bytes>, StoreError> {
+        let mut undecided = tx.open_table(UNDECIDED_BLOCK_DATA_TABLE)?;
+        let data = undecided
+            .get(&UndecidedKey::new(height, round))?
+            .map(|data| Bytes::from(data.value()));
+
+        let keys = self.block_data_range(&undecided, UndecidedKey::all_rounds_of(height))?;
+        for key in &keys {
+            undecided.remove(key)?;
+        }
+
+        let mut decided = tx.open_table(DECIDED_BLOCK_DATA_TABLE)?;
+        let data = match (data, decided.get(&height)?.map(|data| data.value())) {
+            (_, Some(existing)) => Some(decode_block_data(existing)?),
+            (Some(data), None) => {
+                let compressed = self
+                    .compression
+                    .compress(data.clone(), self.compression_level)?;
+                *write_bytes += compressed.len() as u64;
+                decided.insert(height, compressed.to_vec())?;
+                index_block(tx, height, &data)?;
+                Some(data)
+            }
+            (None, None) => None,
+        };
+
+        Ok(data)
+    }
+
+    fn get_namespaces(&self) -> Result<Vec<([u8; 32], Registration)>, StoreError> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(NAMESPACES_TABLE)?;
+
+        let mut registrations = Vec::new();
+        for entry in table.iter()? {
+            let (key, value) = entry?;
+            let Ok(namespace) = <[u8; 32]>::try_from(key.value()) else {
+                error!("Invalid namespace key in the store");
+                continue;
+            };
+            registrations.push((namespace, serde_json::from_slice(&value.value())?));
+        }
+
+        Ok(registrations)
+    }
+
+    fn get_governance(&self) -> Result<Vec<([u8; 32], Proposal)>, StoreError> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(GOVERNANCE_TABLE)?;
+
+        let mut proposals = Vec::new();
+        for entry in table.iter()? {
+            let (key, value) = entry?;
+            let Ok(id) = <[u8; 32]>::try_from(key.value()) else {
+                error!("Invalid parameter change key in the store");
+                continue;
+            };
+            proposals.push((id, serde_json::from_slice(&value.value())?));
+        }
+
+        Ok(proposals)
+    }
+
+    fn get_liveness(&self) -> Result<Vec<(Address, ValidatorLiveness)>, StoreError> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(LIVENESS_TABLE)?;
+
+        let mut records = Vec::new();
+        for entry in table.iter()? {
+            let (key, value) = entry?;
+            let Ok(address) = <[u8; 20]>::try_from(key.value()) else {
+                error!("Invalid validator address key in the store");
+                continue;
+            };
+            records.push((
+                Address::new(address),
+                serde_json::from_slice(&value.value())?,
+            ));
+        }
+
+        Ok(records)
+    }
+
+    fn insert_evidence(
+        &self,
+        tx: &redb::WriteTransaction,
+        record: EvidenceRecord,
+    ) -> Result<bool, StoreError> {
+        let key = (
+            record.evidence.height(),
+            record.evidence.validator().into_inner(),
+        );
+
+        let mut table = tx.open_table(EVIDENCE_TABLE)?;
+        let mut records: Vec<EvidenceRecord> = match table.get(key)? {
+            Some(value) => serde_json::from_slice(&value.value())?,
+            None => Vec::new(),
+        };
+        if records.iter().any(|r| r.evidence == record.evidence) {
+            return Ok(false);
+        }
+        records.push(record);
+        let value = serde_json::to_vec(&records)?;
+        table.insert(key, value.clone())?;
+
+        self.metrics.add_write_bytes(value.len() as u64);
+
+        Ok(true)
+    }
+
+    fn get_evidence(
+        &self,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<Vec<EvidenceRecord>, StoreError> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(EVIDENCE_TABLE)?;
+
+        let mut records = Vec::new();
+        for entry in table.range((from_height, [0; 20])..=(to_height, [u8::MAX; 20]))? {
+            let (_, value) = entry?;
+            records.extend(serde_json::from_slice::<Vec<EvidenceRecord>>(
+                &value.value(),
+            )?);
+        }
+
+        Ok(records)
+    }
+
+    fn get_account_nonces(&self) -> Result<Vec<([u8; 32], u64)>, StoreError> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(ACCOUNT_NONCES_TABLE)?;
+
+        let mut nonces = Vec::new();
+        for entry in table.iter()? {
+            let (key, value) = entry?;
+            let Ok(sender) = <[u8; 32]>::try_from(key.value()) else {
+                error!("Invalid account nonce key in the store");
+                continue;
+            };
+            nonces.push((sender, value.value()));
+        }
+
+        Ok(nonces)
+    }
+
+    fn insert_stream_offset(
+        &self,
+        tx: &redb::WriteTransaction,
+        stream: &str,
+        height: u64,
+    ) -> Result<(), StoreError> {
+        let mut table = tx.open_table(STREAM_OFFSETS_TABLE)?;
+        table.insert(stream, height)?;
+
+        self.metrics.add_write_bytes(stream.len() as u64 + 8);
 
         Ok(())
     }
 
+    fn get_stream_offset(&self, stream: &str) -> Result<Option<u64>, StoreError> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(STREAM_OFFSETS_TABLE)?;
+        Ok(table.get(stream)?.map(|height| height.value()))
+    }
+
     pub fn get_decided_block(&self, height: Height) -> Result<Option<Bytes>, StoreError> {
         let start = Instant::now();
         let tx = self.db.begin_read()?;
@@ -425,26 +1165,267 @@ impl Db {
             self.metrics.observe_read_time(start.elapsed());
             self.metrics.add_read_bytes(read_bytes);
             self.metrics.add_key_read_bytes(size_of::<Height>() as u64);
-            return Ok(Some(Bytes::copy_from_slice(&bytes)));
+            return Ok(Some(decode_block_data(bytes)?));
         }
         Ok(None)
     }
 }
 
+/// Most writes the [`StoreWriter`] applies in one transaction in a batch.
+const MAX_BATCH_WRITES: usize = 256;
+
+/// Reply to the sender of a write applied in a batch, sent once the batch is committed or aborted.
+type Reply = Box<dyn FnOnce(Result<(), StoreError>) + Send>;
+
+/// Write applied by the [`StoreWriter`] within the transaction of its batch.
+trait BatchedWrite: Send {
+    /// Writes within `tx`, returning the reply to send once `tx` is committed. On failure,
+    /// replies with the error and returns it as the cause that aborts `tx`.
+    fn apply(self: Box<Self>, db: &Db, tx: &redb::WriteTransaction) -> Result<Reply, String>;
+
+    /// Replies with `error` without writing.
+    fn reject(self: Box<Self>, error: StoreError);
+}
+
+/// Write sent by [`StoreWriter::write`], replying on its channel.
+struct PendingWrite<F, T> {
+    write: F,
+    reply: tokio::sync::oneshot::Sender<Result<T, StoreError>>,
+}
+
+impl<F, T> BatchedWrite for PendingWrite<F, T>
+where
+    F: FnOnce(&Db, &redb::WriteTransaction) -> Result<T, StoreError> + Send,
+    T: Send + 'static,
+{
+    fn apply(self: Box<Self>, db: &Db, tx: &redb::WriteTransaction) -> Result<Reply, String> {
+        let Self { write, reply } = *self;
+        match write(db, tx) {
+            Ok(value) => Ok(Box::new(move |committed: Result<(), StoreError>| {
+                let _ = reply.send(committed.map(|()| value));
+            })),
+            Err(e) => {
+                let cause = e.to_string();
+                let _ = reply.send(Err(e));
+                Err(cause)
+            }
+        }
+    }
+
+    fn reject(self: Box<Self>, error: StoreError) {
+        let _ = self.reply.send(Err(error));
+    }
+}
+
+/// Command run by the [`StoreWriter`], replying to its sender.
+enum WriteCommand {
+    /// Applied in one transaction with the batched commands queued around it
+    Batched(Box<dyn BatchedWrite>),
+    /// Run on its own, committing transactions of its own
+    Alone(Box<dyn FnOnce(&Db) + Send>),
+}
+
+/// Dedicated thread applying every write of a store, in the order they were sent.
+///
+/// redb has a single write transaction at a time: writes from the consensus path and from
+/// background jobs or RPC handlers, each on a blocking thread of their own, would otherwise queue
+/// on its lock in no particular order while holding threads of the blocking pool. The batched
+/// commands queued while a transaction commits are applied together in the next one, synced to
+/// disk once for all of them.
+///
+/// A failing write aborts the writes applied before it in its transaction, which fail with
+/// [`StoreError::Aborted`]; the writes queued after it go to the next transaction. Writes are
+/// only acknowledged once committed, so a sender never sees a write that is later rolled back.
+#[derive(Clone)]
+struct StoreWriter {
+    /// `None` for a read-only store, whose writes fail with [`StoreError::ReadOnly`]
+    commands: Option<mpsc::Sender<WriteCommand>>,
+}
+
+impl StoreWriter {
+    /// Stops once every handle of the store is dropped, after the commands already sent.
+    fn spawn(db: Arc<Db>) -> Result<Self, StoreError> {
+        let (commands, receiver) = mpsc::channel::<WriteCommand>();
+        std::thread::Builder::new()
+            .name("store-writer".to_string())
+            .spawn(move || Self::run(&db, &receiver))?;
+
+        Ok(Self {
+            commands: Some(commands),
+        })
+    }
+
+    fn read_only() -> Self {
+        Self { commands: None }
+    }
+
+    fn run(db: &Db, receiver: &mpsc::Receiver<WriteCommand>) {
+        let mut queue = VecDeque::new();
+        while let Ok(command) = receiver.recv() {
+            queue.push_back(command);
+            queue.extend(receiver.try_iter());
+
+            while let Some(command) = queue.pop_front() {
+                let write = match command {
+                    WriteCommand::Alone(run) => {
+                        run(db);
+                        continue;
+                    }
+                    WriteCommand::Batched(write) => write,
+                };
+
+                let mut batch = vec![write];
+                while batch.len() < MAX_BATCH_WRITES {
+                    match queue.pop_front() {
+                        Some(WriteCommand::Batched(write)) => batch.push(write),
+                        Some(command) => {
+                            queue.push_front(command);
+                            break;
+                        }
+                        None => break,
+                    }
+                }
+
+                let unapplied = Self::commit_batch(db, batch);
+                for write in unapplied.into_iter().rev() {
+                    queue.push_front(WriteCommand::Batched(write));
+                }
+            }
+        }
+    }
+
+    /// Applies `batch` in one transaction and replies to its writes once it is committed.
+    /// Returns the writes left unapplied after a failing one.
+    fn commit_batch(db: &Db, batch: Vec<Box<dyn BatchedWrite>>) -> Vec<Box<dyn BatchedWrite>> {
+        let start = Instant::now();
+
+        let mut tx = match db.db.begin_write() {
+            Ok(tx) => tx,
+            Err(e) => {
+                let cause = e.to_string();
+                for write in batch {
+                    write.reject(StoreError::Aborted(cause.clone()));
+                }
+                return Vec::new();
+            }
+        };
+        tx.set_durability(redb::Durability::Immediate);
+
+        let mut replies = Vec::with_capacity(batch.len());
+        let mut writes = batch.into_iter();
+        while let Some(write) = writes.next() {
+            match write.apply(db, &tx) {
+                Ok(reply) => replies.push(reply),
+                Err(cause) => {
+                    if let Err(e) = tx.abort() {
+                        error!(error = %e, "Failed to abort a store transaction");
+                    }
+                    for reply in replies {
+                        reply(Err(StoreError::Aborted(cause.clone())));
+                    }
+                    return writes.collect();
+                }
+            }
+        }
+
+        let committed = tx.commit().map_err(|e| e.to_string());
+        if committed.is_ok() {
+            db.metrics.observe_write_time(start.elapsed());
+        }
+        for reply in replies {
+            reply(committed.clone().map_err(StoreError::Aborted));
+        }
+
+        Vec::new()
+    }
+
+    fn send(&self, command: WriteCommand) -> Result<(), StoreError> {
+        self.commands
+            .as_ref()
+            .ok_or(StoreError::ReadOnly)?
+            .send(command)
+            .map_err(|_| StoreError::WriterStopped)
+    }
+
+    /// Applies `write` within the transaction of its batch.
+    async fn write<T: Send + 'static>(
+        &self,
+        write: impl FnOnce(&Db, &redb::WriteTransaction) -> Result<T, StoreError> + Send + 'static,
+    ) -> Result<T, StoreError> {
+        let (reply, result) = tokio::sync::oneshot::channel();
+        self.send(WriteCommand::Batched(Box::new(PendingWrite {
+            write,
+            reply,
+        })))?;
+
+        result.await.map_err(|_| StoreError::WriterStopped)?
+    }
+
+    /// Runs `write` on its own, for writes committing transactions of their own.
+    async fn write_alone<T: Send + 'static>(
+        &self,
+        write: impl FnOnce(&Db) -> Result<T, StoreError> + Send + 'static,
+    ) -> Result<T, StoreError> {
+        let (reply, result) = tokio::sync::oneshot::channel();
+        self.send(WriteCommand::Alone(Box::new(move |db| {
+            let _ = reply.send(write(db));
+        })))?;
+
+        result.await.map_err(|_| StoreError::WriterStopped)?
+    }
+}
+
 #[derive(Clone)]
 pub struct Store {
     db: Arc<Db>,
+    writer: StoreWriter,
     path: PathBuf,
 }
 
 impl Store {
-    pub fn open(path: impl AsRef<Path>, metrics: DbMetrics) -> Result<Self, StoreError> {
+    pub fn open(
+        path: impl AsRef<Path>,
+        metrics: DbMetrics,
+        compression: &CompressionConfig,
+        storage: &StorageConfig,
+    ) -> Result<Self, StoreError> {
         let path_buf = path.as_ref().to_path_buf();
-        let db = Db::new(&path_buf, metrics)?;
+        let db = redb::Database::create(&path_buf)?;
+        Self::with_database(db, path_buf, metrics, compression, storage)
+    }
+
+    fn with_database(
+        db: redb::Database,
+        path: PathBuf,
+        metrics: DbMetrics,
+        compression: &CompressionConfig,
+        storage: &StorageConfig,
+    ) -> Result<Self, StoreError> {
+        let db = Db::new(db, metrics, compression, storage);
         db.create_tables()?;
+        let db = Arc::new(db);
+
+        Ok(Self {
+            writer: StoreWriter::spawn(db.clone())?,
+            db,
+            path,
+        })
+    }
+
+    /// Opens the store at `path` for reading, for tools like exporters and monitoring agents.
+    /// Writes fail with [`StoreError::ReadOnly`].
+    ///
+    /// Several processes can open a store read-only at once, but redb locks the file while it
+    /// is open for writing: opening the store of a running node fails with
+    /// [`StoreError::Locked`], and a node does not start while a tool has its store open. Query
+    /// a running node through the JSON-RPC API instead.
+    pub fn open_read_only(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        let path_buf = path.as_ref().to_path_buf();
+        let db = Arc::new(Db::read_only(&path_buf)?);
 
         Ok(Self {
-            db: Arc::new(db),
+            writer: StoreWriter::read_only(),
+            db,
             path: path_buf,
         })
     }
@@ -453,6 +1434,31 @@ impl Store {
         &self.path
     }
 
+    pub fn metrics(&self) -> &DbMetrics {
+        &self.db.metrics
+    }
+
+    /// Decided heights whose data is retained when the chain retains `chain_retain_heights`, see
+    /// [`StorageConfig::da_retain_heights`].
+    pub fn retain_heights(&self, chain_retain_heights: u64) -> u64 {
+        chain_retain_heights.max(self.db.da_retain_heights)
+    }
+
+    /// See [`StorageConfig::consensus_retain_heights`].
+    pub fn consensus_retain_heights(&self) -> u64 {
+        self.db.consensus_retain_heights
+    }
+
+    /// Cap on the pages in use, see [`StorageConfig::max_db_size`].
+    pub fn max_db_size(&self) -> u64 {
+        self.db.max_db_size
+    }
+
+    /// Size of the database file in bytes.
+    pub fn db_size(&self) -> Result<u64, StoreError> {
+        Ok(std::fs::metadata(&self.path)?.len())
+    }
+
     pub async fn min_decided_value_height(&self) -> Option<Height> {
         let db = Arc::clone(&self.db);
         tokio::task::spawn_blocking(move || db.min_decided_value_height())
@@ -469,6 +1475,22 @@ impl Store {
             .flatten()
     }
 
+    pub async fn min_decided_block_height(&self) -> Option<Height> {
+        let db = Arc::clone(&self.db);
+        tokio::task::spawn_blocking(move || db.min_decided_block_height())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    pub async fn min_certificate_height(&self) -> Option<Height> {
+        let db = Arc::clone(&self.db);
+        tokio::task::spawn_blocking(move || db.min_certificate_height())
+            .await
+            .ok()
+            .flatten()
+    }
+
     pub async fn get_decided_value(
         &self,
         height: Height,
@@ -478,26 +1500,50 @@ impl Store {
         tokio::task::spawn_blocking(move || db.get_decided_value(height)).await?
     }
 
-    pub async fn store_decided_value(
+    /// Persists the decided `value` with its `certificate` and moves the block data of the decided
+    /// round out of the undecided table, durably and atomically with the state `derived` from the
+    /// decided block. Returns the uncompressed data, or fails with
+    /// [`StoreError::MissingBlockData`] without writing anything.
+    pub async fn commit_decided(
         &self,
         certificate: &CommitCertificate<TestContext>,
         value: Value,
-    ) -> Result<(), StoreError> {
+        derived: DerivedState,
+    ) -> Result<Bytes, StoreError> {
+        let round = certificate.round;
         let decided_value = DecidedValue {
             value,
             certificate: certificate.clone(),
         };
 
-        let db = Arc::clone(&self.db);
-        tokio::task::spawn_blocking(move || db.insert_decided_value(decided_value)).await?
+        self.writer
+            .write(move |db, tx| db.commit_decided(tx, decided_value, round, derived))
+            .await
+    }
+
+    /// Completes the commits of the heights whose certificate was stored without their block
+    /// data, returning those heights.
+    pub async fn complete_commits(&self) -> Result<Vec<Height>, StoreError> {
+        self.writer
+            .write(move |db, tx| {
+                db.incomplete_commits(tx)?
+                    .into_iter()
+                    .map(|(height, round)| {
+                        db.complete_commit(tx, height, round)?;
+                        Ok(height)
+                    })
+                    .collect()
+            })
+            .await
     }
 
     pub async fn store_undecided_proposal(
         &self,
         value: ProposedValue<TestContext>,
     ) -> Result<(), StoreError> {
-        let db = Arc::clone(&self.db);
-        tokio::task::spawn_blocking(move || db.insert_undecided_proposal(value)).await?
+        self.writer
+            .write(move |db, tx| db.insert_undecided_proposal(tx, value))
+            .await
     }
 
     pub async fn get_undecided_proposal(
@@ -509,10 +1555,71 @@ impl Store {
         tokio::task::spawn_blocking(move || db.get_undecided_proposal(height, round)).await?
     }
 
+    /// Undecided proposals of all the rounds at `height`, by round.
+    pub async fn get_undecided_proposals(
+        &self,
+        height: Height,
+    ) -> Result<Vec<ProposedValue<TestContext>>, StoreError> {
+        let db = Arc::clone(&self.db);
+        tokio::task::spawn_blocking(move || db.get_undecided_proposals(height)).await?
+    }
+
+    /// Prunes the decided data below `retain_height`, see [`Self::retain_heights`].
     pub async fn prune(&self, retain_height: Height) -> Result<Vec<Height>, StoreError> {
+        self.writer
+            .write(move |db, tx| db.prune(tx, retain_height))
+            .await
+    }
+
+    /// Prunes the undecided proposals and block data below `retain_height`, see
+    /// [`Self::consensus_retain_heights`].
+    pub async fn prune_undecided(&self, retain_height: Height) -> Result<(), StoreError> {
+        self.writer
+            .write(move |db, tx| db.prune_undecided(tx, retain_height))
+            .await
+    }
+    /// Prunes the block data of the oldest heights while the store is above
+    /// [`StorageConfig::max_db_size`], keeping their certificates and headers. Freed pages only
+    /// show in the size once committed, so this commits a transaction per pruned height.
+    pub async fn prune_to_size(&self) -> Result<Vec<Height>, StoreError> {
+        self.writer.write_alone(move |db| db.prune_to_size()).await
+    }
+
+    /// Certificate that decided `height`, kept after its block data is pruned for size.
+    pub async fn get_certificate(
+        &self,
+        height: Height,
+    ) -> Result<Option<CommitCertificate<TestContext>>, StoreError> {
         let db = Arc::clone(&self.db);
-        tokio::task::spawn_blocking(move || db.prune(retain_height)).await?
+        tokio::task::spawn_blocking(move || db.get_certificate(height)).await?
     }
+
+    /// Height and index of the first decided blob committed to by `commitment`, as long as the
+    /// block data of that height is kept.
+    pub async fn get_blob_location(
+        &self,
+        commitment: [u8; 32],
+    ) -> Result<Option<(Height, usize)>, StoreError> {
+        let db = Arc::clone(&self.db);
+        tokio::task::spawn_blocking(move || db.get_blob_location(commitment)).await?
+    }
+
+    /// Height and index of the decided transaction with `hash`, as long as the block data of
+    /// that height is kept.
+    pub async fn get_transaction_location(
+        &self,
+        hash: [u8; 32],
+    ) -> Result<Option<(Height, usize)>, StoreError> {
+        let db = Arc::clone(&self.db);
+        tokio::task::spawn_blocking(move || db.get_transaction_location(hash)).await?
+    }
+
+    /// Header of a height whose block data was pruned for size.
+    pub async fn get_pruned_header(&self, height: Height) -> Result<Option<Header>, StoreError> {
+        let db = Arc::clone(&self.db);
+        tokio::task::spawn_blocking(move || db.get_pruned_header(height)).await?
+    }
+
     pub async fn get_block_data(
         &self,
         height: Height,
@@ -533,9 +1640,9 @@ impl Store {
         round: Round,
         data: Bytes,
     ) -> Result<(), StoreError> {
-        let db = Arc::clone(&self.db);
-        tokio::task::spawn_blocking(move || db.insert_undecided_block_data(height, round, data))
-            .await?
+        self.writer
+            .write(move |db, tx| db.insert_undecided_block_data(tx, height, round, data))
+            .await
     }
 
     pub async fn store_decided_block_data(
@@ -543,7 +1650,327 @@ impl Store {
         height: Height,
         data: Bytes,
     ) -> Result<(), StoreError> {
+        self.writer
+            .write(move |db, tx| db.insert_decided_block_data(tx, height, data))
+            .await
+    }
+
+    pub async fn get_namespaces(&self) -> Result<Vec<([u8; 32], Registration)>, StoreError> {
+        let db = Arc::clone(&self.db);
+        tokio::task::spawn_blocking(move || db.get_namespaces()).await?
+    }
+
+    pub async fn get_governance(&self) -> Result<Vec<([u8; 32], Proposal)>, StoreError> {
+        let db = Arc::clone(&self.db);
+        tokio::task::spawn_blocking(move || db.get_governance()).await?
+    }
+
+    pub async fn get_liveness(&self) -> Result<Vec<(Address, ValidatorLiveness)>, StoreError> {
+        let db = Arc::clone(&self.db);
+        tokio::task::spawn_blocking(move || db.get_liveness()).await?
+    }
+
+    /// Persists double-sign evidence, returning `false` if it was already recorded.
+    pub async fn store_evidence(&self, record: EvidenceRecord) -> Result<bool, StoreError> {
+        self.writer
+            .write(move |db, tx| db.insert_evidence(tx, record))
+            .await
+    }
+
+    /// Returns the evidence recorded for heights `from_height..=to_height`.
+    pub async fn get_evidence(
+        &self,
+        from_height: u64,
+        to_height: u64,
+    ) -> Result<Vec<EvidenceRecord>, StoreError> {
+        let db = Arc::clone(&self.db);
+        tokio::task::spawn_blocking(move || db.get_evidence(from_height, to_height)).await?
+    }
+
+    pub async fn get_account_nonces(&self) -> Result<Vec<([u8; 32], u64)>, StoreError> {
         let db = Arc::clone(&self.db);
-        tokio::task::spawn_blocking(move || db.insert_decided_block_data(height, data)).await?
+        tokio::task::spawn_blocking(move || db.get_account_nonces()).await?
+    }
+
+    /// Records `height` as the last height published by `stream`.
+    pub async fn store_stream_offset(&self, stream: String, height: u64) -> Result<(), StoreError> {
+        self.writer
+            .write(move |db, tx| db.insert_stream_offset(tx, &stream, height))
+            .await
+    }
+
+    pub async fn get_stream_offset(&self, stream: String) -> Result<Option<u64>, StoreError> {
+        let db = Arc::clone(&self.db);
+        tokio::task::spawn_blocking(move || db.get_stream_offset(&stream)).await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::mock_make_validator;
+    use crate::transactions::Transaction;
+    use malachitebft_app_channel::app::types::core::AggregatedSignature;
+    use redb::backends::InMemoryBackend;
+
+    fn in_memory_store(storage: &StorageConfig) -> Store {
+        let db = redb::Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .unwrap();
+        Store::with_database(
+            db,
+            PathBuf::new(),
+            DbMetrics::new(),
+            &CompressionConfig::default(),
+            storage,
+        )
+        .unwrap()
+    }
+
+    fn block_data(height: u64) -> Bytes {
+        Block::new(
+            height,
+            0,
+            [0; 32],
+            mock_make_validator(),
+            vec![Transaction::random()],
+        )
+        .to_bytes()
+        .unwrap()
+    }
+
+    /// Decides `data` at `height` in round 0, as consensus would.
+    async fn decide(store: &Store, height: u64, data: Bytes) {
+        let (height, round) = (Height::new(height), Round::new(0));
+        store
+            .store_undecided_block_data(height, round, data.clone())
+            .await
+            .unwrap();
+
+        let value = Value::new(data);
+        let certificate = CommitCertificate {
+            height,
+            round,
+            value_id: value.id(),
+            aggregated_signature: AggregatedSignature {
+                signatures: Vec::new(),
+            },
+        };
+        store
+            .commit_decided(&certificate, value, DerivedState::default())
+            .await
+            .unwrap();
+    }
+
+    fn undecided_block_data_keys(store: &Store) -> Vec<UndecidedKey> {
+        let tx = store.db.db.begin_read().unwrap();
+        let table = tx.open_table(UNDECIDED_BLOCK_DATA_TABLE).unwrap();
+        table
+            .iter()
+            .unwrap()
+            .map(|entry| entry.unwrap().0.value())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn writes_apply_in_send_order() {
+        let store = in_memory_store(&StorageConfig::default());
+        let stream = "events".to_string();
+        let (height, round) = (Height::new(1), Round::new(0));
+
+        // Sent before the writer picks any of them up, so likely applied in a single batch
+        let results = tokio::join!(
+            store.store_stream_offset(stream.clone(), 1),
+            store.store_undecided_block_data(height, round, Bytes::from_static(b"first")),
+            store.store_stream_offset(stream.clone(), 2),
+            store.store_undecided_block_data(height, round, Bytes::from_static(b"second")),
+            store.store_stream_offset(stream.clone(), 3),
+        );
+        results.0.unwrap();
+        results.1.unwrap();
+        results.2.unwrap();
+        results.3.unwrap();
+        results.4.unwrap();
+
+        assert_eq!(store.get_stream_offset(stream).await.unwrap(), Some(3));
+        assert_eq!(
+            store.get_block_data(height, round).await.unwrap(),
+            Some(Bytes::from_static(b"first"))
+        );
+    }
+
+    #[tokio::test]
+    async fn failing_write_leaves_later_writes_applied() {
+        let store = in_memory_store(&StorageConfig::default());
+        let stream = "events".to_string();
+
+        let (before, invalid, after) = tokio::join!(
+            store.store_stream_offset(stream.clone(), 1),
+            store.store_decided_block_data(Height::new(1), Bytes::from_static(b"not a block")),
+            store.store_stream_offset(stream.clone(), 2),
+        );
+        assert!(matches!(before, Ok(()) | Err(StoreError::Aborted(_))));
+        assert!(matches!(invalid, Err(StoreError::Protobuf(_))));
+        after.unwrap();
+
+        assert_eq!(store.get_stream_offset(stream).await.unwrap(), Some(2));
+        assert_eq!(store.get_decided_block(Height::new(1)).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn read_only_store_rejects_writes() {
+        let store = in_memory_store(&StorageConfig::default());
+        let store = Store {
+            writer: StoreWriter::read_only(),
+            ..store
+        };
+
+        assert!(matches!(
+            store.store_stream_offset("events".to_string(), 1).await,
+            Err(StoreError::ReadOnly)
+        ));
+    }
+
+    #[tokio::test]
+    async fn deciding_block_data_leaves_no_undecided_row() {
+        let store = in_memory_store(&StorageConfig::default());
+        let (height, next) = (Height::new(1), Height::new(2));
+        let decided = block_data(1);
+
+        for round in [Round::new(0), Round::new(2)] {
+            store
+                .store_undecided_block_data(height, round, block_data(1))
+                .await
+                .unwrap();
+        }
+        store
+            .store_undecided_block_data(height, Round::new(1), decided.clone())
+            .await
+            .unwrap();
+        store
+            .store_undecided_block_data(next, Round::new(0), block_data(2))
+            .await
+            .unwrap();
+
+        let data = store
+            .writer
+            .write(move |db, tx| db.decide_block_data(tx, height, Round::new(1), &mut 0))
+            .await
+            .unwrap();
+        assert_eq!(data, Some(decided.clone()));
+
+        // Only the next height keeps undecided data
+        assert_eq!(
+            undecided_block_data_keys(&store),
+            vec![UndecidedKey::new(next, Round::new(0))]
+        );
+        assert_eq!(
+            store.get_decided_block(height).await.unwrap(),
+            Some(decided)
+        );
+    }
+
+    #[tokio::test]
+    async fn size_pruning_keeps_latest_block_and_certificates() {
+        let store = in_memory_store(&StorageConfig {
+            max_db_size: 1,
+            ..StorageConfig::default()
+        });
+        let blocks = (1..=4).map(block_data).collect::<Vec<_>>();
+        for (height, data) in (1..).zip(&blocks) {
+            decide(&store, height, data.clone()).await;
+        }
+
+        let pruned = store.prune_to_size().await.unwrap();
+        assert_eq!(pruned, (1..=3).map(Height::new).collect::<Vec<_>>());
+
+        for height in (1..=4).map(Height::new) {
+            assert!(store.get_certificate(height).await.unwrap().is_some());
+        }
+        for height in (1..=3).map(Height::new) {
+            assert_eq!(store.get_decided_block(height).await.unwrap(), None);
+            assert!(store.get_pruned_header(height).await.unwrap().is_some());
+        }
+        assert_eq!(
+            store.get_decided_block(Height::new(4)).await.unwrap(),
+            Some(blocks[3].clone())
+        );
+    }
+
+    #[tokio::test]
+    async fn retention_windows_prune_independently() {
+        let store = in_memory_store(&StorageConfig::default());
+        for height in 1..=3 {
+            decide(&store, height, block_data(height)).await;
+        }
+        for height in 4..=6 {
+            store
+                .store_undecided_block_data(Height::new(height), Round::new(0), block_data(height))
+                .await
+                .unwrap();
+        }
+
+        // Consensus pruning leaves the decided data
+        store.prune_undecided(Height::new(5)).await.unwrap();
+        assert_eq!(
+            undecided_block_data_keys(&store),
+            [5, 6].map(|height| UndecidedKey::new(Height::new(height), Round::new(0)))
+        );
+        for height in (1..=3).map(Height::new) {
+            assert!(store.get_certificate(height).await.unwrap().is_some());
+            assert!(store.get_decided_block(height).await.unwrap().is_some());
+        }
+
+        // Pruning the decided data leaves the undecided data
+        let pruned = store.prune(Height::new(2)).await.unwrap();
+        assert_eq!(pruned, vec![Height::new(1)]);
+        assert!(store
+            .get_certificate(Height::new(1))
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(store.get_decided_block(Height::new(1)).await.unwrap(), None);
+        assert!(store
+            .get_decided_block(Height::new(2))
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(undecided_block_data_keys(&store).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn pruning_a_block_removes_its_transactions_from_the_index() {
+        let store = in_memory_store(&StorageConfig {
+            max_db_size: 1,
+            ..StorageConfig::default()
+        });
+        let mut hashes = Vec::new();
+        for height in 1..=3 {
+            let data = block_data(height);
+            hashes.push(Block::from_bytes(&data).unwrap().transactions()[0].hash());
+            decide(&store, height, data).await;
+        }
+        for (height, hash) in (1..=3).map(Height::new).zip(&hashes) {
+            assert_eq!(
+                store.get_transaction_location(*hash).await.unwrap(),
+                Some((height, 0))
+            );
+        }
+
+        store.prune(Height::new(2)).await.unwrap();
+        assert_eq!(
+            store.get_transaction_location(hashes[0]).await.unwrap(),
+            None
+        );
+
+        store.prune_to_size().await.unwrap();
+        assert_eq!(
+            store.get_transaction_location(hashes[1]).await.unwrap(),
+            None
+        );
+        assert_eq!(
+            store.get_transaction_location(hashes[2]).await.unwrap(),
+            Some((Height::new(3), 0))
+        );
     }
 }
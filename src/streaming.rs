@@ -1,13 +1,161 @@
 use std::cmp::Ordering;
-use std::collections::{BTreeMap, BinaryHeap, HashSet};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
 
-use crate::malachite_types::{
-    address::Address, height::Height, proposal_part::ProposalInit, proposal_part::ProposalPart,
+use bytes::{Bytes, BytesMut};
+use malachitebft_app_channel::app::metrics::prometheus::metrics::counter::Counter;
+use malachitebft_app_channel::app::metrics::prometheus::metrics::gauge::Gauge;
+use malachitebft_app_channel::app::metrics::SharedRegistry;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::compression::Compression;
+use crate::error::StreamError;
+use crate::types::sign_bytes::ProposalPartsHasher;
+use crate::types::signing::{Ed25519Provider, Signature};
+use crate::types::{
+    address::Address,
+    height::Height,
+    proposal_part::{ProposalData, ProposalFin, ProposalInit, ProposalPart},
 };
-use malachitebft_app_channel::app::streaming::{Sequence, StreamId, StreamMessage};
+use malachitebft_app_channel::app::streaming::{Sequence, StreamContent, StreamId, StreamMessage};
 use malachitebft_app_channel::app::types::core::Round;
 use malachitebft_app_channel::app::types::PeerId;
 
+/// Size of the chunks in which the block data is split for streaming when the genesis file does
+/// not set it, see [`crate::consensus_params::ConsensusParams::chunk_size`]
+pub const DEFAULT_CHUNK_SIZE: usize = 128 * 1024; // 128 KiB
+
+/// `[streaming]` section of the node configuration.
+///
+/// The reassembly limits bound the memory a single peer can make us hold to
+/// `max_streams_per_peer * max_stream_bytes`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamingConfig {
+    /// Maximum number of messages in a single incoming stream
+    pub max_stream_parts: usize,
+    /// Maximum number of data bytes buffered for a single incoming stream
+    pub max_stream_bytes: usize,
+    /// Maximum number of incomplete streams buffered per peer
+    pub max_streams_per_peer: usize,
+    /// Incomplete streams that did not receive a part for this many seconds are dropped
+    pub stream_timeout_secs: u64,
+    /// Incomplete streams are dropped this many seconds after their first part, even if their
+    /// parts keep trickling in
+    pub max_stream_age_secs: u64,
+    /// Peers exceeding a limit have their parts ignored for this many seconds
+    pub ban_duration_secs: u64,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        Self {
+            max_stream_parts: 1024,
+            max_stream_bytes: 64 * 1024 * 1024,
+            max_streams_per_peer: 8,
+            stream_timeout_secs: 30,
+            max_stream_age_secs: 120,
+            ban_duration_secs: 60,
+        }
+    }
+}
+
+impl StreamingConfig {
+    fn stream_timeout(&self) -> Duration {
+        Duration::from_secs(self.stream_timeout_secs)
+    }
+
+    fn max_stream_age(&self) -> Duration {
+        Duration::from_secs(self.max_stream_age_secs)
+    }
+
+    fn ban_duration(&self) -> Duration {
+        Duration::from_secs(self.ban_duration_secs)
+    }
+}
+
+/// Outgoing stream of proposal parts: `Init`, one `Data` part per chunk, `Fin` and the end of
+/// stream marker.
+///
+/// Parts are produced one at a time as the stream is consumed, so that only the part being
+/// published is materialized and a full network channel slows down the producer. The chunks are
+/// slices of the block data, hashed in parallel and signed up front.
+pub struct ProposalStream {
+    stream_id: StreamId,
+    sequence: Sequence,
+    init: Option<ProposalInit>,
+    chunks: VecDeque<Bytes>,
+    signature: Option<Signature>,
+    finished: bool,
+}
+
+impl ProposalStream {
+    /// `data` is streamed as is, it must already be compressed as announced in `init`.
+    pub fn new(
+        stream_id: StreamId,
+        init: ProposalInit,
+        data: Bytes,
+        chunk_size: usize,
+        mut hasher: ProposalPartsHasher,
+        signing_provider: &Ed25519Provider,
+    ) -> Self {
+        let chunk_size = chunk_size.max(1);
+        let chunks: VecDeque<Bytes> = (0..data.len())
+            .step_by(chunk_size)
+            .map(|start| data.slice(start..(start + chunk_size).min(data.len())))
+            .collect();
+
+        hasher.update_all(chunks.make_contiguous());
+        let signature = signing_provider.sign(&hasher.finalize());
+
+        Self {
+            stream_id,
+            sequence: 0,
+            init: Some(init),
+            chunks,
+            signature: Some(signature),
+            finished: false,
+        }
+    }
+
+    fn next_content(&mut self) -> Option<StreamContent<ProposalPart>> {
+        if let Some(init) = self.init.take() {
+            return Some(StreamContent::Data(ProposalPart::Init(init)));
+        }
+
+        if let Some(chunk) = self.chunks.pop_front() {
+            return Some(StreamContent::Data(ProposalPart::Data(ProposalData::new(
+                chunk,
+            ))));
+        }
+
+        if let Some(signature) = self.signature.take() {
+            return Some(StreamContent::Data(ProposalPart::Fin(ProposalFin::new(
+                signature,
+            ))));
+        }
+
+        if !self.finished {
+            self.finished = true;
+            return Some(StreamContent::Fin);
+        }
+
+        None
+    }
+}
+
+impl Iterator for ProposalStream {
+    type Item = StreamMessage<ProposalPart>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let content = self.next_content()?;
+        let msg = StreamMessage::new(self.stream_id.clone(), self.sequence, content);
+        self.sequence += 1;
+        Some(msg)
+    }
+}
+
 struct MinSeq<T>(StreamMessage<T>);
 
 impl<T> PartialEq for MinSeq<T> {
@@ -43,103 +191,569 @@ impl<T> MinHeap<T> {
         self.0.push(MinSeq(msg));
     }
 
-    fn len(&self) -> usize {
-        self.0.len()
+    /// Pops the message with the lowest sequence if it is `sequence`.
+    fn pop_if_next(&mut self, sequence: Sequence) -> Option<StreamMessage<T>> {
+        if self.0.peek()?.0.sequence != sequence {
+            return None;
+        }
+        self.0.pop().map(|MinSeq(msg)| msg)
     }
+}
 
-    fn drain(&mut self) -> Vec<T> {
-        let mut vec = Vec::with_capacity(self.0.len());
-        while let Some(MinSeq(msg)) = self.0.pop() {
-            if let Some(data) = msg.content.into_data() {
-                vec.push(data);
-            }
+/// Proposal being assembled from the parts received in order, from the `Init` part on.
+struct Assembly {
+    init: ProposalInit,
+    hasher: ProposalPartsHasher,
+    data: BytesMut,
+    signature: Option<Signature>,
+}
+
+impl Assembly {
+    fn new(chain_id: &str, init: ProposalInit) -> Self {
+        let hasher = ProposalPartsHasher::new(
+            chain_id,
+            init.height,
+            init.round,
+            &init.proposer,
+            init.compression,
+        );
+
+        Self {
+            init,
+            hasher,
+            data: BytesMut::new(),
+            signature: None,
+        }
+    }
+
+    fn finish(self) -> ProposalParts {
+        ProposalParts {
+            height: self.init.height,
+            round: self.init.round,
+            proposer: self.init.proposer,
+            compression: self.init.compression,
+            data: self.data.freeze(),
+            digest: self.hasher.finalize(),
+            signature: self.signature,
         }
-        vec
     }
 }
 
-#[derive(Default)]
+/// Incoming stream. Parts are appended to the assembly as soon as all the parts before them were
+/// received, only the parts received out of order are buffered.
 struct StreamState {
-    buffer: MinHeap<ProposalPart>,
-    init_info: Option<ProposalInit>,
+    /// The stream was found invalid, its parts are dropped until it goes stale
+    rejected: bool,
+    /// Parts received ahead of `next_sequence`
+    pending: MinHeap<ProposalPart>,
+    /// Sequence of the next part to append
+    next_sequence: Sequence,
+    assembly: Option<Assembly>,
     seen_sequences: HashSet<Sequence>,
     total_messages: usize,
     fin_received: bool,
+    /// Data bytes received so far
+    total_bytes: usize,
+    started_at: Instant,
+    last_update: Instant,
 }
 
 impl StreamState {
+    fn new(now: Instant) -> Self {
+        Self {
+            rejected: false,
+            pending: MinHeap::default(),
+            next_sequence: 0,
+            assembly: None,
+            seen_sequences: HashSet::new(),
+            total_messages: 0,
+            fin_received: false,
+            total_bytes: 0,
+            started_at: now,
+            last_update: now,
+        }
+    }
+
+    /// Height of the proposal, known once the `Init` part is appended.
+    fn height(&self) -> Option<Height> {
+        self.assembly.as_ref().map(|assembly| assembly.init.height)
+    }
+
     fn is_done(&self) -> bool {
-        self.init_info.is_some() && self.fin_received && self.buffer.len() == self.total_messages
+        self.fin_received && self.next_sequence as usize == self.total_messages
     }
 
-    fn insert(&mut self, msg: StreamMessage<ProposalPart>) -> Option<ProposalParts> {
-        if msg.is_first() {
-            self.init_info = msg.content.as_data().and_then(|p| p.as_init()).cloned();
+    /// Checks that buffering `msg` keeps the stream within the configured limits.
+    fn check_limits(
+        &self,
+        msg: &StreamMessage<ProposalPart>,
+        config: &StreamingConfig,
+        chunk_size: usize,
+    ) -> Result<(), StreamError> {
+        // Proposers split the data in chunks of the size of the genesis file
+        if data_len(msg) > chunk_size {
+            return Err(StreamError::ChunkTooLarge(chunk_size));
+        }
+
+        // Sequences are dense, a stream can't be longer than its highest sequence number
+        if self.seen_sequences.len() >= config.max_stream_parts
+            || msg.sequence as usize >= config.max_stream_parts
+        {
+            return Err(StreamError::TooManyParts(config.max_stream_parts));
         }
 
+        if self.total_bytes + data_len(msg) > config.max_stream_bytes {
+            return Err(StreamError::TooLarge(config.max_stream_bytes));
+        }
+
+        Ok(())
+    }
+
+    /// Appends `msg` and the buffered parts following it, returns the assembled proposal once
+    /// all its parts were received.
+    fn insert(
+        &mut self,
+        chain_id: &str,
+        msg: StreamMessage<ProposalPart>,
+    ) -> Result<Option<ProposalParts>, StreamError> {
         if msg.is_fin() {
             self.fin_received = true;
             self.total_messages = msg.sequence as usize + 1;
         }
 
-        self.buffer.push(msg);
+        self.total_bytes += data_len(&msg);
+        self.pending.push(msg);
 
-        if self.is_done() {
-            let init_info = self.init_info.take()?;
+        while let Some(msg) = self.pending.pop_if_next(self.next_sequence) {
+            self.append(chain_id, msg)?;
+            self.next_sequence += 1;
+        }
 
-            Some(ProposalParts {
-                height: init_info.height,
-                round: init_info.round,
-                proposer: init_info.proposer,
-                parts: self.buffer.drain(),
-            })
-        } else {
-            None
+        if !self.is_done() {
+            return Ok(None);
+        }
+
+        // The first part was appended, so it was the `Init` part
+        Ok(self.assembly.take().map(Assembly::finish))
+    }
+
+    /// Checks that the parts come as `Init`, `Data`..., `Fin` while hashing the data.
+    fn append(
+        &mut self,
+        chain_id: &str,
+        msg: StreamMessage<ProposalPart>,
+    ) -> Result<(), StreamError> {
+        let sequence = msg.sequence;
+        // End of stream marker
+        let Some(part) = msg.content.into_data() else {
+            return Ok(());
+        };
+
+        match (part, self.assembly.as_mut()) {
+            (ProposalPart::Init(init), None) => {
+                self.assembly = Some(Assembly::new(chain_id, init));
+            }
+            (ProposalPart::Data(data), Some(assembly)) if assembly.signature.is_none() => {
+                assembly.hasher.update(&data.bytes);
+                assembly.data.extend_from_slice(&data.bytes);
+            }
+            (ProposalPart::Fin(fin), Some(assembly)) if assembly.signature.is_none() => {
+                assembly.signature = Some(fin.signature);
+            }
+            _ => return Err(StreamError::UnexpectedPart(sequence)),
         }
+
+        Ok(())
     }
 }
 
+/// Proposal assembled from its parts, the signature is not verified.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ProposalParts {
     pub height: Height,
     pub round: Round,
     pub proposer: Address,
-    pub parts: Vec<ProposalPart>,
+    pub compression: Compression,
+    /// Data chunks concatenated as sent, i.e. compressed
+    pub data: Bytes,
+    /// Digest of the parts, see [`ProposalPartsHasher`]
+    pub digest: [u8; 32],
+    /// Signature of `digest` carried by the `Fin` part, if any
+    pub signature: Option<Signature>,
 }
 
-#[derive(Default)]
+/// Number of data bytes carried by a stream message, the `Init` and `Fin` parts are bounded by the
+/// number of parts instead.
+fn data_len(msg: &StreamMessage<ProposalPart>) -> usize {
+    msg.content
+        .as_data()
+        .and_then(|part| part.as_data())
+        .map_or(0, |data| data.bytes.len())
+}
+
+/// Metrics of the incoming proposal streams.
+#[derive(Debug, Clone, Default)]
+pub struct StreamMetrics {
+    /// Number of incomplete streams
+    streams: Gauge,
+    /// Data bytes held by incomplete streams
+    stream_bytes: Gauge,
+    /// Number of streams dropped for being inactive or too old
+    streams_timed_out_total: Counter,
+    /// Number of incomplete streams dropped once their height was decided
+    streams_outdated_total: Counter,
+}
+
+impl StreamMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(registry: &SharedRegistry) -> Self {
+        let metrics = Self::new();
+
+        registry.with_prefix("app_channel", |registry| {
+            registry.register(
+                "proposal_streams",
+                "Number of incomplete incoming proposal streams",
+                metrics.streams.clone(),
+            );
+
+            registry.register(
+                "proposal_stream_bytes",
+                "Data bytes held by incomplete incoming proposal streams",
+                metrics.stream_bytes.clone(),
+            );
+
+            registry.register(
+                "proposal_streams_timed_out_total",
+                "Number of incoming proposal streams dropped for being inactive or too old",
+                metrics.streams_timed_out_total.clone(),
+            );
+
+            registry.register(
+                "proposal_streams_outdated_total",
+                "Number of incomplete incoming proposal streams dropped once their height was decided",
+                metrics.streams_outdated_total.clone(),
+            );
+        });
+
+        metrics
+    }
+}
+
+/// Incoming proposal part streams being reassembled, bounded per stream and per peer.
 pub struct PartStreamsMap {
+    chain_id: String,
+    config: StreamingConfig,
+    /// Largest data part, see [`crate::consensus_params::ConsensusParams::chunk_size`]
+    chunk_size: usize,
     streams: BTreeMap<(PeerId, StreamId), StreamState>,
+    banned: HashMap<PeerId, Instant>,
+    metrics: StreamMetrics,
 }
 
 impl PartStreamsMap {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(
+        chain_id: impl Into<String>,
+        config: StreamingConfig,
+        chunk_size: usize,
+        metrics: StreamMetrics,
+    ) -> Self {
+        Self {
+            chain_id: chain_id.into(),
+            config,
+            chunk_size,
+            streams: BTreeMap::new(),
+            banned: HashMap::new(),
+            metrics,
+        }
     }
 
+    /// Appends `msg` to its stream and returns the assembled proposal once all its parts were
+    /// received.
+    ///
+    /// A peer exceeding one of the limits or sending parts out of place has all its streams
+    /// dropped and its parts ignored for [`StreamingConfig::ban_duration_secs`].
     pub fn insert(
         &mut self,
         peer_id: PeerId,
         msg: StreamMessage<ProposalPart>,
-    ) -> Option<ProposalParts> {
-        let stream_id = msg.stream_id.clone();
+    ) -> Result<Option<ProposalParts>, StreamError> {
+        let now = Instant::now();
+        self.prune(now);
+
+        if self.banned.contains_key(&peer_id) {
+            return Err(StreamError::PeerBanned);
+        }
+
+        let key = (peer_id, msg.stream_id.clone());
+
+        if !self.streams.contains_key(&key)
+            && self.peer_streams(peer_id) >= self.config.max_streams_per_peer
+        {
+            return Err(self.ban(
+                peer_id,
+                now,
+                StreamError::TooManyStreams(self.config.max_streams_per_peer),
+            ));
+        }
 
         let state = self
             .streams
-            .entry((peer_id, stream_id.clone()))
-            .or_default();
+            .entry(key.clone())
+            .or_insert_with(|| StreamState::new(now));
 
-        if !state.seen_sequences.insert(msg.sequence) {
+        if state.rejected {
+            state.last_update = now;
+            return Ok(None);
+        }
+
+        if state.seen_sequences.contains(&msg.sequence) {
             // We have already seen a message with this sequence number.
-            return None;
+            return Ok(None);
+        }
+
+        if let Err(e) = state.check_limits(&msg, &self.config, self.chunk_size) {
+            return Err(self.ban(peer_id, now, e));
         }
 
-        let result = state.insert(msg);
+        state.seen_sequences.insert(msg.sequence);
+        state.last_update = now;
+        let result = match state.insert(&self.chain_id, msg) {
+            Ok(result) => result,
+            Err(e) => return Err(self.ban(peer_id, now, e)),
+        };
 
         if state.is_done() {
-            self.streams.remove(&(peer_id, stream_id));
+            self.streams.remove(&key);
+        }
+        self.update_metrics();
+
+        Ok(result)
+    }
+
+    /// Drops the parts buffered for a stream and ignores the ones still to come.
+    /// Rejected streams still count towards [`StreamingConfig::max_streams_per_peer`] until they
+    /// go stale.
+    pub fn reject(&mut self, peer_id: PeerId, stream_id: StreamId) {
+        let now = Instant::now();
+        let key = (peer_id, stream_id);
+
+        if !self.streams.contains_key(&key)
+            && self.peer_streams(peer_id) >= self.config.max_streams_per_peer
+        {
+            self.ban(
+                peer_id,
+                now,
+                StreamError::TooManyStreams(self.config.max_streams_per_peer),
+            );
+            return;
+        }
+
+        let mut state = StreamState::new(now);
+        state.rejected = true;
+        self.streams.insert(key, state);
+        self.update_metrics();
+    }
+
+    /// Drops the incomplete streams of proposals for heights below `height`, which was reached
+    /// by deciding them, as well as the stale streams.
+    pub fn evict_outdated(&mut self, height: Height) {
+        let before = self.streams.len();
+        self.streams
+            .retain(|_, state| state.height().map_or(true, |h| h >= height));
+        let evicted = before - self.streams.len();
+        if evicted > 0 {
+            debug!(%height, evicted, "Dropped proposal streams of decided heights");
+            self.metrics.streams_outdated_total.inc_by(evicted as u64);
         }
 
-        result
+        self.prune(Instant::now());
+    }
+
+    /// Number of incomplete streams buffered for all peers.
+    pub fn len(&self) -> usize {
+        self.streams.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+
+    fn peer_streams(&self, peer_id: PeerId) -> usize {
+        self.streams
+            .keys()
+            .filter(|(peer, _)| *peer == peer_id)
+            .count()
+    }
+
+    /// Drops the streams of `peer_id` and ignores its parts for a while.
+    fn ban(&mut self, peer_id: PeerId, now: Instant, error: StreamError) -> StreamError {
+        self.streams.retain(|(peer, _), _| *peer != peer_id);
+        self.banned
+            .insert(peer_id, now + self.config.ban_duration());
+        self.update_metrics();
+        error
+    }
+
+    /// Drops stale streams, i.e. inactive or too old, and expired bans.
+    fn prune(&mut self, now: Instant) {
+        let timeout = self.config.stream_timeout();
+        let max_age = self.config.max_stream_age();
+        let before = self.streams.len();
+        self.streams.retain(|_, state| {
+            now.duration_since(state.last_update) < timeout
+                && now.duration_since(state.started_at) < max_age
+        });
+        let timed_out = before - self.streams.len();
+        if timed_out > 0 {
+            debug!(timed_out, "Dropped stale proposal streams");
+            self.metrics
+                .streams_timed_out_total
+                .inc_by(timed_out as u64);
+        }
+
+        self.banned.retain(|_, until| *until > now);
+        self.update_metrics();
+    }
+
+    fn update_metrics(&self) {
+        let bytes: usize = self.streams.values().map(|state| state.total_bytes).sum();
+        self.metrics.streams.set(self.streams.len() as i64);
+        self.metrics.stream_bytes.set(bytes as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use malachitebft_signing_ed25519::PrivateKey;
+
+    use super::*;
+
+    fn data_msg(sequence: Sequence, len: usize) -> StreamMessage<ProposalPart> {
+        let part = ProposalPart::Data(ProposalData::new(Bytes::from(vec![0; len])));
+        StreamMessage::new(
+            StreamId::new(Bytes::from_static(b"stream")),
+            sequence,
+            StreamContent::Data(part),
+        )
+    }
+
+    #[test]
+    fn stream_limits() {
+        let config = StreamingConfig {
+            max_stream_parts: 3,
+            max_stream_bytes: 10,
+            ..StreamingConfig::default()
+        };
+        let mut state = StreamState::new(Instant::now());
+
+        state.check_limits(&data_msg(1, 6), &config, 8).unwrap();
+        state.seen_sequences.insert(1);
+        state.insert("test", data_msg(1, 6)).unwrap();
+
+        assert_eq!(
+            state.check_limits(&data_msg(2, 5), &config, 8),
+            Err(StreamError::TooLarge(10))
+        );
+        assert_eq!(
+            state.check_limits(&data_msg(3, 1), &config, 8),
+            Err(StreamError::TooManyParts(3))
+        );
+        assert_eq!(
+            state.check_limits(&data_msg(2, 4), &config, 3),
+            Err(StreamError::ChunkTooLarge(3))
+        );
+        state.check_limits(&data_msg(2, 4), &config, 8).unwrap();
+    }
+
+    #[test]
+    fn assembles_parts_received_out_of_order() {
+        let signing_provider =
+            Ed25519Provider::new(PrivateKey::generate(rand::thread_rng()), "test");
+        let proposer = Address::new([0x01; 20]);
+        let (height, round) = (Height::new(1), Round::new(0));
+        let hasher =
+            || ProposalPartsHasher::new("test", height, round, &proposer, Compression::None);
+        let data = Bytes::from((0..10).collect::<Vec<u8>>());
+
+        let msgs: Vec<_> = ProposalStream::new(
+            StreamId::new(Bytes::from_static(b"stream")),
+            ProposalInit::new(height, round, proposer, Compression::None),
+            data.clone(),
+            4,
+            hasher(),
+            &signing_provider,
+        )
+        .collect();
+
+        let mut state = StreamState::new(Instant::now());
+        let mut assembled = None;
+        for msg in msgs.into_iter().rev() {
+            assert!(assembled.is_none());
+            assembled = state.insert("test", msg).unwrap();
+        }
+        let parts = assembled.unwrap();
+        assert_eq!(parts.data, data);
+
+        let mut expected = hasher();
+        expected.update_all(&[&data[..4], &data[4..8], &data[8..]]);
+        assert_eq!(parts.digest, expected.finalize());
+        assert!(signing_provider.verify(
+            &parts.digest,
+            parts.signature.as_ref().unwrap(),
+            &signing_provider.private_key().public_key()
+        ));
+    }
+
+    #[test]
+    fn rejects_parts_out_of_place() {
+        let mut state = StreamState::new(Instant::now());
+        assert_eq!(
+            state.insert("test", data_msg(0, 4)),
+            Err(StreamError::UnexpectedPart(0))
+        );
+    }
+
+    #[test]
+    fn proposal_stream_chunks_data_lazily() {
+        let signing_provider =
+            Ed25519Provider::new(PrivateKey::generate(rand::thread_rng()), "test");
+        let proposer = Address::new([0x01; 20]);
+        let (height, round) = (Height::new(1), Round::new(0));
+
+        let stream = ProposalStream::new(
+            StreamId::new(Bytes::from_static(b"stream")),
+            ProposalInit::new(height, round, proposer, Compression::None),
+            Bytes::from(vec![0xAB; 10]),
+            4,
+            ProposalPartsHasher::new("test", height, round, &proposer, Compression::None),
+            &signing_provider,
+        );
+        let msgs: Vec<_> = stream.collect();
+
+        // Init, 3 chunks (4 + 4 + 2 bytes), Fin part and end of stream
+        assert_eq!(msgs.len(), 6);
+        assert!(msgs.iter().map(|msg| msg.sequence).eq(0..6));
+        assert!(msgs[0].content.as_data().unwrap().as_init().is_some());
+        let chunk_sizes: Vec<_> = msgs[1..4]
+            .iter()
+            .map(|msg| {
+                msg.content
+                    .as_data()
+                    .unwrap()
+                    .as_data()
+                    .unwrap()
+                    .bytes
+                    .len()
+            })
+            .collect();
+        assert_eq!(chunk_sizes, [4, 4, 2]);
+        assert!(matches!(
+            msgs[4].content.as_data(),
+            Some(ProposalPart::Fin(_))
+        ));
+        assert!(msgs[5].is_fin());
     }
 }
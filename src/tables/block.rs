@@ -1,11 +1,10 @@
 use std::sync::Arc;
 
-use crate::error::StoreError;
-use eyre::Ok;
+use crate::store::StoreError;
 use redb::{Database, TypeName, Value};
 
 use crate::block::Block;
-use bincode::{decode_from_slice, encode_to_vec};
+use malachitebft_proto::Protobuf;
 
 use super::Table;
 
@@ -20,23 +19,22 @@ impl Table for DaBlockTable {
     type Key = u64;
     type Value = Block;
     fn get(&self, key: u64) -> Result<Option<Self::Value>, StoreError> {
-        let tx = self.db.begin_read().unwrap();
-        let table = tx.open_table(DA_BLOCK_TABLE).unwrap();
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(DA_BLOCK_TABLE)?;
         let res = table.get(key)?;
-        // .map_err(|e| StoreError::DatabaseError(e.to_string()))?;
 
-        Ok(res.map(|value| value.value())).map_err(|e| StoreError::UnknownError(e.to_string()))
+        Ok(res.map(|value| value.value()))
     }
 
     fn insert(&self, key: Self::Key, value: Self::Value) -> Result<(), StoreError> {
-        let write_txn = self.db.begin_write().unwrap();
+        let write_txn = self.db.begin_write()?;
         {
             let mut table = write_txn.open_table(DA_BLOCK_TABLE)?;
 
             table.insert(key, value)?;
         }
         write_txn.commit()?;
-        Ok(()).map_err(|e| StoreError::UnknownError(e.to_string()))
+        Ok(())
     }
 }
 
@@ -52,16 +50,16 @@ impl Value for Block {
     where
         Self: 'a,
     {
-        decode_from_slice::<Block, _>(data, bincode::config::standard())
-            .expect("Unable to decode data")
-            .0
+        <Block as Protobuf>::from_bytes(data).expect("Unable to decode data")
     }
 
     fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
     where
         Self: 'b,
     {
-        encode_to_vec(value, bincode::config::standard()).expect("Unable to encode data")
+        Protobuf::to_bytes(value)
+            .expect("Unable to encode data")
+            .to_vec()
     }
 
     fn type_name() -> redb::TypeName {
@@ -1,10 +1,10 @@
+use core::cmp::Ordering;
 use core::mem::size_of;
+use core::ops::{Range, RangeTo};
 
-use crate::malachite_types::height::Height;
+use crate::types::height::Height;
 use malachitebft_app_channel::app::types::core::Round;
 
-pub type UndecidedValueKey = (HeightKey, RoundKey);
-
 #[derive(Copy, Clone, Debug)]
 pub struct HeightKey;
 
@@ -81,3 +81,167 @@ impl redb::Key for RoundKey {
         <i64 as redb::Key>::compare(data1, data2)
     }
 }
+
+/// Key of the undecided tables: a height, then a round of that height.
+///
+/// Stored as the height as a little-endian `u64` followed by the round as a little-endian
+/// `i64`, the layout of the `(HeightKey, RoundKey)` tuple it replaces, and compared as the
+/// integers `(height, round)`. A round is encoded with [`Round::as_i64`], so the rounds of a
+/// height all lie within `i64::MIN..=i64::MAX` whatever they are, and the ranges of
+/// [`Self::all_rounds_of`] and [`Self::all_rounds_below`] are bounded by `i64::MIN` rather than
+/// by a `Round`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UndecidedKey {
+    height: u64,
+    round: i64,
+}
+
+impl UndecidedKey {
+    const WIDTH: usize = size_of::<u64>() + size_of::<i64>();
+
+    pub fn new(height: Height, round: Round) -> Self {
+        Self {
+            height: height.as_u64(),
+            round: round.as_i64(),
+        }
+    }
+
+    /// Lowest key of `height`, below every round of it.
+    const fn start_of(height: u64) -> Self {
+        Self {
+            height,
+            round: i64::MIN,
+        }
+    }
+
+    pub fn height(&self) -> Height {
+        Height::new(self.height)
+    }
+
+    pub fn round(&self) -> Round {
+        Round::from(self.round)
+    }
+
+    /// Keys of every round of `height`.
+    pub fn all_rounds_of(height: Height) -> Range<Self> {
+        Self::start_of(height.as_u64())..Self::start_of(height.as_u64() + 1)
+    }
+
+    /// Keys of every round of the heights below `height`.
+    pub fn all_rounds_below(height: Height) -> RangeTo<Self> {
+        ..Self::start_of(height.as_u64())
+    }
+
+    fn decode(data: &[u8]) -> Self {
+        let (height, round) = data.split_at(size_of::<u64>());
+        Self {
+            height: u64::from_le_bytes(height.try_into().unwrap()),
+            round: i64::from_le_bytes(round.try_into().unwrap()),
+        }
+    }
+}
+
+impl redb::Value for UndecidedKey {
+    type SelfType<'a> = UndecidedKey;
+    type AsBytes<'a> = [u8; UndecidedKey::WIDTH];
+
+    fn fixed_width() -> Option<usize> {
+        Some(Self::WIDTH)
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> Self::SelfType<'a>
+    where
+        Self: 'a,
+    {
+        Self::decode(data)
+    }
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a Self::SelfType<'b>) -> Self::AsBytes<'a>
+    where
+        Self: 'a,
+        Self: 'b,
+    {
+        let mut bytes = [0; Self::WIDTH];
+        bytes[..size_of::<u64>()].copy_from_slice(&value.height.to_le_bytes());
+        bytes[size_of::<u64>()..].copy_from_slice(&value.round.to_le_bytes());
+        bytes
+    }
+
+    /// Name of the tuple key, so that existing tables open with this key
+    fn type_name() -> redb::TypeName {
+        <(HeightKey, RoundKey) as redb::Value>::type_name()
+    }
+}
+
+impl redb::Key for UndecidedKey {
+    fn compare(data1: &[u8], data2: &[u8]) -> Ordering {
+        Self::decode(data1).cmp(&Self::decode(data2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redb::backends::InMemoryBackend;
+    use redb::{Database, ReadableTable, TableDefinition};
+
+    const TABLE: TableDefinition<UndecidedKey, u8> = TableDefinition::new("undecided");
+
+    #[test]
+    fn ranges_cover_every_round() {
+        let db = Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .unwrap();
+        let keys = [
+            (1, Round::new(0)),
+            (1, Round::new(300)),
+            (2, Round::Nil),
+            (2, Round::new(0)),
+            (2, Round::new(u32::MAX)),
+            (3, Round::new(1)),
+            (256, Round::new(0)),
+        ]
+        .map(|(height, round)| UndecidedKey::new(Height::new(height), round));
+
+        let tx = db.begin_write().unwrap();
+        {
+            let mut table = tx.open_table(TABLE).unwrap();
+            for key in keys.iter().rev() {
+                table.insert(key, 0).unwrap();
+            }
+        }
+        tx.commit().unwrap();
+
+        let tx = db.begin_read().unwrap();
+        let table = tx.open_table(TABLE).unwrap();
+        let range = |range: Range<UndecidedKey>| -> Vec<UndecidedKey> {
+            table
+                .range(range)
+                .unwrap()
+                .map(|entry| entry.unwrap().0.value())
+                .collect()
+        };
+        let below = |height: u64| -> Vec<UndecidedKey> {
+            table
+                .range(UndecidedKey::all_rounds_below(Height::new(height)))
+                .unwrap()
+                .map(|entry| entry.unwrap().0.value())
+                .collect()
+        };
+
+        assert_eq!(
+            range(UndecidedKey::all_rounds_of(Height::new(2))),
+            keys[2..5]
+        );
+        assert_eq!(range(UndecidedKey::all_rounds_of(Height::new(4))), []);
+        assert_eq!(
+            range(UndecidedKey::all_rounds_of(Height::new(256))),
+            keys[6..]
+        );
+        assert_eq!(below(2), keys[..2]);
+        assert_eq!(below(3), keys[..5]);
+        assert_eq!(below(1), []);
+        assert_eq!(keys[2].round(), Round::Nil);
+        assert_eq!(keys[4].round(), Round::new(u32::MAX));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::error::StoreError;
+use crate::store::StoreError;
 
 pub mod block;
 pub mod keys;
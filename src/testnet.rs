@@ -0,0 +1,109 @@
+//! Port assignments of the nodes of a local testnet and, with `testnet --docker-compose`, a
+//! docker-compose file to run them in containers.
+//!
+//! The generated configurations listen on and dial `127.0.0.1`, so the containers share the
+//! network of the host and the ports below must be free on it.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::error::TestnetError;
+
+/// Flag of the `testnet` command generating the docker-compose file and the port map.
+pub const DOCKER_COMPOSE_FLAG: &str = "--docker-compose";
+
+pub const CONSENSUS_BASE_PORT: u16 = 27000;
+pub const RPC_BASE_PORT: u16 = 8545;
+pub const METRICS_BASE_PORT: u16 = 29000;
+
+/// Ports of the node at `index` of a testnet.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct NodePorts {
+    pub index: usize,
+    pub consensus: u16,
+    pub rpc: u16,
+    pub metrics: u16,
+}
+
+impl NodePorts {
+    pub fn new(index: usize) -> Self {
+        let offset = index as u16;
+        Self {
+            index,
+            consensus: CONSENSUS_BASE_PORT + offset,
+            rpc: RPC_BASE_PORT + offset,
+            metrics: METRICS_BASE_PORT + offset,
+        }
+    }
+}
+
+pub fn port_map(nodes: usize) -> Vec<NodePorts> {
+    (0..nodes).map(NodePorts::new).collect()
+}
+
+/// One service per node, each mounting its home generated by the `testnet` command.
+pub fn docker_compose(nodes: usize) -> String {
+    let mut out = String::from("# Generated by `mikan testnet --docker-compose`\nservices:\n");
+    for ports in port_map(nodes) {
+        let index = ports.index;
+        // Writing to a String cannot fail
+        let _ = write!(
+            out,
+            "  node-{index}:
+    image: ${{MIKAN_IMAGE:-mikan}}
+    command: [\"mikan\", \"start\", \"--home\", \"/nodes/{index}\"]
+    network_mode: host
+    restart: unless-stopped
+    volumes:
+      - ./{index}:/nodes/{index}
+    # consensus {consensus}, rpc {rpc}, metrics {metrics}
+",
+            consensus = ports.consensus,
+            rpc = ports.rpc,
+            metrics = ports.metrics,
+        );
+    }
+    out
+}
+
+/// Writes `docker-compose.yml` and `ports.json` next to the node homes in `home_dir`.
+pub fn write_docker_compose(home_dir: &Path, nodes: usize) -> Result<(), TestnetError> {
+    write_file(&home_dir.join("docker-compose.yml"), docker_compose(nodes))?;
+    write_file(
+        &home_dir.join("ports.json"),
+        serde_json::to_vec_pretty(&port_map(nodes))?,
+    )
+}
+
+fn write_file(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), TestnetError> {
+    std::fs::write(path, contents).map_err(|source| TestnetError::Write {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assigns_distinct_ports_per_node() {
+        let ports = port_map(3);
+        assert_eq!(
+            ports[2],
+            NodePorts {
+                index: 2,
+                consensus: 27002,
+                rpc: 8547,
+                metrics: 29002,
+            }
+        );
+
+        let compose = docker_compose(3);
+        assert_eq!(compose.matches("network_mode: host").count(), 3);
+        assert!(compose.contains("  node-2:\n"));
+        assert!(compose.contains("- ./2:/nodes/2"));
+    }
+}
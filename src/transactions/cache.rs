@@ -0,0 +1,183 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use malachitebft_test::Signature;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use super::Transaction;
+use crate::blob::Blob;
+
+/// Number of transactions whose validation result is remembered.
+pub const DEFAULT_CACHE_CAPACITY: usize = 4096;
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// Signature verified for each transaction hash
+    signatures: HashMap<[u8; 32], Signature>,
+    /// Insertion order, for eviction
+    order: VecDeque<[u8; 32]>,
+}
+
+/// Transactions whose signature was already verified, shared between the RPC server (which sees
+/// transactions first) and block validation, so that a transaction is only verified once.
+///
+/// Entries are keyed by the hash recomputed from the transaction content, and hold the verified
+/// signature: a transaction re-using the hash of a valid one with another content or signature
+/// misses the cache.
+#[derive(Debug, Clone)]
+pub struct ValidationCache {
+    /// Chain id transactions must be signed for
+    chain_id: Arc<str>,
+    capacity: usize,
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl ValidationCache {
+    pub fn new(chain_id: &str) -> Self {
+        Self::with_capacity(chain_id, DEFAULT_CACHE_CAPACITY)
+    }
+
+    pub fn with_capacity(chain_id: &str, capacity: usize) -> Self {
+        Self {
+            chain_id: chain_id.into(),
+            capacity,
+            entries: Default::default(),
+        }
+    }
+
+    /// Same as [`Transaction::validate`] against the declared blob commitments, skipping the
+    /// signature check for known transactions.
+    ///
+    /// The blobs are not checked against their commitments, which holds for transactions built
+    /// or signed locally. Decoded ones are checked by [`Self::validate_all_with_commitments`].
+    pub fn validate(&self, tx: &Transaction) -> bool {
+        let sign_bytes = tx.sign_bytes(&self.chain_id);
+        let hash = Transaction::hash_sign_bytes(&sign_bytes);
+        if hash != tx.hash() {
+            return false;
+        }
+
+        if self.contains(&hash, &tx.signature()) {
+            return true;
+        }
+
+        if !tx.verify_signature(&sign_bytes) {
+            return false;
+        }
+
+        self.insert(hash, tx.signature());
+        true
+    }
+
+    /// Validates all transactions in parallel along with their blobs, see [`Self::validate`].
+    pub fn validate_all(&self, txs: &[Transaction]) -> bool {
+        let commitments: Vec<[u8; 32]> = txs
+            .par_iter()
+            .flat_map_iter(|tx| tx.data())
+            .map(Blob::commitment)
+            .collect();
+
+        self.validate_all_with_commitments(txs, &commitments)
+    }
+
+    /// Same as [`Self::validate_all`], with the commitments computed from all blobs of `txs`, in
+    /// order, which must match the commitments declared by the transactions.
+    pub fn validate_all_with_commitments(
+        &self,
+        txs: &[Transaction],
+        commitments: &[[u8; 32]],
+    ) -> bool {
+        let matches_blobs = txs
+            .iter()
+            .all(|tx| tx.blob_commitments().len() == tx.data().len())
+            && txs
+                .iter()
+                .flat_map(|tx| tx.blob_commitments())
+                .eq(commitments.iter());
+        if !matches_blobs {
+            return false;
+        }
+
+        txs.par_iter().all(|tx| self.validate(tx))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().signatures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn contains(&self, hash: &[u8; 32], signature: &Signature) -> bool {
+        self.entries
+            .lock()
+            .unwrap()
+            .signatures
+            .get(hash)
+            .is_some_and(|known| known == signature)
+    }
+
+    fn insert(&self, hash: [u8; 32], signature: Signature) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.signatures.insert(hash, signature).is_some() {
+            return;
+        }
+
+        entries.order.push_back(hash);
+        while entries.order.len() > self.capacity {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.signatures.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::sign_bytes::DEFAULT_CHAIN_ID;
+
+    #[test]
+    fn remembers_valid_transactions() {
+        let cache = ValidationCache::new(DEFAULT_CHAIN_ID);
+        let tx = Transaction::random();
+
+        assert!(cache.validate(&tx));
+        assert_eq!(cache.len(), 1);
+        assert!(cache.validate_all(&[tx.clone(), tx]));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn rejects_blobs_not_matching_their_commitments() {
+        use malachitebft_proto::Protobuf;
+
+        let cache = ValidationCache::new(DEFAULT_CHAIN_ID);
+        let mut proto = Transaction::random().to_proto().unwrap();
+        proto.data[0] = Blob::random().to_proto().unwrap();
+        let tampered = Transaction::from_proto(proto).unwrap();
+
+        assert!(cache.validate(&tampered));
+        assert!(!cache.validate_all(&[tampered]));
+    }
+
+    #[test]
+    fn rejects_transactions_for_another_chain() {
+        let cache = ValidationCache::new("other-chain");
+        assert!(!cache.validate(&Transaction::random()));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn evicts_oldest_entries() {
+        let cache = ValidationCache::with_capacity(DEFAULT_CHAIN_ID, 1);
+        let (first, second) = (Transaction::random(), Transaction::random());
+
+        assert!(cache.validate(&first));
+        assert!(cache.validate(&second));
+        assert_eq!(cache.len(), 1);
+        assert!(!cache.contains(&first.hash(), &first.signature()));
+        assert!(cache.contains(&second.hash(), &second.signature()));
+    }
+}
@@ -0,0 +1,124 @@
+//! Exchange of mempool contents with peers joining the network, so that a freshly started node
+//! has transactions to propose instead of an empty pool until users resubmit them.
+//!
+//! The consensus network only carries proposal parts, so the exchange goes through the RPC
+//! servers of the peers: when a peer joins, the node fetches the hashes of its pool with
+//! `mikan_txPoolHashes`, then the transactions it misses with `mikan_getPoolTransaction`. They go
+//! through the checks of [`TransactionPool::add_transaction`] like any submission. The peer does
+//! the same when it sees us join, so summaries are exchanged both ways.
+
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+
+use malachitebft_app_channel::app::types::PeerId;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use super::pool::TransactionPool;
+use crate::client::{ClientError, MikanClient};
+
+/// `[mempool_exchange]` section of the node configuration, disabled by default.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MempoolExchangeConfig {
+    pub enabled: bool,
+    /// RPC URL of each peer, by peer id as listed by `mikan_peers`
+    pub peers: BTreeMap<String, String>,
+    /// Most transactions fetched from a joining peer
+    pub max_transactions: usize,
+    /// The exchange with a peer is abandoned after this many milliseconds
+    pub timeout_ms: u64,
+}
+
+impl Default for MempoolExchangeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            peers: BTreeMap::new(),
+            max_transactions: 256,
+            timeout_ms: 10_000,
+        }
+    }
+}
+
+/// Fetches in the background the transactions of `peer_id` missing from `pool`, if the exchange
+/// is enabled and the RPC URL of the peer is known.
+pub fn spawn(config: &MempoolExchangeConfig, pool: TransactionPool, peer_id: PeerId) {
+    if !config.enabled {
+        return;
+    }
+    let Some(url) = config.peers.get(&peer_id.to_string()).cloned() else {
+        debug!(%peer_id, "No RPC URL for peer, not exchanging mempools");
+        return;
+    };
+    let (max_transactions, timeout) = (
+        config.max_transactions,
+        Duration::from_millis(config.timeout_ms),
+    );
+
+    tokio::spawn(async move {
+        let exchange = async {
+            let client = MikanClient::new(&url)?;
+            fetch_missing(&pool, &client, max_transactions).await
+        };
+        match tokio::time::timeout(timeout, exchange).await {
+            Ok(Ok(admitted)) => info!(%peer_id, admitted, "Fetched the mempool of a joining peer"),
+            Ok(Err(e)) => warn!(%peer_id, %url, error = %e, "Mempool exchange failed"),
+            Err(_) => warn!(%peer_id, %url, "Mempool exchange timed out"),
+        }
+    });
+}
+
+/// Fetches from `client` the transactions of its pool missing from `pool`, up to
+/// `max_transactions`, and returns the number of them admitted to `pool`.
+pub async fn fetch_missing(
+    pool: &TransactionPool,
+    client: &MikanClient,
+    max_transactions: usize,
+) -> Result<usize, ClientError> {
+    let hashes = client.tx_pool_hashes().await?;
+
+    let mut admitted = 0;
+    for hash in missing(pool, hashes, max_transactions) {
+        // Included in a block or evicted in the meantime
+        let Some(tx) = client.get_pool_transaction(hash, pool.chain_id()).await? else {
+            continue;
+        };
+        match pool.add_transaction(tx) {
+            Ok(()) => admitted += 1,
+            Err(e) => {
+                debug!(hash = %hex::encode(hash), error = %e, "Fetched transaction not admitted")
+            }
+        }
+    }
+    Ok(admitted)
+}
+
+/// The first `max` of `hashes` that are not in `pool`.
+fn missing(pool: &TransactionPool, hashes: Vec<[u8; 32]>, max: usize) -> Vec<[u8; 32]> {
+    let known: HashSet<[u8; 32]> = pool.hashes().into_iter().collect();
+    hashes
+        .into_iter()
+        .filter(|hash| !known.contains(hash))
+        .take(max)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transactions::Transaction;
+    use crate::types::sign_bytes::DEFAULT_CHAIN_ID;
+
+    #[test]
+    fn skips_known_transactions() {
+        let pool = TransactionPool::new(DEFAULT_CHAIN_ID);
+        let known = Transaction::random();
+        pool.add_transaction(known.clone()).unwrap();
+
+        let others: Vec<_> = (0..3).map(|_| Transaction::random().hash()).collect();
+        let hashes = vec![known.hash(), others[0], others[1], others[2]];
+        assert_eq!(missing(&pool, hashes.clone(), 10), others);
+        assert_eq!(missing(&pool, hashes, 2), others[..2]);
+    }
+}
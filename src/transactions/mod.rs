@@ -1,87 +1,272 @@
-use crate::{blob::Blob, rpc::RpcTransaction};
-use bincode::{Decode, Encode};
+use crate::gas::GasSchedule;
+use crate::governance::ParamChange;
+use crate::namespaces::NamespacePolicy;
+use crate::types::codec::proto::{
+    decode_hash, decode_public_key, decode_signature, encode_signature,
+};
+use crate::types::sign_bytes::{transaction_sign_bytes, DEFAULT_CHAIN_ID};
+use crate::types::{proto, serde_hex};
+use crate::{blob::Blob, error::TransactionError};
+use malachitebft_proto::{Error as ProtoError, Protobuf};
 use malachitebft_test::{PrivateKey, PublicKey, Signature};
-use rand::{thread_rng, Rng};
+use rand::{thread_rng, CryptoRng, Rng};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
 use sha3::Digest;
 use std::cmp::Ordering;
 
+pub mod cache;
+pub mod exchange;
+pub mod nonces;
 pub mod pool;
-#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+
+/// Most blobs a transaction can carry, whatever the limit of the genesis file.
+pub const MAX_BLOBS_PER_TX: usize = 16;
+
+/// Blobs per transaction allowed when the genesis file does not set a limit.
+pub const DEFAULT_MAX_BLOBS_PER_TX: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Transaction {
-    #[bincode(with_serde)]
+    #[serde(with = "serde_hex::signature")]
     signature: Signature,
-    #[bincode(with_serde)]
     from: PublicKey,
-    #[bincode(with_serde)]
     to: PublicKey,
     value: u64,
-    data: [Blob; 4],
+    data: Vec<Blob>,
+    /// Commitment of each blob of `data`, signed instead of the blob data, see
+    /// [`Self::verify_blobs`]
+    #[serde(with = "serde_hex::hashes")]
+    blob_commitments: Vec<[u8; 32]>,
     nonce: u64,
     gas_price: u64,
+    /// Maximum gas paid for, see [`crate::gas`]
+    gas_limit: u64,
+    /// Policy of the namespace `to` registered by this transaction, see [`crate::namespaces`]
+    #[serde(default)]
+    registration: Option<NamespacePolicy>,
+    /// Parameter change voted for by this transaction, see [`crate::governance`]
+    #[serde(default)]
+    governance: Option<ParamChange>,
+    /// Returns the sender, a jailed validator, to the validator set, see [`crate::liveness`]
+    #[serde(default)]
+    unjail: bool,
+    #[serde(with = "hex::serde")]
     hash: [u8; 32],
 }
-impl From<RpcTransaction> for Transaction {
-    fn from(rpc_tx: RpcTransaction) -> Self {
-        Self::new(
-            rpc_tx.from,
-            rpc_tx.to,
-            rpc_tx.signature,
-            rpc_tx.value,
-            rpc_tx.data,
-            rpc_tx.nonce,
-            rpc_tx.gas_price,
-        )
-    }
-}
 impl Transaction {
+    /// Builds a transaction from its fields, checking that `signature` is a signature by `from`
+    /// over the canonical signing payload for `chain_id` and that it carries at most
+    /// [`MAX_BLOBS_PER_TX`] blobs.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
+        chain_id: &str,
         from: PublicKey,
         to: PublicKey,
         signature: Signature,
         value: u64,
-        data: [Blob; 4],
+        data: Vec<Blob>,
         nonce: u64,
         gas_price: u64,
-    ) -> Self {
+        gas_limit: u64,
+        registration: Option<NamespacePolicy>,
+        governance: Option<ParamChange>,
+        unjail: bool,
+    ) -> Result<Self, TransactionError> {
+        if data.len() > MAX_BLOBS_PER_TX {
+            return Err(TransactionError::TooManyBlobs {
+                max: MAX_BLOBS_PER_TX,
+                actual: data.len(),
+            });
+        }
         let mut tx = Self {
             signature,
             from,
             to,
             value,
+            blob_commitments: compute_blob_commitments(&data),
             data,
             nonce,
             gas_price,
+            gas_limit,
+            registration,
+            governance,
+            unjail,
             hash: Default::default(),
         };
-        let tx_bytes = tx.to_bytes();
-        let hash: [u8; 32] = sha3::Keccak256::digest(&tx_bytes).into();
-        tx.hash = hash;
+        let sign_bytes = tx.sign_bytes(chain_id);
+        if !tx.verify_signature(&sign_bytes) {
+            return Err(TransactionError::InvalidSignature);
+        }
+        tx.hash = Self::hash_sign_bytes(&sign_bytes);
+        Ok(tx)
+    }
+
+    /// Builds and signs a transaction from `private_key`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn sign(
+        chain_id: &str,
+        private_key: &PrivateKey,
+        to: PublicKey,
+        value: u64,
+        data: Vec<Blob>,
+        nonce: u64,
+        gas_price: u64,
+        gas_limit: u64,
+    ) -> Self {
+        let mut tx = Self {
+            signature: private_key.sign(&[]),
+            from: private_key.public_key(),
+            to,
+            value,
+            blob_commitments: compute_blob_commitments(&data),
+            data,
+            nonce,
+            gas_price,
+            gas_limit,
+            registration: None,
+            governance: None,
+            unjail: false,
+            hash: Default::default(),
+        };
+        let sign_bytes = tx.sign_bytes(chain_id);
+        tx.signature = private_key.sign(&sign_bytes);
+        tx.hash = Self::hash_sign_bytes(&sign_bytes);
         tx
     }
 
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = vec![];
-        bytes.extend_from_slice(self.from.as_bytes());
-        bytes.extend_from_slice(self.to.as_bytes());
-        bytes.extend_from_slice(&self.value.to_be_bytes());
-        for blob in &self.data {
-            bytes.extend_from_slice(blob.data());
-        }
-        bytes.extend_from_slice(&self.nonce.to_be_bytes());
-        bytes.extend_from_slice(&self.gas_price.to_be_bytes());
-        bytes
+    /// Builds and signs a transaction from `private_key` registering `namespace` with `policy`.
+    pub fn sign_registration(
+        chain_id: &str,
+        private_key: &PrivateKey,
+        namespace: PublicKey,
+        policy: NamespacePolicy,
+        nonce: u64,
+        gas_price: u64,
+        gas_limit: u64,
+    ) -> Self {
+        let mut tx = Self::sign(
+            chain_id,
+            private_key,
+            namespace,
+            0,
+            Default::default(),
+            nonce,
+            gas_price,
+            gas_limit,
+        );
+        tx.registration = Some(policy);
+        let sign_bytes = tx.sign_bytes(chain_id);
+        tx.signature = private_key.sign(&sign_bytes);
+        tx.hash = Self::hash_sign_bytes(&sign_bytes);
+        tx
     }
-    pub fn validate(&self) -> bool {
-        if self.data.len() > 4 {
-            return false;
-        }
-        let tx_bytes = self.to_bytes();
-        let hash: [u8; 32] = sha3::Keccak256::digest(&tx_bytes).into();
-        self.hash == hash && self.from.verify(&hash, &self.signature).is_ok()
+
+    /// Builds and signs a transaction from `private_key`, a validator, voting for `change`.
+    pub fn sign_governance(
+        chain_id: &str,
+        private_key: &PrivateKey,
+        change: ParamChange,
+        nonce: u64,
+        gas_price: u64,
+        gas_limit: u64,
+    ) -> Self {
+        let mut tx = Self::sign(
+            chain_id,
+            private_key,
+            private_key.public_key(),
+            0,
+            Default::default(),
+            nonce,
+            gas_price,
+            gas_limit,
+        );
+        tx.governance = Some(change);
+        let sign_bytes = tx.sign_bytes(chain_id);
+        tx.signature = private_key.sign(&sign_bytes);
+        tx.hash = Self::hash_sign_bytes(&sign_bytes);
+        tx
+    }
+
+    /// Builds and signs a transaction from `private_key`, a jailed validator, returning to the
+    /// validator set.
+    pub fn sign_unjail(
+        chain_id: &str,
+        private_key: &PrivateKey,
+        nonce: u64,
+        gas_price: u64,
+        gas_limit: u64,
+    ) -> Self {
+        let mut tx = Self::sign(
+            chain_id,
+            private_key,
+            private_key.public_key(),
+            0,
+            Default::default(),
+            nonce,
+            gas_price,
+            gas_limit,
+        );
+        tx.unjail = true;
+        let sign_bytes = tx.sign_bytes(chain_id);
+        tx.signature = private_key.sign(&sign_bytes);
+        tx.hash = Self::hash_sign_bytes(&sign_bytes);
+        tx
+    }
+
+    /// Canonical payload signed by the sender, see [`transaction_sign_bytes`].
+    pub fn sign_bytes(&self, chain_id: &str) -> Vec<u8> {
+        transaction_sign_bytes(
+            chain_id,
+            self.nonce,
+            &self.from,
+            &self.to,
+            self.value,
+            self.gas_price,
+            self.gas_limit,
+            &self.blob_commitments,
+            self.registration.as_ref(),
+            self.governance.as_ref(),
+            self.unjail,
+        )
+    }
+
+    /// Commitments declared for the blobs, computed when the transaction was built or signed.
+    /// Those of a decoded transaction are only trusted once checked with [`Self::verify_blobs`].
+    pub fn blob_commitments(&self) -> &[[u8; 32]] {
+        &self.blob_commitments
+    }
+
+    /// Whether the blobs match their declared commitments, the most expensive part of validation.
+    pub fn verify_blobs(&self) -> bool {
+        self.blob_commitments.len() == self.data.len()
+            && compute_blob_commitments(&self.data) == self.blob_commitments
+    }
+
+    /// Transaction hash for the given signing payload.
+    pub fn hash_sign_bytes(sign_bytes: &[u8]) -> [u8; 32] {
+        sha3::Keccak256::digest(sign_bytes).into()
+    }
+
+    /// Checks the signature against the signing payload, see [`Self::sign_bytes`].
+    pub fn verify_signature(&self, sign_bytes: &[u8]) -> bool {
+        self.from.verify(sign_bytes, &self.signature).is_ok()
+    }
+
+    pub fn validate(&self, chain_id: &str) -> bool {
+        let sign_bytes = self.sign_bytes(chain_id);
+        self.hash == Self::hash_sign_bytes(&sign_bytes)
+            && self.verify_signature(&sign_bytes)
+            && self.verify_blobs()
     }
-    pub fn data(&self) -> &[Blob; 4] {
+    pub fn data(&self) -> &[Blob] {
         &self.data
     }
+    /// Size of the protobuf encoding, as gossiped and stored.
+    pub fn encoded_len(&self) -> usize {
+        self.to_proto()
+            .map_or(usize::MAX, |proto| prost::Message::encoded_len(&proto))
+    }
     pub fn hash(&self) -> [u8; 32] {
         self.hash
     }
@@ -100,40 +285,215 @@ impl Transaction {
     pub fn gas_price(&self) -> u64 {
         self.gas_price
     }
+    pub fn gas_limit(&self) -> u64 {
+        self.gas_limit
+    }
     pub fn signature(&self) -> Signature {
         self.signature
     }
+    pub fn registration(&self) -> Option<&NamespacePolicy> {
+        self.registration.as_ref()
+    }
+    pub fn governance(&self) -> Option<&ParamChange> {
+        self.governance.as_ref()
+    }
+    pub fn unjail(&self) -> bool {
+        self.unjail
+    }
 
+    /// A random transaction signed for [`DEFAULT_CHAIN_ID`].
     pub fn random() -> Self {
-        let mut rng = thread_rng();
-        let private_key = PrivateKey::generate(&mut rng);
-        let public_key = private_key.public_key();
-        let data = [
-            Blob::random(),
-            Blob::random(),
-            Blob::random(),
-            Blob::random(),
-        ];
-        let signature = private_key.sign(&[]);
-        let value = rng.gen_range(0..1000000000000000000);
-        let mut tx = Self {
-            signature,
-            from: public_key,
-            to: public_key,
-            value,
+        Self::random_with_rng(&mut thread_rng())
+    }
+
+    /// A random transaction signed for [`DEFAULT_CHAIN_ID`], drawn from `rng`, see [`crate::rng`]
+    /// for replayable fixtures. The sender is a fresh key, so the nonce is 0, and the gas limit is
+    /// the intrinsic gas under the default [`GasSchedule`].
+    pub fn random_with_rng<R: Rng + CryptoRng>(rng: &mut R) -> Self {
+        let private_key = PrivateKey::generate(&mut *rng);
+        let data = (0..DEFAULT_MAX_BLOBS_PER_TX)
+            .map(|_| Blob::random_with_rng(rng))
+            .collect::<Vec<_>>();
+        let gas_limit = GasSchedule::default().intrinsic_gas(&data);
+        Self::sign(
+            DEFAULT_CHAIN_ID,
+            &private_key,
+            private_key.public_key(),
+            rng.gen_range(0..1000000000000000000),
             data,
-            nonce: rng.gen_range(0..1000000000000000000),
-            gas_price: rng.gen_range(0..1000000000000000000),
-            hash: Default::default(),
+            0,
+            rng.gen_range(0..1000000000000000000),
+            gas_limit,
+        )
+    }
+}
+impl Protobuf for Transaction {
+    type Proto = proto::Transaction;
+
+    fn from_proto(proto: Self::Proto) -> Result<Self, ProtoError> {
+        if proto.data.len() > MAX_BLOBS_PER_TX {
+            return Err(ProtoError::Other(format!(
+                "Expected at most {MAX_BLOBS_PER_TX} blobs, got {}",
+                proto.data.len()
+            )));
+        }
+        let data = proto
+            .data
+            .into_iter()
+            .map(Blob::from_proto)
+            .collect::<Result<Vec<_>, _>>()?;
+        if proto.blob_commitments.len() != data.len() {
+            return Err(ProtoError::Other(format!(
+                "Expected {} blob commitments, got {}",
+                data.len(),
+                proto.blob_commitments.len()
+            )));
+        }
+        let blob_commitments = proto
+            .blob_commitments
+            .iter()
+            .map(|commitment| decode_hash(commitment))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            signature: proto
+                .signature
+                .ok_or_else(|| ProtoError::missing_field::<Self::Proto>("signature"))
+                .and_then(decode_signature)?,
+            from: decode_public_key(&proto.from)?,
+            to: decode_public_key(&proto.to)?,
+            value: proto.value,
+            data,
+            blob_commitments,
+            nonce: proto.nonce,
+            gas_price: proto.gas_price,
+            gas_limit: proto.gas_limit,
+            registration: proto
+                .registration
+                .map(|policy| {
+                    Ok::<_, ProtoError>(NamespacePolicy {
+                        fee_multiplier: policy.fee_multiplier,
+                        allowed_posters: policy
+                            .allowed_posters
+                            .iter()
+                            .map(|poster| decode_public_key(poster))
+                            .collect::<Result<_, _>>()?,
+                    })
+                })
+                .transpose()?,
+            governance: proto.governance.map(|change| ParamChange {
+                height: change.height,
+                max_block_bytes: change.max_block_bytes,
+                gas: change.gas.map(|gas| GasSchedule {
+                    per_transfer: gas.per_transfer,
+                    per_blob_byte: gas.per_blob_byte,
+                    per_execution_step: gas.per_execution_step,
+                    block_gas_limit: gas.block_gas_limit,
+                }),
+                retain_heights: change.retain_heights,
+            }),
+            unjail: proto.unjail,
+            hash: decode_hash(&proto.hash)?,
+        })
+    }
+
+    fn to_proto(&self) -> Result<Self::Proto, ProtoError> {
+        Ok(proto::Transaction {
+            signature: Some(encode_signature(&self.signature)),
+            from: self.from.as_bytes().to_vec().into(),
+            to: self.to.as_bytes().to_vec().into(),
+            value: self.value,
+            data: self
+                .data
+                .iter()
+                .map(Blob::to_proto)
+                .collect::<Result<_, _>>()?,
+            blob_commitments: self
+                .blob_commitments
+                .iter()
+                .map(|commitment| commitment.to_vec().into())
+                .collect(),
+            nonce: self.nonce,
+            gas_price: self.gas_price,
+            gas_limit: self.gas_limit,
+            registration: self
+                .registration
+                .as_ref()
+                .map(|policy| proto::NamespacePolicy {
+                    fee_multiplier: policy.fee_multiplier,
+                    allowed_posters: policy
+                        .allowed_posters
+                        .iter()
+                        .map(|poster| poster.as_bytes().to_vec().into())
+                        .collect(),
+                }),
+            governance: self.governance.as_ref().map(|change| proto::ParamChange {
+                height: change.height,
+                max_block_bytes: change.max_block_bytes,
+                gas: change.gas.as_ref().map(|gas| proto::GasSchedule {
+                    per_transfer: gas.per_transfer,
+                    per_blob_byte: gas.per_blob_byte,
+                    per_execution_step: gas.per_execution_step,
+                    block_gas_limit: gas.block_gas_limit,
+                }),
+                retain_heights: change.retain_heights,
+            }),
+            unjail: self.unjail,
+            hash: self.hash.to_vec().into(),
+        })
+    }
+}
+
+/// A well-formed transaction whose signature, blob commitments and hash are arbitrary rather
+/// than computed, so that generating one stays cheap.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Transaction {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        use crate::fuzz::{arbitrary_public_key, arbitrary_signature};
+
+        let mut data = Vec::new();
+        let mut blob_commitments = Vec::new();
+        for _ in 0..u.int_in_range(0..=MAX_BLOBS_PER_TX)? {
+            data.push(u.arbitrary()?);
+            blob_commitments.push(u.arbitrary()?);
+        }
+        let registration = if u.arbitrary()? {
+            let fee_multiplier = u.arbitrary()?;
+            let mut allowed_posters = Vec::new();
+            for _ in 0..u.int_in_range(0..=4)? {
+                allowed_posters.push(arbitrary_public_key(u)?);
+            }
+            Some(NamespacePolicy {
+                fee_multiplier,
+                allowed_posters,
+            })
+        } else {
+            None
         };
-        let tx_bytes = tx.to_bytes();
-        let hash: [u8; 32] = sha3::Keccak256::digest(&tx_bytes).into();
-        tx.hash = hash;
-        let signature = private_key.sign(&hash);
-        tx.signature = signature;
-        tx
+
+        Ok(Self {
+            signature: arbitrary_signature(u)?,
+            from: arbitrary_public_key(u)?,
+            to: arbitrary_public_key(u)?,
+            value: u.arbitrary()?,
+            data,
+            blob_commitments,
+            nonce: u.arbitrary()?,
+            gas_price: u.arbitrary()?,
+            gas_limit: u.arbitrary()?,
+            registration,
+            governance: u.arbitrary()?,
+            unjail: u.arbitrary()?,
+            hash: u.arbitrary()?,
+        })
     }
 }
+
+/// Commitments to `blobs`, computed in parallel.
+fn compute_blob_commitments(blobs: &[Blob]) -> Vec<[u8; 32]> {
+    blobs.par_iter().map(Blob::commitment).collect()
+}
+
 impl Ord for Transaction {
     fn cmp(&self, other: &Self) -> Ordering {
         self.gas_price.cmp(&other.gas_price)
@@ -152,6 +512,155 @@ mod tests {
     fn test_random() {
         let tx = Transaction::random();
         println!("tx: {:?}", tx);
-        assert!(tx.validate());
+        assert!(tx.validate(DEFAULT_CHAIN_ID));
+    }
+
+    #[test]
+    fn test_signature_is_chain_bound() {
+        let tx = Transaction::random();
+        assert!(!tx.validate("other-chain"));
+        assert!(matches!(
+            Transaction::new(
+                "other-chain",
+                tx.from_(),
+                tx.to(),
+                tx.signature(),
+                tx.value(),
+                tx.data().to_vec(),
+                tx.nonce(),
+                tx.gas_price(),
+                tx.gas_limit(),
+                None,
+                None,
+                false,
+            ),
+            Err(TransactionError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_new_rejects_tampered_fields() {
+        let tx = Transaction::random();
+        let rebuilt = Transaction::new(
+            DEFAULT_CHAIN_ID,
+            tx.from_(),
+            tx.to(),
+            tx.signature(),
+            tx.value(),
+            tx.data().to_vec(),
+            tx.nonce(),
+            tx.gas_price(),
+            tx.gas_limit(),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(rebuilt, tx);
+
+        let tampered = Transaction::new(
+            DEFAULT_CHAIN_ID,
+            tx.from_(),
+            tx.to(),
+            tx.signature(),
+            tx.value().wrapping_add(1),
+            tx.data().to_vec(),
+            tx.nonce(),
+            tx.gas_price(),
+            tx.gas_limit(),
+            None,
+            None,
+            false,
+        );
+        assert!(matches!(tampered, Err(TransactionError::InvalidSignature)));
+    }
+
+    #[test]
+    fn test_new_rejects_too_many_blobs() {
+        let tx = Transaction::random();
+        let result = Transaction::new(
+            DEFAULT_CHAIN_ID,
+            tx.from_(),
+            tx.to(),
+            tx.signature(),
+            tx.value(),
+            vec![Blob::default(); MAX_BLOBS_PER_TX + 1],
+            tx.nonce(),
+            tx.gas_price(),
+            tx.gas_limit(),
+            None,
+            None,
+            false,
+        );
+        assert!(matches!(
+            result,
+            Err(TransactionError::TooManyBlobs { actual, .. }) if actual == MAX_BLOBS_PER_TX + 1
+        ));
+    }
+
+    #[test]
+    fn test_protobuf_roundtrip() {
+        let tx = Transaction::random();
+        let bytes = Protobuf::to_bytes(&tx).unwrap();
+        let decoded = <Transaction as Protobuf>::from_bytes(&bytes).unwrap();
+        assert_eq!(tx, decoded);
+        assert!(decoded.validate(DEFAULT_CHAIN_ID));
+    }
+
+    #[test]
+    fn test_detects_blobs_not_matching_their_commitments() {
+        let tx = Transaction::random();
+        let mut proto = tx.to_proto().unwrap();
+        proto.data[1] = Blob::random().to_proto().unwrap();
+        let tampered = Transaction::from_proto(proto).unwrap();
+
+        assert_eq!(tampered.hash(), tx.hash());
+        assert!(!tampered.verify_blobs());
+        assert!(!tampered.validate(DEFAULT_CHAIN_ID));
+    }
+
+    #[test]
+    fn test_governance_roundtrip() {
+        let key = PrivateKey::generate(thread_rng());
+        let change = ParamChange {
+            height: 10,
+            max_block_bytes: None,
+            gas: Some(GasSchedule::default()),
+            retain_heights: Some(100),
+        };
+        let tx = Transaction::sign_governance(DEFAULT_CHAIN_ID, &key, change.clone(), 0, 1, 21_000);
+        assert_eq!(tx.governance(), Some(&change));
+
+        let bytes = Protobuf::to_bytes(&tx).unwrap();
+        let decoded = <Transaction as Protobuf>::from_bytes(&bytes).unwrap();
+        assert_eq!(tx, decoded);
+        assert!(decoded.validate(DEFAULT_CHAIN_ID));
+    }
+
+    #[test]
+    fn test_unjail_roundtrip() {
+        let key = PrivateKey::generate(thread_rng());
+        let tx = Transaction::sign_unjail(DEFAULT_CHAIN_ID, &key, 0, 1, 21_000);
+        assert!(tx.unjail());
+
+        let bytes = Protobuf::to_bytes(&tx).unwrap();
+        let decoded = <Transaction as Protobuf>::from_bytes(&bytes).unwrap();
+        assert_eq!(tx, decoded);
+        assert!(decoded.validate(DEFAULT_CHAIN_ID));
+
+        let mut proto = tx.to_proto().unwrap();
+        proto.unjail = false;
+        assert!(!Transaction::from_proto(proto)
+            .unwrap()
+            .validate(DEFAULT_CHAIN_ID));
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let tx = Transaction::random();
+        let json = serde_json::to_string(&tx).unwrap();
+        let decoded: Transaction = serde_json::from_str(&json).unwrap();
+        assert_eq!(tx, decoded);
+        assert!(decoded.validate(DEFAULT_CHAIN_ID));
     }
 }
@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use malachitebft_test::PublicKey;
+
+use super::Transaction;
+
+/// Next nonce of each sender, one past the highest nonce decided for it, shared between the
+/// mempool and the commit of decided blocks.
+///
+/// Persisted in the store, as pruning drops the blocks the nonces were decided in.
+#[derive(Debug, Clone, Default)]
+pub struct AccountNonces {
+    next: Arc<Mutex<HashMap<[u8; 32], u64>>>,
+}
+
+impl AccountNonces {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds nonces loaded from the store.
+    pub fn load(&self, nonces: impl IntoIterator<Item = ([u8; 32], u64)>) {
+        self.next.lock().unwrap().extend(nonces);
+    }
+
+    /// Next nonce of `sender`, 0 if it never had a transaction decided.
+    pub fn get(&self, sender: &PublicKey) -> u64 {
+        self.next
+            .lock()
+            .unwrap()
+            .get(sender.as_bytes())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Advances the nonces of the senders of a decided block, returns the new ones to be
+    /// persisted.
+    pub fn apply(&self, txs: &[Transaction]) -> Vec<([u8; 32], u64)> {
+        let mut next = self.next.lock().unwrap();
+        let mut updated = HashMap::new();
+        for tx in txs {
+            let sender = *tx.from_().as_bytes();
+            let nonce = tx.nonce().saturating_add(1);
+            let current = next.entry(sender).or_default();
+            if nonce > *current {
+                *current = nonce;
+                updated.insert(sender, nonce);
+            }
+        }
+        updated.into_iter().collect()
+    }
+}
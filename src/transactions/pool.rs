@@ -1,25 +1,256 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 
+use malachitebft_test::PublicKey;
 use sorted_vec::SortedSet;
 
-use super::Transaction;
+use super::cache::ValidationCache;
+use super::nonces::AccountNonces;
+use super::{Transaction, DEFAULT_MAX_BLOBS_PER_TX};
+use crate::block::BlockConfig;
+use crate::consensus_params::ConsensusParams;
+use crate::error::TransactionError;
+use crate::gas::GasSchedule;
+use crate::governance::Governance;
+use crate::liveness::Liveness;
+use crate::namespaces::NamespaceRegistry;
 
-#[derive(Debug, Clone, Default)]
+/// Maximum number of pending transactions, each one carries up to `max_blobs_per_tx` blobs.
+pub const DEFAULT_POOL_CAPACITY: usize = 1024;
+
+/// Maximum number of queued transactions, whose nonce is ahead of the next one of their sender.
+pub const DEFAULT_QUEUE_CAPACITY: usize = 256;
+
+/// Transactions waiting for the previous nonces of their sender.
+#[derive(Debug, Default)]
+struct Queue {
+    by_sender: HashMap<[u8; 32], BTreeMap<u64, Transaction>>,
+    len: usize,
+    /// One past the highest pending nonce of the senders with pending transactions
+    next_nonces: HashMap<[u8; 32], u64>,
+}
+
+impl Queue {
+    fn insert(&mut self, tx: Transaction) {
+        let queued = self.by_sender.entry(*tx.from_().as_bytes()).or_default();
+        if queued.insert(tx.nonce(), tx).is_none() {
+            self.len += 1;
+        }
+    }
+
+    /// Moves the queued transactions of `sender` following `next` to the pending ones, as long as
+    /// there is room for them.
+    fn promote(
+        &mut self,
+        sender: [u8; 32],
+        mut next: u64,
+        pending: &mut SortedSet<Transaction>,
+        capacity: usize,
+    ) {
+        if let Some(queued) = self.by_sender.get_mut(&sender) {
+            while pending.len() < capacity {
+                let Some(tx) = queued.remove(&next) else {
+                    break;
+                };
+                pending.push(tx);
+                self.len -= 1;
+                next += 1;
+            }
+            if queued.is_empty() {
+                self.by_sender.remove(&sender);
+            }
+        }
+        self.next_nonces.insert(sender, next);
+    }
+}
+
+/// Pending transactions, ordered by gas price for block building, and queued ones, whose nonce
+/// is ahead of the next one of their sender. Queued transactions are promoted to pending once
+/// the gap is filled, by a submission or a decided block.
+#[derive(Debug, Clone)]
 pub struct TransactionPool {
+    /// Chain id transactions must be signed for
+    chain_id: Arc<str>,
+    capacity: usize,
     transactions: Arc<Mutex<SortedSet<Transaction>>>,
+    queue_capacity: usize,
+    queue: Arc<Mutex<Queue>>,
+    /// Decided nonces, transactions below them are not admitted
+    nonces: AccountNonces,
+    /// Transactions are verified once on submission, block validation reuses the result
+    validation_cache: ValidationCache,
+    /// Decided registrations, transactions breaking a namespace policy are not admitted
+    namespaces: NamespaceRegistry,
+    /// Transactions that do not pay for their gas are not admitted
+    gas: GasSchedule,
+    /// Decided votes, only validators can vote for parameter changes
+    governance: Governance,
+    /// Decided jailings, only jailed validators can unjail
+    liveness: Liveness,
+    max_blobs_per_tx: usize,
+    /// Maximum length of the data of a blob, see [`ConsensusParams::max_blob_bytes`]
+    max_blob_bytes: usize,
+    /// Maximum encoded size of a transaction, see [`BlockConfig::max_tx_bytes`]
+    max_tx_bytes: usize,
 }
 
 impl TransactionPool {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(chain_id: &str) -> Self {
+        Self::with_capacity(chain_id, DEFAULT_POOL_CAPACITY)
     }
 
-    pub fn add_transaction(&self, transaction: Transaction) {
-        if transaction.validate() {
-            self.transactions.try_lock().unwrap().push(transaction);
+    pub fn with_capacity(chain_id: &str, capacity: usize) -> Self {
+        Self {
+            chain_id: chain_id.into(),
+            capacity,
+            transactions: Default::default(),
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            queue: Default::default(),
+            nonces: AccountNonces::new(),
+            validation_cache: ValidationCache::new(chain_id),
+            namespaces: NamespaceRegistry::new(),
+            gas: GasSchedule::default(),
+            governance: Governance::default(),
+            liveness: Liveness::default(),
+            max_blobs_per_tx: DEFAULT_MAX_BLOBS_PER_TX,
+            max_blob_bytes: ConsensusParams::default().max_blob_bytes,
+            max_tx_bytes: BlockConfig::default().max_tx_bytes(),
         }
     }
 
+    /// Sets the gas schedule of the chain, the default one otherwise.
+    pub fn with_gas_schedule(mut self, gas: GasSchedule) -> Self {
+        self.gas = gas;
+        self
+    }
+
+    /// Shares the decided votes of the chain, without which every vote is rejected.
+    pub fn with_governance(mut self, governance: Governance) -> Self {
+        self.governance = governance;
+        self
+    }
+
+    /// Shares the decided jailings of the chain, without which every unjail is rejected.
+    pub fn with_liveness(mut self, liveness: Liveness) -> Self {
+        self.liveness = liveness;
+        self
+    }
+
+    /// Sets the most blobs a transaction can carry, [`DEFAULT_MAX_BLOBS_PER_TX`] otherwise.
+    pub fn with_max_blobs_per_tx(mut self, max_blobs_per_tx: usize) -> Self {
+        self.max_blobs_per_tx = max_blobs_per_tx;
+        self
+    }
+
+    /// Sets the longest blob data, [`crate::blob::BLOB_SIZE`] otherwise.
+    pub fn with_max_blob_bytes(mut self, max_blob_bytes: usize) -> Self {
+        self.max_blob_bytes = max_blob_bytes;
+        self
+    }
+
+    pub fn with_max_tx_bytes(mut self, max_tx_bytes: usize) -> Self {
+        self.max_tx_bytes = max_tx_bytes;
+        self
+    }
+
+    pub fn max_tx_bytes(&self) -> usize {
+        self.max_tx_bytes
+    }
+
+    pub fn chain_id(&self) -> &str {
+        &self.chain_id
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn validation_cache(&self) -> &ValidationCache {
+        &self.validation_cache
+    }
+
+    pub fn namespaces(&self) -> &NamespaceRegistry {
+        &self.namespaces
+    }
+
+    pub fn gas_schedule(&self) -> &GasSchedule {
+        &self.gas
+    }
+
+    pub fn governance(&self) -> &Governance {
+        &self.governance
+    }
+
+    pub fn liveness(&self) -> &Liveness {
+        &self.liveness
+    }
+
+    pub fn account_nonces(&self) -> &AccountNonces {
+        &self.nonces
+    }
+
+    pub fn add_transaction(&self, transaction: Transaction) -> Result<(), TransactionError> {
+        let size = transaction.encoded_len();
+        if size > self.max_tx_bytes {
+            return Err(TransactionError::TooLarge {
+                max: self.max_tx_bytes,
+                actual: size,
+            });
+        }
+        if transaction.data().len() > self.max_blobs_per_tx {
+            return Err(TransactionError::TooManyBlobs {
+                max: self.max_blobs_per_tx,
+                actual: transaction.data().len(),
+            });
+        }
+        if let Some(blob) = transaction
+            .data()
+            .iter()
+            .find(|blob| blob.data().len() > self.max_blob_bytes)
+        {
+            return Err(TransactionError::BlobTooLarge {
+                max: self.max_blob_bytes,
+                actual: blob.data().len(),
+            });
+        }
+        if !self.validation_cache.validate(&transaction) {
+            return Err(TransactionError::InvalidSignature);
+        }
+        self.namespaces.check(&transaction)?;
+        self.gas.gas_used(&transaction)?;
+        self.governance.check_pending(&transaction)?;
+        self.liveness.check_pending(&transaction)?;
+
+        let sender = *transaction.from_().as_bytes();
+        let decided = self.nonces.get(&transaction.from_());
+        let mut queue = self.queue.lock().unwrap();
+        let expected = queue
+            .next_nonces
+            .get(&sender)
+            .map_or(decided, |&next| next.max(decided));
+        if transaction.nonce() < expected {
+            return Err(TransactionError::NonceTooLow {
+                expected,
+                actual: transaction.nonce(),
+            });
+        }
+        if transaction.nonce() > expected {
+            if queue.len >= self.queue_capacity {
+                return Err(TransactionError::QueueFull(self.queue_capacity));
+            }
+            queue.insert(transaction);
+            return Ok(());
+        }
+
+        let mut transactions = self.transactions.try_lock().unwrap();
+        if transactions.len() >= self.capacity {
+            return Err(TransactionError::PoolFull(self.capacity));
+        }
+        transactions.push(transaction);
+        queue.promote(sender, expected + 1, &mut transactions, self.capacity);
+        Ok(())
+    }
+
     pub fn remove_transaction(&self, transaction: &Transaction) {
         self.transactions
             .try_lock()
@@ -29,6 +260,49 @@ impl TransactionPool {
     pub fn tx_count(&self) -> usize {
         self.transactions.try_lock().unwrap().len()
     }
+    pub fn queued_count(&self) -> usize {
+        self.queue.lock().unwrap().len
+    }
+
+    /// Hashes of the pending and queued transactions, the summary exchanged with peers.
+    pub fn hashes(&self) -> Vec<[u8; 32]> {
+        let mut hashes: Vec<[u8; 32]> = self
+            .transactions
+            .try_lock()
+            .unwrap()
+            .iter()
+            .map(Transaction::hash)
+            .collect();
+        let queue = self.queue.lock().unwrap();
+        hashes.extend(
+            queue
+                .by_sender
+                .values()
+                .flat_map(BTreeMap::values)
+                .map(Transaction::hash),
+        );
+        hashes
+    }
+
+    /// Pending or queued transaction with `hash`.
+    pub fn get(&self, hash: &[u8; 32]) -> Option<Transaction> {
+        if let Some(tx) = self
+            .transactions
+            .try_lock()
+            .unwrap()
+            .iter()
+            .find(|tx| tx.hash() == *hash)
+        {
+            return Some(tx.clone());
+        }
+        let queue = self.queue.lock().unwrap();
+        queue
+            .by_sender
+            .values()
+            .flat_map(BTreeMap::values)
+            .find(|tx| tx.hash() == *hash)
+            .cloned()
+    }
     pub fn get_top_transaction(&self) -> Option<Transaction> {
         let mut transactions = self.transactions.try_lock().unwrap();
         if !transactions.is_empty() {
@@ -45,4 +319,179 @@ impl TransactionPool {
             .drain(..count)
             .collect()
     }
+
+    /// Drops the transactions of a decided block, which another proposer may have included.
+    pub fn remove_decided(&self, decided: &[Transaction]) -> usize {
+        let decided: HashSet<[u8; 32]> = decided.iter().map(Transaction::hash).collect();
+        self.retain(|tx| !decided.contains(&tx.hash()))
+    }
+
+    /// Evicts the transactions made invalid by the decided state, e.g. posts to a namespace
+    /// registered in the meantime, nonces already decided, votes for a height already reached or
+    /// unjails already applied, so that proposers do not pull them only to skip them. Then promotes the queued
+    /// transactions whose gap was filled.
+    /// Returns the number of evicted transactions.
+    pub fn revalidate(&self) -> usize {
+        let valid = |tx: &Transaction| {
+            tx.nonce() >= self.nonces.get(&tx.from_())
+                && self.namespaces.check(tx).is_ok()
+                && self.gas.gas_used(tx).is_ok()
+                && self.governance.check_pending(tx).is_ok()
+                && self.liveness.check_pending(tx).is_ok()
+        };
+        let mut evicted = self.retain(&valid);
+
+        let mut queue = self.queue.lock().unwrap();
+        let mut transactions = self.transactions.try_lock().unwrap();
+        for queued in queue.by_sender.values_mut() {
+            let count = queued.len();
+            queued.retain(|_, tx| valid(tx));
+            evicted += count - queued.len();
+        }
+        queue.by_sender.retain(|_, queued| !queued.is_empty());
+        queue.len = queue.by_sender.values().map(BTreeMap::len).sum();
+
+        // Transactions taken for a block that was not decided are gone, their nonces can be
+        // submitted again
+        queue.next_nonces.clear();
+        for tx in transactions.iter() {
+            let next = queue.next_nonces.entry(*tx.from_().as_bytes()).or_default();
+            *next = (*next).max(tx.nonce() + 1);
+        }
+
+        let senders: Vec<[u8; 32]> = queue.by_sender.keys().copied().collect();
+        for sender in senders {
+            let decided = self.nonces.get(&PublicKey::from_bytes(sender));
+            let next = queue
+                .next_nonces
+                .get(&sender)
+                .map_or(decided, |&next| next.max(decided));
+            queue.promote(sender, next, &mut transactions, self.capacity);
+        }
+        evicted
+    }
+
+    fn retain(&self, keep: impl Fn(&Transaction) -> bool) -> usize {
+        let mut transactions = self.transactions.try_lock().unwrap();
+        let count = transactions.len();
+        let kept: Vec<Transaction> = transactions.drain(..).filter(|tx| keep(tx)).collect();
+        for tx in kept {
+            transactions.push(tx);
+        }
+        count - transactions.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::namespaces::NamespacePolicy;
+    use crate::types::sign_bytes::DEFAULT_CHAIN_ID;
+    use malachitebft_test::PrivateKey;
+    use rand::thread_rng;
+
+    /// Covers four blobs under the default gas schedule
+    const GAS_LIMIT: u64 = 5_000_000;
+
+    fn transfer(from: &PrivateKey, to: PublicKey, nonce: u64, gas_price: u64) -> Transaction {
+        Transaction::sign(
+            DEFAULT_CHAIN_ID,
+            from,
+            to,
+            0,
+            Default::default(),
+            nonce,
+            gas_price,
+            GAS_LIMIT,
+        )
+    }
+
+    #[test]
+    fn drops_decided_and_invalidated_transactions() {
+        let (owner, poster) = (
+            PrivateKey::generate(thread_rng()),
+            PrivateKey::generate(thread_rng()),
+        );
+        let namespace = PrivateKey::generate(thread_rng()).public_key();
+
+        let pool = TransactionPool::new(DEFAULT_CHAIN_ID);
+        pool.add_transaction(transfer(&poster, namespace, 0, 1))
+            .unwrap();
+        pool.add_transaction(transfer(&poster, namespace, 1, 2))
+            .unwrap();
+        pool.add_transaction(transfer(&poster, namespace, 2, 20))
+            .unwrap();
+
+        // The decided block includes a post and registers the namespace with a higher gas price
+        let register = Transaction::sign_registration(
+            DEFAULT_CHAIN_ID,
+            &owner,
+            namespace,
+            NamespacePolicy {
+                fee_multiplier: 10,
+                allowed_posters: Vec::new(),
+            },
+            0,
+            10,
+            GAS_LIMIT,
+        );
+        let decided = vec![transfer(&poster, namespace, 0, 1), register];
+        pool.namespaces().apply(&decided);
+        pool.account_nonces().apply(&decided);
+
+        assert_eq!(pool.remove_decided(&decided), 1);
+        assert_eq!(pool.revalidate(), 1);
+        assert_eq!(
+            pool.get_top_transaction(),
+            Some(transfer(&poster, namespace, 2, 20))
+        );
+        assert_eq!(pool.tx_count(), 0);
+    }
+
+    #[test]
+    fn rejects_transactions_above_the_size_limit() {
+        let tx = Transaction::random();
+        let size = tx.encoded_len();
+
+        let pool = TransactionPool::new(DEFAULT_CHAIN_ID).with_max_tx_bytes(size - 1);
+        assert!(matches!(
+            pool.add_transaction(tx.clone()),
+            Err(TransactionError::TooLarge { actual, .. }) if actual == size
+        ));
+
+        let pool = TransactionPool::new(DEFAULT_CHAIN_ID).with_max_tx_bytes(size);
+        assert!(pool.add_transaction(tx).is_ok());
+    }
+
+    #[test]
+    fn queues_transactions_until_the_nonce_gap_is_filled() {
+        let sender = PrivateKey::generate(thread_rng());
+        let to = sender.public_key();
+        let pool = TransactionPool::new(DEFAULT_CHAIN_ID);
+
+        pool.add_transaction(transfer(&sender, to, 2, 1)).unwrap();
+        pool.add_transaction(transfer(&sender, to, 1, 2)).unwrap();
+        assert_eq!((pool.tx_count(), pool.queued_count()), (0, 2));
+
+        pool.add_transaction(transfer(&sender, to, 0, 3)).unwrap();
+        assert_eq!((pool.tx_count(), pool.queued_count()), (3, 0));
+        assert_eq!(pool.hashes().len(), 3);
+        let first = transfer(&sender, to, 0, 3);
+        assert_eq!(pool.get(&first.hash()), Some(first));
+        assert!(matches!(
+            pool.add_transaction(transfer(&sender, to, 1, 4)),
+            Err(TransactionError::NonceTooLow {
+                expected: 3,
+                actual: 1
+            })
+        ));
+
+        // A decided block fills the gap of a queued transaction
+        pool.add_transaction(transfer(&sender, to, 4, 5)).unwrap();
+        let decided = pool.get_transactions(3);
+        pool.account_nonces().apply(&[transfer(&sender, to, 3, 6)]);
+        assert_eq!(pool.remove_decided(&decided), 0);
+        assert_eq!(pool.revalidate(), 0);
+        assert_eq!((pool.tx_count(), pool.queued_count()), (1, 0));
+    }
 }
@@ -0,0 +1,241 @@
+use core::fmt;
+use core::str::FromStr;
+
+use bech32::{Bech32, Hrp};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha3::{Digest, Keccak256};
+use thiserror::Error;
+
+use malachitebft_proto::{Error as ProtoError, Protobuf};
+
+use super::proto;
+use super::signing::PublicKey;
+use malachitebft_test::Hashable;
+
+/// Human readable part of bech32-encoded addresses (`mikan1...`).
+pub const BECH32_HRP: Hrp = Hrp::parse_unchecked("mikan");
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AddressParseError {
+    #[error("Invalid address length: expected 20 bytes, got {0}")]
+    InvalidLength(usize),
+    #[error("Invalid hex address: {0}")]
+    InvalidHex(#[from] hex::FromHexError),
+    #[error("Invalid address checksum")]
+    InvalidChecksum,
+    #[error("Invalid bech32 address: {0}")]
+    InvalidBech32(String),
+    #[error("Invalid bech32 prefix: expected {BECH32_HRP}, got {0}")]
+    InvalidPrefix(String),
+}
+
+/// A validator address.
+///
+/// Displayed and serialized as EIP-55 checksummed hex (`0x5aAe...`). Parsing accepts checksummed
+/// hex, all-lowercase or all-uppercase hex with or without the `0x` prefix, and bech32
+/// (`mikan1...`). Mixed-case hex is only accepted if its checksum is valid.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Address([u8; Self::LENGTH]);
+
+impl Address {
+    const LENGTH: usize = 20;
+
+    pub const fn new(value: [u8; Self::LENGTH]) -> Self {
+        Self(value)
+    }
+
+    pub fn from_public_key(public_key: &PublicKey) -> Self {
+        let hash = public_key.hash();
+        let mut address = [0; Self::LENGTH];
+        address.copy_from_slice(&hash[..Self::LENGTH]);
+        Self(address)
+    }
+
+    pub fn into_inner(self) -> [u8; Self::LENGTH] {
+        self.0
+    }
+
+    /// EIP-55 checksummed hex representation, with the `0x` prefix
+    pub fn to_checksum_hex(&self) -> String {
+        let lower = hex::encode(self.0);
+        let hash = Keccak256::digest(lower.as_bytes());
+
+        let mut out = String::with_capacity(2 + lower.len());
+        out.push_str("0x");
+        for (i, c) in lower.chars().enumerate() {
+            let nibble = (hash[i / 2] >> (4 * (1 - i % 2))) & 0x0f;
+            if nibble >= 8 {
+                out.push(c.to_ascii_uppercase());
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    /// Bech32 representation, with the [`BECH32_HRP`] prefix
+    pub fn to_bech32(&self) -> String {
+        bech32::encode::<Bech32>(BECH32_HRP, &self.0).expect("20 bytes always fit in bech32")
+    }
+
+    pub fn from_bech32(s: &str) -> Result<Self, AddressParseError> {
+        let (hrp, data) =
+            bech32::decode(s).map_err(|e| AddressParseError::InvalidBech32(e.to_string()))?;
+        if hrp != BECH32_HRP {
+            return Err(AddressParseError::InvalidPrefix(hrp.to_string()));
+        }
+        Self::from_slice(&data)
+    }
+
+    fn from_hex(s: &str) -> Result<Self, AddressParseError> {
+        let digits = s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .unwrap_or(s);
+        let address = Self::from_slice(&hex::decode(digits)?)?;
+
+        let is_mixed_case = digits.chars().any(|c| c.is_ascii_lowercase())
+            && digits.chars().any(|c| c.is_ascii_uppercase());
+        if is_mixed_case && address.to_checksum_hex()[2..] != *digits {
+            return Err(AddressParseError::InvalidChecksum);
+        }
+
+        Ok(address)
+    }
+
+    fn from_slice(bytes: &[u8]) -> Result<Self, AddressParseError> {
+        let bytes = <[u8; Self::LENGTH]>::try_from(bytes)
+            .map_err(|_| AddressParseError::InvalidLength(bytes.len()))?;
+        Ok(Self(bytes))
+    }
+}
+
+impl FromStr for Address {
+    type Err = AddressParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let prefix = format!("{BECH32_HRP}1");
+        if s.len() > prefix.len() && s[..prefix.len()].eq_ignore_ascii_case(&prefix) {
+            Self::from_bech32(s)
+        } else {
+            Self::from_hex(s)
+        }
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_checksum_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_checksum_hex())
+    }
+}
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Address({})", self)
+    }
+}
+
+impl malachitebft_core_types::Address for Address {}
+
+impl Protobuf for Address {
+    type Proto = proto::Address;
+
+    fn from_proto(proto: Self::Proto) -> Result<Self, ProtoError> {
+        if proto.value.len() != Self::LENGTH {
+            return Err(ProtoError::Other(format!(
+                "Invalid address length: expected {}, got {}",
+                Self::LENGTH,
+                proto.value.len()
+            )));
+        }
+
+        let mut address = [0; Self::LENGTH];
+        address.copy_from_slice(&proto.value);
+        Ok(Self(address))
+    }
+
+    fn to_proto(&self) -> Result<Self::Proto, ProtoError> {
+        Ok(proto::Address {
+            value: self.0.to_vec().into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vectors from EIP-55
+    const EIP55_VECTORS: [&str; 4] = [
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+        "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+    ];
+
+    #[test]
+    fn checksum_hex_matches_eip55() {
+        for vector in EIP55_VECTORS {
+            let address = Address::from_str(&vector.to_lowercase()).unwrap();
+            assert_eq!(address.to_checksum_hex(), vector);
+            assert_eq!(Address::from_str(vector).unwrap(), address);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        // Flip the case of a single letter
+        let typo = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD";
+        assert_eq!(
+            Address::from_str(typo),
+            Err(AddressParseError::InvalidChecksum)
+        );
+    }
+
+    #[test]
+    fn accepts_legacy_uppercase_hex() {
+        let address = Address::new([0xAB; 20]);
+        let legacy = "AB".repeat(20);
+        assert_eq!(Address::from_str(&legacy).unwrap(), address);
+    }
+
+    #[test]
+    fn bech32_roundtrip() {
+        let address = Address::new([7; 20]);
+        let encoded = address.to_bech32();
+        assert!(encoded.starts_with("mikan1"));
+        assert_eq!(Address::from_str(&encoded).unwrap(), address);
+
+        let other_hrp = bech32::encode::<Bech32>(Hrp::parse_unchecked("other"), &[7; 20]).unwrap();
+        assert!(matches!(
+            Address::from_str(&other_hrp),
+            Err(AddressParseError::InvalidLength(_)) | Err(AddressParseError::InvalidHex(_))
+        ));
+        assert!(matches!(
+            Address::from_bech32(&other_hrp),
+            Err(AddressParseError::InvalidPrefix(_))
+        ));
+    }
+
+    #[test]
+    fn serde_uses_checksummed_hex() {
+        let address = Address::from_str(EIP55_VECTORS[0]).unwrap();
+        let json = serde_json::to_string(&address).unwrap();
+        assert_eq!(json, format!("\"{}\"", EIP55_VECTORS[0]));
+        assert_eq!(serde_json::from_str::<Address>(&json).unwrap(), address);
+    }
+}
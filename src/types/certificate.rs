@@ -0,0 +1,108 @@
+//! Serde representation of [`CommitCertificate`], which does not implement serde itself.
+//!
+//! Use with `#[serde(with = "crate::types::certificate")]`:
+//!
+//! ```json
+//! {
+//!   "height": 12,
+//!   "round": 0,
+//!   "value_id": "<hex>",
+//!   "signatures": [{ "address": "0x...", "signature": "<hex>" }]
+//! }
+//! ```
+
+use malachitebft_core_types::{AggregatedSignature, CommitCertificate, CommitSignature, Round};
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::address::Address;
+use super::context::TestContext;
+use super::height::Height;
+use super::serde_hex;
+use super::signing::Signature;
+use super::value::ValueId;
+
+#[derive(Serialize, Deserialize)]
+struct CertificateRepr {
+    height: Height,
+    round: u32,
+    value_id: ValueId,
+    signatures: Vec<CommitSignatureRepr>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CommitSignatureRepr {
+    address: Address,
+    #[serde(with = "serde_hex::signature")]
+    signature: Signature,
+}
+
+pub fn serialize<S: Serializer>(
+    certificate: &CommitCertificate<TestContext>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let round = certificate
+        .round
+        .as_u32()
+        .ok_or_else(|| S::Error::custom("certificate round should not be nil"))?;
+
+    CertificateRepr {
+        height: certificate.height,
+        round,
+        value_id: certificate.value_id,
+        signatures: certificate
+            .aggregated_signature
+            .signatures
+            .iter()
+            .map(|s| CommitSignatureRepr {
+                address: s.address,
+                signature: s.signature,
+            })
+            .collect(),
+    }
+    .serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<CommitCertificate<TestContext>, D::Error> {
+    let repr = CertificateRepr::deserialize(deserializer)?;
+
+    let signatures = repr
+        .signatures
+        .into_iter()
+        .map(|s| CommitSignature {
+            address: s.address,
+            signature: s.signature,
+        })
+        .collect();
+
+    Ok(CommitCertificate {
+        height: repr.height,
+        round: Round::new(repr.round),
+        value_id: repr.value_id,
+        aggregated_signature: AggregatedSignature { signatures },
+    })
+}
+
+/// Optional certificate, `null` when absent.
+pub mod option {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(transparent)]
+    struct Wrapper(#[serde(with = "super")] CommitCertificate<TestContext>);
+
+    pub fn serialize<S: Serializer>(
+        certificate: &Option<CommitCertificate<TestContext>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        certificate.clone().map(Wrapper).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<CommitCertificate<TestContext>>, D::Error> {
+        Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|w| w.0))
+    }
+}
@@ -1,3 +1,5 @@
+pub mod ssz;
+
 pub mod proto {
     use bytes::Bytes;
     use prost::Message;
@@ -10,11 +12,13 @@ pub mod proto {
         SignedProposal, SignedVote, Validity, VoteSet,
     };
     use malachitebft_proto::{Error as ProtoError, Protobuf};
-    use malachitebft_signing_ed25519::Signature;
+    use malachitebft_signing_ed25519::{PublicKey, Signature};
     use malachitebft_sync::{self as sync, PeerId};
 
-    use crate::malachite_types::proto;
-    use crate::malachite_types::{
+    use crate::block::Block;
+    use crate::header::Header;
+    use crate::types::proto;
+    use crate::types::{
         address::Address,
         context::TestContext,
         height::Height,
@@ -210,7 +214,8 @@ pub mod proto {
                 .ok_or_else(|| ProtoError::missing_field::<proto::Status>("peer_id"))?;
 
             Ok(sync::Status {
-                peer_id: PeerId::from_bytes(proto_peer_id.id.as_ref()).unwrap(),
+                peer_id: PeerId::from_bytes(proto_peer_id.id.as_ref())
+                    .map_err(|e| ProtoError::Other(format!("Invalid peer id: {e}")))?,
                 height: Height::new(proto.height),
                 history_min_height: Height::new(proto.earliest_height),
             })
@@ -527,4 +532,47 @@ pub mod proto {
             .map_err(|_| ProtoError::Other("Invalid signature length".to_string()))?;
         Ok(Signature::from_bytes(bytes))
     }
+
+    pub fn decode_hash(bytes: &[u8]) -> Result<[u8; 32], ProtoError> {
+        <[u8; 32]>::try_from(bytes).map_err(|_| {
+            ProtoError::Other(format!(
+                "Invalid hash length: expected 32, got {}",
+                bytes.len()
+            ))
+        })
+    }
+
+    pub fn decode_public_key(bytes: &[u8]) -> Result<PublicKey, ProtoError> {
+        let bytes = <[u8; 32]>::try_from(bytes).map_err(|_| {
+            ProtoError::Other(format!(
+                "Invalid public key length: expected 32, got {}",
+                bytes.len()
+            ))
+        })?;
+        Ok(PublicKey::from_bytes(bytes))
+    }
+
+    impl Codec<Header> for ProtobufCodec {
+        type Error = ProtoError;
+
+        fn decode(&self, bytes: Bytes) -> Result<Header, Self::Error> {
+            Protobuf::from_bytes(&bytes)
+        }
+
+        fn encode(&self, msg: &Header) -> Result<Bytes, Self::Error> {
+            Protobuf::to_bytes(msg)
+        }
+    }
+
+    impl Codec<Block> for ProtobufCodec {
+        type Error = ProtoError;
+
+        fn decode(&self, bytes: Bytes) -> Result<Block, Self::Error> {
+            Protobuf::from_bytes(&bytes)
+        }
+
+        fn encode(&self, msg: &Block) -> Result<Bytes, Self::Error> {
+            Protobuf::to_bytes(msg)
+        }
+    }
 }
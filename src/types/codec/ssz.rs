@@ -0,0 +1,251 @@
+//! SSZ encoding of block headers, for consumers using Ethereum tooling (light clients, on-chain
+//! verifiers).
+//!
+//! The header maps to the following SSZ container, all fields being fixed-size:
+//!
+//! ```text
+//! class Header(Container):
+//!     block_number: uint64
+//!     timestamp: uint64
+//!     block_hash: Bytes32
+//!     da_root: Bytes32
+//!     blob_count: uint32
+//!     parent_hash: Bytes32
+//!     tx_commitment: Bytes32
+//!     proposer_address: Bytes20
+//!     version: uint32
+//!     share_root: Bytes32
+//! ```
+//!
+//! `share_root` is only encoded from [`SHARES_VERSION`] on, so that the encoding of earlier
+//! headers is unchanged. Their `hash_tree_root` is unchanged too, the root of the zero chunk
+//! standing in for it being the padding of the container.
+
+use bytes::Bytes;
+use malachitebft_codec::Codec;
+use rs_merkle::{algorithms::Sha256, Hasher};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::header::Header;
+use crate::types::address::Address;
+use crate::verify::SHARES_VERSION;
+
+/// Size of an SSZ-encoded [`Header`] before [`SHARES_VERSION`].
+pub const HEADER_SSZ_LEN: usize = 8 + 8 + 32 + 32 + 4 + 32 + 32 + 20 + 4;
+
+/// Size of an SSZ-encoded [`Header`] from [`SHARES_VERSION`] on.
+pub const HEADER_SSZ_LEN_WITH_SHARES: usize = HEADER_SSZ_LEN + 32;
+
+fn header_ssz_len(version: u32) -> usize {
+    if version >= SHARES_VERSION {
+        HEADER_SSZ_LEN_WITH_SHARES
+    } else {
+        HEADER_SSZ_LEN
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SszError {
+    #[error("Invalid SSZ length: expected {expected}, got {actual}")]
+    InvalidLength { expected: usize, actual: usize },
+}
+
+/// Encoding used when serving headers to clients.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeaderEncoding {
+    #[default]
+    Protobuf,
+    Ssz,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SszCodec;
+
+impl Codec<Header> for SszCodec {
+    type Error = SszError;
+
+    fn decode(&self, bytes: Bytes) -> Result<Header, Self::Error> {
+        decode_header(&bytes)
+    }
+
+    fn encode(&self, msg: &Header) -> Result<Bytes, Self::Error> {
+        Ok(Bytes::from(encode_header(msg)))
+    }
+}
+
+pub fn encode_header(header: &Header) -> Vec<u8> {
+    let mut out = Vec::with_capacity(header_ssz_len(header.version));
+    out.extend_from_slice(&header.block_number.to_le_bytes());
+    out.extend_from_slice(&header.timestamp.to_le_bytes());
+    out.extend_from_slice(&header.block_hash);
+    out.extend_from_slice(&header.da_root);
+    out.extend_from_slice(&header.blob_count.to_le_bytes());
+    out.extend_from_slice(&header.parent_hash);
+    out.extend_from_slice(&header.tx_commitment);
+    out.extend_from_slice(&header.proposer_address.into_inner());
+    out.extend_from_slice(&header.version.to_le_bytes());
+    if header.version >= SHARES_VERSION {
+        out.extend_from_slice(&header.share_root);
+    }
+    out
+}
+
+pub fn decode_header(bytes: &[u8]) -> Result<Header, SszError> {
+    if bytes.len() != HEADER_SSZ_LEN && bytes.len() != HEADER_SSZ_LEN_WITH_SHARES {
+        return Err(SszError::InvalidLength {
+            expected: HEADER_SSZ_LEN,
+            actual: bytes.len(),
+        });
+    }
+
+    let mut reader = Reader(bytes);
+    let block_number = u64::from_le_bytes(reader.take());
+    let timestamp = u64::from_le_bytes(reader.take());
+    let block_hash = reader.take();
+    let da_root = reader.take();
+    let blob_count = u32::from_le_bytes(reader.take());
+    let parent_hash = reader.take();
+    let tx_commitment = reader.take();
+    let proposer_address = Address::new(reader.take());
+    let version = u32::from_le_bytes(reader.take());
+    if bytes.len() != header_ssz_len(version) {
+        return Err(SszError::InvalidLength {
+            expected: header_ssz_len(version),
+            actual: bytes.len(),
+        });
+    }
+    let share_root = if version >= SHARES_VERSION {
+        reader.take()
+    } else {
+        [0; 32]
+    };
+
+    Ok(Header {
+        block_number,
+        timestamp,
+        block_hash,
+        da_root,
+        blob_count,
+        parent_hash,
+        tx_commitment,
+        proposer_address,
+        version,
+        share_root,
+    })
+}
+
+/// SSZ `hash_tree_root` of the header, using SHA-256 as mandated by the spec.
+pub fn hash_tree_root(header: &Header) -> [u8; 32] {
+    let field_roots = [
+        uint64_root(header.block_number),
+        uint64_root(header.timestamp),
+        header.block_hash,
+        header.da_root,
+        bytes_root(&header.blob_count.to_le_bytes()),
+        header.parent_hash,
+        header.tx_commitment,
+        bytes_root(&header.proposer_address.into_inner()),
+        bytes_root(&header.version.to_le_bytes()),
+        header.share_root,
+    ];
+
+    merkleize(&field_roots)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let (head, tail) = self.0.split_at(N);
+        self.0 = tail;
+        head.try_into().expect("length checked by the caller")
+    }
+}
+
+fn uint64_root(value: u64) -> [u8; 32] {
+    bytes_root(&value.to_le_bytes())
+}
+
+/// Root of a basic value fitting in a single chunk: the value right-padded with zeroes.
+fn bytes_root(bytes: &[u8]) -> [u8; 32] {
+    let mut chunk = [0; 32];
+    chunk[..bytes.len()].copy_from_slice(bytes);
+    chunk
+}
+
+/// Merkleize chunks, padding with zero chunks up to the next power of two.
+fn merkleize(chunks: &[[u8; 32]]) -> [u8; 32] {
+    let mut layer = chunks.to_vec();
+    layer.resize(chunks.len().next_power_of_two(), [0; 32]);
+
+    while layer.len() > 1 {
+        layer = layer
+            .chunks(2)
+            .map(|pair| Sha256::concat_and_hash(&pair[0], Some(&pair[1])))
+            .collect();
+    }
+
+    layer[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_header() -> Header {
+        Header {
+            block_number: 42,
+            timestamp: 1_700_000_000,
+            block_hash: [0x11; 32],
+            da_root: [0x22; 32],
+            blob_count: 4,
+            parent_hash: [0x33; 32],
+            tx_commitment: [0x44; 32],
+            proposer_address: Address::new([0x55; 20]),
+            version: 1,
+            share_root: [0; 32],
+        }
+    }
+
+    #[test]
+    fn ssz_roundtrip() {
+        let header = test_header();
+        let bytes = SszCodec.encode(&header).unwrap();
+        assert_eq!(bytes.len(), HEADER_SSZ_LEN);
+
+        let decoded = SszCodec.decode(bytes).unwrap();
+        assert_eq!(encode_header(&decoded), encode_header(&header));
+    }
+
+    #[test]
+    fn ssz_roundtrip_with_shares() {
+        let header = Header {
+            version: SHARES_VERSION,
+            share_root: [0x66; 32],
+            ..test_header()
+        };
+        let bytes = SszCodec.encode(&header).unwrap();
+        assert_eq!(bytes.len(), HEADER_SSZ_LEN_WITH_SHARES);
+
+        let decoded = SszCodec.decode(bytes).unwrap();
+        assert_eq!(decoded.share_root, header.share_root);
+        assert_ne!(hash_tree_root(&header), hash_tree_root(&test_header()));
+    }
+
+    #[test]
+    fn ssz_rejects_wrong_length() {
+        assert!(decode_header(&[0; HEADER_SSZ_LEN - 1]).is_err());
+        // A header before shares with a share root
+        assert!(decode_header(&[0; HEADER_SSZ_LEN_WITH_SHARES]).is_err());
+    }
+
+    #[test]
+    fn ssz_hash_tree_root_vector() {
+        let expected =
+            hex::decode("79bbd1137dd70e63c66c1c35a2be10dc8401300284d238058c08ecbc9fad1d17")
+                .unwrap();
+        assert_eq!(hash_tree_root(&test_header()).to_vec(), expected);
+    }
+}
@@ -0,0 +1,96 @@
+use super::address::Address;
+use super::height::Height;
+use super::sign_bytes::DEFAULT_CHAIN_ID;
+use super::validator_set::ValidatorSet;
+use crate::block::Block;
+use crate::consensus_params::ConsensusParams;
+use crate::gas::GasSchedule;
+use crate::governance::GovernanceConfig;
+use crate::liveness::LivenessConfig;
+use crate::transactions::DEFAULT_MAX_BLOBS_PER_TX;
+use crate::upgrades::ProtocolSchedule;
+use serde::{Deserialize, Serialize};
+
+/// Height of the genesis block, the parent of the first block decided by consensus.
+pub const GENESIS_HEIGHT: Height = Height::new(0);
+
+/// Genesis timestamp used when the genesis file does not specify one.
+pub const DEFAULT_GENESIS_TIME: u64 = 69420;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Genesis {
+    /// Identifier of the chain, bound into every signature
+    #[serde(default = "default_chain_id")]
+    pub chain_id: String,
+    /// Timestamp of the genesis block, in seconds since the Unix epoch
+    #[serde(default = "default_genesis_time")]
+    pub genesis_time: u64,
+    pub validator_set: ValidatorSet,
+    /// Gas costs and block gas limit, see [`crate::gas`]
+    #[serde(default)]
+    pub gas: GasSchedule,
+    /// Most blobs a transaction can carry, up to [`crate::transactions::MAX_BLOBS_PER_TX`]
+    #[serde(default = "default_max_blobs_per_tx")]
+    pub max_blobs_per_tx: usize,
+    /// Protocol version of the first blocks and scheduled upgrades, see [`crate::upgrades`]
+    #[serde(flatten)]
+    pub protocol: ProtocolSchedule,
+    /// Limits of block validation, see [`crate::consensus_params`]
+    #[serde(default)]
+    pub consensus: ConsensusParams,
+    /// Quorum of validator votes and initial parameters they can change, see
+    /// [`crate::governance`]
+    #[serde(default)]
+    pub governance: GovernanceConfig,
+    /// Window and threshold of missed blocks jailing validators, see [`crate::liveness`]
+    #[serde(default)]
+    pub liveness: LivenessConfig,
+}
+
+impl Genesis {
+    /// The canonical genesis block: empty, with a zero parent hash and the genesis timestamp, so
+    /// that every node started from the same genesis file agrees on the parent of height 1.
+    pub fn block(&self) -> Block {
+        Block::new(
+            GENESIS_HEIGHT.as_u64(),
+            self.genesis_time,
+            [0; 32],
+            Address::default(),
+            vec![],
+        )
+        .with_version(self.protocol.version_at(GENESIS_HEIGHT.as_u64()))
+    }
+}
+
+fn default_chain_id() -> String {
+    DEFAULT_CHAIN_ID.to_string()
+}
+
+fn default_genesis_time() -> u64 {
+    DEFAULT_GENESIS_TIME
+}
+
+fn default_max_blobs_per_tx() -> usize {
+    DEFAULT_MAX_BLOBS_PER_TX
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn genesis_block_is_derived_from_genesis_file() {
+        let genesis: Genesis =
+            serde_json::from_str(r#"{"validator_set":{"validators":[]}}"#).unwrap();
+        assert_eq!(genesis.block().hash(), Block::genesis().hash());
+
+        let later = Genesis {
+            genesis_time: DEFAULT_GENESIS_TIME + 1,
+            ..genesis
+        };
+        let block = later.block();
+        assert_eq!(block.header().block_number, 0);
+        assert_eq!(block.parent_hash(), [0; 32]);
+        assert_ne!(block.hash(), Block::genesis().hash());
+    }
+}
@@ -1,11 +1,15 @@
 pub mod address;
+pub mod certificate;
 pub mod codec;
 pub mod context;
 pub mod genesis;
 pub mod height;
 pub mod proposal;
 pub mod proposal_part;
+pub mod proposer_selector;
 pub mod proto;
+pub mod serde_hex;
+pub mod sign_bytes;
 pub mod signing;
 pub mod validator_set;
 pub mod value;
@@ -11,6 +11,7 @@ use malachitebft_signing_ed25519::Signature;
 use super::address::Address;
 use super::context::TestContext;
 use super::height::Height;
+use crate::compression::Compression;
 
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ProposalData {
@@ -89,14 +90,16 @@ pub struct ProposalInit {
     #[serde(with = "RoundDef")]
     pub round: Round,
     pub proposer: Address,
+    pub compression: Compression,
 }
 
 impl ProposalInit {
-    pub fn new(height: Height, round: Round, proposer: Address) -> Self {
+    pub fn new(height: Height, round: Round, proposer: Address, compression: Compression) -> Self {
         Self {
             height,
             round,
             proposer,
+            compression,
         }
     }
 }
@@ -122,6 +125,22 @@ impl malachitebft_core_types::ProposalPart<TestContext> for ProposalPart {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for ProposalPart {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(match u.int_in_range(0..=2)? {
+            0 => Self::Init(ProposalInit::new(
+                Height::new(u.arbitrary()?),
+                crate::fuzz::arbitrary_round(u)?,
+                Address::new(u.arbitrary()?),
+                u.arbitrary()?,
+            )),
+            1 => Self::Data(ProposalData::new(Bytes::from(u.arbitrary::<Vec<u8>>()?))),
+            _ => Self::Fin(ProposalFin::new(crate::fuzz::arbitrary_signature(u)?)),
+        })
+    }
+}
+
 impl Protobuf for ProposalPart {
     type Proto = super::proto::ProposalPart;
 
@@ -140,6 +159,16 @@ impl Protobuf for ProposalPart {
                     .proposer
                     .ok_or_else(|| ProtoError::missing_field::<Self::Proto>("proposer"))
                     .and_then(Address::from_proto)?,
+                compression: match super::proto::Compression::try_from(init.compression) {
+                    Ok(super::proto::Compression::None) => Compression::None,
+                    Ok(super::proto::Compression::Zstd) => Compression::Zstd,
+                    Err(_) => {
+                        return Err(ProtoError::Other(format!(
+                            "Unknown compression: {}",
+                            init.compression
+                        )))
+                    }
+                },
             })),
             Part::Data(data) => Ok(Self::Data(ProposalData::new(data.bytes))),
             Part::Fin(fin) => Ok(Self::Fin(ProposalFin {
@@ -161,6 +190,11 @@ impl Protobuf for ProposalPart {
                     height: init.height.as_u64(),
                     round: init.round.as_u32().unwrap(),
                     proposer: Some(init.proposer.to_proto()?),
+                    compression: match init.compression {
+                        Compression::None => proto::Compression::None,
+                        Compression::Zstd => proto::Compression::Zstd,
+                    }
+                    .into(),
                 })),
             }),
             Self::Data(data) => Ok(Self::Proto {
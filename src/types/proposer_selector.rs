@@ -1,6 +1,6 @@
 use malachitebft_core_types::{Context, Round};
 
-use crate::{Address, Height, TestContext, ValidatorSet};
+use super::{address::Address, context::TestContext, height::Height, validator_set::ValidatorSet};
 
 /// Defines how to select a proposer amongst a validator set for a given round.
 pub trait ProposerSelector<Ctx>
@@ -39,8 +39,20 @@ impl ProposerSelector<TestContext> for RotateProposer {
         let height = height.as_u64() as usize;
         let round = round.as_i64() as usize;
 
-        let proposer_index = (height - 1 + round) % validator_set.validators.len();
-        validator_set.validators[proposer_index].address
+        // Jailed validators keep their place in the set with no voting power, see
+        // `crate::liveness`
+        let candidates: Vec<_> = validator_set
+            .validators
+            .iter()
+            .filter(|validator| validator.voting_power > 0)
+            .collect();
+        if candidates.is_empty() {
+            let proposer_index = (height - 1 + round) % validator_set.validators.len();
+            return validator_set.validators[proposer_index].address;
+        }
+
+        let proposer_index = (height - 1 + round) % candidates.len();
+        candidates[proposer_index].address
     }
 }
 
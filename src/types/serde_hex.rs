@@ -0,0 +1,70 @@
+//! Serde helpers encoding byte fields as lowercase hex strings, for use with
+//! `#[serde(with = "...")]`.
+//!
+//! Fixed-size hashes can use `hex::serde` directly, these cover the remaining shapes.
+
+use bytes::Bytes;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::signing::Signature;
+
+/// Variable-length bytes as a single hex string
+pub mod bytes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(bytes: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(s).map(Bytes::from).map_err(D::Error::custom)
+    }
+}
+
+/// List of 32-byte hashes as an array of hex strings
+pub mod hashes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(hashes: &[[u8; 32]], serializer: S) -> Result<S::Ok, S::Error> {
+        hashes
+            .iter()
+            .map(hex::encode)
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<[u8; 32]>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|s| {
+                let mut hash = [0; 32];
+                hex::decode_to_slice(s, &mut hash).map_err(D::Error::custom)?;
+                Ok(hash)
+            })
+            .collect()
+    }
+}
+
+/// Ed25519 signature as a hex string
+pub mod signature {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        signature: &Signature,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(signature.to_bytes()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Signature, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let mut bytes = [0; 64];
+        hex::decode_to_slice(s, &mut bytes)
+            .map_err(|e| D::Error::custom(format!("Invalid signature hex: {e}")))?;
+        Ok(Signature::from_bytes(bytes))
+    }
+}
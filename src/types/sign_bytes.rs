@@ -0,0 +1,585 @@
+//! Canonical byte layout of every payload signed by a validator.
+//!
+//! A payload is the concatenation of:
+//!
+//! 1. the domain tag of the message kind (e.g. `mikan/vote/v2`), length-prefixed,
+//! 2. the chain id, length-prefixed,
+//! 3. the message fields, in the order documented on each function.
+//!
+//! Integers are written as fixed-width big-endian values, variable-length fields are prefixed
+//! with their length as a big-endian `u32`. Nil rounds and values are written as a `0` flag byte,
+//! present ones as a `1` flag byte followed by the value.
+//!
+//! Changing any of this is a breaking change for signatures: bump the version in the domain tag.
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use sha3::{Digest, Keccak256};
+
+use crate::compression::Compression;
+use crate::governance::ParamChange;
+use crate::namespaces::NamespacePolicy;
+
+use malachitebft_core_types::{NilOrVal, Round, VoteType};
+
+use super::address::Address;
+use super::height::Height;
+use super::proposal::Proposal;
+use super::signing::PublicKey;
+use super::value::ValueId;
+use super::vote::Vote;
+
+/// Chain id used when the genesis file does not specify one.
+pub const DEFAULT_CHAIN_ID: &str = "mikan-devnet";
+
+/// The kind of message being signed. Each kind has its own domain tag so that a signature over
+/// one can never be replayed as a signature over another.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Domain {
+    Vote,
+    Proposal,
+    ProposalParts,
+    VoteExtension,
+    Transaction,
+}
+
+impl Domain {
+    pub const fn tag(&self) -> &'static [u8] {
+        match self {
+            Self::Vote => crate::verify::VOTE_DOMAIN,
+            Self::Proposal => b"mikan/proposal/v2",
+            Self::ProposalParts => b"mikan/proposal-parts/v3",
+            Self::VoteExtension => b"mikan/vote-extension/v1",
+            Self::Transaction => b"mikan/transaction/v3",
+        }
+    }
+}
+
+/// Writer for canonical sign-bytes, generic over the sink so that large payloads (proposal data)
+/// can be hashed incrementally instead of being buffered.
+pub trait SignBytesWriter {
+    fn write(&mut self, bytes: &[u8]);
+
+    fn write_header(&mut self, domain: Domain, chain_id: &str) {
+        self.write_bytes(domain.tag());
+        self.write_bytes(chain_id.as_bytes());
+    }
+
+    fn write_u8(&mut self, value: u8) {
+        self.write(&[value]);
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_be_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_be_bytes());
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.write(bytes);
+    }
+
+    fn write_height(&mut self, height: Height) {
+        self.write_u64(height.as_u64());
+    }
+
+    fn write_round(&mut self, round: Round) {
+        match round.as_u32() {
+            None => self.write_u8(0),
+            Some(round) => {
+                self.write_u8(1);
+                self.write_u32(round);
+            }
+        }
+    }
+
+    fn write_value_id(&mut self, value_id: &ValueId) {
+        self.write(value_id.as_bytes());
+    }
+
+    fn write_address(&mut self, address: &Address) {
+        self.write_bytes(&address.into_inner());
+    }
+}
+
+impl SignBytesWriter for Vec<u8> {
+    fn write(&mut self, bytes: &[u8]) {
+        self.extend_from_slice(bytes);
+    }
+}
+
+impl SignBytesWriter for Keccak256 {
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+}
+
+/// `header || vote_type (u8) || height || round || value || validator_address`
+pub fn vote_sign_bytes(chain_id: &str, vote: &Vote) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.write_header(Domain::Vote, chain_id);
+    out.write_u8(match vote.typ {
+        VoteType::Prevote => 0,
+        VoteType::Precommit => 1,
+    });
+    out.write_height(vote.height);
+    out.write_round(vote.round);
+    match &vote.value {
+        NilOrVal::Nil => out.write_u8(0),
+        NilOrVal::Val(value_id) => {
+            out.write_u8(1);
+            out.write_value_id(value_id);
+        }
+    }
+    out.write_address(&vote.validator_address);
+    out
+}
+
+/// `header || height || round || pol_round || value_id || validator_address`
+///
+/// The proposal commits to the value through its id, the value payload itself is streamed and
+/// signed separately as proposal parts.
+pub fn proposal_sign_bytes(chain_id: &str, proposal: &Proposal) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.write_header(Domain::Proposal, chain_id);
+    out.write_height(proposal.height);
+    out.write_round(proposal.round);
+    out.write_round(proposal.pol_round);
+    out.write_value_id(&proposal.value.id());
+    out.write_address(&proposal.validator_address);
+    out
+}
+
+/// `header || extension`
+pub fn vote_extension_sign_bytes(chain_id: &str, extension: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.write_header(Domain::VoteExtension, chain_id);
+    out.write_bytes(extension);
+    out
+}
+
+/// `header || nonce || from || to || value || gas_price || gas_limit || blob_commitment_0 || ... || registration`
+///
+/// Blobs are bound through their commitments rather than their data, which keeps the payload small,
+/// the commitments prefixed with their count.
+/// The namespace registration is `fee_multiplier || allowed_poster_0 || ...`, the posters prefixed
+/// with their count.
+///
+/// Governance votes append `1 || param_change`, see [`param_change_bytes`], and unjails append
+/// `2`. Nothing is appended for other transactions, which keep the payload they had before
+/// governance.
+#[allow(clippy::too_many_arguments)]
+pub fn transaction_sign_bytes(
+    chain_id: &str,
+    nonce: u64,
+    from: &PublicKey,
+    to: &PublicKey,
+    value: u64,
+    gas_price: u64,
+    gas_limit: u64,
+    blob_commitments: &[[u8; 32]],
+    registration: Option<&NamespacePolicy>,
+    governance: Option<&ParamChange>,
+    unjail: bool,
+) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.write_header(Domain::Transaction, chain_id);
+    out.write_u64(nonce);
+    out.write_bytes(from.as_bytes());
+    out.write_bytes(to.as_bytes());
+    out.write_u64(value);
+    out.write_u64(gas_price);
+    out.write_u64(gas_limit);
+    out.write_u32(blob_commitments.len() as u32);
+    for commitment in blob_commitments {
+        out.write(commitment);
+    }
+    match registration {
+        None => out.write_u8(0),
+        Some(policy) => {
+            out.write_u8(1);
+            out.write_u64(policy.fee_multiplier);
+            out.write_u32(policy.allowed_posters.len() as u32);
+            for poster in &policy.allowed_posters {
+                out.write_bytes(poster.as_bytes());
+            }
+        }
+    }
+    if let Some(change) = governance {
+        out.write_u8(1);
+        write_param_change(&mut out, change);
+    }
+    if unjail {
+        out.write_u8(2);
+    }
+    out
+}
+
+/// `height || max_block_bytes || gas || retain_heights`, each parameter being optional and the
+/// gas schedule `per_transfer || per_blob_byte || per_execution_step || block_gas_limit`.
+pub fn param_change_bytes(change: &ParamChange) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_param_change(&mut out, change);
+    out
+}
+
+fn write_param_change(out: &mut Vec<u8>, change: &ParamChange) {
+    out.write_u64(change.height);
+    write_optional_u64(out, change.max_block_bytes);
+    match &change.gas {
+        None => out.write_u8(0),
+        Some(gas) => {
+            out.write_u8(1);
+            out.write_u64(gas.per_transfer);
+            out.write_u64(gas.per_blob_byte);
+            out.write_u64(gas.per_execution_step);
+            out.write_u64(gas.block_gas_limit);
+        }
+    }
+    write_optional_u64(out, change.retain_heights);
+}
+
+fn write_optional_u64(out: &mut Vec<u8>, value: Option<u64>) {
+    match value {
+        None => out.write_u8(0),
+        Some(value) => {
+            out.write_u8(1);
+            out.write_u64(value);
+        }
+    }
+}
+
+/// Incremental hasher for a streamed proposal.
+///
+/// The signed digest is
+/// `keccak256(header || height || round || proposer || compression (u8) || keccak256(chunk_0) || ... )`
+/// so that moving bytes between chunks changes the digest, and so that the proposer can hash the
+/// chunks in parallel, see [`Self::update_all`]. Chunks are hashed as sent, i.e. compressed.
+pub struct ProposalPartsHasher {
+    hasher: Keccak256,
+}
+
+impl ProposalPartsHasher {
+    pub fn new(
+        chain_id: &str,
+        height: Height,
+        round: Round,
+        proposer: &Address,
+        compression: Compression,
+    ) -> Self {
+        let mut hasher = Keccak256::new();
+        hasher.write_header(Domain::ProposalParts, chain_id);
+        hasher.write_height(height);
+        hasher.write_round(round);
+        hasher.write_address(proposer);
+        hasher.write_u8(compression_tag(compression));
+        Self { hasher }
+    }
+
+    pub fn update(&mut self, chunk: &[u8]) {
+        self.hasher.write(&chunk_digest(chunk));
+    }
+
+    /// Same as [`Self::update`] for each chunk in order, hashing the chunks in parallel.
+    pub fn update_all<C: AsRef<[u8]> + Sync>(&mut self, chunks: &[C]) {
+        let digests: Vec<[u8; 32]> = chunks
+            .par_iter()
+            .map(|chunk| chunk_digest(chunk.as_ref()))
+            .collect();
+        for digest in digests {
+            self.hasher.write(&digest);
+        }
+    }
+
+    pub fn finalize(self) -> [u8; 32] {
+        self.hasher.finalize().into()
+    }
+}
+
+fn chunk_digest(chunk: &[u8]) -> [u8; 32] {
+    Keccak256::digest(chunk).into()
+}
+
+fn compression_tag(compression: Compression) -> u8 {
+    match compression {
+        Compression::None => 0,
+        Compression::Zstd => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::value::Value;
+    use bytes::Bytes;
+
+    const CHAIN_ID: &str = "mikan-test";
+
+    // These vectors pin the current layouts. If one of them changes, signatures produced by older
+    // nodes no longer verify: bump the domain tag version instead of updating the vector.
+
+    #[test]
+    fn vote_sign_bytes_v2_vector() {
+        let vote = Vote::new_precommit(
+            Height::new(5),
+            Round::new(1),
+            NilOrVal::Val(ValueId::new([0x07; 32])),
+            Address::new([0xAA; 20]),
+        );
+
+        let expected = hex::decode(concat!(
+            "0000000d",
+            "6d696b616e2f766f74652f7632", // "mikan/vote/v2"
+            "0000000a",
+            "6d696b616e2d74657374", // "mikan-test"
+            "01",                   // precommit
+            "0000000000000005",     // height
+            "01",
+            "00000001", // round
+            "01",
+            "0707070707070707070707070707070707070707070707070707070707070707", // value id
+            "00000014",
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        ))
+        .unwrap();
+
+        assert_eq!(vote_sign_bytes(CHAIN_ID, &vote), expected);
+    }
+
+    #[test]
+    fn nil_vote_sign_bytes_v2_vector() {
+        let vote = Vote::new_prevote(
+            Height::new(1),
+            Round::new(0),
+            NilOrVal::Nil,
+            Address::new([0x01; 20]),
+        );
+
+        let expected = hex::decode(concat!(
+            "0000000d",
+            "6d696b616e2f766f74652f7632",
+            "0000000a",
+            "6d696b616e2d74657374",
+            "00",
+            "0000000000000001",
+            "01",
+            "00000000",
+            "00",
+            "00000014",
+            "0101010101010101010101010101010101010101",
+        ))
+        .unwrap();
+
+        assert_eq!(vote_sign_bytes(CHAIN_ID, &vote), expected);
+    }
+
+    #[test]
+    fn vote_extension_sign_bytes_v1_vector() {
+        let expected = hex::decode(concat!(
+            "00000017",
+            "6d696b616e2f766f74652d657874656e73696f6e2f7631",
+            "0000000a",
+            "6d696b616e2d74657374",
+            "00000003",
+            "010203",
+        ))
+        .unwrap();
+
+        assert_eq!(vote_extension_sign_bytes(CHAIN_ID, &[1, 2, 3]), expected);
+    }
+
+    #[test]
+    fn transaction_sign_bytes_v3_vector() {
+        let key = PublicKey::from_bytes([0x03; 32]);
+        let sign_bytes = transaction_sign_bytes(
+            CHAIN_ID,
+            9,
+            &key,
+            &key,
+            100,
+            2,
+            50_000,
+            &[[0x04; 32], [0x05; 32]],
+            None,
+            None,
+            false,
+        );
+
+        let expected = hex::decode(concat!(
+            "00000014",
+            "6d696b616e2f7472616e73616374696f6e2f7633", // "mikan/transaction/v3"
+            "0000000a",
+            "6d696b616e2d74657374",
+            "0000000000000009", // nonce
+            "00000020",
+            "0303030303030303030303030303030303030303030303030303030303030303", // from
+            "00000020",
+            "0303030303030303030303030303030303030303030303030303030303030303", // to
+            "0000000000000064",                                                 // value
+            "0000000000000002",                                                 // gas price
+            "000000000000c350",                                                 // gas limit
+            "00000002",
+            "0404040404040404040404040404040404040404040404040404040404040404",
+            "0505050505050505050505050505050505050505050505050505050505050505",
+            "00", // no registration
+        ))
+        .unwrap();
+
+        assert_eq!(sign_bytes, expected);
+
+        let policy = NamespacePolicy {
+            fee_multiplier: 7,
+            allowed_posters: vec![key],
+        };
+        let registration = transaction_sign_bytes(
+            CHAIN_ID,
+            9,
+            &key,
+            &key,
+            100,
+            2,
+            50_000,
+            &[],
+            Some(&policy),
+            None,
+            false,
+        );
+        assert!(registration.ends_with(
+            &hex::decode(concat!(
+                "01",
+                "0000000000000007", // fee multiplier
+                "00000001",
+                "00000020",
+                "0303030303030303030303030303030303030303030303030303030303030303",
+            ))
+            .unwrap()
+        ));
+
+        let change = ParamChange {
+            height: 10,
+            max_block_bytes: Some(1024),
+            gas: None,
+            retain_heights: None,
+        };
+        let vote = transaction_sign_bytes(
+            CHAIN_ID,
+            9,
+            &key,
+            &key,
+            0,
+            2,
+            50_000,
+            &[],
+            None,
+            Some(&change),
+            false,
+        );
+        assert!(vote.ends_with(
+            &hex::decode(concat!(
+                "00", // no registration
+                "01",
+                "000000000000000a", // height
+                "01",
+                "0000000000000400", // max block bytes
+                "00",               // no gas schedule
+                "00",               // no retention
+            ))
+            .unwrap()
+        ));
+
+        let unjail =
+            transaction_sign_bytes(CHAIN_ID, 9, &key, &key, 0, 2, 50_000, &[], None, None, true);
+        assert!(unjail.ends_with(&hex::decode("0002").unwrap()));
+    }
+
+    #[test]
+    fn sign_bytes_are_domain_and_chain_separated() {
+        let vote = Vote::new_prevote(
+            Height::new(1),
+            Round::new(0),
+            NilOrVal::Nil,
+            Address::new([0x01; 20]),
+        );
+        assert_ne!(
+            vote_sign_bytes(CHAIN_ID, &vote),
+            vote_sign_bytes("other-chain", &vote)
+        );
+
+        let proposal = Proposal::new(
+            Height::new(1),
+            Round::new(0),
+            Value::new(Bytes::from_static(b"block")),
+            Round::Nil,
+            Address::new([0x01; 20]),
+        );
+        assert!(proposal_sign_bytes(CHAIN_ID, &proposal)
+            .starts_with(&hex::decode("000000116d696b616e2f70726f706f73616c2f7632").unwrap()));
+    }
+
+    #[test]
+    fn proposal_parts_hash_matches_buffered_layout() {
+        let proposer = Address::new([0x02; 20]);
+        let chunks: [&[u8]; 2] = [b"hello", b"world"];
+
+        let mut hasher = ProposalPartsHasher::new(
+            CHAIN_ID,
+            Height::new(3),
+            Round::new(0),
+            &proposer,
+            Compression::Zstd,
+        );
+        for chunk in chunks {
+            hasher.update(chunk);
+        }
+
+        let mut buffered = Vec::new();
+        buffered.write_header(Domain::ProposalParts, CHAIN_ID);
+        buffered.write_height(Height::new(3));
+        buffered.write_round(Round::new(0));
+        buffered.write_address(&proposer);
+        buffered.write_u8(1);
+        for chunk in chunks {
+            buffered.write(&Keccak256::digest(chunk));
+        }
+
+        let expected: [u8; 32] = Keccak256::digest(&buffered).into();
+        assert_eq!(hasher.finalize(), expected);
+
+        // Hashing the chunks in parallel gives the same digest
+        let mut parallel = ProposalPartsHasher::new(
+            CHAIN_ID,
+            Height::new(3),
+            Round::new(0),
+            &proposer,
+            Compression::Zstd,
+        );
+        parallel.update_all(&chunks);
+        assert_eq!(parallel.finalize(), expected);
+
+        // Re-chunking the same bytes must not produce the same digest
+        let mut rechunked = ProposalPartsHasher::new(
+            CHAIN_ID,
+            Height::new(3),
+            Round::new(0),
+            &proposer,
+            Compression::Zstd,
+        );
+        rechunked.update(b"helloworld");
+        assert_ne!(rechunked.finalize(), expected);
+
+        // Neither must flipping the compression flag
+        let mut uncompressed = ProposalPartsHasher::new(
+            CHAIN_ID,
+            Height::new(3),
+            Round::new(0),
+            &proposer,
+            Compression::None,
+        );
+        for chunk in chunks {
+            uncompressed.update(chunk);
+        }
+        assert_ne!(uncompressed.finalize(), expected);
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashSet;
+
+use bytes::Bytes;
+use malachitebft_core_types::{
+    CertificateError, CommitCertificate, CommitSignature, NilOrVal, SignedExtension,
+    SignedProposal, SignedProposalPart, SignedVote, SigningProvider, VotingPower,
+};
+
+use super::{
+    context::TestContext,
+    proposal::Proposal,
+    proposal_part::ProposalPart,
+    sign_bytes::{proposal_sign_bytes, vote_extension_sign_bytes, vote_sign_bytes},
+    validator_set::{Validator, ValidatorSet},
+    vote::Vote,
+};
+use crate::error::CommitCertificateError;
+
+pub use malachitebft_signing_ed25519::*;
+
+pub trait Hashable {
+    type Output;
+    fn hash(&self) -> Self::Output;
+}
+
+impl Hashable for PublicKey {
+    type Output = [u8; 32];
+
+    fn hash(&self) -> [u8; 32] {
+        use sha3::{Digest, Keccak256};
+        let mut hasher = Keccak256::new();
+        hasher.update(self.as_bytes());
+        hasher.finalize().into()
+    }
+}
+
+#[derive(Debug)]
+pub struct Ed25519Provider {
+    private_key: PrivateKey,
+    chain_id: String,
+}
+
+impl Ed25519Provider {
+    pub fn new(private_key: PrivateKey, chain_id: impl Into<String>) -> Self {
+        Self {
+            private_key,
+            chain_id: chain_id.into(),
+        }
+    }
+
+    pub fn private_key(&self) -> &PrivateKey {
+        &self.private_key
+    }
+
+    pub fn chain_id(&self) -> &str {
+        &self.chain_id
+    }
+
+    pub fn sign(&self, data: &[u8]) -> Signature {
+        self.private_key.sign(data)
+    }
+
+    pub fn verify(&self, data: &[u8], signature: &Signature, public_key: &PublicKey) -> bool {
+        public_key.verify(data, signature).is_ok()
+    }
+}
+
+impl Ed25519Provider {
+    /// Checks that `certificate` carries valid precommits from members of `validator_set` with
+    /// more than 2/3 of its voting power.
+    pub fn verify_certificate(
+        &self,
+        certificate: &CommitCertificate<TestContext>,
+        validator_set: &ValidatorSet,
+    ) -> Result<(), CommitCertificateError> {
+        verify_certificate(&self.chain_id, certificate, validator_set)
+    }
+}
+
+/// [`Ed25519Provider::verify_certificate`] without a signing key, for checks that only know the
+/// chain, like the validation of the last commit of a block.
+pub fn verify_certificate(
+    chain_id: &str,
+    certificate: &CommitCertificate<TestContext>,
+    validator_set: &ValidatorSet,
+) -> Result<(), CommitCertificateError> {
+    let mut signers = HashSet::new();
+    let mut signed: VotingPower = 0;
+
+    for commit_sig in &certificate.aggregated_signature.signatures {
+        let address = commit_sig.address;
+        let validator = validator_set
+            .get_by_address(&address)
+            .ok_or(CommitCertificateError::UnknownValidator(address))?;
+
+        if !signers.insert(address) {
+            return Err(CommitCertificateError::DuplicateSignature(address));
+        }
+
+        signed += verify_commit_signature(chain_id, certificate, commit_sig, validator)
+            .map_err(|_| CommitCertificateError::InvalidSignature(address))?;
+    }
+
+    let total = validator_set.total_voting_power();
+    if 3 * signed <= 2 * total {
+        return Err(CommitCertificateError::NotEnoughVotingPower { signed, total });
+    }
+
+    Ok(())
+}
+
+fn verify_commit_signature(
+    chain_id: &str,
+    certificate: &CommitCertificate<TestContext>,
+    commit_sig: &CommitSignature<TestContext>,
+    validator: &Validator,
+) -> Result<VotingPower, CertificateError<TestContext>> {
+    use malachitebft_core_types::Validator;
+
+    // Reconstruct the vote that was signed
+    let vote = Vote::new_precommit(
+        certificate.height,
+        certificate.round,
+        NilOrVal::Val(certificate.value_id),
+        *validator.address(),
+    );
+
+    // Verify signature
+    if validator
+        .public_key()
+        .verify(&vote_sign_bytes(chain_id, &vote), &commit_sig.signature)
+        .is_err()
+    {
+        return Err(CertificateError::InvalidSignature(commit_sig.clone()));
+    }
+
+    Ok(validator.voting_power())
+}
+
+impl SigningProvider<TestContext> for Ed25519Provider {
+    fn sign_vote(&self, vote: Vote) -> SignedVote<TestContext> {
+        let signature = self.sign(&vote_sign_bytes(&self.chain_id, &vote));
+        SignedVote::new(vote, signature)
+    }
+
+    fn verify_signed_vote(
+        &self,
+        vote: &Vote,
+        signature: &Signature,
+        public_key: &PublicKey,
+    ) -> bool {
+        public_key
+            .verify(&vote_sign_bytes(&self.chain_id, vote), signature)
+            .is_ok()
+    }
+
+    fn sign_proposal(&self, proposal: Proposal) -> SignedProposal<TestContext> {
+        let signature = self.sign(&proposal_sign_bytes(&self.chain_id, &proposal));
+        SignedProposal::new(proposal, signature)
+    }
+
+    fn verify_signed_proposal(
+        &self,
+        proposal: &Proposal,
+        signature: &Signature,
+        public_key: &PublicKey,
+    ) -> bool {
+        public_key
+            .verify(&proposal_sign_bytes(&self.chain_id, proposal), signature)
+            .is_ok()
+    }
+
+    fn sign_proposal_part(&self, proposal_part: ProposalPart) -> SignedProposalPart<TestContext> {
+        let signature = self.private_key.sign(&proposal_part.to_sign_bytes());
+        SignedProposalPart::new(proposal_part, signature)
+    }
+
+    fn verify_signed_proposal_part(
+        &self,
+        proposal_part: &ProposalPart,
+        signature: &Signature,
+        public_key: &PublicKey,
+    ) -> bool {
+        public_key
+            .verify(&proposal_part.to_sign_bytes(), signature)
+            .is_ok()
+    }
+
+    fn sign_vote_extension(&self, extension: Bytes) -> SignedExtension<TestContext> {
+        let signature = self.sign(&vote_extension_sign_bytes(&self.chain_id, &extension));
+        SignedExtension::new(extension, signature)
+    }
+
+    fn verify_signed_vote_extension(
+        &self,
+        extension: &Bytes,
+        signature: &Signature,
+        public_key: &PublicKey,
+    ) -> bool {
+        public_key
+            .verify(
+                &vote_extension_sign_bytes(&self.chain_id, extension),
+                signature,
+            )
+            .is_ok()
+    }
+
+    fn verify_commit_signature(
+        &self,
+        certificate: &CommitCertificate<TestContext>,
+        commit_sig: &CommitSignature<TestContext>,
+        validator: &Validator,
+    ) -> Result<VotingPower, CertificateError<TestContext>> {
+        verify_commit_signature(&self.chain_id, certificate, commit_sig, validator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{address::Address, height::Height, sign_bytes::DEFAULT_CHAIN_ID};
+    use crate::types::{validator_set::ValidatorSet, value::ValueId};
+    use malachitebft_core_types::{AggregatedSignature, Round};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn providers() -> Vec<Ed25519Provider> {
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..4)
+            .map(|_| Ed25519Provider::new(PrivateKey::generate(&mut rng), DEFAULT_CHAIN_ID))
+            .collect()
+    }
+
+    fn address(provider: &Ed25519Provider) -> Address {
+        Address::from_public_key(&provider.private_key().public_key())
+    }
+
+    fn certificate(signers: &[Ed25519Provider]) -> CommitCertificate<TestContext> {
+        let (height, round, value_id) = (Height::new(1), Round::new(0), ValueId::new([1; 32]));
+        let signatures = signers
+            .iter()
+            .map(|signer| {
+                let vote =
+                    Vote::new_precommit(height, round, NilOrVal::Val(value_id), address(signer));
+                CommitSignature {
+                    address: address(signer),
+                    signature: signer.sign_vote(vote).signature,
+                }
+            })
+            .collect();
+
+        CommitCertificate {
+            height,
+            round,
+            value_id,
+            aggregated_signature: AggregatedSignature { signatures },
+        }
+    }
+
+    #[test]
+    fn verifies_certificate_signatures_and_voting_power() {
+        let providers = providers();
+        let validator_set = ValidatorSet::new(
+            providers
+                .iter()
+                .map(|p| Validator::new(p.private_key().public_key(), 1)),
+        );
+        let verifier = &providers[0];
+
+        let valid = certificate(&providers[..3]);
+        assert_eq!(verifier.verify_certificate(&valid, &validator_set), Ok(()));
+
+        assert_eq!(
+            verifier.verify_certificate(&certificate(&providers[..2]), &validator_set),
+            Err(CommitCertificateError::NotEnoughVotingPower {
+                signed: 2,
+                total: 4
+            })
+        );
+
+        let mut forged = valid.clone();
+        forged.value_id = ValueId::new([2; 32]);
+        assert_eq!(
+            verifier.verify_certificate(&forged, &validator_set),
+            Err(CommitCertificateError::InvalidSignature(address(
+                &providers[0]
+            )))
+        );
+
+        let mut duplicated = valid;
+        let first = duplicated.aggregated_signature.signatures[0].clone();
+        duplicated.aggregated_signature.signatures.push(first);
+        assert_eq!(
+            verifier.verify_certificate(&duplicated, &validator_set),
+            Err(CommitCertificateError::DuplicateSignature(address(
+                &providers[0]
+            )))
+        );
+    }
+}
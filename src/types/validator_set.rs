@@ -1,7 +1,9 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use malachitebft_core_types::VotingPower;
 use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
 
 use super::signing::PublicKey;
 use super::{address::Address, context::TestContext};
@@ -51,20 +53,55 @@ impl malachitebft_core_types::Validator<TestContext> for Validator {
 }
 
 /// A validator set contains a list of validators sorted by address.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "ValidatorList")]
 pub struct ValidatorSet {
     pub validators: Arc<Vec<Validator>>,
+    /// Index in `validators` by address, since every signature check looks its signer up
+    #[serde(skip)]
+    by_address: Arc<HashMap<Address, usize>>,
+}
+
+/// Serialized form of [`ValidatorSet`], which rebuilds the index on deserialization.
+#[derive(Deserialize)]
+struct ValidatorList {
+    validators: Vec<Validator>,
+}
+
+impl From<ValidatorList> for ValidatorSet {
+    fn from(list: ValidatorList) -> Self {
+        Self::from_validators(list.validators)
+    }
 }
 
+impl PartialEq for ValidatorSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.validators == other.validators
+    }
+}
+
+impl Eq for ValidatorSet {}
+
 impl ValidatorSet {
     pub fn new(validators: impl IntoIterator<Item = Validator>) -> Self {
-        let mut validators: Vec<_> = validators.into_iter().collect();
+        let set = Self::from_validators(validators.into_iter().collect());
+        assert!(!set.validators.is_empty());
+        set
+    }
+
+    /// Unlike [`ValidatorSet::new`], accepts an empty list.
+    pub(crate) fn from_validators(mut validators: Vec<Validator>) -> Self {
         ValidatorSet::sort_validators(&mut validators);
 
-        assert!(!validators.is_empty());
+        let by_address = validators
+            .iter()
+            .enumerate()
+            .map(|(index, validator)| (validator.address, index))
+            .collect();
 
         Self {
             validators: Arc::new(validators),
+            by_address: Arc::new(by_address),
         }
     }
 
@@ -73,9 +110,23 @@ impl ValidatorSet {
         self.validators.iter().map(|v| v.voting_power).sum()
     }
 
+    /// SHA3-256 of `address || public_key || voting_power (u64, big-endian)` of each validator,
+    /// in order, committing to the whole set.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hasher = Sha3_256::new();
+        for validator in self.validators.iter() {
+            hasher.update(validator.address.into_inner());
+            hasher.update(validator.public_key.as_bytes());
+            hasher.update(validator.voting_power.to_be_bytes());
+        }
+        hasher.finalize().into()
+    }
+
     /// Get a validator by its address
     pub fn get_by_address(&self, address: &Address) -> Option<&Validator> {
-        self.validators.iter().find(|v| &v.address == address)
+        self.by_address
+            .get(address)
+            .and_then(|index| self.validators.get(*index))
     }
 
     pub fn get_by_public_key(&self, public_key: &PublicKey) -> Option<&Validator> {
@@ -119,3 +170,26 @@ impl malachitebft_core_types::ValidatorSet<TestContext> for ValidatorSet {
         self.validators.get(index)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::signing::PrivateKey;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn looks_validators_up_by_address_after_deserialization() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let set = ValidatorSet::new(
+            (0..4).map(|i| Validator::new(PrivateKey::generate(&mut rng).public_key(), i + 1)),
+        );
+        let json = serde_json::to_string(&set).unwrap();
+        let decoded: ValidatorSet = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, set);
+
+        for validator in set.validators.iter() {
+            assert_eq!(decoded.get_by_address(&validator.address), Some(validator));
+        }
+        assert_eq!(decoded.get_by_address(&Address::default()), None);
+    }
+}
@@ -0,0 +1,148 @@
+use bytes::Bytes;
+use core::fmt;
+use malachitebft_proto::{Error as ProtoError, Protobuf};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+use super::codec::proto::decode_hash;
+use super::proto;
+
+/// Identifier of a value: the SHA3-256 hash of the encoded block, the same hash function
+/// used for [`crate::header::Header::block_hash`].
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Serialize, Deserialize)]
+pub struct ValueId(
+    #[serde(
+        serialize_with = "hex::serde::serialize",
+        deserialize_with = "hex::serde::deserialize"
+    )]
+    [u8; 32],
+);
+
+impl ValueId {
+    pub const fn new(id: [u8; 32]) -> Self {
+        Self(id)
+    }
+
+    pub const fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> [u8; 32] {
+        self.0
+    }
+}
+
+impl From<[u8; 32]> for ValueId {
+    fn from(value: [u8; 32]) -> Self {
+        Self::new(value)
+    }
+}
+
+impl fmt::Display for ValueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl Protobuf for ValueId {
+    type Proto = proto::ValueId;
+
+    fn from_proto(proto: Self::Proto) -> Result<Self, ProtoError> {
+        Ok(ValueId::new(decode_hash(&proto.value)?))
+    }
+
+    fn to_proto(&self) -> Result<Self::Proto, ProtoError> {
+        Ok(proto::ValueId {
+            value: self.0.to_vec().into(),
+        })
+    }
+}
+
+/// The value to decide on
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Value {
+    pub id: ValueId,
+    #[serde(with = "super::serde_hex::bytes")]
+    pub extensions: Bytes,
+}
+
+impl Value {
+    /// Creates a new Value identified by the SHA3-256 hash of the provided bytes
+    pub fn new(data: Bytes) -> Self {
+        let id: [u8; 32] = Sha3_256::digest(&data).into();
+
+        Self {
+            id: ValueId::new(id),
+            extensions: data, // Store original bytes as extensions
+        }
+    }
+
+    pub fn id(&self) -> ValueId {
+        self.id
+    }
+
+    pub fn size_bytes(&self) -> usize {
+        std::mem::size_of_val(&self.id) + self.extensions.len()
+    }
+}
+
+impl malachitebft_core_types::Value for Value {
+    type Id = ValueId;
+
+    fn id(&self) -> ValueId {
+        self.id()
+    }
+}
+
+impl Protobuf for Value {
+    type Proto = proto::Value;
+
+    fn from_proto(proto: Self::Proto) -> Result<Self, ProtoError> {
+        let id = ValueId::new(decode_hash(&proto.id)?);
+        let value = Value::new(proto.extensions);
+
+        // The id is a content hash, never trust the one sent along with the data
+        if value.id != id {
+            return Err(ProtoError::Other(format!(
+                "Value id mismatch: expected {}, got {}",
+                value.id, id
+            )));
+        }
+
+        Ok(value)
+    }
+
+    fn to_proto(&self) -> Result<Self::Proto, ProtoError> {
+        Ok(proto::Value {
+            id: self.id.0.to_vec().into(),
+            extensions: self.extensions.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn value_id_is_content_hash() {
+        let a = Value::new(Bytes::from_static(b"block a"));
+        let b = Value::new(Bytes::from_static(b"block b"));
+
+        assert_ne!(a.id(), b.id());
+        assert_eq!(a.id(), Value::new(Bytes::from_static(b"block a")).id());
+    }
+
+    #[test]
+    fn value_protobuf_rejects_forged_id() {
+        let value = Value::new(Bytes::from_static(b"block"));
+        let mut proto = value.to_proto().unwrap();
+        assert_eq!(Value::from_proto(proto.clone()).unwrap(), value);
+
+        proto.id = vec![0; 32].into();
+        assert!(Value::from_proto(proto).is_err());
+    }
+}
@@ -105,7 +105,7 @@ impl Protobuf for Vote {
 
     fn from_proto(proto: Self::Proto) -> Result<Self, ProtoError> {
         Ok(Self {
-            typ: decode_votetype(proto.vote_type()),
+            typ: decode_votetype(proto.vote_type)?,
             height: Height::from_proto(proto.height)?,
             round: Round::new(proto.round),
             value: match proto.value {
@@ -135,6 +135,26 @@ impl Protobuf for Vote {
     }
 }
 
+/// A vote without extension.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Vote {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let height = Height::new(u.arbitrary()?);
+        let round = crate::fuzz::arbitrary_round(u)?;
+        let value = match u.arbitrary()? {
+            Some(id) => NilOrVal::Val(ValueId::new(id)),
+            None => NilOrVal::Nil,
+        };
+        let address = Address::new(u.arbitrary()?);
+
+        Ok(if u.arbitrary()? {
+            Self::new_prevote(height, round, value, address)
+        } else {
+            Self::new_precommit(height, round, value, address)
+        })
+    }
+}
+
 fn encode_votetype(vote_type: VoteType) -> proto::VoteType {
     match vote_type {
         VoteType::Prevote => proto::VoteType::Prevote,
@@ -142,24 +162,51 @@ fn encode_votetype(vote_type: VoteType) -> proto::VoteType {
     }
 }
 
-fn decode_votetype(vote_type: proto::VoteType) -> VoteType {
+fn decode_votetype(vote_type: i32) -> Result<VoteType, ProtoError> {
+    match proto::VoteType::try_from(vote_type) {
+        Ok(proto::VoteType::Prevote) => Ok(VoteType::Prevote),
+        Ok(proto::VoteType::Precommit) => Ok(VoteType::Precommit),
+        Err(_) => Err(ProtoError::Other(format!("Unknown vote type: {vote_type}"))),
+    }
+}
+
+/// Version of the bincode layout of [`Vote`], written as the first byte of every encoded vote.
+///
+/// Bump this whenever a field is added, removed or reordered, and keep decoding the older
+/// versions so that existing WAL and store entries remain readable.
+///
+/// Version 1 carried 8-byte value ids, which cannot be mapped to the 32-byte content hashes used
+/// since version 2, so version 1 entries are rejected.
+pub const VOTE_ENCODING_VERSION: u8 = 2;
+
+fn encode_vote_type(vote_type: VoteType) -> u8 {
     match vote_type {
-        proto::VoteType::Prevote => VoteType::Prevote,
-        proto::VoteType::Precommit => VoteType::Precommit,
+        VoteType::Prevote => 0,
+        VoteType::Precommit => 1,
+    }
+}
+
+fn decode_vote_type(byte: u8) -> Result<VoteType, DecodeError> {
+    match byte {
+        0 => Ok(VoteType::Prevote),
+        1 => Ok(VoteType::Precommit),
+        other => Err(DecodeError::OtherString(format!(
+            "Invalid vote type: {other}"
+        ))),
     }
 }
 
 impl Encode for Vote {
     fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        VOTE_ENCODING_VERSION.encode(encoder)?;
+
         self.height.as_u64().encode(encoder)?;
         self.round.as_u32().encode(encoder)?;
-
-        unsafe { std::mem::transmute::<VoteType, u8>(self.typ) }.encode(encoder)?;
-
+        encode_vote_type(self.typ).encode(encoder)?;
         self.validator_address.into_inner().encode(encoder)?;
         match &self.value {
             NilOrVal::Nil => None,
-            NilOrVal::Val(v) => Some(v.as_u64()),
+            NilOrVal::Val(v) => Some(v.into_inner()),
         }
         .encode(encoder)?;
         // Don't encode the extension field at all
@@ -169,16 +216,21 @@ impl Encode for Vote {
 
 impl<Context> Decode<Context> for Vote {
     fn decode<D: Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let version = u8::decode(decoder)?;
+        if version != VOTE_ENCODING_VERSION {
+            return Err(DecodeError::OtherString(format!(
+                "Unsupported vote encoding version: {version}"
+            )));
+        }
+
         let height = Height::new(u64::decode(decoder)?);
         let round = match Option::<u32>::decode(decoder)? {
             Some(val) => Round::new(val),
             None => Round::Nil,
         };
-
-        let typ = unsafe { std::mem::transmute::<u8, VoteType>(u8::decode(decoder)?) };
-
+        let typ = decode_vote_type(u8::decode(decoder)?)?;
         let validator_address = Address::new(<[u8; 20]>::decode(decoder)?);
-        let value = match Option::<u64>::decode(decoder)? {
+        let value = match Option::<[u8; 32]>::decode(decoder)? {
             Some(val) => NilOrVal::Val(ValueId::new(val)),
             None => NilOrVal::Nil,
         };
@@ -203,7 +255,7 @@ mod tests {
         Vote::new_prevote(
             Height::new(100),
             Round::new(2),
-            NilOrVal::Val(ValueId::new(3)),
+            NilOrVal::Val(ValueId::new([3u8; 32])),
             Address::new([3u8; 20]),
         )
     }
@@ -220,7 +272,7 @@ mod tests {
     #[test]
     fn test_vote_bincode_with_value() {
         let mut vote = create_test_vote();
-        vote.value = NilOrVal::Val(ValueId::new(4));
+        vote.value = NilOrVal::Val(ValueId::new([4u8; 32]));
 
         let encoded = bincode::encode_to_vec(&vote, bincode::config::standard()).unwrap();
         println!("Encoded bytes with value: {:?}", encoded);
@@ -230,4 +282,50 @@ mod tests {
         assert_eq!(vote, decoded);
         assert_eq!(vote.value, decoded.value);
     }
+
+    #[test]
+    fn test_vote_bincode_precommit_nil() {
+        let vote = Vote::new_precommit(
+            Height::new(7),
+            Round::new(0),
+            NilOrVal::Nil,
+            Address::new([9u8; 20]),
+        );
+        let config = bincode::config::standard();
+        let encoded = bincode::encode_to_vec(&vote, config).unwrap();
+        assert_eq!(encoded[0], VOTE_ENCODING_VERSION);
+
+        let (decoded, _): (Vote, _) = bincode::decode_from_slice(&encoded, config).unwrap();
+        assert_eq!(vote, decoded);
+    }
+
+    #[test]
+    fn test_vote_bincode_rejects_unknown_version() {
+        let config = bincode::config::standard();
+        let mut encoded = bincode::encode_to_vec(create_test_vote(), config).unwrap();
+        encoded[0] = VOTE_ENCODING_VERSION + 1;
+
+        assert!(bincode::decode_from_slice::<Vote, _>(&encoded, config).is_err());
+    }
+
+    #[test]
+    fn test_vote_bincode_rejects_invalid_vote_type() {
+        let config = bincode::config::standard();
+        let vote = create_test_vote();
+        let mut encoded = bincode::encode_to_vec(&vote, config).unwrap();
+
+        // version (1) + height varint (1) + Some(round) (2) puts the vote type at index 4
+        assert_eq!(encoded[4], 0);
+        encoded[4] = 7;
+
+        assert!(bincode::decode_from_slice::<Vote, _>(&encoded, config).is_err());
+    }
+
+    #[test]
+    fn test_vote_proto_rejects_unknown_vote_type() {
+        let mut proto = create_test_vote().to_proto().unwrap();
+        proto.vote_type = 7;
+
+        assert!(Vote::from_proto(proto).is_err());
+    }
 }
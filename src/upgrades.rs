@@ -0,0 +1,282 @@
+//! Protocol versions and scheduled upgrades.
+//!
+//! Every block header carries the protocol version it was built under, and blocks of another
+//! version than the one scheduled for their height are rejected. The genesis file sets the
+//! version of the first blocks, and upgrades switch to a new version from a given height on.
+//!
+//! Upgrades are listed in the genesis file or, to upgrade a running network, in the `[protocol]`
+//! section of the configuration of every node: the genesis file, and so the genesis block, stays
+//! the same. A node only builds and accepts blocks of versions up to [`PROTOCOL_VERSION`], so it
+//! stops at the upgrade height until it is restarted with a binary supporting the new version.
+//!
+//! Behavior changes that do not need a new binary are [`Feature`]s instead, activated at the
+//! height given by the `forks` section of the genesis file or of the node configuration.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::UpgradeError;
+
+/// Highest protocol version this node can build and validate blocks for. Version 2 headers
+/// commit to the shares of their blobs, see [`crate::shares`].
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Version of the first blocks when the genesis file does not set one.
+pub const DEFAULT_PROTOCOL_VERSION: u32 = 1;
+
+/// Switch to `version` from `height` on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Upgrade {
+    pub height: u64,
+    pub version: u32,
+}
+
+/// Rule of block validation or execution that only applies from its activation height on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// Namespace registrations and their posting policies, see [`crate::namespaces`]
+    Namespaces,
+    /// Block gas limit and gas limits of the transactions of a block, see [`crate::gas`]
+    GasMetering,
+    /// Votes of validators for parameter changes, see [`crate::governance`]
+    Governance,
+    /// Jailing of validators missing too many blocks, and unjails, see [`crate::liveness`]
+    Jailing,
+}
+
+impl Feature {
+    /// Activation height of a feature missing from the `forks` section. Features that predate
+    /// activations are active from genesis, later ones are only active once scheduled.
+    fn default_activation(self) -> Option<u64> {
+        match self {
+            Self::Namespaces | Self::GasMetering => Some(0),
+            Self::Governance | Self::Jailing => None,
+        }
+    }
+}
+
+/// `[protocol]` section of the node configuration.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProtocolConfig {
+    /// Upgrades scheduled on top of those of the genesis file, identical on every node
+    pub upgrades: Vec<Upgrade>,
+    /// Activation heights added to the `forks` section of the genesis file, identical on every
+    /// node
+    pub forks: BTreeMap<Feature, u64>,
+}
+
+/// Protocol version of every height, part of the genesis file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtocolSchedule {
+    /// Version of the blocks before the first upgrade, genesis included
+    #[serde(default = "default_protocol_version")]
+    pub protocol_version: u32,
+    /// Upgrades by increasing height and version
+    #[serde(default)]
+    pub upgrades: Vec<Upgrade>,
+    /// Activation height of each feature, see [`Feature`]
+    #[serde(default)]
+    pub forks: BTreeMap<Feature, u64>,
+}
+
+impl Default for ProtocolSchedule {
+    fn default() -> Self {
+        Self {
+            protocol_version: DEFAULT_PROTOCOL_VERSION,
+            upgrades: Vec::new(),
+            forks: BTreeMap::new(),
+        }
+    }
+}
+
+impl ProtocolSchedule {
+    /// Adds `upgrades`, e.g. from [`ProtocolConfig`], to the schedule. Those already scheduled
+    /// are ignored, the others must follow the previous ones in height and version.
+    pub fn schedule(&mut self, upgrades: &[Upgrade]) -> Result<(), UpgradeError> {
+        for upgrade in upgrades {
+            if !self.upgrades.contains(upgrade) {
+                self.upgrades.push(*upgrade);
+            }
+        }
+        self.upgrades.sort_by_key(|upgrade| upgrade.height);
+        self.check()
+    }
+
+    /// Checks that heights and versions strictly increase, starting after the genesis block.
+    pub fn check(&self) -> Result<(), UpgradeError> {
+        let (mut height, mut version) = (0, self.protocol_version);
+        for upgrade in &self.upgrades {
+            if upgrade.height <= height || upgrade.version <= version {
+                return Err(UpgradeError::OutOfOrder {
+                    height: upgrade.height,
+                    version: upgrade.version,
+                });
+            }
+            (height, version) = (upgrade.height, upgrade.version);
+        }
+        Ok(())
+    }
+
+    /// Adds the activation heights of `forks`, e.g. from [`ProtocolConfig`]. A feature already
+    /// scheduled must keep its height.
+    pub fn activate(&mut self, forks: &BTreeMap<Feature, u64>) -> Result<(), UpgradeError> {
+        for (&feature, &height) in forks {
+            match self.forks.get(&feature) {
+                Some(&scheduled) if scheduled != height => {
+                    return Err(UpgradeError::ConflictingActivation {
+                        feature,
+                        height,
+                        scheduled,
+                    });
+                }
+                _ => {
+                    self.forks.insert(feature, height);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the rules of `feature` apply to the block at `height`.
+    pub fn is_active(&self, feature: Feature, height: u64) -> bool {
+        self.forks
+            .get(&feature)
+            .copied()
+            .or(feature.default_activation())
+            .is_some_and(|activation| height >= activation)
+    }
+
+    /// Version of the block at `height`.
+    pub fn version_at(&self, height: u64) -> u32 {
+        self.upgrades
+            .iter()
+            .rev()
+            .find(|upgrade| upgrade.height <= height)
+            .map_or(self.protocol_version, |upgrade| upgrade.version)
+    }
+
+    /// First upgrade after `height`.
+    pub fn next_upgrade(&self, height: u64) -> Option<Upgrade> {
+        self.upgrades
+            .iter()
+            .find(|upgrade| upgrade.height > height)
+            .copied()
+    }
+
+    /// First height whose version this node does not support, if any.
+    pub fn unsupported_from(&self) -> Option<u64> {
+        if self.protocol_version > PROTOCOL_VERSION {
+            return Some(0);
+        }
+        self.upgrades
+            .iter()
+            .find(|upgrade| upgrade.version > PROTOCOL_VERSION)
+            .map(|upgrade| upgrade.height)
+    }
+
+    /// Checks that a block at `height` has the scheduled `version`, and that this node supports it.
+    pub fn check_version(&self, height: u64, version: u32) -> Result<(), UpgradeError> {
+        let expected = self.version_at(height);
+        if expected > PROTOCOL_VERSION {
+            return Err(UpgradeError::Unsupported {
+                height,
+                version: expected,
+                supported: PROTOCOL_VERSION,
+            });
+        }
+        if version != expected {
+            return Err(UpgradeError::UnexpectedVersion {
+                height,
+                expected,
+                actual: version,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn default_protocol_version() -> u32 {
+    DEFAULT_PROTOCOL_VERSION
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upgrade(height: u64, version: u32) -> Upgrade {
+        Upgrade { height, version }
+    }
+
+    #[test]
+    fn switches_version_at_the_upgrade_height() {
+        let mut schedule = ProtocolSchedule::default();
+        schedule.schedule(&[upgrade(10, 2)]).unwrap();
+        // Already scheduled, e.g. by both the genesis file and the configuration
+        schedule.schedule(&[upgrade(10, 2)]).unwrap();
+
+        assert_eq!(schedule.version_at(0), 1);
+        assert_eq!(schedule.version_at(9), 1);
+        assert_eq!(schedule.version_at(10), 2);
+        assert_eq!(schedule.next_upgrade(5), Some(upgrade(10, 2)));
+        assert_eq!(schedule.next_upgrade(10), None);
+
+        assert!(schedule.check_version(9, 1).is_ok());
+        assert!(matches!(
+            schedule.check_version(9, 2),
+            Err(UpgradeError::UnexpectedVersion { expected: 1, .. })
+        ));
+        assert_eq!(schedule.unsupported_from(), Some(10));
+        assert!(matches!(
+            schedule.check_version(10, 2),
+            Err(UpgradeError::Unsupported { version: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn activates_features_at_their_height() {
+        let mut schedule = ProtocolSchedule::default();
+        assert!(schedule.is_active(Feature::Namespaces, 0));
+
+        schedule
+            .activate(&BTreeMap::from([(Feature::Namespaces, 100)]))
+            .unwrap();
+        assert!(!schedule.is_active(Feature::Namespaces, 99));
+        assert!(schedule.is_active(Feature::Namespaces, 100));
+        assert!(schedule.is_active(Feature::GasMetering, 0));
+        assert!(!schedule.is_active(Feature::Governance, u64::MAX));
+
+        assert!(schedule
+            .activate(&BTreeMap::from([(Feature::Namespaces, 100)]))
+            .is_ok());
+        assert!(matches!(
+            schedule.activate(&BTreeMap::from([(Feature::Namespaces, 200)])),
+            Err(UpgradeError::ConflictingActivation { scheduled: 100, .. })
+        ));
+
+        let genesis: ProtocolSchedule =
+            serde_json::from_str(r#"{"forks":{"gas_metering":10}}"#).unwrap();
+        assert!(!genesis.is_active(Feature::GasMetering, 9));
+    }
+
+    #[test]
+    fn rejects_upgrades_out_of_order() {
+        let mut schedule = ProtocolSchedule::default();
+        assert!(schedule.schedule(&[upgrade(0, 2)]).is_err());
+
+        let mut schedule = ProtocolSchedule::default();
+        assert!(schedule.schedule(&[upgrade(10, 1)]).is_err());
+
+        let mut schedule = ProtocolSchedule::default();
+        assert!(schedule
+            .schedule(&[upgrade(10, 3), upgrade(20, 2)])
+            .is_err());
+
+        let mut schedule = ProtocolSchedule::default();
+        assert!(schedule
+            .schedule(&[upgrade(10, 2), upgrade(10, 3)])
+            .is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use crate::blob::{Blob, BLOB_SIZE};
+use crate::error::UploadError;
+
+/// Maximum number of uploads in progress at the same time, each one can buffer up to
+/// [`BLOB_SIZE`] bytes.
+pub const MAX_PENDING_UPLOADS: usize = 64;
+
+/// Uploads that did not receive a chunk for this long are dropped.
+pub const UPLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Recommended chunk size for clients, keeps each JSON-RPC body around 128 KiB.
+pub const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug)]
+struct Upload {
+    data: Vec<u8>,
+    last_update: Instant,
+}
+
+/// Blobs being uploaded in chunks through the RPC server, before being attached to a transaction.
+#[derive(Debug, Clone, Default)]
+pub struct BlobUploads {
+    next_id: Arc<AtomicU64>,
+    uploads: Arc<Mutex<HashMap<u64, Upload>>>,
+}
+
+impl BlobUploads {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a new upload and returns its id.
+    pub fn begin(&self) -> Result<u64, UploadError> {
+        let mut uploads = self.uploads.lock().unwrap();
+        uploads.retain(|_, upload| upload.last_update.elapsed() < UPLOAD_TIMEOUT);
+
+        if uploads.len() >= MAX_PENDING_UPLOADS {
+            return Err(UploadError::TooManyUploads(MAX_PENDING_UPLOADS));
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        uploads.insert(
+            id,
+            Upload {
+                data: Vec::new(),
+                last_update: Instant::now(),
+            },
+        );
+        Ok(id)
+    }
+
+    /// Appends `chunk` at `offset`, which must be the number of bytes received so far.
+    /// Returns the new number of bytes received.
+    pub fn append(&self, id: u64, offset: usize, chunk: &[u8]) -> Result<usize, UploadError> {
+        let mut uploads = self.uploads.lock().unwrap();
+        let upload = uploads.get_mut(&id).ok_or(UploadError::UnknownUpload(id))?;
+
+        if offset != upload.data.len() {
+            return Err(UploadError::UnexpectedOffset {
+                expected: upload.data.len(),
+                actual: offset,
+            });
+        }
+        if upload.data.len() + chunk.len() > BLOB_SIZE {
+            return Err(UploadError::TooLarge(BLOB_SIZE));
+        }
+
+        upload.data.extend_from_slice(chunk);
+        upload.last_update = Instant::now();
+        Ok(upload.data.len())
+    }
+
+    /// Ends the upload and returns the assembled blob.
+    pub fn finish(&self, id: u64) -> Result<Blob, UploadError> {
+        let upload = self
+            .uploads
+            .lock()
+            .unwrap()
+            .remove(&id)
+            .ok_or(UploadError::UnknownUpload(id))?;
+
+        Blob::try_new(Bytes::from(upload.data)).map_err(|_| UploadError::TooLarge(BLOB_SIZE))
+    }
+
+    pub fn len(&self) -> usize {
+        self.uploads.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_are_assembled_in_order() {
+        let uploads = BlobUploads::new();
+        let id = uploads.begin().unwrap();
+
+        assert_eq!(uploads.append(id, 0, b"hello ").unwrap(), 6);
+        assert_eq!(
+            uploads.append(id, 0, b"again"),
+            Err(UploadError::UnexpectedOffset {
+                expected: 6,
+                actual: 0
+            })
+        );
+        assert_eq!(uploads.append(id, 6, b"world").unwrap(), 11);
+
+        assert_eq!(uploads.finish(id).unwrap().data(), b"hello world");
+        assert_eq!(uploads.finish(id), Err(UploadError::UnknownUpload(id)));
+        assert!(uploads.is_empty());
+    }
+
+    #[test]
+    fn rejects_oversized_blobs() {
+        let uploads = BlobUploads::new();
+        let id = uploads.begin().unwrap();
+
+        uploads.append(id, 0, &vec![0; BLOB_SIZE]).unwrap();
+        assert_eq!(
+            uploads.append(id, BLOB_SIZE, &[0]),
+            Err(UploadError::TooLarge(BLOB_SIZE))
+        );
+    }
+
+    #[test]
+    fn limits_pending_uploads() {
+        let uploads = BlobUploads::new();
+        for _ in 0..MAX_PENDING_UPLOADS {
+            uploads.begin().unwrap();
+        }
+        assert_eq!(
+            uploads.begin(),
+            Err(UploadError::TooManyUploads(MAX_PENDING_UPLOADS))
+        );
+    }
+}
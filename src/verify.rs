@@ -0,0 +1,366 @@
+//! Verification of headers, Merkle proofs and commit certificates over plain bytes, for light
+//! clients embedded in constrained environments and in provable programs.
+//!
+//! This module only uses `core` and `alloc`, and is the one module built without the default
+//! `std` feature (`mikan = { default-features = false }`). The node hashes its headers with
+//! [`header_hash`] and signs votes under [`VOTE_DOMAIN`], so a check made here agrees with the
+//! chain.
+//!
+//! A light client accepts a header by:
+//!
+//! 1. checking that [`header_hash`] of its fields is the claimed block hash,
+//! 2. checking the certificate deciding it with [`verify_commit`], the value id being the hash
+//!    of the encoded block,
+//! 3. then checking transaction and blob inclusion with [`verify_merkle_proof`] against the
+//!    `tx_commitment` and `da_root` of the header, and byte ranges of blobs with
+//!    [`verify_shares`] against its `share_root`.
+
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+
+use ed25519_consensus::{Signature, VerificationKey};
+use rs_merkle::{algorithms::Sha256, Hasher, MerkleProof};
+use sha3::{Digest, Sha3_256};
+
+/// Domain tag of votes, see [`crate::types::sign_bytes`].
+pub const VOTE_DOMAIN: &[u8] = b"mikan/vote/v2";
+
+/// First protocol version whose headers commit to the shares of their blobs.
+pub const SHARES_VERSION: u32 = 2;
+
+/// Size of the shares blobs are split into, the last share of a blob being shorter.
+pub const SHARE_SIZE: usize = 512;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum VerifyError {
+    #[error("Malformed Merkle proof")]
+    MalformedProof,
+    #[error("Merkle proof does not lead to the root")]
+    InvalidProof,
+    #[error("Shares do not match the length of the blob")]
+    MalformedShares,
+    #[error("Signer {0:02x?} is not in the validator set")]
+    UnknownValidator([u8; 20]),
+    #[error("Signer {0:02x?} signed more than once")]
+    DuplicateSignature([u8; 20]),
+    #[error("Invalid signature of {0:02x?}")]
+    InvalidSignature([u8; 20]),
+    #[error("Not enough voting power: signed {signed} of {total}")]
+    NotEnoughVotingPower { signed: u64, total: u64 },
+}
+
+/// Member of the validator set a certificate is checked against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorKey {
+    pub address: [u8; 20],
+    /// Ed25519 public key
+    pub public_key: [u8; 32],
+    pub voting_power: u64,
+}
+
+/// Precommit of a certificate, signing [`precommit_sign_bytes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitSignature {
+    pub address: [u8; 20],
+    /// Ed25519 signature
+    pub signature: [u8; 64],
+}
+
+/// Hash of the fields of a header that its block hash commits to, in the order of
+/// [`crate::header::Header`]. `share_root` is only committed to from [`SHARES_VERSION`] on.
+#[allow(clippy::too_many_arguments)]
+pub fn header_hash(
+    block_number: u64,
+    parent_hash: &[u8; 32],
+    tx_commitment: &[u8; 32],
+    da_root: &[u8; 32],
+    blob_count: u32,
+    proposer_address: &[u8; 20],
+    version: u32,
+    share_root: &[u8; 32],
+) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+
+    hasher.update(block_number.to_le_bytes());
+    hasher.update(parent_hash);
+    hasher.update(tx_commitment);
+    hasher.update(da_root);
+    hasher.update(blob_count.to_le_bytes());
+    hasher.update(proposer_address);
+    hasher.update(version.to_le_bytes());
+    if version >= SHARES_VERSION {
+        hasher.update(share_root);
+    }
+
+    hasher.finalize().into()
+}
+
+/// Checks that `proof`, as produced by `MerkleProof::to_bytes` in the RPC responses, proves that
+/// `leaf` is at `index` of a tree of `leaf_count` leaves with root `root`.
+///
+/// The leaves of `tx_commitment` are the transaction hashes, those of `da_root` the blob
+/// commitments.
+pub fn verify_merkle_proof(
+    root: &[u8; 32],
+    leaf: &[u8; 32],
+    index: usize,
+    leaf_count: usize,
+    proof: &[u8],
+) -> Result<(), VerifyError> {
+    let proof =
+        MerkleProof::<Sha256>::from_bytes(proof).map_err(|_| VerifyError::MalformedProof)?;
+    if index >= leaf_count || !proof.verify(*root, &[index], &[*leaf], leaf_count) {
+        return Err(VerifyError::InvalidProof);
+    }
+    Ok(())
+}
+
+/// Number of shares of a blob of `blob_len` bytes.
+pub fn share_count(blob_len: u64) -> usize {
+    blob_len.div_ceil(SHARE_SIZE as u64) as usize
+}
+
+/// Leaf of the share tree of a blob.
+pub fn share_leaf(share: &[u8]) -> [u8; 32] {
+    Sha256::hash(share)
+}
+
+/// Leaf of the `share_root` of a header for a blob of `blob_len` bytes whose shares have the
+/// Merkle root `blob_share_root`.
+pub fn blob_share_leaf(blob_len: u64, blob_share_root: &[u8; 32]) -> [u8; 32] {
+    let mut leaf = [0; 40];
+    leaf[..8].copy_from_slice(&blob_len.to_be_bytes());
+    leaf[8..].copy_from_slice(blob_share_root);
+    Sha256::hash(&leaf)
+}
+
+/// Checks that `shares` are the consecutive shares of a blob from `first_share` on, as returned
+/// by `mikan_getShares`: the blob of `blob_len` bytes whose shares have the root
+/// `blob_share_root` is at `blob_index` of the `blob_count` blobs of a header with `share_root`,
+/// which `blob_proof` proves, and `shares_proof` proves the shares against `blob_share_root`.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_shares(
+    share_root: &[u8; 32],
+    blob_count: usize,
+    blob_index: usize,
+    blob_len: u64,
+    blob_share_root: &[u8; 32],
+    blob_proof: &[u8],
+    first_share: usize,
+    shares: &[&[u8]],
+    shares_proof: &[u8],
+) -> Result<(), VerifyError> {
+    verify_merkle_proof(
+        share_root,
+        &blob_share_leaf(blob_len, blob_share_root),
+        blob_index,
+        blob_count,
+        blob_proof,
+    )?;
+
+    let count = share_count(blob_len);
+    let end = first_share + shares.len();
+    if shares.is_empty() || end > count {
+        return Err(VerifyError::MalformedShares);
+    }
+    let last_len = blob_len as usize - (count - 1) * SHARE_SIZE;
+    for (index, share) in (first_share..end).zip(shares) {
+        let expected = if index == count - 1 {
+            last_len
+        } else {
+            SHARE_SIZE
+        };
+        if share.len() != expected {
+            return Err(VerifyError::MalformedShares);
+        }
+    }
+
+    let proof =
+        MerkleProof::<Sha256>::from_bytes(shares_proof).map_err(|_| VerifyError::MalformedProof)?;
+    let indices: Vec<usize> = (first_share..end).collect();
+    let leaves: Vec<[u8; 32]> = shares.iter().map(|share| share_leaf(share)).collect();
+    if !proof.verify(*blob_share_root, &indices, &leaves, count) {
+        return Err(VerifyError::InvalidProof);
+    }
+    Ok(())
+}
+
+/// Bytes signed by the precommit of `address` for `value_id`, the layout of
+/// [`crate::types::sign_bytes::vote_sign_bytes`].
+pub fn precommit_sign_bytes(
+    chain_id: &str,
+    height: u64,
+    round: u32,
+    value_id: &[u8; 32],
+    address: &[u8; 20],
+) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_bytes(&mut out, VOTE_DOMAIN);
+    write_bytes(&mut out, chain_id.as_bytes());
+    // Precommit
+    out.push(1);
+    out.extend_from_slice(&height.to_be_bytes());
+    out.push(1);
+    out.extend_from_slice(&round.to_be_bytes());
+    out.push(1);
+    out.extend_from_slice(value_id);
+    write_bytes(&mut out, address);
+    out
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+/// Checks that `signatures` are valid precommits for `value_id` at `height` and `round`, from
+/// distinct members of `validators` with more than 2/3 of its voting power. The counterpart of
+/// [`crate::types::signing::verify_certificate`].
+pub fn verify_commit(
+    chain_id: &str,
+    height: u64,
+    round: u32,
+    value_id: &[u8; 32],
+    signatures: &[CommitSignature],
+    validators: &[ValidatorKey],
+) -> Result<(), VerifyError> {
+    let mut signers = BTreeSet::new();
+    let mut signed: u64 = 0;
+
+    for commit_sig in signatures {
+        let address = commit_sig.address;
+        let validator = validators
+            .iter()
+            .find(|validator| validator.address == address)
+            .ok_or(VerifyError::UnknownValidator(address))?;
+
+        if !signers.insert(address) {
+            return Err(VerifyError::DuplicateSignature(address));
+        }
+
+        let sign_bytes = precommit_sign_bytes(chain_id, height, round, value_id, &address);
+        VerificationKey::try_from(validator.public_key)
+            .and_then(|key| key.verify(&Signature::from(commit_sig.signature), &sign_bytes))
+            .map_err(|_| VerifyError::InvalidSignature(address))?;
+
+        signed += validator.voting_power;
+    }
+
+    let total: u64 = validators
+        .iter()
+        .map(|validator| validator.voting_power)
+        .sum();
+    if 3 * signed <= 2 * total {
+        return Err(VerifyError::NotEnoughVotingPower { signed, total });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::address::Address;
+    use crate::types::height::Height;
+    use crate::types::sign_bytes::{vote_sign_bytes, DEFAULT_CHAIN_ID};
+    use crate::types::value::ValueId;
+    use crate::types::vote::Vote;
+    use malachitebft_core_types::{NilOrVal, Round};
+    use malachitebft_test::PrivateKey;
+    use rand::thread_rng;
+
+    #[test]
+    fn matches_the_vote_encoding() {
+        let address = Address::new([7; 20]);
+        let vote = Vote::new_precommit(
+            Height::new(5),
+            Round::new(2),
+            NilOrVal::Val(ValueId::new([3; 32])),
+            address,
+        );
+        assert_eq!(
+            precommit_sign_bytes(DEFAULT_CHAIN_ID, 5, 2, &[3; 32], &address.into_inner()),
+            vote_sign_bytes(DEFAULT_CHAIN_ID, &vote)
+        );
+    }
+
+    #[test]
+    fn verifies_commits() {
+        let keys: Vec<PrivateKey> = (0..3).map(|_| PrivateKey::generate(thread_rng())).collect();
+        let validators: Vec<ValidatorKey> = keys
+            .iter()
+            .map(|key| ValidatorKey {
+                address: Address::from_public_key(&key.public_key()).into_inner(),
+                public_key: *key.public_key().as_bytes(),
+                voting_power: 1,
+            })
+            .collect();
+        let sign = |key: &PrivateKey, validator: &ValidatorKey| CommitSignature {
+            address: validator.address,
+            signature: key
+                .sign(&precommit_sign_bytes(
+                    DEFAULT_CHAIN_ID,
+                    5,
+                    0,
+                    &[3; 32],
+                    &validator.address,
+                ))
+                .to_bytes(),
+        };
+        let signatures: Vec<CommitSignature> = keys
+            .iter()
+            .zip(&validators)
+            .map(|(k, v)| sign(k, v))
+            .collect();
+
+        assert_eq!(
+            verify_commit(DEFAULT_CHAIN_ID, 5, 0, &[3; 32], &signatures, &validators),
+            Ok(())
+        );
+        assert_eq!(
+            verify_commit(
+                DEFAULT_CHAIN_ID,
+                5,
+                0,
+                &[3; 32],
+                &signatures[..2],
+                &validators
+            ),
+            Err(VerifyError::NotEnoughVotingPower {
+                signed: 2,
+                total: 3
+            })
+        );
+        assert_eq!(
+            verify_commit(DEFAULT_CHAIN_ID, 5, 0, &[4; 32], &signatures, &validators),
+            Err(VerifyError::InvalidSignature(validators[0].address))
+        );
+        let duplicated = [signatures[0].clone(), signatures[0].clone()];
+        assert_eq!(
+            verify_commit(DEFAULT_CHAIN_ID, 5, 0, &[3; 32], &duplicated, &validators),
+            Err(VerifyError::DuplicateSignature(validators[0].address))
+        );
+    }
+
+    #[test]
+    fn verifies_merkle_proofs() {
+        let leaves = [[1; 32], [2; 32], [3; 32]];
+        let tree = rs_merkle::MerkleTree::<Sha256>::from_leaves(&leaves);
+        let root = tree.root().unwrap();
+        let proof = tree.proof(&[1]).to_bytes();
+
+        assert_eq!(verify_merkle_proof(&root, &[2; 32], 1, 3, &proof), Ok(()));
+        assert_eq!(
+            verify_merkle_proof(&root, &[1; 32], 1, 3, &proof),
+            Err(VerifyError::InvalidProof)
+        );
+        assert_eq!(
+            verify_merkle_proof(&root, &[2; 32], 3, 3, &proof),
+            Err(VerifyError::InvalidProof)
+        );
+        assert_eq!(
+            verify_merkle_proof(&root, &[2; 32], 1, 3, &[0; 5]),
+            Err(VerifyError::MalformedProof)
+        );
+    }
+}
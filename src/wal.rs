@@ -0,0 +1,444 @@
+//! Tools for the consensus write-ahead log.
+//!
+//! `mikan repair-wal` truncates the corrupted tail of a WAL.
+//! A crash in the middle of writing an entry, typically a large proposal part while streaming
+//! blobs, leaves a partial entry at the end of the WAL and the node refuses to restart. The valid
+//! entries are copied to a new log with the same sequence (height), which then replaces the
+//! original. The original is kept next to it with a `.corrupted` suffix.
+//!
+//! On startup, [`recover`] reconciles the height of the WAL with the last height of the store.
+//! A height is only acknowledged to consensus once its certificate and block data are durably
+//! stored, so the node always resumes after the last decided height: a WAL for a decided height
+//! is discarded by consensus, and a WAL ahead of the store, left by a store that lost its last
+//! writes, is kept with a `.ahead` suffix while value sync replays the missing heights from peers.
+//!
+//! `mikan dump-wal --json` prints one JSON object per entry instead of Malachite's debug output,
+//! for scripts and `jq`. Entries are decoded with mikan's protobuf codec, following the framing
+//! of the consensus engine: a tag byte, then either a length-prefixed message or a timeout. An
+//! entry that can't be decoded is still printed, with its size and error.
+
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use clap::Parser;
+use malachitebft_codec::Codec;
+use malachitebft_core_consensus::{ProposedValue, SignedConsensusMsg};
+use malachitebft_core_types::{NilOrVal, VoteType};
+use malachitebft_wal::Log;
+use serde::Serialize;
+
+use crate::error::WalError;
+use crate::types::codec::proto::ProtobufCodec;
+use crate::types::context::TestContext;
+use crate::types::value::ValueId;
+
+/// Tags of the consensus engine WAL entries.
+const TAG_CONSENSUS: u8 = 0x01;
+const TAG_TIMEOUT: u8 = 0x02;
+const TAG_PROPOSED_VALUE: u8 = 0x04;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "mikan repair-wal",
+    about = "Truncate the corrupted tail of a WAL"
+)]
+pub struct RepairWalCmd {
+    /// WAL file, e.g. `nodes/0/wal/consensus.wal`
+    pub wal_file: PathBuf,
+
+    /// Only report the corruption, without changing any file
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Outcome of scanning a WAL.
+#[derive(Debug, PartialEq, Eq)]
+pub struct WalScan {
+    /// Height the log was started for
+    pub sequence: u64,
+    /// Entries readable before the first corrupted one
+    pub valid_entries: usize,
+    /// Error reading the first corrupted entry, if any
+    pub corruption: Option<String>,
+}
+
+impl RepairWalCmd {
+    pub fn run(&self) -> Result<(), WalError> {
+        let scan = scan(&self.wal_file)?;
+
+        let Some(corruption) = &scan.corruption else {
+            println!(
+                "{}: intact, {} entries at height {}",
+                self.wal_file.display(),
+                scan.valid_entries,
+                scan.sequence
+            );
+            return Ok(());
+        };
+
+        println!(
+            "{}: corrupted after {} valid entries at height {}: {corruption}",
+            self.wal_file.display(),
+            scan.valid_entries,
+            scan.sequence
+        );
+
+        if self.dry_run {
+            println!("Dry run, nothing was changed");
+            return Ok(());
+        }
+
+        let backup = repair(&self.wal_file, &scan)?;
+        println!(
+            "Truncated to {} entries, the original was moved to {}",
+            scan.valid_entries,
+            backup.display()
+        );
+        Ok(())
+    }
+}
+
+/// Reads entries until the first one that fails to decode or checksum.
+pub fn scan(path: &Path) -> Result<WalScan, WalError> {
+    let mut log = open(path)?;
+    let sequence = log.sequence();
+
+    let mut valid_entries = 0;
+    let mut corruption = None;
+    for entry in log.iter()? {
+        match entry {
+            Ok(_) => valid_entries += 1,
+            Err(e) => {
+                corruption = Some(e.to_string());
+                break;
+            }
+        }
+    }
+
+    Ok(WalScan {
+        sequence,
+        valid_entries,
+        corruption,
+    })
+}
+
+fn open(path: &Path) -> Result<Log, WalError> {
+    Log::open(path).map_err(|source| WalError::Open {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// How consensus resumes, from the height of the WAL and the last decided height of the store.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Recovery {
+    /// The WAL is for the height after the last decided one, or there is no WAL
+    Resume { height: u64 },
+    /// The WAL is for a height already decided, the node stopped between the commit and the
+    /// next height
+    Stale { height: u64, wal_height: u64 },
+    /// The WAL is ahead of the store, the heights in between are replayed from peers
+    Gap { height: u64, wal_height: u64 },
+}
+
+impl Recovery {
+    /// Height to start consensus at.
+    pub fn height(&self) -> u64 {
+        match self {
+            Self::Resume { height } | Self::Stale { height, .. } | Self::Gap { height, .. } => {
+                *height
+            }
+        }
+    }
+}
+
+pub fn recover(wal_height: Option<u64>, decided_height: Option<u64>) -> Recovery {
+    let height = decided_height.map_or(1, |decided| decided + 1);
+    match wal_height {
+        Some(wal_height) if wal_height < height => Recovery::Stale { height, wal_height },
+        Some(wal_height) if wal_height > height => Recovery::Gap { height, wal_height },
+        _ => Recovery::Resume { height },
+    }
+}
+
+/// Copies the WAL at `path` next to it with a `.ahead` suffix, returns the path of the copy.
+pub fn keep_ahead(path: &Path) -> Result<PathBuf, WalError> {
+    let backup_path = path.with_extension("wal.ahead");
+    std::fs::copy(path, &backup_path)?;
+    Ok(backup_path)
+}
+
+/// Replaces the WAL at `path` with its first `scan.valid_entries` entries, returns the path the
+/// original was moved to.
+fn repair(path: &Path, scan: &WalScan) -> Result<PathBuf, WalError> {
+    let repaired_path = path.with_extension("wal.repaired");
+    let backup_path = path.with_extension("wal.corrupted");
+
+    // Left over by an interrupted repair
+    if repaired_path.exists() {
+        std::fs::remove_file(&repaired_path)?;
+    }
+
+    {
+        let mut original = open(path)?;
+        let mut repaired = Log::open(&repaired_path)?;
+        repaired.restart(scan.sequence)?;
+
+        for entry in original.iter()?.take(scan.valid_entries) {
+            repaired.append(&entry?)?;
+        }
+
+        repaired.flush()?;
+        repaired.sync()?;
+    }
+
+    std::fs::rename(path, &backup_path)?;
+    std::fs::rename(&repaired_path, path)?;
+
+    Ok(backup_path)
+}
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "mikan dump-wal",
+    about = "Print the entries of a WAL as JSON lines"
+)]
+pub struct DumpWalJsonCmd {
+    /// WAL file, e.g. `nodes/0/wal/consensus.wal`
+    pub wal_file: PathBuf,
+
+    /// Print entries as JSON lines, without it the Malachite command is used
+    #[arg(long)]
+    pub json: bool,
+}
+
+impl DumpWalJsonCmd {
+    pub fn run(&self) -> Result<(), WalError> {
+        let mut log = open(&self.wal_file)?;
+        let sequence = log.sequence();
+
+        for (index, entry) in log.iter()?.enumerate() {
+            let line = match entry {
+                Ok(bytes) => WalEntryLine {
+                    sequence,
+                    index,
+                    size: bytes.len(),
+                    entry: decode_entry(&bytes),
+                },
+                Err(e) => WalEntryLine {
+                    sequence,
+                    index,
+                    size: 0,
+                    entry: WalEntrySummary::Corrupted {
+                        error: e.to_string(),
+                    },
+                },
+            };
+
+            println!("{}", serde_json::to_string(&line)?);
+
+            if matches!(line.entry, WalEntrySummary::Corrupted { .. }) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// One line of `dump-wal --json` output.
+#[derive(Debug, Serialize)]
+pub struct WalEntryLine {
+    /// Height the log was started for
+    pub sequence: u64,
+    pub index: usize,
+    /// Size of the encoded entry in bytes
+    pub size: usize,
+    #[serde(flatten)]
+    pub entry: WalEntrySummary,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WalEntrySummary {
+    Vote {
+        vote_type: &'static str,
+        height: u64,
+        round: i64,
+        /// `None` for a nil vote
+        value_id: Option<ValueId>,
+        validator: String,
+    },
+    Proposal {
+        height: u64,
+        round: i64,
+        pol_round: i64,
+        value_id: ValueId,
+        proposer: String,
+    },
+    ProposedValue {
+        height: u64,
+        round: i64,
+        valid_round: i64,
+        value_id: ValueId,
+        proposer: String,
+        valid: bool,
+    },
+    Timeout {
+        /// Kind of timeout as encoded by the engine, `propose`, `prevote`, `precommit` or
+        /// `kind_<n>` for the others
+        kind: String,
+        round: i64,
+    },
+    /// Entry with an unknown tag or that failed to decode
+    Undecodable { tag: Option<u8>, error: String },
+    /// The log can't be read past this entry, see `mikan repair-wal`
+    Corrupted { error: String },
+}
+
+/// Decodes a WAL entry into a summary, never fails.
+pub fn decode_entry(bytes: &[u8]) -> WalEntrySummary {
+    let undecodable = |tag: Option<u8>, error: String| WalEntrySummary::Undecodable { tag, error };
+
+    let Some((&tag, rest)) = bytes.split_first() else {
+        return undecodable(None, "empty entry".to_string());
+    };
+
+    match tag {
+        TAG_CONSENSUS => match length_prefixed(rest).map(|msg| {
+            <ProtobufCodec as Codec<SignedConsensusMsg<TestContext>>>::decode(&ProtobufCodec, msg)
+        }) {
+            Some(Ok(msg)) => consensus_summary(msg),
+            Some(Err(e)) => undecodable(Some(tag), e.to_string()),
+            None => undecodable(Some(tag), "truncated message".to_string()),
+        },
+        TAG_PROPOSED_VALUE => match length_prefixed(rest).map(|msg| {
+            <ProtobufCodec as Codec<ProposedValue<TestContext>>>::decode(&ProtobufCodec, msg)
+        }) {
+            Some(Ok(value)) => WalEntrySummary::ProposedValue {
+                height: value.height.as_u64(),
+                round: value.round.as_i64(),
+                valid_round: value.valid_round.as_i64(),
+                value_id: value.value.id(),
+                proposer: value.proposer.to_string(),
+                valid: value.validity.is_valid(),
+            },
+            Some(Err(e)) => undecodable(Some(tag), e.to_string()),
+            None => undecodable(Some(tag), "truncated message".to_string()),
+        },
+        TAG_TIMEOUT => match rest {
+            [kind, round @ ..] if round.len() == 8 => WalEntrySummary::Timeout {
+                kind: match kind {
+                    0 => "propose".to_string(),
+                    1 => "prevote".to_string(),
+                    2 => "precommit".to_string(),
+                    n => format!("kind_{n}"),
+                },
+                round: i64::from_be_bytes(round.try_into().expect("8 bytes")),
+            },
+            _ => undecodable(Some(tag), "truncated timeout".to_string()),
+        },
+        _ => undecodable(Some(tag), "unknown entry tag".to_string()),
+    }
+}
+
+/// The message following a big endian `u64` length.
+fn length_prefixed(bytes: &[u8]) -> Option<Bytes> {
+    let (len, rest) = bytes.split_first_chunk::<8>()?;
+    let len = usize::try_from(u64::from_be_bytes(*len)).ok()?;
+    rest.get(..len).map(Bytes::copy_from_slice)
+}
+
+fn consensus_summary(msg: SignedConsensusMsg<TestContext>) -> WalEntrySummary {
+    match msg {
+        SignedConsensusMsg::Vote(vote) => {
+            let vote = vote.message;
+            WalEntrySummary::Vote {
+                vote_type: match vote.typ {
+                    VoteType::Prevote => "prevote",
+                    VoteType::Precommit => "precommit",
+                },
+                height: vote.height.as_u64(),
+                round: vote.round.as_i64(),
+                value_id: match vote.value {
+                    NilOrVal::Val(id) => Some(id),
+                    NilOrVal::Nil => None,
+                },
+                validator: vote.validator_address.to_string(),
+            }
+        }
+        SignedConsensusMsg::Proposal(proposal) => {
+            let proposal = proposal.message;
+            WalEntrySummary::Proposal {
+                height: proposal.height.as_u64(),
+                round: proposal.round.as_i64(),
+                pol_round: proposal.pol_round.as_i64(),
+                value_id: proposal.value.id(),
+                proposer: proposal.validator_address.to_string(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resumes_after_the_decided_height() {
+        assert_eq!(recover(None, None), Recovery::Resume { height: 1 });
+        assert_eq!(recover(Some(1), None), Recovery::Resume { height: 1 });
+        assert_eq!(recover(Some(8), Some(7)), Recovery::Resume { height: 8 });
+        assert_eq!(
+            recover(Some(7), Some(7)),
+            Recovery::Stale {
+                height: 8,
+                wal_height: 7
+            }
+        );
+        assert_eq!(
+            recover(Some(10), Some(7)),
+            Recovery::Gap {
+                height: 8,
+                wal_height: 10
+            }
+        );
+        assert_eq!(recover(Some(10), Some(7)).height(), 8);
+    }
+
+    #[test]
+    fn decodes_timeouts() {
+        let mut entry = vec![TAG_TIMEOUT, 1];
+        entry.extend_from_slice(&3i64.to_be_bytes());
+
+        assert_eq!(
+            decode_entry(&entry),
+            WalEntrySummary::Timeout {
+                kind: "prevote".to_string(),
+                round: 3
+            }
+        );
+    }
+
+    #[test]
+    fn reports_undecodable_entries() {
+        let mut truncated = vec![TAG_CONSENSUS];
+        truncated.extend_from_slice(&100u64.to_be_bytes());
+        truncated.extend_from_slice(b"short");
+
+        assert_eq!(
+            decode_entry(&truncated),
+            WalEntrySummary::Undecodable {
+                tag: Some(TAG_CONSENSUS),
+                error: "truncated message".to_string()
+            }
+        );
+        assert!(matches!(
+            decode_entry(&[0x7F]),
+            WalEntrySummary::Undecodable {
+                tag: Some(0x7F),
+                ..
+            }
+        ));
+    }
+}
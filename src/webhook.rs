@@ -0,0 +1,164 @@
+//! Chain event sink POSTing a JSON summary of each committed block to webhooks, for integrations
+//! that do not run a message queue.
+//!
+//! The body is the [`ChainEvent::BlockCommitted`] event. When a secret is configured, it is
+//! signed with HMAC-SHA256 and the signature sent in the [`SIGNATURE_HEADER`] header as
+//! `sha256=<hex>`, for receivers to check the notification comes from the node. Failed deliveries
+//! are retried with exponential backoff by a task of the sink, so a slow endpoint does not hold
+//! up the other sinks; notifications beyond [`QUEUE_SIZE`] waiting for it are dropped.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use reqwest::StatusCode;
+use sha2::Sha256;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use crate::error::EventError;
+use crate::events::{ChainEvent, EventSink};
+
+/// Header carrying the signature of the body.
+pub const SIGNATURE_HEADER: &str = "X-Mikan-Signature";
+
+/// Notifications waiting for delivery beyond this number are dropped.
+pub const QUEUE_SIZE: usize = 256;
+
+/// Delay before the first retry, doubled at each of the following ones.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Timeout of each request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct WebhookSink {
+    sender: mpsc::Sender<Vec<u8>>,
+}
+
+impl WebhookSink {
+    /// Spawns the task delivering to `urls`, signing with the secret in the environment variable
+    /// `secret_env` if any. Must be called within a Tokio runtime.
+    pub fn start(
+        urls: Vec<String>,
+        secret_env: Option<&str>,
+        max_retries: u32,
+    ) -> Result<Self, EventError> {
+        let secret = match secret_env {
+            Some(name) => Some(
+                std::env::var(name)
+                    .map_err(|_| EventError::WebhookSecretNotSet(name.to_string()))?
+                    .into_bytes(),
+            ),
+            None => None,
+        };
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+
+        let (sender, mut receiver) = mpsc::channel::<Vec<u8>>(QUEUE_SIZE);
+        tokio::spawn(async move {
+            while let Some(body) = receiver.recv().await {
+                let signature = secret.as_deref().map(|secret| sign(secret, &body));
+                for url in &urls {
+                    deliver(&client, url, &body, signature.as_deref(), max_retries).await;
+                }
+            }
+        });
+
+        Ok(Self { sender })
+    }
+}
+
+#[async_trait]
+impl EventSink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn handle(&mut self, event: &ChainEvent) -> Result<(), EventError> {
+        if !matches!(event, ChainEvent::BlockCommitted { .. }) {
+            return Ok(());
+        }
+        self.sender
+            .try_send(serde_json::to_vec(event)?)
+            .map_err(|_| EventError::WebhookQueueFull)
+    }
+}
+
+/// `sha256=<hex>` HMAC of `body` with `secret`.
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Whether a delivery answered with `status` may succeed later.
+fn is_retryable(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+}
+
+/// POSTs `body` to `url`, retrying up to `max_retries` times.
+async fn deliver(
+    client: &reqwest::Client,
+    url: &str,
+    body: &[u8],
+    signature: Option<&str>,
+    max_retries: u32,
+) {
+    let mut backoff = INITIAL_BACKOFF;
+    for attempt in 0..=max_retries {
+        if attempt > 0 {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+
+        let mut request = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_vec());
+        if let Some(signature) = signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+
+        match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                debug!(%url, attempt, "Delivered webhook");
+                return;
+            }
+            Ok(response) if !is_retryable(response.status()) => {
+                warn!(%url, status = %response.status(), "Webhook rejected the notification");
+                return;
+            }
+            Ok(response) => {
+                warn!(%url, attempt, status = %response.status(), "Webhook delivery failed")
+            }
+            Err(e) => warn!(%url, attempt, error = %e, "Webhook delivery failed"),
+        }
+    }
+
+    warn!(%url, max_retries, "Giving up on webhook delivery");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_with_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign(b"Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn retries_transient_failures_only() {
+        assert!(is_retryable(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable(StatusCode::BAD_REQUEST));
+        assert!(!is_retryable(StatusCode::NOT_FOUND));
+    }
+}